@@ -1,10 +1,27 @@
 use std::io;
 use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Ok, Result};
+use log::error;
 
-use printnanny_gst_pipelines::factory::PrintNannyPipelineFactory;
-use printnanny_settings::{cam::CameraVideoSource, SettingsFormat};
+use printnanny_gst_pipelines::backend::PipelineBackendKind;
+use printnanny_gst_pipelines::bus::{subscribe_bus_messages, GstBusWatcher};
+use printnanny_gst_pipelines::export::RecordingExport;
+use printnanny_gst_pipelines::factory::{PrintNannyPipelineFactory, H264_RECORDING_PIPELINE};
+use printnanny_gst_pipelines::fragments::handle_fragment_messages;
+use printnanny_gst_pipelines::gst_client::gstd_types::BusMessageType;
+use printnanny_gst_pipelines::reconciler::PipelineReconciler;
+use printnanny_services::recording_recovery;
+use printnanny_services::retention;
+use printnanny_services::video_recording_sync::sync_all_video_recordings;
+use printnanny_settings::printnanny::PrintNannySettings;
+use printnanny_settings::{
+    cam::{CameraVideoSource, GstElementTarget},
+    SettingsFormat,
+};
 
 pub struct CameraCommand;
 
@@ -26,7 +43,7 @@ impl CameraCommand {
     async fn start_pipelines(args: &clap::ArgMatches) -> Result<()> {
         let address = args.value_of("http-address").unwrap();
         let port: i32 = args.value_of_t("http-port").unwrap();
-        let factory = PrintNannyPipelineFactory::new(address.into(), port);
+        let factory = PrintNannyPipelineFactory::new(address.into(), port)?;
         factory.start_pipelines().await?;
         Ok(())
     }
@@ -34,11 +51,139 @@ impl CameraCommand {
     async fn stop_pipelines(args: &clap::ArgMatches) -> Result<()> {
         let address = args.value_of("http-address").unwrap();
         let port: i32 = args.value_of_t("http-port").unwrap();
-        let factory = PrintNannyPipelineFactory::new(address.into(), port);
+        let factory = PrintNannyPipelineFactory::new(address.into(), port)?;
         factory.stop_pipelines().await?;
         Ok(())
     }
 
+    async fn render_pipelines(args: &clap::ArgMatches) -> Result<()> {
+        let settings = PrintNannySettings::new().await?;
+        let elements: GstElementTarget = args.value_of_t("elements").unwrap();
+        let report =
+            PrintNannyPipelineFactory::render_pipelines(&settings.video_streams(), elements)?;
+        let f: SettingsFormat = args.value_of_t("format").unwrap();
+
+        let v = match f {
+            SettingsFormat::Json => serde_json::to_vec_pretty(&report)?,
+            SettingsFormat::Toml => toml::ser::to_vec(&report)?,
+            other => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported render-pipelines format={:?}, expected json or toml",
+                    other
+                ))
+            }
+        };
+        io::stdout().write_all(&v)?;
+
+        if !report.is_valid() {
+            return Err(anyhow::anyhow!(
+                "Pipeline validation failed with {} error(s)",
+                report.errors().count()
+            ));
+        }
+        Ok(())
+    }
+
+    async fn reconcile_pipelines(args: &clap::ArgMatches) -> Result<()> {
+        let address = args.value_of("http-address").unwrap();
+        let port: i32 = args.value_of_t("http-port").unwrap();
+        let interval: u64 = args.value_of_t("interval").unwrap();
+        let healthy_period: u64 = args.value_of_t("healthy-period").unwrap();
+        let backend: PipelineBackendKind = args.value_of_t("backend").unwrap();
+        // the in-process backend runs every pipeline inside this process, until the reconciler exits
+        let factory = PrintNannyPipelineFactory::with_backend_kind(address.into(), port, backend)?;
+        let hostname = match args.value_of("hostname") {
+            Some(hostname) => hostname.to_string(),
+            None => printnanny_settings::sys_info::hostname()
+                .unwrap_or_else(|_| "printnanny".to_string()),
+        };
+        let bus_errors = match backend {
+            // gstd pipeline buses are read by GstBusWatcher (printnanny-nats-gstmultifile), which publishes errors to NATS
+            PipelineBackendKind::Gstd => {
+                let nats_server_uri = args.value_of("nats-server-uri").unwrap();
+                let nats_creds = args.value_of("nats-creds").map(PathBuf::from);
+                subscribe_bus_messages(
+                    nats_server_uri,
+                    &nats_creds,
+                    &hostname,
+                    &BusMessageType::Error,
+                    100,
+                )
+                .await?
+            }
+            // in-process pipeline buses can only be read by this process, so GstBusWatcher runs here
+            // it drains every pipeline bus, and handles each camera's h264_record fragment messages like printnanny-nats-gstmultifile
+            PipelineBackendKind::InProcess => {
+                let settings = PrintNannySettings::new().await?;
+                let sqlite_connection = settings.paths.db().display().to_string();
+                let watcher = Arc::new(GstBusWatcher::new(factory.clone(), &hostname));
+                let bus_errors = watcher.subscribe(None, &[BusMessageType::Error], 100);
+                for camera in settings.video_streams() {
+                    let fragments = watcher.subscribe(
+                        Some(&camera.namespaced_pipeline_name(H264_RECORDING_PIPELINE)),
+                        &[BusMessageType::Element],
+                        100,
+                    );
+                    let sqlite_connection = sqlite_connection.clone();
+                    tokio::spawn(async move {
+                        handle_fragment_messages(fragments, &sqlite_connection).await;
+                    });
+                }
+                tokio::spawn(async move {
+                    if let Err(e) = watcher.run().await {
+                        error!("GstBusWatcher failed error={}", e);
+                    }
+                });
+                bus_errors
+            }
+        };
+        let mut reconciler = PipelineReconciler::new(factory, Duration::from_secs(interval))
+            .with_healthy_period(Duration::from_secs(healthy_period))
+            .with_bus_errors(bus_errors);
+        reconciler.run().await?;
+        Ok(())
+    }
+
+    async fn export_recording(args: &clap::ArgMatches) -> Result<()> {
+        let address = args.value_of("http-address").unwrap();
+        let port: i32 = args.value_of_t("http-port").unwrap();
+        let id = args.value_of("id").unwrap();
+        let settings = PrintNannySettings::new().await?;
+        let sqlite_connection = settings.paths.db().display().to_string();
+        let export = RecordingExport::new(&sqlite_connection, id, &settings.paths.video_exports())?;
+        let factory = PrintNannyPipelineFactory::new(address.into(), port)?;
+        let output = factory.export_video_recording(&export).await?;
+        println!("{}", output.display());
+        Ok(())
+    }
+
+    async fn retention(args: &clap::ArgMatches) -> Result<()> {
+        if args.is_present("daemon") {
+            retention::run_retention_manager().await?;
+            return Ok(());
+        }
+        let settings = PrintNannySettings::new().await?;
+        let status = match args.is_present("enforce") {
+            true => retention::enforce(&settings)?,
+            false => retention::status(&settings)?,
+        };
+        let v = serde_json::to_vec_pretty(&status)?;
+        io::stdout().write_all(&v)?;
+        Ok(())
+    }
+
+    async fn recover_recordings(args: &clap::ArgMatches) -> Result<()> {
+        let settings = PrintNannySettings::new().await?;
+        let report =
+            recording_recovery::recover(&settings, &PrintNannyPipelineFactory::default()).await?;
+        let v = serde_json::to_vec_pretty(&report)?;
+        io::stdout().write_all(&v)?;
+        if args.is_present("sync") {
+            sync_all_video_recordings().await?;
+        }
+        Ok(())
+    }
+
     // async fn start_multifilesink_listener(args: &clap::ArgMatches) -> Result<()> {
     //     let address = args.value_of("http-address").unwrap();
     //     let port: i32 = args.value_of_t("http-port").unwrap();
@@ -58,6 +203,11 @@ impl CameraCommand {
             // }
             Some(("start-pipelines", args)) => Self::start_pipelines(args).await,
             Some(("stop-pipelines", args)) => Self::stop_pipelines(args).await,
+            Some(("render-pipelines", args)) => Self::render_pipelines(args).await,
+            Some(("reconcile-pipelines", args)) => Self::reconcile_pipelines(args).await,
+            Some(("retention", args)) => Self::retention(args).await,
+            Some(("recover-recordings", args)) => Self::recover_recordings(args).await,
+            Some(("export-recording", args)) => Self::export_recording(args).await,
             _ => unimplemented!(),
         }
     }
@@ -1,6 +1,6 @@
 use printnanny_services::error::ServiceError;
 use printnanny_services::printnanny_api::ApiService;
-use printnanny_services::video_recording_sync::sync_all_video_recordings;
+use printnanny_services::video_recording_sync::{run_upload_queue, sync_all_video_recordings};
 use printnanny_settings::printnanny::PrintNannySettings;
 use std::io::{self, Write};
 
@@ -16,8 +16,12 @@ impl CloudDataCommand {
                 service.refresh_nats_creds().await?;
             }
 
-            Some(("sync-videos", _args)) => {
-                sync_all_video_recordings().await?;
+            Some(("sync-videos", args)) => {
+                if args.is_present("daemon") {
+                    run_upload_queue().await?;
+                } else {
+                    sync_all_video_recordings().await?;
+                }
             }
             Some(("show", _args)) => {
                 let service = ApiService::from(&settings);
@@ -92,6 +92,125 @@ async fn main() -> Result<()> {
                         .default_value("5001")
                         .help("Attach to the server through a given port")
             ))
+            .subcommand(Command::new("render-pipelines")
+                .author(crate_authors!())
+                .about(crate_description!())
+                .version(GIT_VERSION)
+                .about("Render and validate PrintNanny Vision pipeline descriptions, without contacting gstd")      
+                .arg(Arg::new("format")
+                .short('f')
+                .long("format")
+                .takes_value(true)
+                .possible_values(["json", "toml"])
+                .default_value("json")
+                .help("Output format"))
+                .arg(Arg::new("elements")
+                .long("elements")
+                .takes_value(true)
+                .possible_values(["raspberry-pi", "software", "host"])
+                .default_value("raspberry-pi")
+                .help("Render \"auto\" gstreamer elements for this target. host probes this machine's gstreamer element registry")
+            ))
+            .subcommand(Command::new("reconcile-pipelines")
+                .author(crate_authors!())
+                .about(crate_description!())
+                .version(GIT_VERSION)
+                .about("Keep PrintNanny Vision pipelines converged to configured pipeline graph, recreating failed pipelines")      
+                .arg(
+                    Arg::new("http-address")
+                    .takes_value(true)
+                    .long("http-address")
+                    .default_value("127.0.0.1")
+                    .help("Attach to the server through a given address"))
+                .arg(
+                        Arg::new("http-port")
+                        .takes_value(true)
+                        .long("http-port")
+                        .default_value("5001")
+                        .help("Attach to the server through a given port"))
+                .arg(
+                        Arg::new("interval")
+                        .takes_value(true)
+                        .long("interval")
+                        .default_value("5")
+                        .help("Seconds between reconcile passes"))
+                .arg(
+                        Arg::new("healthy-period")
+                        .takes_value(true)
+                        .long("healthy-period")
+                        .default_value("60")
+                        .help("Seconds a recreated pipeline must stay PLAYING without bus errors before its backoff is reset"))
+                .arg(
+                        Arg::new("backend")
+                        .takes_value(true)
+                        .long("backend")
+                        .default_value("gstd")
+                        .possible_values(["gstd", "in-process"])
+                        .help("Run pipelines in gstd, or in-process inside this service"))
+                .arg(
+                        Arg::new("hostname")
+                        .takes_value(true)
+                        .long("hostname")
+                        .help("Subscribe to pipeline bus errors published by this device (default: this device's hostname)"))
+                .arg(
+                        Arg::new("nats-server-uri")
+                        .takes_value(true)
+                        .long("nats-server-uri")
+                        .default_value("nats://localhost:4223")
+                        .help("Subscribe to pipeline bus errors published to this NATS server"))
+                .arg(
+                        Arg::new("nats-creds")
+                        .takes_value(true)
+                        .long("nats-creds")
+                        .help("NATS credentials file")
+            ))
+            .subcommand(Command::new("export-recording")
+                .author(crate_authors!())
+                .about(crate_description!())
+                .version(GIT_VERSION)
+                .about("Concatenate local video recording parts into one playable file")      
+                .arg(
+                    Arg::new("id")
+                    .takes_value(true)
+                    .required(true)
+                    .help("VideoRecording id"))
+                .arg(
+                    Arg::new("http-address")
+                    .takes_value(true)
+                    .long("http-address")
+                    .default_value("127.0.0.1")
+                    .help("Attach to the gstd server through a given address"))
+                .arg(
+                        Arg::new("http-port")
+                        .takes_value(true)
+                        .long("http-port")
+                        .default_value("5001")
+                        .help("Attach to the gstd server through a given port")
+            ))
+            .subcommand(Command::new("retention")
+                .author(crate_authors!())
+                .about(crate_description!())
+                .version(GIT_VERSION)
+                .about("Report disk usage of recordings, snapshots and HLS segments, enforcing retention policy")      
+                .arg(
+                    Arg::new("enforce")
+                    .long("enforce")
+                    .help("Delete files exceeding retention policy"))
+                .arg(
+                    Arg::new("daemon")
+                    .long("daemon")
+                    .help("Enforce retention policy every retention.interval_secs")
+            ))
+            .subcommand(Command::new("recover-recordings")
+                .author(crate_authors!())
+                .about(crate_description!())
+                .version(GIT_VERSION)
+                .about("Close recordings interrupted by an unclean shutdown, repair partial segments and queue missing parts for upload. Refuses to run while a recording is in progress. Also runs when printnanny-nats-edge-worker starts")
+                .arg(
+                    Arg::new("sync")
+                    .long("sync")
+                    .help("Upload queued video recording parts after recovery"))
+            )
             .subcommand(Command::new("list-pipelines")
                 .author(crate_authors!())
                 .about(crate_description!())
@@ -203,7 +322,12 @@ async fn main() -> Result<()> {
                 .author(crate_authors!())
                 .about(crate_description!())
                 .version(GIT_VERSION)
-                .about("Sync PrintNanny Cloud models (Pi, SystemInfo, etc")          
+                .about("Upload queued video recording parts to PrintNanny Cloud")          
+                .arg(
+                    Arg::new("daemon")
+                    .long("daemon")
+                    .help("Drain upload queue every upload_queue.interval_secs")
+                )
             )
         )
         
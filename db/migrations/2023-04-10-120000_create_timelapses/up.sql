@@ -0,0 +1,13 @@
+CREATE TABLE timelapses (
+  id VARCHAR PRIMARY KEY NOT NULL,
+  dir VARCHAR NOT NULL,
+  capture_trigger TEXT CHECK(capture_trigger IN ('interval', 'layer')) NOT NULL,
+  frame_count BIGINT NOT NULL,
+  last_z DOUBLE,
+  capture_start DATETIME,
+  capture_end DATETIME,
+  file_name VARCHAR,
+  gcode_file_name TEXT,
+  video_recording_id VARCHAR,
+  FOREIGN KEY(video_recording_id) REFERENCES video_recordings(id)
+)
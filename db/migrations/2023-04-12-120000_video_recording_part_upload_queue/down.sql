@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE video_recording_parts DROP COLUMN sync_error;
+ALTER TABLE video_recording_parts DROP COLUMN sync_next_attempt;
+ALTER TABLE video_recording_parts DROP COLUMN sync_attempts;
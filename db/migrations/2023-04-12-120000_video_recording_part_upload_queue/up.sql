@@ -0,0 +1,3 @@
+ALTER TABLE video_recording_parts ADD COLUMN sync_attempts BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE video_recording_parts ADD COLUMN sync_next_attempt DATETIME;
+ALTER TABLE video_recording_parts ADD COLUMN sync_error TEXT;
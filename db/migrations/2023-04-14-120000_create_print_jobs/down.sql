@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE print_job_alerts;
+DROP TABLE print_jobs;
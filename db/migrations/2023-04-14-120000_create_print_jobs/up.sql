@@ -0,0 +1,18 @@
+CREATE TABLE print_jobs (
+  id VARCHAR PRIMARY KEY NOT NULL,
+  gcode_file_name TEXT,
+  status TEXT NOT NULL,
+  job_start DATETIME,
+  job_end DATETIME,
+  video_recording_id VARCHAR,
+  FOREIGN KEY(video_recording_id) REFERENCES video_recordings(id)
+);
+CREATE TABLE print_job_alerts (
+  id VARCHAR PRIMARY KEY NOT NULL,
+  print_job_id VARCHAR NOT NULL,
+  event_type TEXT NOT NULL,
+  cloud_alert_id VARCHAR,
+  created_dt DATETIME NOT NULL,
+  payload TEXT,
+  FOREIGN KEY(print_job_id) REFERENCES print_jobs(id)
+)
@@ -0,0 +1,13 @@
+CREATE TABLE print_job_actions (
+  id VARCHAR PRIMARY KEY NOT NULL,
+  print_job_id VARCHAR NOT NULL,
+  print_job_alert_id VARCHAR,
+  action TEXT NOT NULL,
+  printer_api TEXT NOT NULL,
+  dry_run BOOLEAN NOT NULL,
+  status TEXT NOT NULL,
+  error TEXT,
+  created_dt DATETIME NOT NULL,
+  FOREIGN KEY(print_job_id) REFERENCES print_jobs(id),
+  FOREIGN KEY(print_job_alert_id) REFERENCES print_job_alerts(id)
+)
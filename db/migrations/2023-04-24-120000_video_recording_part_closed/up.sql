@@ -0,0 +1,3 @@
+ALTER TABLE video_recording_parts ADD COLUMN closed BOOLEAN NOT NULL DEFAULT 0;
+-- parts inserted before this migration were uploaded on fragment-closed, treat them as closed
+UPDATE video_recording_parts SET closed = 1;
@@ -190,6 +190,19 @@ impl EmailAlertSettings {
         );
         Ok(result)
     }
+    // None if EmailAlertSettings were never synced from PrintNanny Cloud
+    pub fn get_optional(
+        connection_str: &str,
+    ) -> Result<Option<EmailAlertSettings>, diesel::result::Error> {
+        use crate::schema::email_alert_settings::dsl::*;
+
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let result = email_alert_settings
+            .order_by(id)
+            .first::<EmailAlertSettings>(connection)
+            .optional()?;
+        Ok(result)
+    }
     pub fn insert(
         connection_str: &str,
         row: EmailAlertSettings,
@@ -3,8 +3,10 @@ pub mod connection;
 pub mod janus;
 pub mod nats_app;
 pub mod octoprint;
+pub mod print_job;
 pub mod schema;
 pub mod sql_types;
+pub mod timelapse;
 pub mod user;
 pub mod video_recording;
 
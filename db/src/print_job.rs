@@ -0,0 +1,379 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid;
+
+use crate::connection::establish_sqlite_connection;
+use crate::schema::print_job_actions;
+use crate::schema::print_job_alerts;
+use crate::schema::print_jobs;
+
+// Print job captured by a VideoRecording, populated from OctoPrint JobStatusChanged events
+#[derive(Queryable, Identifiable, Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[diesel(table_name = print_jobs)]
+pub struct PrintJob {
+    pub id: String,
+    pub gcode_file_name: Option<String>,
+    // last job status reported by OctoPrint, e.g. PrintStarted, PrintDone
+    pub status: String,
+    pub job_start: Option<DateTime<Utc>>,
+    pub job_end: Option<DateTime<Utc>>,
+    pub video_recording_id: Option<String>,
+}
+
+// Print alert raised while a PrintJob was running
+#[derive(Queryable, Identifiable, Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[diesel(table_name = print_job_alerts)]
+pub struct PrintJobAlert {
+    pub id: String,
+    pub print_job_id: String,
+    pub event_type: String,
+    // PrintNanny Cloud PrintJobAlert id, None if the alert was not sent to PrintNanny Cloud
+    pub cloud_alert_id: Option<String>,
+    pub created_dt: DateTime<Utc>,
+    // json-encoded alert payload
+    pub payload: Option<String>,
+}
+
+// Printer action (pause or cancel) taken in response to a PrintJobAlert, including dry runs and failed requests
+#[derive(Queryable, Identifiable, Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[diesel(table_name = print_job_actions)]
+pub struct PrintJobAction {
+    pub id: String,
+    pub print_job_id: String,
+    pub print_job_alert_id: Option<String>,
+    // e.g. pause, cancel
+    pub action: String,
+    // e.g. octoprint, moonraker
+    pub printer_api: String,
+    // dry runs are recorded without sending the action to the printer
+    pub dry_run: bool,
+    // e.g. dry_run, success, error
+    pub status: String,
+    pub error: Option<String>,
+    pub created_dt: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = print_jobs)]
+pub struct NewPrintJob<'a> {
+    pub id: &'a str,
+    pub gcode_file_name: Option<&'a str>,
+    pub status: &'a str,
+    pub job_start: &'a DateTime<Utc>,
+    pub video_recording_id: Option<&'a str>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = print_job_alerts)]
+pub struct NewPrintJobAlert<'a> {
+    pub id: &'a str,
+    pub print_job_id: &'a str,
+    pub event_type: &'a str,
+    pub cloud_alert_id: Option<&'a str>,
+    pub created_dt: &'a DateTime<Utc>,
+    pub payload: Option<&'a str>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = print_job_actions)]
+pub struct NewPrintJobAction<'a> {
+    pub id: &'a str,
+    pub print_job_id: &'a str,
+    pub print_job_alert_id: Option<&'a str>,
+    pub action: &'a str,
+    pub printer_api: &'a str,
+    pub dry_run: bool,
+    pub status: &'a str,
+    pub error: Option<&'a str>,
+    pub created_dt: &'a DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, AsChangeset)]
+#[diesel(table_name = print_jobs)]
+pub struct UpdatePrintJob<'a> {
+    pub status: Option<&'a str>,
+    pub job_end: Option<&'a DateTime<Utc>>,
+    pub video_recording_id: Option<&'a str>,
+}
+
+impl PrintJob {
+    pub fn start_new(
+        connection_str: &str,
+        gcode_file_name: Option<&str>,
+        status: &str,
+        video_recording_id: Option<&str>,
+    ) -> Result<PrintJob, diesel::result::Error> {
+        use crate::schema::print_jobs::dsl::print_jobs;
+        let connection = &mut establish_sqlite_connection(connection_str);
+
+        let row_id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let row = NewPrintJob {
+            id: &row_id,
+            gcode_file_name,
+            status,
+            job_start: &now,
+            video_recording_id,
+        };
+        diesel::insert_into(print_jobs)
+            .values(&row)
+            .execute(connection)?;
+        info!(
+            "Created new PrintJob with id={} gcode_file_name={:?}",
+            &row_id, gcode_file_name
+        );
+        let result = print_jobs.find(&row_id).first(connection)?;
+        Ok(result)
+    }
+
+    pub fn get_by_id(
+        connection_str: &str,
+        row_id: &str,
+    ) -> Result<PrintJob, diesel::result::Error> {
+        use crate::schema::print_jobs::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        print_jobs
+            .filter(id.eq(row_id))
+            .first::<PrintJob>(connection)
+    }
+
+    // job which has not finished
+    pub fn get_current(connection_str: &str) -> Result<Option<PrintJob>, diesel::result::Error> {
+        use crate::schema::print_jobs::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let result = print_jobs
+            .filter(job_end.is_null())
+            .order(job_start.desc())
+            .first::<PrintJob>(connection)
+            .optional()?;
+        Ok(result)
+    }
+
+    pub fn get_by_video_recording_id(
+        connection_str: &str,
+        video_recording: &str,
+    ) -> Result<Vec<PrintJob>, diesel::result::Error> {
+        use crate::schema::print_jobs::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let result = print_jobs
+            .filter(video_recording_id.eq(video_recording))
+            .order(job_start.asc())
+            .load::<PrintJob>(connection)?;
+        Ok(result)
+    }
+
+    pub fn update(
+        connection_str: &str,
+        row_id: &str,
+        row: UpdatePrintJob,
+    ) -> Result<(), diesel::result::Error> {
+        use crate::schema::print_jobs::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        diesel::update(print_jobs.filter(id.eq(row_id)))
+            .set(row)
+            .execute(connection)?;
+        info!("Updated PrintJob with id {}", row_id);
+        Ok(())
+    }
+
+    // mark all unfinished jobs as finished, e.g. when a new job starts without a terminal status event
+    pub fn finish_all(
+        connection_str: &str,
+        final_status: &str,
+    ) -> Result<(), diesel::result::Error> {
+        use crate::schema::print_jobs::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let now = Utc::now();
+        let count = diesel::update(print_jobs.filter(job_end.is_null()))
+            .set(UpdatePrintJob {
+                status: Some(final_status),
+                job_end: Some(&now),
+                video_recording_id: None,
+            })
+            .execute(connection)?;
+        if count > 0 {
+            info!("Marked {} PrintJob rows as finished", count);
+        }
+        Ok(())
+    }
+
+    // mark unfinished jobs started before the given time as finished, e.g. jobs interrupted by a reboot
+    pub fn finish_started_before(
+        connection_str: &str,
+        final_status: &str,
+        before: &DateTime<Utc>,
+    ) -> Result<(), diesel::result::Error> {
+        use crate::schema::print_jobs::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let now = Utc::now();
+        let count = diesel::update(
+            print_jobs
+                .filter(job_end.is_null())
+                .filter(job_start.is_null().or(job_start.lt(before))),
+        )
+        .set(UpdatePrintJob {
+            status: Some(final_status),
+            job_end: Some(&now),
+            video_recording_id: None,
+        })
+        .execute(connection)?;
+        if count > 0 {
+            info!(
+                "Marked {} PrintJob rows started before {} as finished",
+                count, before
+            );
+        }
+        Ok(())
+    }
+}
+
+impl PrintJobAlert {
+    pub fn insert(
+        connection_str: &str,
+        print_job_id: &str,
+        event_type: &str,
+        cloud_alert_id: Option<&str>,
+        payload: Option<&str>,
+    ) -> Result<PrintJobAlert, diesel::result::Error> {
+        use crate::schema::print_job_alerts::dsl;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let row_id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let row = NewPrintJobAlert {
+            id: &row_id,
+            print_job_id,
+            event_type,
+            cloud_alert_id,
+            created_dt: &now,
+            payload,
+        };
+        diesel::insert_into(dsl::print_job_alerts)
+            .values(&row)
+            .execute(connection)?;
+        info!(
+            "Created PrintJobAlert id={} print_job_id={} event_type={}",
+            &row_id, print_job_id, event_type
+        );
+        let result = dsl::print_job_alerts.find(&row_id).first(connection)?;
+        Ok(result)
+    }
+
+    pub fn get_by_print_job_id(
+        connection_str: &str,
+        job_id: &str,
+    ) -> Result<Vec<PrintJobAlert>, diesel::result::Error> {
+        use crate::schema::print_job_alerts::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let result = print_job_alerts
+            .filter(print_job_id.eq(job_id))
+            .order(created_dt.asc())
+            .load::<PrintJobAlert>(connection)?;
+        Ok(result)
+    }
+}
+
+impl PrintJobAction {
+    // status is derived from dry_run and error
+    pub fn insert(
+        connection_str: &str,
+        print_job_id: &str,
+        print_job_alert_id: Option<&str>,
+        action: &str,
+        printer_api: &str,
+        dry_run: bool,
+        error: Option<&str>,
+    ) -> Result<PrintJobAction, diesel::result::Error> {
+        use crate::schema::print_job_actions::dsl;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let row_id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now();
+        let status = match (dry_run, error) {
+            (_, Some(_)) => "error",
+            (true, None) => "dry_run",
+            (false, None) => "success",
+        };
+        let row = NewPrintJobAction {
+            id: &row_id,
+            print_job_id,
+            print_job_alert_id,
+            action,
+            printer_api,
+            dry_run,
+            status,
+            error,
+            created_dt: &now,
+        };
+        diesel::insert_into(dsl::print_job_actions)
+            .values(&row)
+            .execute(connection)?;
+        info!(
+            "Created PrintJobAction id={} print_job_id={} action={} printer_api={} status={}",
+            &row_id, print_job_id, action, printer_api, status
+        );
+        let result = dsl::print_job_actions.find(&row_id).first(connection)?;
+        Ok(result)
+    }
+
+    // most recent action sent (or dry run) for job_id, used to enforce FailureActionSettings.cooldown_secs
+    // failed actions don't start a cooldown, so the next alert retries the action
+    pub fn get_last(
+        connection_str: &str,
+        job_id: &str,
+    ) -> Result<Option<PrintJobAction>, diesel::result::Error> {
+        use crate::schema::print_job_actions::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let result = print_job_actions
+            .filter(print_job_id.eq(job_id))
+            .filter(status.ne("error"))
+            .order(created_dt.desc())
+            .first::<PrintJobAction>(connection)
+            .optional()?;
+        Ok(result)
+    }
+
+    pub fn get_by_print_job_id(
+        connection_str: &str,
+        job_id: &str,
+    ) -> Result<Vec<PrintJobAction>, diesel::result::Error> {
+        use crate::schema::print_job_actions::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let result = print_job_actions
+            .filter(print_job_id.eq(job_id))
+            .order(created_dt.asc())
+            .load::<PrintJobAction>(connection)?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finish_started_before() {
+        let dir =
+            std::env::temp_dir().join(format!("printnanny-print-job-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let connection_str = dir.join("db.sqlite").display().to_string();
+        crate::connection::run_migrations(&connection_str).unwrap();
+
+        let interrupted = PrintJob::start_new(&connection_str, None, "PrintStarted", None).unwrap();
+        let boot_time = Utc::now();
+        let printing = PrintJob::start_new(&connection_str, None, "PrintStarted", None).unwrap();
+
+        PrintJob::finish_started_before(&connection_str, "Unknown", &boot_time).unwrap();
+        let interrupted = PrintJob::get_by_id(&connection_str, &interrupted.id).unwrap();
+        assert_eq!(interrupted.status, "Unknown");
+        assert!(interrupted.job_end.is_some());
+        // jobs started since boot are left running
+        assert_eq!(
+            PrintJob::get_current(&connection_str)
+                .unwrap()
+                .map(|job| job.id),
+            Some(printing.id)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -74,6 +74,69 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel::sqlite::sql_types::*;
+
+    print_job_actions (id) {
+        id -> Text,
+        print_job_id -> Text,
+        print_job_alert_id -> Nullable<Text>,
+        action -> Text,
+        printer_api -> Text,
+        dry_run -> Bool,
+        status -> Text,
+        error -> Nullable<Text>,
+        created_dt -> TimestamptzSqlite,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel::sqlite::sql_types::*;
+
+    print_job_alerts (id) {
+        id -> Text,
+        print_job_id -> Text,
+        event_type -> Text,
+        cloud_alert_id -> Nullable<Text>,
+        created_dt -> TimestamptzSqlite,
+        payload -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel::sqlite::sql_types::*;
+
+    print_jobs (id) {
+        id -> Text,
+        gcode_file_name -> Nullable<Text>,
+        status -> Text,
+        job_start -> Nullable<TimestamptzSqlite>,
+        job_end -> Nullable<TimestamptzSqlite>,
+        video_recording_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use diesel::sqlite::sql_types::*;
+
+    timelapses (id) {
+        id -> Text,
+        dir -> Text,
+        capture_trigger -> Text,
+        frame_count -> BigInt,
+        last_z -> Nullable<Double>,
+        capture_start -> Nullable<TimestamptzSqlite>,
+        capture_end -> Nullable<TimestamptzSqlite>,
+        file_name -> Nullable<Text>,
+        gcode_file_name -> Nullable<Text>,
+        video_recording_id -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use diesel::sqlite::sql_types::*;
@@ -100,6 +163,10 @@ diesel::table! {
         sync_end -> Nullable<TimestamptzSqlite>,
         file_name -> Text,
         video_recording_id -> Text,
+        sync_attempts -> BigInt,
+        sync_next_attempt -> Nullable<TimestamptzSqlite>,
+        sync_error -> Nullable<Text>,
+        closed -> Bool,
     }
 }
 
@@ -141,6 +208,11 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(print_job_actions -> print_job_alerts (print_job_alert_id));
+diesel::joinable!(print_job_actions -> print_jobs (print_job_id));
+diesel::joinable!(print_job_alerts -> print_jobs (print_job_id));
+diesel::joinable!(print_jobs -> video_recordings (video_recording_id));
+diesel::joinable!(timelapses -> video_recordings (video_recording_id));
 diesel::joinable!(video_recording_parts -> video_recordings (video_recording_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
@@ -148,6 +220,10 @@ diesel::allow_tables_to_appear_in_same_query!(
     nats_apps,
     octoprint_servers,
     pis,
+    print_job_actions,
+    print_job_alerts,
+    print_jobs,
+    timelapses,
     users,
     video_recording_parts,
     video_recordings,
@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use log::info;
+use serde::{Deserialize, Serialize};
+use uuid;
+
+use crate::connection::establish_sqlite_connection;
+use crate::schema::timelapses;
+
+// Frames captured during a print job, assembled into {dir}/timelapse.mp4 when the job completes
+#[derive(Queryable, Identifiable, Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[diesel(table_name = timelapses)]
+pub struct Timelapse {
+    pub id: String,
+    pub dir: String,
+    pub capture_trigger: String,
+    pub frame_count: i64,
+    // last Z height seen in layer-triggered captures
+    pub last_z: Option<f64>,
+    pub capture_start: Option<DateTime<Utc>>,
+    pub capture_end: Option<DateTime<Utc>>,
+    // assembled mp4, set after capture_end
+    pub file_name: Option<String>,
+    pub gcode_file_name: Option<String>,
+    pub video_recording_id: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = timelapses)]
+pub struct NewTimelapse<'a> {
+    pub id: &'a str,
+    pub dir: &'a str,
+    pub capture_trigger: &'a str,
+    pub frame_count: &'a i64,
+    pub capture_start: &'a DateTime<Utc>,
+    pub gcode_file_name: Option<&'a str>,
+    pub video_recording_id: Option<&'a str>,
+}
+
+#[derive(Clone, Debug, PartialEq, Default, AsChangeset)]
+#[diesel(table_name = timelapses)]
+pub struct UpdateTimelapse<'a> {
+    pub frame_count: Option<&'a i64>,
+    pub last_z: Option<&'a f64>,
+    pub capture_end: Option<&'a DateTime<Utc>>,
+    pub file_name: Option<&'a str>,
+}
+
+impl Timelapse {
+    pub fn start_new(
+        connection_str: &str,
+        timelapse_path: PathBuf,
+        trigger: &str,
+        gcode_file_name: Option<&str>,
+        video_recording_id: Option<&str>,
+    ) -> Result<Timelapse, diesel::result::Error> {
+        use crate::schema::timelapses::dsl::timelapses;
+        let connection = &mut establish_sqlite_connection(connection_str);
+
+        let row_id = uuid::Uuid::new_v4().to_string();
+        let dirname = timelapse_path.join(&row_id);
+        fs::create_dir_all(&dirname).unwrap_or_else(|e| {
+            panic!(
+                "Failed to create directory {} error={}",
+                dirname.display(),
+                e
+            )
+        });
+        info!("Created {}", dirname.display());
+        let now = Utc::now();
+        let row = NewTimelapse {
+            id: &row_id,
+            dir: &dirname.display().to_string(),
+            capture_trigger: trigger,
+            frame_count: &0,
+            capture_start: &now,
+            gcode_file_name,
+            video_recording_id,
+        };
+        diesel::insert_into(timelapses)
+            .values(&row)
+            .execute(connection)?;
+        info!("Created new Timelapse with id {}", &row_id);
+        let result = timelapses.find(&row_id).first(connection)?;
+        Ok(result)
+    }
+
+    pub fn get_by_id(
+        connection_str: &str,
+        row_id: &str,
+    ) -> Result<Timelapse, diesel::result::Error> {
+        use crate::schema::timelapses::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        timelapses
+            .filter(id.eq(row_id))
+            .first::<Timelapse>(connection)
+    }
+
+    pub fn get_all(connection_str: &str) -> Result<Vec<Timelapse>, diesel::result::Error> {
+        use crate::schema::timelapses::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let result = timelapses
+            .order_by(capture_start.desc())
+            .load::<Timelapse>(connection)?;
+        Ok(result)
+    }
+
+    // timelapse which is still capturing frames
+    pub fn get_current(connection_str: &str) -> Result<Option<Timelapse>, diesel::result::Error> {
+        use crate::schema::timelapses::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let result = timelapses
+            .filter(capture_end.is_null())
+            .order(capture_start.desc())
+            .first::<Timelapse>(connection)
+            .optional()?;
+        Ok(result)
+    }
+
+    pub fn update(
+        connection_str: &str,
+        row_id: &str,
+        row: UpdateTimelapse,
+    ) -> Result<(), diesel::result::Error> {
+        use crate::schema::timelapses::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        diesel::update(timelapses.filter(id.eq(row_id)))
+            .set(row)
+            .execute(connection)?;
+        info!("Updated Timelapse with id {}", row_id);
+        Ok(())
+    }
+}
@@ -40,6 +40,12 @@ pub struct VideoRecordingPart {
     pub sync_end: Option<DateTime<Utc>>,
     pub file_name: String,
     pub video_recording_id: String,
+    // upload queue state, see printnanny_services::video_recording_sync
+    pub sync_attempts: i64,
+    pub sync_next_attempt: Option<DateTime<Utc>>,
+    pub sync_error: Option<String>,
+    // splitmuxsink finished writing the fragment, only closed parts are uploaded
+    pub closed: bool,
 }
 
 #[derive(Debug, Insertable)]
@@ -61,6 +67,7 @@ pub struct NewVideoRecordingPart<'a> {
     pub deleted: &'a bool,
     pub file_name: &'a str,
     pub video_recording_id: &'a str,
+    pub closed: &'a bool,
 }
 
 #[derive(Clone, Debug, PartialEq, AsChangeset)]
@@ -79,6 +86,9 @@ pub struct UpdateVideoRecordingPart<'a> {
     pub deleted: Option<&'a bool>,
     pub sync_start: Option<&'a DateTime<Utc>>,
     pub sync_end: Option<&'a DateTime<Utc>>,
+    pub sync_attempts: Option<&'a i64>,
+    pub sync_next_attempt: Option<&'a DateTime<Utc>>,
+    pub sync_error: Option<&'a str>,
 }
 
 impl VideoRecording {
@@ -169,7 +179,7 @@ impl VideoRecording {
             .load::<VideoRecording>(connection)?;
         let count = unfinished_recordings.len();
 
-        if count > 1 {
+        if count > 0 {
             let now = Utc::now();
             info!(
                 "Marking {} VideoRecording rows as finished",
@@ -201,10 +211,8 @@ impl VideoRecording {
 
         let row_id = uuid::Uuid::new_v4().to_string();
         let dirname = video_path.join(&row_id);
-        fs::create_dir_all(&dirname).expect(&format!(
-            "Failed to create directory {}",
-            &dirname.display()
-        ));
+        fs::create_dir_all(&dirname)
+            .unwrap_or_else(|_| panic!("Failed to create directory {}", &dirname.display()));
         info!("Created {}", dirname.display());
         let row = NewVideoRecording {
             id: &row_id,
@@ -218,6 +226,57 @@ impl VideoRecording {
         let result = video_recordings.find(&row_id).first(connection)?;
         Ok(result)
     }
+
+    // recording which already finished, e.g. committed from the pre-roll buffer
+    // built in memory and only inserted by insert_finished after its parts were written, so get_current never returns it
+    pub fn new_finished(
+        video_path: PathBuf,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> VideoRecording {
+        let row_id = uuid::Uuid::new_v4().to_string();
+        let dirname = video_path.join(&row_id);
+        VideoRecording {
+            id: row_id,
+            dir: dirname.display().to_string(),
+            recording_start: Some(start),
+            recording_end: Some(end),
+            ..VideoRecording::default()
+        }
+    }
+
+    pub fn insert_finished(
+        connection_str: &str,
+        recording: &VideoRecording,
+    ) -> Result<VideoRecording, diesel::result::Error> {
+        use crate::schema::video_recordings::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let result = connection.transaction::<_, diesel::result::Error, _>(|connection| {
+            let row = NewVideoRecording {
+                id: &recording.id,
+                cloud_sync_done: &false,
+                dir: &recording.dir,
+            };
+            diesel::insert_into(video_recordings)
+                .values(&row)
+                .execute(connection)?;
+            let update = UpdateVideoRecording {
+                cloud_sync_done: None,
+                dir: None,
+                recording_start: recording.recording_start.as_ref(),
+                recording_end: recording.recording_end.as_ref(),
+                gcode_file_name: recording.gcode_file_name.as_deref(),
+            };
+            diesel::update(video_recordings.filter(id.eq(&recording.id)))
+                .set(update)
+                .execute(connection)?;
+            video_recordings
+                .find(&recording.id)
+                .first::<VideoRecording>(connection)
+        })?;
+        info!("Inserted finished VideoRecording with id {}", &recording.id);
+        Ok(result)
+    }
 }
 
 impl From<VideoRecording> for printnanny_os_models::VideoRecording {
@@ -248,36 +307,38 @@ impl From<VideoRecording> for models::VideoRecordingRequest {
 }
 
 // parse recording id from path like: /home/printnanny/.local/share/printnanny/video/66b3a3a0-30b5-41f2-9907-a335de57c921/00025.mp4
-pub fn parse_video_recording_id(filename: &str) -> String {
+// None if filename has no parent directory
+pub fn parse_video_recording_id(filename: &str) -> Option<String> {
     let path = PathBuf::from(filename);
     let mut components = path.components();
     components
         .nth_back(1)
-        .unwrap()
-        .as_os_str()
-        .to_str()
-        .unwrap()
-        .into()
+        .and_then(|c| c.as_os_str().to_str())
+        .map(|c| c.to_string())
 }
 
-pub fn parse_video_recording_index(filename: &str) -> i64 {
+// None if the file name doesn't contain an index
+pub fn parse_video_recording_index(filename: &str) -> Option<i64> {
     let path = PathBuf::from(filename);
-    let mut components = path.components();
-    let last: String = components
-        .nth_back(0)
-        .unwrap()
-        .as_os_str()
-        .to_str()
-        .unwrap()
-        .into();
-    last.split('.').next().unwrap().parse().unwrap()
+    let last = path.file_name()?.to_str()?;
+    // filenames are rendered from RecordingFormatSettings.filename_template + extension, e.g. 00025.mp4 or segment00025.ts
+    // the index is the last run of digits before the extension, RecordingFormatSettings::validate rejects templates with digits after or directly before the conversion
+    let stem = last.split('.').next()?;
+    let digits: String = stem
+        .chars()
+        .rev()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.chars().rev().collect::<String>().parse().ok()
 }
 
 impl VideoRecordingPart {
-    pub fn row_id_from_filename(filename: &str) -> String {
-        let video_recording_id = parse_video_recording_id(filename);
-        let index = parse_video_recording_index(filename);
-        format!("{video_recording_id}__{index}")
+    // None if the recording id or index can't be parsed from filename
+    pub fn row_id_from_filename(filename: &str) -> Option<String> {
+        let video_recording_id = parse_video_recording_id(filename)?;
+        let index = parse_video_recording_index(filename)?;
+        Some(format!("{video_recording_id}__{index}"))
     }
 
     pub fn insert(
@@ -309,18 +370,21 @@ impl VideoRecordingPart {
         use crate::schema::video_recording_parts::dsl::*;
         let connection = &mut establish_sqlite_connection(connection_str);
 
-        let sync_start_value =
-            <chrono::DateTime<chrono::FixedOffset> as std::convert::Into<DateTime<Utc>>>::into(
-                DateTime::parse_from_rfc3339(&obj.sync_start).unwrap(),
-            );
-        let sync_end_value = <chrono::DateTime<chrono::FixedOffset> as std::convert::Into<
-            DateTime<Utc>,
-        >>::into(DateTime::parse_from_rfc3339(&obj.sync_end).unwrap());
+        // timestamps which can't be parsed are not updated, so a part without sync_end stays in the upload queue
+        let sync_start_value: Option<DateTime<Utc>> = DateTime::parse_from_rfc3339(&obj.sync_start)
+            .ok()
+            .map(Into::into);
+        let sync_end_value: Option<DateTime<Utc>> = DateTime::parse_from_rfc3339(&obj.sync_end)
+            .ok()
+            .map(Into::into);
 
         let row_update = UpdateVideoRecordingPart {
             deleted: None,
-            sync_start: Some(&sync_start_value),
-            sync_end: Some(&sync_end_value),
+            sync_start: sync_start_value.as_ref(),
+            sync_end: sync_end_value.as_ref(),
+            sync_attempts: None,
+            sync_next_attempt: None,
+            sync_error: None,
         };
         diesel::update(video_recording_parts.filter(id.eq(&obj.id)))
             .set(row_update)
@@ -335,10 +399,143 @@ impl VideoRecordingPart {
         let connection = &mut establish_sqlite_connection(connection_str);
         let result = video_recording_parts
             .filter(sync_start.is_null())
+            .filter(deleted.eq(false))
             .load::<VideoRecordingPart>(connection)?;
         Ok(result)
     }
 
+    // parts waiting for upload whose backoff has elapsed, oldest recordings first
+    // parts with sync_attempts >= max_attempts are skipped, max_attempts 0 retries forever
+    // only closed parts are queued, or parts of finished recordings whose fragment-closed message was missed
+    pub fn get_upload_queue(
+        connection_str: &str,
+        now: &DateTime<Utc>,
+        max_attempts: i64,
+        limit: i64,
+    ) -> Result<Vec<VideoRecordingPart>, diesel::result::Error> {
+        use crate::schema::video_recording_parts::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let finished_recordings = video_recordings::table
+            .filter(video_recordings::recording_end.is_not_null())
+            .select(video_recordings::id);
+        let mut query = video_recording_parts
+            .filter(
+                closed
+                    .eq(true)
+                    .or(video_recording_id.eq_any(finished_recordings)),
+            )
+            .filter(sync_end.is_null())
+            .filter(deleted.eq(false))
+            .filter(sync_next_attempt.is_null().or(sync_next_attempt.le(now)))
+            .into_boxed();
+        if max_attempts > 0 {
+            query = query.filter(sync_attempts.lt(max_attempts));
+        }
+        let result = query
+            .order((video_recording_id.asc(), buffer_index.asc()))
+            .limit(limit)
+            .load::<VideoRecordingPart>(connection)?;
+        Ok(result)
+    }
+
+    // file names of parts which are not uploaded yet, including parts still waiting for their backoff
+    // parts abandoned after max_attempts failed uploads are not returned, max_attempts 0 retries forever
+    pub fn get_pending_upload_file_names(
+        connection_str: &str,
+        max_attempts: i64,
+    ) -> Result<Vec<String>, diesel::result::Error> {
+        use crate::schema::video_recording_parts::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let mut query = video_recording_parts
+            .filter(sync_end.is_null())
+            .filter(deleted.eq(false))
+            .select(file_name)
+            .into_boxed();
+        if max_attempts > 0 {
+            query = query.filter(sync_attempts.lt(max_attempts));
+        }
+        query.load::<String>(connection)
+    }
+
+    // parts the upload queue gave up on after max_attempts failed uploads, including parts whose file was deleted since
+    // max_attempts 0 retries forever, so no part fails
+    pub fn get_upload_failed(
+        connection_str: &str,
+        max_attempts: i64,
+    ) -> Result<Vec<VideoRecordingPart>, diesel::result::Error> {
+        use crate::schema::video_recording_parts::dsl::*;
+        if max_attempts <= 0 {
+            return Ok(vec![]);
+        }
+        let connection = &mut establish_sqlite_connection(connection_str);
+        video_recording_parts
+            .filter(sync_end.is_null())
+            .filter(sync_attempts.ge(max_attempts))
+            .order((video_recording_id.asc(), buffer_index.asc()))
+            .load::<VideoRecordingPart>(connection)
+    }
+
+    // splitmuxsink finished writing the fragment, update its final size and add it to the upload queue
+    pub fn mark_closed(
+        connection_str: &str,
+        row_id: &str,
+        final_size: i64,
+    ) -> Result<(), diesel::result::Error> {
+        use crate::schema::video_recording_parts::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        diesel::update(video_recording_parts.filter(id.eq(row_id)))
+            .set((closed.eq(true), size.eq(final_size)))
+            .execute(connection)?;
+        info!(
+            "Marked VideoRecordingPart closed id={} size={}",
+            row_id, final_size
+        );
+        Ok(())
+    }
+
+    // increment sync_attempts and schedule the next upload attempt
+    pub fn record_upload_failure(
+        connection_str: &str,
+        row_id: &str,
+        error: &str,
+        next_attempt: &DateTime<Utc>,
+    ) -> Result<(), diesel::result::Error> {
+        use crate::schema::video_recording_parts::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        diesel::update(video_recording_parts.filter(id.eq(row_id)))
+            .set((
+                sync_attempts.eq(sync_attempts + 1),
+                sync_next_attempt.eq(Some(next_attempt)),
+                sync_error.eq(Some(error)),
+            ))
+            .execute(connection)?;
+        Ok(())
+    }
+
+    // mark parts backed by file_name as deleted, returns number of updated rows
+    pub fn mark_deleted_by_file_name(
+        connection_str: &str,
+        path: &str,
+    ) -> Result<usize, diesel::result::Error> {
+        use crate::schema::video_recording_parts::dsl::*;
+        let connection = &mut establish_sqlite_connection(connection_str);
+        let row = UpdateVideoRecordingPart {
+            deleted: Some(&true),
+            sync_start: None,
+            sync_end: None,
+            sync_attempts: None,
+            sync_next_attempt: None,
+            sync_error: None,
+        };
+        let count = diesel::update(video_recording_parts.filter(file_name.eq(path)))
+            .set(row)
+            .execute(connection)?;
+        if count > 0 {
+            info!("Marked VideoRecordingPart deleted file_name={}", path);
+        }
+        Ok(count)
+    }
+
     pub fn get_parts_by_video_recording_id(
         connection_str: &str,
         video_recording: &str,
@@ -400,8 +597,13 @@ impl From<&printnanny_os_models::VideoRecordingPart> for VideoRecordingPart {
             buffer_runningtime: obj.buffer_runningtime,
             video_recording_id: obj.video_recording_id.clone(),
             file_name: obj.file_name.clone(),
+            // parts known to the API were written and uploaded, so they are closed
+            closed: true,
             sync_start,
             sync_end,
+            sync_attempts: 0,
+            sync_next_attempt: None,
+            sync_error: None,
         }
     }
 }
@@ -415,15 +617,22 @@ mod tests {
         let filename = "/home/printnanny/.local/share/printnanny/video/66b3a3a0-30b5-41f2-9907-a335de57c921/00025.mp4";
         let expected = "66b3a3a0-30b5-41f2-9907-a335de57c921";
         let result = parse_video_recording_id(filename);
-        assert_eq!(result, expected);
+        assert_eq!(result.as_deref(), Some(expected));
     }
 
     #[test]
     fn test_parse_video_recording_index() {
         let filename = "/home/printnanny/.local/share/printnanny/video/66b3a3a0-30b5-41f2-9907-a335de57c921/00025.mp4";
-        let expected = 25;
+        let expected = Some(25);
         let result = parse_video_recording_index(filename);
         assert_eq!(result, expected);
+
+        let filename = "/home/printnanny/.local/share/printnanny/video/66b3a3a0-30b5-41f2-9907-a335de57c921/segment00025.ts";
+        assert_eq!(parse_video_recording_index(filename), expected);
+        let filename = "/home/printnanny/.local/share/printnanny/video/66b3a3a0-30b5-41f2-9907-a335de57c921/25-part.mkv";
+        assert_eq!(parse_video_recording_index(filename), expected);
+        let filename = "/home/printnanny/.local/share/printnanny/video/66b3a3a0-30b5-41f2-9907-a335de57c921/v2_segment00025_part.ts";
+        assert_eq!(parse_video_recording_index(filename), expected);
     }
 
     #[test]
@@ -432,6 +641,102 @@ mod tests {
         let result = VideoRecordingPart::row_id_from_filename(filename);
         let expected = "66b3a3a0-30b5-41f2-9907-a335de57c921__25";
 
-        assert_eq!(result, expected);
+        assert_eq!(result.as_deref(), Some(expected));
+    }
+
+    #[test]
+    fn test_insert_finished_is_not_current() {
+        let dir = std::env::temp_dir().join(format!(
+            "printnanny-video-recording-test-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let connection_str = dir.join("db.sqlite").display().to_string();
+        crate::connection::run_migrations(&connection_str).unwrap();
+
+        let end = Utc::now();
+        let start = end - chrono::Duration::seconds(30);
+        let recording = VideoRecording::new_finished(dir.join("video"), start, end);
+        // nothing is written until the recording's parts are committed
+        assert!(VideoRecording::get_by_id(&connection_str, &recording.id).is_err());
+
+        let inserted = VideoRecording::insert_finished(&connection_str, &recording).unwrap();
+        assert_eq!(inserted.id, recording.id);
+        assert_eq!(inserted.dir, recording.dir);
+        assert!(inserted.recording_end.is_some());
+        assert_eq!(VideoRecording::get_current(&connection_str).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_get_upload_failed() {
+        let dir = std::env::temp_dir().join(format!(
+            "printnanny-upload-failed-test-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let connection_str = dir.join("db.sqlite").display().to_string();
+        crate::connection::run_migrations(&connection_str).unwrap();
+
+        let end = Utc::now();
+        let recording = VideoRecording::new_finished(
+            dir.join("video"),
+            end - chrono::Duration::seconds(30),
+            end,
+        );
+        let recording = VideoRecording::insert_finished(&connection_str, &recording).unwrap();
+        for index in 0..2_i64 {
+            let file_name = format!("{}/{:05}.ts", recording.dir, index);
+            let row_id = VideoRecordingPart::row_id_from_filename(&file_name).unwrap();
+            VideoRecordingPart::insert(
+                &connection_str,
+                NewVideoRecordingPart {
+                    id: &row_id,
+                    size: &0,
+                    buffer_index: &index,
+                    buffer_runningtime: &0,
+                    deleted: &false,
+                    file_name: &file_name,
+                    video_recording_id: &recording.id,
+                    closed: &true,
+                },
+            )
+            .unwrap();
+        }
+        let parts =
+            VideoRecordingPart::get_parts_by_video_recording_id(&connection_str, &recording.id)
+                .unwrap();
+        for _ in 0..3 {
+            VideoRecordingPart::record_upload_failure(
+                &connection_str,
+                &parts[0].id,
+                "connection refused",
+                &end,
+            )
+            .unwrap();
+        }
+
+        assert!(VideoRecordingPart::get_upload_failed(&connection_str, 0)
+            .unwrap()
+            .is_empty());
+        let failed = VideoRecordingPart::get_upload_failed(&connection_str, 3).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].id, parts[0].id);
+        assert_eq!(failed[0].sync_error.as_deref(), Some("connection refused"));
+        // failed parts stay visible after retention deletes their file
+        VideoRecordingPart::mark_deleted_by_file_name(&connection_str, &parts[0].file_name)
+            .unwrap();
+        let failed = VideoRecordingPart::get_upload_failed(&connection_str, 3).unwrap();
+        assert!(failed[0].deleted);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_filename_without_index() {
+        let filename = "/home/printnanny/.local/share/printnanny/video/66b3a3a0-30b5-41f2-9907-a335de57c921/segment.ts";
+        assert_eq!(parse_video_recording_index(filename), None);
+        assert_eq!(VideoRecordingPart::row_id_from_filename(filename), None);
+        assert_eq!(parse_video_recording_index(""), None);
+        assert_eq!(parse_video_recording_id("00025.mp4"), None);
     }
 }
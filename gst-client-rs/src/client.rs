@@ -2,64 +2,227 @@
 //! [`GStreamer Daemon`][1] API.
 //!
 //! [1]: https://developer.ridgerun.com/wiki/index.php/GStreamer_Daemon
+use std::time::Duration;
+
 use crate::{gstd_types, resources, Error};
-use reqwest::{Client, Response};
+use log::warn;
+use reqwest::{Client, Method, Response, StatusCode};
 use url::Url;
 
+/// Retry policy of failed [`GstClient`] requests.
+///
+/// Idempotent requests are retried if the connection failed, the request
+/// timed out or [`GStreamer Daemon`][1] was unavailable. Requests changing
+/// gstd state (`POST`, bus reads and signal callbacks) are only retried if
+/// the connection failed, because gstd never received them.
+///
+/// [1]: https://developer.ridgerun.com/wiki/index.php/GStreamer_Daemon
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled before every next retry
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Never retry failed requests
+    pub const NONE: Self = Self {
+        max_retries: 0,
+        backoff: Duration::ZERO,
+    };
+
+    /// Delay before retry number `attempt`, starting at 1
+    #[must_use]
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            backoff: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Connection pool, timeouts and [`RetryPolicy`] of a [`GstClient`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GstClientConfig {
+    /// Timeout of every request, `None` waits forever
+    pub timeout: Option<Duration>,
+    /// Timeout of connecting to [`GStreamer Daemon`][1]
+    ///
+    /// [1]: https://developer.ridgerun.com/wiki/index.php/GStreamer_Daemon
+    pub connect_timeout: Duration,
+    /// Maximum number of idle pooled connections
+    pub pool_max_idle: usize,
+    /// Idle pooled connections are closed after this timeout
+    pub pool_idle_timeout: Option<Duration>,
+    /// Retry policy of failed requests
+    pub retry: RetryPolicy,
+}
+
+impl Default for GstClientConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Some(Duration::from_secs(10)),
+            connect_timeout: Duration::from_secs(2),
+            pool_max_idle: 8,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+impl GstClientConfig {
+    fn http_client(&self) -> Result<Client, reqwest::Error> {
+        Client::builder()
+            .connect_timeout(self.connect_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .build()
+    }
+}
+
 /// [`GstClient`] for [`GStreamer Daemon`][1] API.
 ///
+/// Clones of a [`GstClient`] share one pool of connections, so a client
+/// should be built once and cloned wherever it's needed.
+///
 /// [1]: https://developer.ridgerun.com/wiki/index.php/GStreamer_Daemon
 #[derive(Debug, Clone)]
 pub struct GstClient {
     http_client: Client,
     pub(crate) base_url: Url,
+    config: GstClientConfig,
 }
 
 impl GstClient {
-    /// Build [`GstClient`] for future call to [`GStreamer Daemon`][1] API.
+    /// Build [`GstClient`] for future call to [`GStreamer Daemon`][1] API,
+    /// using the default [`GstClientConfig`].
     ///
     /// # Errors
     ///
-    /// If incorrect `base_url` passed
+    /// If incorrect `base_url` passed, or the HTTP client cannot be built
     ///
     /// [1]: https://developer.ridgerun.com/wiki/index.php/GStreamer_Daemon
     pub fn build<S: Into<String>>(base_url: S) -> Result<Self, Error> {
+        Self::build_with_config(base_url, GstClientConfig::default())
+    }
+
+    /// Build [`GstClient`] with a connection pool, timeouts and
+    /// [`RetryPolicy`] configured by `config`.
+    ///
+    /// # Errors
+    ///
+    /// If incorrect `base_url` passed, or the HTTP client cannot be built
+    pub fn build_with_config<S: Into<String>>(
+        base_url: S,
+        config: GstClientConfig,
+    ) -> Result<Self, Error> {
         Ok(Self {
-            http_client: Client::new(),
+            http_client: config.http_client().map_err(Error::ClientBuildFailed)?,
             base_url: Url::parse(&base_url.into()).map_err(Error::IncorrectBaseUrl)?,
+            config,
         })
     }
 
+    /// Returns a [`GstClient`] sending requests through `http_client`,
+    /// sharing its connection pool with other users of `http_client`
+    #[must_use]
+    pub fn with_http_client(mut self, http_client: Client) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    /// Returns a [`GstClient`] sharing this client's connection pool,
+    /// with a different timeout of every request
+    ///
+    /// Useful for requests which block in gstd, like reading a bus message
+    /// with a bus timeout.
+    #[must_use]
+    pub fn with_timeout(&self, timeout: Option<Duration>) -> Self {
+        let mut client = self.clone();
+        client.config.timeout = timeout;
+        client
+    }
+
+    /// Returns a [`GstClient`] sharing this client's connection pool,
+    /// with a different [`RetryPolicy`]
+    #[must_use]
+    pub fn with_retry(&self, retry: RetryPolicy) -> Self {
+        let mut client = self.clone();
+        client.config.retry = retry;
+        client
+    }
+
+    /// [`GstClientConfig`] of this client
+    #[must_use]
+    pub fn config(&self) -> &GstClientConfig {
+        &self.config
+    }
+
+    // send a request, retrying failures allowed by RetryPolicy
+    async fn send(&self, method: Method, url: Url, idempotent: bool) -> Result<Response, Error> {
+        let mut attempt = 0;
+        loop {
+            let mut request = self.http_client.request(method.clone(), url.clone());
+            if let Some(timeout) = self.config.timeout {
+                request = request.timeout(timeout);
+            }
+            let result = request.send().await;
+            let retry = match &result {
+                Ok(resp) => {
+                    idempotent
+                        && matches!(
+                            resp.status(),
+                            StatusCode::BAD_GATEWAY
+                                | StatusCode::SERVICE_UNAVAILABLE
+                                | StatusCode::GATEWAY_TIMEOUT
+                        )
+                }
+                Err(e) => e.is_connect() || (idempotent && e.is_timeout()),
+            };
+            if !retry || attempt >= self.config.retry.max_retries {
+                return result.map_err(Error::RequestFailed);
+            }
+            attempt += 1;
+            let delay = self.config.retry.delay(attempt);
+            warn!(
+                "Retrying gstd request method={} url={} attempt={} delay={:?} result={:?}",
+                method,
+                url,
+                attempt,
+                delay,
+                result.map(|resp| resp.status())
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
     pub(crate) async fn get(&self, url: reqwest::Url) -> Result<Response, Error> {
-        self.http_client
-            .get(url)
-            .send()
-            .await
-            .map_err(Error::RequestFailed)
+        self.send(Method::GET, url, true).await
+    }
+
+    // GET requests which consume gstd state, like bus messages and signal callbacks
+    pub(crate) async fn get_once(&self, url: reqwest::Url) -> Result<Response, Error> {
+        self.send(Method::GET, url, false).await
     }
 
     pub(crate) async fn post(&self, url: reqwest::Url) -> Result<Response, Error> {
-        self.http_client
-            .post(url)
-            .send()
-            .await
-            .map_err(Error::RequestFailed)
+        self.send(Method::POST, url, false).await
     }
 
     pub(crate) async fn put(&self, url: reqwest::Url) -> Result<Response, Error> {
-        self.http_client
-            .put(url)
-            .send()
-            .await
-            .map_err(Error::RequestFailed)
+        self.send(Method::PUT, url, true).await
     }
 
     pub(crate) async fn delete(&self, url: reqwest::Url) -> Result<Response, Error> {
-        self.http_client
-            .delete(url)
-            .send()
-            .await
-            .map_err(Error::RequestFailed)
+        self.send(Method::DELETE, url, true).await
     }
 
     pub(crate) async fn process_resp(&self, resp: Response) -> Result<gstd_types::Response, Error> {
@@ -95,6 +258,76 @@ impl GstClient {
         let resp = self.get(url).await?;
         self.process_resp(resp).await
     }
+    /// Performs `GET /{uri}` API request, returning the parsed
+    /// [`gstd_types::Response`]
+    ///
+    /// Reads any resource of the gstd object tree, like gstd's `read`
+    /// command, e.g. `pipelines/{name}/elements/{element}/refcount`
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn read(&self, uri: &str) -> Result<gstd_types::Response, Error> {
+        let url = self.base_url.join(uri).map_err(Error::IncorrectApiUrl)?;
+        let resp = self.get(url).await?;
+        self.process_resp(resp).await
+    }
+    /// Performs `PUT /{uri}?name={value}` API request, returning the
+    /// parsed [`gstd_types::Response`]
+    ///
+    /// Updates any resource of the gstd object tree, like gstd's `update`
+    /// command
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn update(&self, uri: &str, value: &str) -> Result<gstd_types::Response, Error> {
+        let mut url = self.base_url.join(uri).map_err(Error::IncorrectApiUrl)?;
+        url.query_pairs_mut().append_pair("name", value);
+        let resp = self.put(url).await?;
+        self.process_resp(resp).await
+    }
+    /// Performs `POST /{uri}?name={name}&description={description}`
+    /// API request, returning the parsed [`gstd_types::Response`]
+    ///
+    /// Creates a resource in the gstd object tree, like gstd's `create`
+    /// command
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn create(
+        &self,
+        uri: &str,
+        name: &str,
+        description: &str,
+    ) -> Result<gstd_types::Response, Error> {
+        let mut url = self.base_url.join(uri).map_err(Error::IncorrectApiUrl)?;
+        url.query_pairs_mut()
+            .append_pair("name", name)
+            .append_pair("description", description);
+        let resp = self.post(url).await?;
+        self.process_resp(resp).await
+    }
+    /// Performs `DELETE /{uri}?name={name}` API request, returning the
+    /// parsed [`gstd_types::Response`]
+    ///
+    /// Deletes a resource from the gstd object tree, like gstd's `delete`
+    /// command
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn remove(&self, uri: &str, name: &str) -> Result<gstd_types::Response, Error> {
+        let mut url = self.base_url.join(uri).map_err(Error::IncorrectApiUrl)?;
+        url.query_pairs_mut().append_pair("name", name);
+        let resp = self.delete(url).await?;
+        self.process_resp(resp).await
+    }
     /// Operate with [`GStreamer Daemon`][1] pipelines.
     ///
     /// # Arguments
@@ -120,28 +353,24 @@ impl GstClient {
 
 impl Default for GstClient {
     fn default() -> Self {
-        Self {
-            http_client: Client::new(),
-            base_url: Url::parse("http://127.0.0.1:5001").unwrap(),
-        }
+        Self::from(Url::parse("http://127.0.0.1:5001").unwrap())
     }
 }
 
 impl From<Url> for GstClient {
     fn from(url: Url) -> Self {
+        let config = GstClientConfig::default();
         Self {
-            http_client: Client::new(),
+            http_client: config.http_client().unwrap_or_default(),
             base_url: url,
+            config,
         }
     }
 }
 
 impl From<&Url> for GstClient {
     fn from(url: &Url) -> Self {
-        Self {
-            http_client: Client::new(),
-            base_url: url.clone(),
-        }
+        Self::from(url.clone())
     }
 }
 
@@ -149,10 +378,10 @@ impl From<&Url> for GstClient {
 mod spec {
     use super::*;
     use http;
-    const BASE_URL: &'static str = "http://localhost:5002";
-    const PIPELINE_NAME: &'static str = "test pipeline";
+    const BASE_URL: &str = "http://localhost:5002";
+    const PIPELINE_NAME: &str = "test pipeline";
 
-    const STATE_RESPONSE: &'static str = r#"
+    const STATE_RESPONSE: &str = r#"
     {
         "code" : 0,
         "description" : "Success",
@@ -168,7 +397,7 @@ mod spec {
       }
     "#;
 
-    const SPLITMUXSINK_FRAGMENT_OPENED: &'static str = r#"
+    const SPLITMUXSINK_FRAGMENT_OPENED: &str = r#"
     {
         "code" : 0,
         "description" : "Success",
@@ -186,7 +415,7 @@ mod spec {
       }
     "#;
 
-    const SPLITMUXSINK_FRAGMENT_CLOSED: &'static str = r#"
+    const SPLITMUXSINK_FRAGMENT_CLOSED: &str = r#"
     {
         "code" : 0,
         "description" : "Success",
@@ -204,6 +433,24 @@ mod spec {
       }
     "#;
 
+    const BUS_TIMEOUT: &str = r#"
+    {
+        "code" : 0,
+        "description" : "Success",
+        "response" : null
+      }
+    "#;
+
+    // bus message payloads, see tests/fixtures/bus/README.md
+    const EOS_MESSAGE: &str = include_str!("../tests/fixtures/bus/eos.json");
+    const ERROR_MESSAGE: &str = include_str!("../tests/fixtures/bus/error.json");
+    const WARNING_MESSAGE: &str = include_str!("../tests/fixtures/bus/warning.json");
+    const STATE_CHANGED_MESSAGE: &str = include_str!("../tests/fixtures/bus/state-changed.json");
+    const QOS_MESSAGE: &str = include_str!("../tests/fixtures/bus/qos.json");
+    const LATENCY_MESSAGE: &str = include_str!("../tests/fixtures/bus/latency.json");
+    const MULTIFILESINK_MESSAGE: &str =
+        include_str!("../tests/fixtures/bus/element-multifilesink.json");
+
     fn expect_url() -> Url {
         Url::parse(BASE_URL).unwrap()
     }
@@ -284,6 +531,116 @@ mod spec {
         assert_eq!(res.response, expected);
     }
 
+    #[tokio::test]
+    async fn process_bus_messages() {
+        let client = GstClient::build(BASE_URL).unwrap();
+
+        let response = http::Response::builder()
+            .status(200)
+            .body(EOS_MESSAGE)
+            .unwrap();
+        let res = client.process_resp(response.into()).await.unwrap();
+        let msg = res.response.into_bus_message().unwrap();
+        assert_eq!(msg.message_type(), gstd_types::BusMessageType::Eos);
+        assert!(!msg.source.is_empty());
+        assert!(msg.fields.is_empty());
+
+        let response = http::Response::builder()
+            .status(200)
+            .body(SPLITMUXSINK_FRAGMENT_CLOSED)
+            .unwrap();
+        let res = client.process_resp(response.into()).await.unwrap();
+        let msg = res.response.into_bus_message().unwrap();
+        assert_eq!(msg.message_type(), gstd_types::BusMessageType::Element);
+        assert!(msg.fields.contains_key("splitmuxsink-fragment-closed"));
+
+        let response = http::Response::builder()
+            .status(200)
+            .body(BUS_TIMEOUT)
+            .unwrap();
+        let res = client.process_resp(response.into()).await.unwrap();
+        assert_eq!(res.response.into_bus_message(), None);
+
+        assert_eq!(
+            gstd_types::BusMessageType::from("state_changed"),
+            gstd_types::BusMessageType::StateChanged
+        );
+        assert_eq!(
+            gstd_types::BusMessageType::StateChanged.to_string(),
+            "state-changed"
+        );
+    }
+
+    async fn typed_bus_message(body: &'static str) -> gstd_types::BusMessageBody {
+        let client = GstClient::build(BASE_URL).unwrap();
+        let response = http::Response::builder().status(200).body(body).unwrap();
+        let res = client.process_resp(response.into()).await.unwrap();
+        res.response.into_bus_message().unwrap().body().unwrap()
+    }
+
+    // checks the shape of each message, so fixtures can be recaptured without changing the test
+    #[tokio::test]
+    async fn process_typed_bus_messages() {
+        match typed_bus_message(ERROR_MESSAGE).await {
+            gstd_types::BusMessageBody::Error(error) => {
+                assert!(!error.message.is_empty());
+                assert!(error.debug.is_some());
+            }
+            other => panic!("Expected error, got {other:?}"),
+        }
+
+        match typed_bus_message(WARNING_MESSAGE).await {
+            gstd_types::BusMessageBody::Warning(warning) => {
+                assert!(!warning.message.is_empty());
+            }
+            other => panic!("Expected warning, got {other:?}"),
+        }
+
+        match typed_bus_message(STATE_CHANGED_MESSAGE).await {
+            gstd_types::BusMessageBody::StateChanged(state) => {
+                assert_ne!(state.old_state, state.new_state);
+            }
+            other => panic!("Expected state-changed, got {other:?}"),
+        }
+
+        match typed_bus_message(QOS_MESSAGE).await {
+            gstd_types::BusMessageBody::Qos(qos) => {
+                assert!(qos.running_time.is_some());
+            }
+            other => panic!("Expected qos, got {other:?}"),
+        }
+
+        assert_eq!(
+            typed_bus_message(LATENCY_MESSAGE).await,
+            gstd_types::BusMessageBody::Latency
+        );
+        assert_eq!(
+            typed_bus_message(EOS_MESSAGE).await,
+            gstd_types::BusMessageBody::Eos
+        );
+
+        match typed_bus_message(MULTIFILESINK_MESSAGE).await {
+            gstd_types::BusMessageBody::Element(element) => {
+                assert_eq!(element.name.as_deref(), Some("GstMultiFileSink"));
+                assert!(element.fields.contains_key("filename"));
+                assert!(element.fields["index"].is_u64());
+            }
+            other => panic!("Expected element, got {other:?}"),
+        }
+
+        match typed_bus_message(SPLITMUXSINK_FRAGMENT_OPENED).await {
+            gstd_types::BusMessageBody::Element(element) => {
+                assert_eq!(
+                    element.name.as_deref(),
+                    Some(gstd_types::GST_SPLIT_MUX_SINK_FRAGMENT_MESSAGE_OPENED)
+                );
+                let fragment: gstd_types::GstSplitMuxSinkFragmentMessage = element.parse().unwrap();
+                assert_eq!(fragment.running_time, 1020189067077);
+            }
+            other => panic!("Expected element, got {other:?}"),
+        }
+    }
+
     #[ignore]
     #[test]
     fn create_client_with_build() {
@@ -294,6 +651,29 @@ mod spec {
         assert_eq!(client.base_url, expect_url());
     }
 
+    #[test]
+    fn create_client_with_config() {
+        let config = GstClientConfig {
+            timeout: None,
+            retry: RetryPolicy::NONE,
+            ..GstClientConfig::default()
+        };
+        let client = GstClient::build_with_config(BASE_URL, config.clone()).unwrap();
+        assert_eq!(client.config(), &config);
+
+        let client = client.with_timeout(Some(Duration::from_secs(1)));
+        assert_eq!(client.config().timeout, Some(Duration::from_secs(1)));
+        assert!(GstClient::build_with_config("not a url", config).is_err());
+    }
+
+    #[test]
+    fn retry_policy_delay() {
+        let retry = RetryPolicy::default();
+        assert_eq!(retry.delay(1), Duration::from_millis(100));
+        assert_eq!(retry.delay(3), Duration::from_millis(400));
+        assert_eq!(RetryPolicy::NONE.delay(1), Duration::ZERO);
+    }
+
     #[ignore]
     #[test]
     fn create_client_from() {
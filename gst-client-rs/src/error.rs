@@ -24,6 +24,12 @@ pub enum Error {
     #[display(fmt = "Failed to decode API response: {}", _0)]
     BadBody(reqwest::Error),
 
+    /// Failed to build the HTTP client of a [`GstClient`]
+    ///
+    /// [`GstClient`]: crate::GstClient
+    #[display(fmt = "Failed to build HTTP client: {}", _0)]
+    ClientBuildFailed(reqwest::Error),
+
     /// Failed to build [`GstClient`] client because incorrect base Url
     ///
     /// [`GstClient`]: crate::GstClient
@@ -76,8 +76,29 @@ pub enum ResponseT {
     Bus(Option<Bus>),
     Properties(Properties),
     Property(Property),
+    SignalCallback(SignalCallback),
     GstSplitMuxSinkFragmentOpened(GstSplitMuxSinkFragmentOpened),
     GstSplitMuxSinkFragmentClosed(GstSplitMuxSinkFragmentClosed),
+    /// Any other bus message, e.g. `eos` or `state-changed`
+    BusMessage(BusMessage),
+}
+
+impl ResponseT {
+    /// Converts a `GET /pipelines/{name}/bus/message` response into a
+    /// [`BusMessage`], returning `None` if the bus timeout elapsed
+    /// without a message
+    pub fn into_bus_message(self) -> Option<BusMessage> {
+        match self {
+            ResponseT::Bus(None)
+            | ResponseT::Properties(_)
+            | ResponseT::Property(_)
+            | ResponseT::SignalCallback(_) => None,
+            ResponseT::BusMessage(msg) => Some(msg),
+            other => serde_json::to_value(other)
+                .and_then(serde_json::from_value)
+                .ok(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
@@ -121,6 +142,172 @@ pub enum PropertyValue {
     String(String),
     Integer(i32),
     Bool(bool),
+    /// `gint64` and `guint64` properties, e.g. `max-size-time`
+    Integer64(i64),
+    UnsignedInteger64(u64),
+    Double(f64),
+    /// Unset boxed or object properties, e.g. `caps`
+    Null,
+}
+
+impl Properties {
+    /// Property named `name`, e.g. from
+    /// `GET /pipelines/{name}/elements/{element}/properties`
+    pub fn get(&self, name: &str) -> Option<&Property> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+
+    /// Names of [`Properties::nodes`], e.g. pipelines, elements or signals
+    pub fn node_names(&self) -> Vec<String> {
+        self.nodes
+            .iter()
+            .flatten()
+            .map(|node| node.name.clone())
+            .collect()
+    }
+}
+
+/// Possible result in [`Response::response`] after
+/// `GET /pipelines/{name}/elements/{element}/signals/{signal}/callback`
+/// API request, once the signal was emitted
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SignalCallback {
+    pub name: String,
+    /// Signal arguments, starting with the emitting element
+    pub arguments: Vec<SignalArgument>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct SignalArgument {
+    /// `GType` name, e.g. `GstBuffer`
+    pub r#type: String,
+    pub value: serde_json::Value,
+}
+
+/// `GstFormat` of [`Seek`] and [`Step`] values
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Eq, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum GstFormat {
+    Undefined = 0,
+    Default = 1,
+    Bytes = 2,
+    /// Nanoseconds
+    Time = 3,
+    Buffers = 4,
+    Percent = 5,
+}
+
+/// `GstSeekType` of [`Seek::start`] and [`Seek::stop`]
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Eq, Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum GstSeekType {
+    /// Position is not changed
+    None = 0,
+    /// Absolute position
+    Set = 1,
+    /// Position relative to the end of the stream
+    End = 2,
+}
+
+pub const GST_SEEK_FLAG_NONE: u32 = 0;
+pub const GST_SEEK_FLAG_FLUSH: u32 = 1 << 0;
+pub const GST_SEEK_FLAG_ACCURATE: u32 = 1 << 1;
+pub const GST_SEEK_FLAG_KEY_UNIT: u32 = 1 << 2;
+pub const GST_SEEK_FLAG_SEGMENT: u32 = 1 << 3;
+pub const GST_SEEK_FLAG_TRICKMODE: u32 = 1 << 4;
+pub const GST_SEEK_FLAG_SNAP_BEFORE: u32 = 1 << 5;
+pub const GST_SEEK_FLAG_SNAP_AFTER: u32 = 1 << 6;
+
+/// Arguments of a `seek` event, see `gst_event_new_seek()`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Seek {
+    /// Playback rate, negative values play backwards
+    pub rate: f64,
+    pub format: GstFormat,
+    /// Bitwise or of `GST_SEEK_FLAG_*`
+    pub flags: u32,
+    pub start_type: GstSeekType,
+    pub start: i64,
+    pub stop_type: GstSeekType,
+    pub stop: i64,
+}
+
+impl Default for Seek {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            format: GstFormat::Time,
+            flags: GST_SEEK_FLAG_FLUSH,
+            start_type: GstSeekType::Set,
+            start: 0,
+            stop_type: GstSeekType::None,
+            stop: -1,
+        }
+    }
+}
+
+impl Seek {
+    /// Flushing seek to `position_ns`, keeping the playback rate
+    #[must_use]
+    pub fn to_time(position_ns: i64) -> Self {
+        Self {
+            start: position_ns,
+            ..Self::default()
+        }
+    }
+
+    /// Event description, formatted like gstd's `event_seek` command:
+    /// `rate format flags start_type start stop_type stop`
+    #[must_use]
+    pub fn description(&self) -> String {
+        format!(
+            "{} {} {} {} {} {} {}",
+            self.rate,
+            self.format as u8,
+            self.flags,
+            self.start_type as u8,
+            self.start,
+            self.stop_type as u8,
+            self.stop
+        )
+    }
+}
+
+/// Arguments of a `step` event, see `gst_event_new_step()`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    /// [`GstFormat::Buffers`] steps frames, [`GstFormat::Time`] steps
+    /// nanoseconds
+    pub format: GstFormat,
+    pub amount: u64,
+    pub rate: f64,
+    pub flush: bool,
+    pub intermediate: bool,
+}
+
+impl Step {
+    /// Steps `amount` frames forward, e.g. while the pipeline is paused
+    #[must_use]
+    pub fn frames(amount: u64) -> Self {
+        Self {
+            format: GstFormat::Buffers,
+            amount,
+            rate: 1.0,
+            flush: true,
+            intermediate: false,
+        }
+    }
+
+    /// Event description: `format amount rate flush intermediate`
+    #[must_use]
+    pub fn description(&self) -> String {
+        format!(
+            "{} {} {} {} {}",
+            self.format as u8, self.amount, self.rate, self.flush, self.intermediate
+        )
+    }
 }
 
 /// Possible result in [`Response::response`] after
@@ -133,6 +320,254 @@ pub struct Bus {
     pub seqnum: i64,
     pub message: String,
     pub debug: String,
+    /// `GError` domain and code, see [`BusError`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<i32>,
+}
+
+/// Type of a [`BusMessage`], named like `gst_message_type_get_name()`
+/// so it can be used in bus filters, e.g. `error+eos`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BusMessageType {
+    Error,
+    Warning,
+    Eos,
+    StateChanged,
+    Qos,
+    Latency,
+    Element,
+    Other(String),
+}
+
+impl BusMessageType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            BusMessageType::Error => "error",
+            BusMessageType::Warning => "warning",
+            BusMessageType::Eos => "eos",
+            BusMessageType::StateChanged => "state-changed",
+            BusMessageType::Qos => "qos",
+            BusMessageType::Latency => "latency",
+            BusMessageType::Element => "element",
+            BusMessageType::Other(name) => name,
+        }
+    }
+}
+
+impl From<&str> for BusMessageType {
+    fn from(name: &str) -> Self {
+        // gstd reports some types with underscores, e.g. state_changed
+        match name.to_lowercase().replace('_', "-").as_str() {
+            "error" => BusMessageType::Error,
+            "warning" => BusMessageType::Warning,
+            "eos" => BusMessageType::Eos,
+            "state-changed" => BusMessageType::StateChanged,
+            "qos" => BusMessageType::Qos,
+            "latency" => BusMessageType::Latency,
+            "element" => BusMessageType::Element,
+            other => BusMessageType::Other(other.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for BusMessageType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Any message read from `GET /pipelines/{name}/bus/message`,
+/// with message-specific fields kept as JSON
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct BusMessage {
+    pub r#type: String,
+    pub source: String,
+    pub timestamp: String,
+    pub seqnum: i64,
+    #[serde(flatten)]
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl BusMessage {
+    pub fn message_type(&self) -> BusMessageType {
+        BusMessageType::from(self.r#type.as_str())
+    }
+
+    /// Parses [`BusMessage::fields`] into the typed model of
+    /// [`BusMessage::message_type`]
+    ///
+    /// # Errors
+    ///
+    /// If fields don't match the message type, e.g. a `state-changed`
+    /// message without `new-state`
+    pub fn body(&self) -> Result<BusMessageBody, serde_json::Error> {
+        let fields = || serde_json::Value::Object(self.fields.clone());
+        let body = match self.message_type() {
+            BusMessageType::Error => BusMessageBody::Error(serde_json::from_value(fields())?),
+            BusMessageType::Warning => BusMessageBody::Warning(serde_json::from_value(fields())?),
+            BusMessageType::Eos => BusMessageBody::Eos,
+            BusMessageType::StateChanged => {
+                BusMessageBody::StateChanged(serde_json::from_value(fields())?)
+            }
+            BusMessageType::Qos => BusMessageBody::Qos(serde_json::from_value(fields())?),
+            BusMessageType::Latency => BusMessageBody::Latency,
+            BusMessageType::Element => BusMessageBody::Element(BusElement::from(&self.fields)),
+            BusMessageType::Other(_) => BusMessageBody::Other(self.fields.clone()),
+        };
+        Ok(body)
+    }
+}
+
+/// Typed fields of a [`BusMessage`], see [`BusMessage::body`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum BusMessageBody {
+    Error(BusError),
+    Warning(BusError),
+    Eos,
+    StateChanged(BusStateChanged),
+    Qos(BusQos),
+    Latency,
+    Element(BusElement),
+    /// Message types without a typed model, e.g. `stream-status`
+    Other(serde_json::Map<String, serde_json::Value>),
+}
+
+/// State of a pipeline or element. gstd reports uppercase names from
+/// `gst_element_state_get_name()` on the bus, and lowercase names
+/// in the pipeline `state` property
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GstState {
+    #[serde(alias = "void-pending")]
+    VoidPending,
+    #[serde(alias = "null")]
+    Null,
+    #[serde(alias = "ready")]
+    Ready,
+    #[serde(alias = "paused")]
+    Paused,
+    #[serde(alias = "playing")]
+    Playing,
+}
+
+/// Fields of an `error` or `warning` [`BusMessage`]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct BusError {
+    /// `GError` message
+    pub message: String,
+    /// Debug string with the source file, function and element path
+    #[serde(default)]
+    pub debug: Option<String>,
+    /// `GError` domain, e.g. `gst-stream-error-quark`.
+    /// Not reported by every gstd version
+    #[serde(default)]
+    pub domain: Option<String>,
+    /// `GError` code within [`BusError::domain`]
+    #[serde(default)]
+    pub code: Option<i32>,
+}
+
+impl std::fmt::Display for BusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(domain) = &self.domain {
+            write!(f, " domain={domain}")?;
+        }
+        if let Some(code) = &self.code {
+            write!(f, " code={code}")?;
+        }
+        if let Some(debug) = &self.debug {
+            write!(f, " debug={debug}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Fields of a `state-changed` [`BusMessage`]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct BusStateChanged {
+    #[serde(rename = "old-state")]
+    pub old_state: GstState,
+    #[serde(rename = "new-state")]
+    pub new_state: GstState,
+    /// [`GstState::VoidPending`] if the state change is complete
+    #[serde(rename = "pending-state")]
+    pub pending_state: GstState,
+}
+
+/// Fields of a `qos` [`BusMessage`], from `gst_message_parse_qos()`,
+/// `gst_message_parse_qos_values()` and `gst_message_parse_qos_stats()`
+///
+/// Clock times are in nanoseconds, `u64::MAX` is `GST_CLOCK_TIME_NONE`
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct BusQos {
+    #[serde(default)]
+    pub live: Option<bool>,
+    #[serde(default, rename = "running-time")]
+    pub running_time: Option<u64>,
+    #[serde(default, rename = "stream-time")]
+    pub stream_time: Option<u64>,
+    #[serde(default)]
+    pub duration: Option<u64>,
+    /// Difference between the buffer timestamp and the time it was
+    /// processed, positive if the buffer was late
+    #[serde(default)]
+    pub jitter: Option<i64>,
+    #[serde(default)]
+    pub proportion: Option<f64>,
+    #[serde(default)]
+    pub quality: Option<i32>,
+    /// Format of `processed` and `dropped`, e.g. `buffers`
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub processed: Option<u64>,
+    #[serde(default)]
+    pub dropped: Option<u64>,
+}
+
+/// Structure posted by an element in an `element` [`BusMessage`],
+/// e.g. `splitmuxsink-fragment-opened`
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct BusElement {
+    /// Structure name, `None` if gstd did not nest the fields
+    /// under the structure name
+    pub name: Option<String>,
+    pub fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl BusElement {
+    /// Deserializes [`BusElement::fields`], e.g. into a
+    /// [`GstSplitMuxSinkFragmentMessage`]
+    ///
+    /// # Errors
+    ///
+    /// If fields can't be deserialized into `T`
+    pub fn parse<T: serde::de::DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        serde_json::from_value(serde_json::Value::Object(self.fields.clone()))
+    }
+}
+
+impl From<&serde_json::Map<String, serde_json::Value>> for BusElement {
+    // gstd nests structure fields under the structure name
+    fn from(fields: &serde_json::Map<String, serde_json::Value>) -> Self {
+        let nested = fields.iter().find_map(|(name, value)| match value {
+            serde_json::Value::Object(nested) => Some((name, nested)),
+            _ => None,
+        });
+        match nested {
+            Some((name, nested)) => Self {
+                name: Some(name.clone()),
+                fields: nested.clone(),
+            },
+            None => Self {
+                name: None,
+                fields: fields.clone(),
+            },
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
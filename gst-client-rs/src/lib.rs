@@ -3,5 +3,9 @@ pub mod error;
 pub mod gstd_types;
 pub mod resources;
 
-pub use crate::{client::GstClient, error::Error, gstd_types::Response};
+pub use crate::{
+    client::{GstClient, GstClientConfig, RetryPolicy},
+    error::Error,
+    gstd_types::Response,
+};
 pub use reqwest;
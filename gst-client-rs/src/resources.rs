@@ -7,4 +7,9 @@ mod debug;
 mod element;
 mod pipeline;
 
-pub use self::{bus::PipelineBus, debug::Debug, element::PipelineElement, pipeline::Pipeline};
+pub use self::{
+    bus::{BusWatch, PipelineBus},
+    debug::Debug,
+    element::PipelineElement,
+    pipeline::Pipeline,
+};
@@ -6,6 +6,8 @@
 //! [`GStreamer`]: https://gstreamer.freedesktop.org/
 //! [1]: https://developer.ridgerun.com/wiki/index.php/GStreamer_Daemon_-_C_API#Bus
 //! [2]: https://gstreamer.freedesktop.org/documentation/additional/design/gstbus.html
+use std::time::Duration;
+
 use crate::{gstd_types, resources::Pipeline, Error, GstClient};
 use log::debug;
 
@@ -36,7 +38,7 @@ impl PipelineBus {
             .base_url
             .join(&format!("pipelines/{}/bus/message", self.pipeline.name))
             .map_err(Error::IncorrectApiUrl)?;
-        let resp = self.client.get(url).await?;
+        let resp = self.client.get_once(url).await?;
         debug!("Gst pipeline message: {:?}", resp);
         self.client.process_resp(resp).await
     }
@@ -84,4 +86,54 @@ impl PipelineBus {
 
         self.client.process_resp(resp).await
     }
+    /// Applies `filter` and `timeout_ns` to the bus, returning a [`BusWatch`]
+    /// which reads [`gstd_types::BusMessage`]s
+    ///
+    /// gstd delivers each message to one reader, so a pipeline bus should
+    /// only be watched once
+    ///
+    /// Reads block in gstd for up to `timeout_ns`, so the request timeout
+    /// of [`BusWatch::next`] is extended by `timeout_ns`.
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn watch(&self, filter: &str, timeout_ns: u64) -> Result<BusWatch, Error> {
+        self.set_filter(filter).await?;
+        self.set_timeout(timeout_ns).await?;
+        let timeout = self
+            .client
+            .config()
+            .timeout
+            .map(|timeout| timeout + Duration::from_nanos(timeout_ns));
+        let mut bus = self.clone();
+        bus.client = self.client.with_timeout(timeout);
+        Ok(BusWatch { bus })
+    }
+}
+
+/// Reads messages from a [`PipelineBus`], see [`PipelineBus::watch`]
+#[derive(Debug, Clone)]
+pub struct BusWatch {
+    bus: PipelineBus,
+}
+
+impl BusWatch {
+    /// Name of the watched pipeline
+    #[must_use]
+    pub fn pipeline_name(&self) -> &str {
+        &self.bus.pipeline.name
+    }
+    /// Waits for the next bus message, returning `None` if the bus
+    /// timeout elapsed without a message
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn next(&self) -> Result<Option<gstd_types::BusMessage>, Error> {
+        let resp = self.bus.read().await?;
+        Ok(resp.response.into_bus_message())
+    }
 }
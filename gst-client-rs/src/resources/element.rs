@@ -26,6 +26,55 @@ impl PipelineElement {
         }
     }
 
+    /// Performs `GET pipelines/{name}/elements/{element}/properties`
+    /// API request, returning the parsed [`gstd_types::Response`]
+    /// with the value and type of every property
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn properties(&self) -> Result<gstd_types::Response, Error> {
+        self.client
+            .read(&format!(
+                "pipelines/{}/elements/{}/properties",
+                self.pipeline.name, self.name
+            ))
+            .await
+    }
+
+    /// Performs `GET pipelines/{name}/elements/{element}/signals`
+    /// API request, returning the parsed [`gstd_types::Response`]
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn signals(&self) -> Result<gstd_types::Response, Error> {
+        self.client
+            .read(&format!(
+                "pipelines/{}/elements/{}/signals",
+                self.pipeline.name, self.name
+            ))
+            .await
+    }
+
+    /// Performs `GET pipelines/{name}/elements/{element}/refcount`
+    /// API request, returning the parsed [`gstd_types::Response`]
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn refcount(&self) -> Result<gstd_types::Response, Error> {
+        self.client
+            .read(&format!(
+                "pipelines/{}/elements/{}/refcount",
+                self.pipeline.name, self.name
+            ))
+            .await
+    }
+
     /// Performs `GET pipelines/{name}/elements/
     /// {element}/properties/{property}`
     /// API request, returning the parsed [`gstd_types::Response`]
@@ -76,6 +125,9 @@ impl PipelineElement {
     /// elements/{element}/signals/{signal}/callback`
     /// API request, returning the parsed [`gstd_types::Response`]
     ///
+    /// Blocks in gstd until the signal is emitted or the signal timeout
+    /// elapses, see [`GstClient::with_timeout`].
+    ///
     /// # Errors
     ///
     /// If API request cannot be performed, or fails.
@@ -90,7 +142,7 @@ impl PipelineElement {
                 self.pipeline.name, self.name
             ))
             .map_err(Error::IncorrectApiUrl)?;
-        let resp = self.client.get(url).await?;
+        let resp = self.client.get_once(url).await?;
         self.client.process_resp(resp).await
     }
 
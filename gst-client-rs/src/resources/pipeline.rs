@@ -162,6 +162,73 @@ impl Pipeline {
         self.client.process_resp(resp).await
     }
 
+    /// Performs `POST pipelines/{name}/event?name={event_name}&description={description}`
+    /// API request, returning the parsed [`gstd_types::Response`]
+    ///
+    /// # Arguments
+    ///
+    /// * `event_name` - event type, e.g. `seek` or `flush_stop`
+    /// * `description` - space-separated event arguments
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn emit_event_with_description(
+        &self,
+        event_name: &str,
+        description: &str,
+    ) -> Result<gstd_types::Response, Error> {
+        let mut url = self
+            .client
+            .base_url
+            .join(&format!("pipelines/{}/event", &self.name))
+            .map_err(Error::IncorrectApiUrl)?;
+        url.query_pairs_mut()
+            .append_pair("name", event_name)
+            .append_pair("description", description);
+
+        let resp = self.client.post(url).await?;
+        self.client.process_resp(resp).await
+    }
+
+    /// Performs `POST pipelines/{name}/event?name=seek&description={seek}`
+    /// API request, returning the parsed [`gstd_types::Response`]
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn seek(&self, seek: &gstd_types::Seek) -> Result<gstd_types::Response, Error> {
+        self.emit_event_with_description("seek", &seek.description())
+            .await
+    }
+
+    /// Performs `POST pipelines/{name}/event?name=step&description={step}`
+    /// API request, returning the parsed [`gstd_types::Response`]
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn step(&self, step: &gstd_types::Step) -> Result<gstd_types::Response, Error> {
+        self.emit_event_with_description("step", &step.description())
+            .await
+    }
+
+    /// Performs `GET /pipelines/{name}/refcount`
+    /// API request, returning the parsed [`gstd_types::Response`]
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn refcount(&self) -> Result<gstd_types::Response, Error> {
+        self.client
+            .read(&format!("pipelines/{}/refcount", &self.name))
+            .await
+    }
+
     /// Performs `POST pipelines/{name}/event?name=eos`
     /// API request, returning the parsed [`gstd_types::Response`]
     ///
@@ -217,6 +284,25 @@ impl Pipeline {
         let resp = self.client.post(url).await?;
         self.client.process_resp(resp).await
     }
+
+    /// Performs `POST pipelines/{name}/event?name=flush_stop&description={reset}`
+    /// API request, returning the parsed [`gstd_types::Response`]
+    ///
+    /// # Arguments
+    ///
+    /// * `reset` - reset the running time of the pipeline
+    ///
+    /// # Errors
+    ///
+    /// If API request cannot be performed, or fails.
+    /// See [`Error`] for details.
+    pub async fn emit_event_flush_stop_reset(
+        &self,
+        reset: bool,
+    ) -> Result<gstd_types::Response, Error> {
+        let val = if reset { "true" } else { "false" };
+        self.emit_event_with_description("flush_stop", val).await
+    }
     /// Performs `PUT pipelines/{name}/state?name=playing`
     /// API request, returning the parsed [`gstd_types::Response`]
     ///
@@ -0,0 +1,47 @@
+#!/usr/bin/env bash
+# Records gstd bus message payloads into this directory, replacing the fixtures read by client.rs tests
+#
+# Usage:
+#   gstd --enable-http-protocol --http-address=127.0.0.1 --http-port=5000 &
+#   ./capture.sh [http://127.0.0.1:5000]
+#
+# Requires curl, gstreamer1.0-plugins-base and gstreamer1.0-plugins-good (jpegenc, multifilesink)
+set -euo pipefail
+
+GSTD_URL="${1:-http://127.0.0.1:5000}"
+DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+TMP="$(mktemp -d)"
+trap 'rm -rf "$TMP"' EXIT
+
+# 5 seconds, in nanoseconds
+BUS_TIMEOUT=5000000000
+
+# capture <fixture> <bus filter> <pipeline description>
+capture() {
+    local fixture="$1" filter="$2" description="$3"
+    local name="capture_${fixture//-/_}"
+    curl -sf -X POST "$GSTD_URL/pipelines" \
+        --data-urlencode "name=$name" \
+        --data-urlencode "description=$description" -G >/dev/null
+    curl -sf -X PUT "$GSTD_URL/pipelines/$name/bus/types?name=$filter" >/dev/null
+    curl -sf -X PUT "$GSTD_URL/pipelines/$name/bus/timeout?name=$BUS_TIMEOUT" >/dev/null
+    curl -sf -X PUT "$GSTD_URL/pipelines/$name/state?name=playing" >/dev/null || true
+    curl -sf "$GSTD_URL/pipelines/$name/bus/message" >"$DIR/$fixture.json"
+    curl -sf -X PUT "$GSTD_URL/pipelines/$name/state?name=null" >/dev/null || true
+    curl -sf -X DELETE "$GSTD_URL/pipelines?name=$name" >/dev/null
+    echo "$fixture.json | $filter | $description"
+}
+
+{
+    echo "gstd: $(gstd --version | head -n1)"
+    echo "gstreamer: $(gst-inspect-1.0 --version | sed -n 2p)"
+    echo
+    echo "fixture | bus filter | pipeline"
+    capture error error "filesrc location=$TMP/missing ! fakesink"
+    capture warning warning "videotestsrc is-live=true ! video/x-raw,framerate=30/1 ! identity sleep-time=50000 ! fakesink sync=true qos=true"
+    capture state-changed state-changed "videotestsrc is-live=true ! fakesink"
+    capture qos qos "videotestsrc is-live=true ! video/x-raw,framerate=30/1 ! identity sleep-time=50000 ! fakesink sync=true qos=true"
+    capture latency latency "videotestsrc is-live=true ! fakesink sync=true"
+    capture eos eos "videotestsrc num-buffers=10 ! fakesink"
+    capture element-multifilesink element "videotestsrc num-buffers=3 ! jpegenc ! multifilesink location=$TMP/%05d.jpg post-messages=true"
+} | tee "$DIR/CAPTURED"
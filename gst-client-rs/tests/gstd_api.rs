@@ -0,0 +1,355 @@
+//! Replays recorded [`GStreamer Daemon`][1] HTTP API responses from
+//! `fixtures/gstd_responses.json`, checking each request is sent to the
+//! recorded method, path and query. Retries and timeouts are checked
+//! against a server responding with scripted statuses.
+//!
+//! [1]: https://developer.ridgerun.com/wiki/index.php/GStreamer_Daemon
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use gst_client_rs::{gstd_types, Error, GstClient, GstClientConfig, RetryPolicy};
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const GSTD_RESPONSES: &str = include_str!("fixtures/gstd_responses.json");
+
+#[derive(Debug, Deserialize)]
+struct RecordedExchange {
+    method: String,
+    path: String,
+    /// Query string as sent on the wire, `None` if the request had no query
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default = "default_status")]
+    status: u16,
+    body: serde_json::Value,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+async fn read_request_head(socket: &mut TcpStream) -> String {
+    let mut head = vec![];
+    let mut buf = [0; 1024];
+    while !head.ends_with(b"\r\n\r\n") {
+        let n = socket.read(&mut buf).await.unwrap();
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    String::from_utf8(head).unwrap()
+}
+
+async fn replay(mut socket: TcpStream, exchanges: Arc<Vec<RecordedExchange>>) {
+    let head = read_request_head(&mut socket).await;
+    let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (target, None),
+    };
+
+    let exchange = exchanges
+        .iter()
+        .find(|e| e.method == method && e.path == path && e.query.as_deref() == query);
+    let (status, body) = match exchange {
+        Some(exchange) => (exchange.status, exchange.body.to_string()),
+        None => (501, format!("No recorded response for {method} {target}")),
+    };
+    let resp = format!(
+        "HTTP/1.1 {status} Replay\r\nContent-Type: application/json\r\n\
+        Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    socket.write_all(resp.as_bytes()).await.unwrap();
+    socket.shutdown().await.unwrap();
+}
+
+// serve recorded responses on a random port, returning a client for it
+async fn replay_client() -> GstClient {
+    let exchanges: Arc<Vec<RecordedExchange>> =
+        Arc::new(serde_json::from_str(GSTD_RESPONSES).unwrap());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            tokio::spawn(replay(socket, exchanges.clone()));
+        }
+    });
+    GstClient::build(base_url).unwrap()
+}
+
+#[tokio::test]
+async fn replay_pipeline_lifecycle() {
+    let client = replay_client().await;
+
+    match client.pipelines().await.unwrap().response {
+        gstd_types::ResponseT::Properties(props) => {
+            assert_eq!(props.node_names(), vec!["camera", "h264_record"]);
+        }
+        other => panic!("Expected pipeline list, got {other:?}"),
+    }
+
+    let pipeline = client.pipeline("camera");
+    pipeline
+        .create("videotestsrc name=src is-live=true ! fakesink name=sink signal-handoffs=true")
+        .await
+        .unwrap();
+    pipeline.play().await.unwrap();
+    pipeline.pause().await.unwrap();
+    match pipeline.state().await.unwrap().response {
+        gstd_types::ResponseT::Property(prop) => {
+            assert_eq!(
+                prop.value,
+                gstd_types::PropertyValue::String("paused".into())
+            );
+        }
+        other => panic!("Expected state property, got {other:?}"),
+    }
+    match pipeline.elements().await.unwrap().response {
+        gstd_types::ResponseT::Properties(props) => {
+            assert_eq!(props.node_names(), vec!["sink", "src"]);
+        }
+        other => panic!("Expected element list, got {other:?}"),
+    }
+    pipeline.delete().await.unwrap();
+}
+
+#[tokio::test]
+async fn replay_pipeline_events() {
+    let client = replay_client().await;
+    let pipeline = client.pipeline("camera");
+
+    pipeline
+        .seek(&gstd_types::Seek::to_time(5_000_000_000))
+        .await
+        .unwrap();
+    pipeline.step(&gstd_types::Step::frames(1)).await.unwrap();
+    pipeline.emit_event_flush_start().await.unwrap();
+    pipeline.emit_event_flush_stop_reset(true).await.unwrap();
+    pipeline.emit_event_eos().await.unwrap();
+}
+
+#[tokio::test]
+async fn replay_element_properties() {
+    let client = replay_client().await;
+    let src = client.pipeline("camera").element("src");
+
+    let props = match src.properties().await.unwrap().response {
+        gstd_types::ResponseT::Properties(props) => props,
+        other => panic!("Expected property list, got {other:?}"),
+    };
+    let num_buffers = props.get("num-buffers").unwrap();
+    assert_eq!(num_buffers.value, gstd_types::PropertyValue::Integer(-1));
+    assert_eq!(num_buffers.param._type, "gint");
+    assert_eq!(
+        props.get("is-live").unwrap().value,
+        gstd_types::PropertyValue::Bool(true)
+    );
+    assert_eq!(
+        props.get("foreground-color").unwrap().value,
+        gstd_types::PropertyValue::Integer64(4_294_967_295)
+    );
+    assert_eq!(
+        props.get("max-lateness").unwrap().value,
+        gstd_types::PropertyValue::UnsignedInteger64(u64::MAX)
+    );
+    assert_eq!(
+        props.get("kx2").unwrap().value,
+        gstd_types::PropertyValue::Double(0.5)
+    );
+    assert_eq!(
+        props.get("caps").unwrap().value,
+        gstd_types::PropertyValue::Null
+    );
+    assert_eq!(props.get("caps").unwrap().param._type, "GstCaps");
+
+    src.set_property("pattern", "ball").await.unwrap();
+}
+
+#[tokio::test]
+async fn replay_element_signals() {
+    let client = replay_client().await;
+    let sink = client.pipeline("camera").element("sink");
+
+    match sink.signals().await.unwrap().response {
+        gstd_types::ResponseT::Properties(props) => {
+            assert_eq!(props.node_names(), vec!["handoff", "preroll-handoff"]);
+        }
+        other => panic!("Expected signal list, got {other:?}"),
+    }
+
+    sink.set_signal_timeout("handoff", "1000000").await.unwrap();
+    match sink.signal_connect("handoff").await.unwrap().response {
+        gstd_types::ResponseT::SignalCallback(callback) => {
+            assert_eq!(callback.name, "handoff");
+            let types: Vec<&str> = callback
+                .arguments
+                .iter()
+                .map(|arg| arg.r#type.as_str())
+                .collect();
+            assert_eq!(types, vec!["GstFakeSink", "GstBuffer", "GstPad"]);
+        }
+        other => panic!("Expected signal callback, got {other:?}"),
+    }
+    sink.signal_disconnect("handoff").await.unwrap();
+}
+
+#[tokio::test]
+async fn replay_refcount() {
+    let client = replay_client().await;
+    let pipeline = client.pipeline("camera");
+
+    let refcount = |res: gstd_types::Response| match res.response {
+        gstd_types::ResponseT::Property(prop) => prop.value,
+        other => panic!("Expected refcount property, got {other:?}"),
+    };
+    assert_eq!(
+        refcount(pipeline.refcount().await.unwrap()),
+        gstd_types::PropertyValue::Integer(2)
+    );
+    assert_eq!(
+        refcount(pipeline.element("src").refcount().await.unwrap()),
+        gstd_types::PropertyValue::Integer(3)
+    );
+    assert_eq!(
+        refcount(client.read("pipelines/camera/refcount").await.unwrap()),
+        gstd_types::PropertyValue::Integer(2)
+    );
+}
+
+#[tokio::test]
+async fn replay_bus_and_debug() {
+    let client = replay_client().await;
+
+    let watch = client
+        .pipeline("camera")
+        .bus()
+        .watch("error+eos", 1_000_000_000)
+        .await
+        .unwrap();
+    let msg = watch.next().await.unwrap().unwrap();
+    assert_eq!(msg.body().unwrap(), gstd_types::BusMessageBody::Eos);
+
+    client.debug().enable().await.unwrap();
+    client.debug().threshold("3").await.unwrap();
+}
+
+#[tokio::test]
+async fn replay_missing_pipeline() {
+    let client = replay_client().await;
+
+    match client.pipeline("missing").state().await {
+        Err(Error::BadStatus(status, Some(body))) => {
+            assert_eq!(status.as_u16(), 404);
+            assert!(body.contains("Resource not found"));
+        }
+        other => panic!("Expected 404, got {other:?}"),
+    }
+}
+
+// respond to the n-th request with statuses[n], or never respond once statuses run out
+async fn scripted_server(statuses: Vec<u16>) -> (String, Arc<AtomicUsize>) {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let counter = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            let status = statuses.get(n).copied();
+            tokio::spawn(async move {
+                read_request_head(&mut socket).await;
+                let Some(status) = status else {
+                    // hold the connection open without responding
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    return;
+                };
+                let body =
+                    r#"{"code":0,"description":"Success","response":{"properties":[],"nodes":[]}}"#;
+                let resp = format!(
+                    "HTTP/1.1 {status} Scripted\r\nContent-Type: application/json\r\n\
+                    Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                socket.write_all(resp.as_bytes()).await.unwrap();
+                socket.shutdown().await.unwrap();
+            });
+        }
+    });
+    (base_url, requests)
+}
+
+fn fast_retry_config() -> GstClientConfig {
+    GstClientConfig {
+        retry: RetryPolicy {
+            max_retries: 2,
+            backoff: Duration::from_millis(1),
+        },
+        ..GstClientConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn retry_idempotent_requests() {
+    let (base_url, requests) = scripted_server(vec![503, 503, 200]).await;
+    let client = GstClient::build_with_config(base_url, fast_retry_config()).unwrap();
+
+    client.pipelines().await.unwrap();
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn retry_gives_up_after_max_retries() {
+    let (base_url, requests) = scripted_server(vec![503, 503, 503, 200]).await;
+    let client = GstClient::build_with_config(base_url, fast_retry_config()).unwrap();
+
+    match client.pipeline("camera").play().await {
+        Err(Error::BadStatus(status, _)) => assert_eq!(status.as_u16(), 503),
+        other => panic!("Expected 503, got {other:?}"),
+    }
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn no_retry_for_create() {
+    let (base_url, requests) = scripted_server(vec![503, 200]).await;
+    let client = GstClient::build_with_config(base_url, fast_retry_config()).unwrap();
+
+    assert!(client
+        .pipeline("camera")
+        .create("fakesrc ! fakesink")
+        .await
+        .is_err());
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn request_timeout() {
+    let (base_url, requests) = scripted_server(vec![]).await;
+    let client = GstClient::build_with_config(base_url, fast_retry_config())
+        .unwrap()
+        .with_timeout(Some(Duration::from_millis(50)));
+
+    // idempotent requests are retried after timing out
+    match client.pipelines().await {
+        Err(Error::RequestFailed(e)) => assert!(e.is_timeout(), "{e:?}"),
+        other => panic!("Expected timeout, got {other:?}"),
+    }
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+    // bus reads are not retried, because gstd may have consumed a message
+    let bus = client
+        .with_retry(RetryPolicy::NONE)
+        .pipeline("camera")
+        .bus();
+    assert!(bus.read().await.is_err());
+    assert_eq!(requests.load(Ordering::SeqCst), 4);
+}
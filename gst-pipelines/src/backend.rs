@@ -0,0 +1,791 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use file_lock::{FileLock, FileOptions};
+use gst::glib::translate::{from_glib, IntoGlib};
+use gst::prelude::*;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use gst_client::gstd_types::{BusMessage, PropertyValue, ResponseT};
+use gst_client::reqwest;
+use gst_client::resources::BusWatch;
+use gst_client::GstClient;
+
+use crate::factory::GstPipelineState;
+
+// timestamp reported by gstd for messages without a timestamp (GST_CLOCK_TIME_NONE)
+const CLOCK_TIME_NONE: &str = "99:99:99.999999999";
+// descriptions of pipelines created in gstd, shared by every process connected to gstd
+// stored in the runtime dir, which is cleared on boot like gstd's pipelines
+// entries are pruned when gstd no longer lists the pipeline, e.g. after gstd restarts without a reboot
+pub const DEFAULT_GSTD_DESCRIPTIONS_FILE: &str = "/var/run/printnanny/gstd-pipelines.json";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PipelineBackendKind {
+    // pipelines are created in gstd, controlled over its HTTP API
+    Gstd,
+    // pipelines run inside the current process, controlled with gstreamer-rs
+    InProcess,
+}
+
+impl FromStr for PipelineBackendKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().replace('_', "-").as_str() {
+            "gstd" => Ok(PipelineBackendKind::Gstd),
+            "in-process" => Ok(PipelineBackendKind::InProcess),
+            other => Err(anyhow!("Unsupported pipeline backend={}", other)),
+        }
+    }
+}
+
+impl fmt::Display for PipelineBackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineBackendKind::Gstd => write!(f, "gstd"),
+            PipelineBackendKind::InProcess => write!(f, "in-process"),
+        }
+    }
+}
+
+// Creates and controls named pipelines described with gst-launch syntax
+#[async_trait]
+pub trait PipelineBackend: fmt::Debug + Send + Sync {
+    fn kind(&self) -> PipelineBackendKind;
+    // succeeds without changes if a pipeline with name already exists
+    async fn create(&self, name: &str, description: &str) -> Result<()>;
+    async fn delete(&self, name: &str) -> Result<()>;
+    async fn play(&self, name: &str) -> Result<()>;
+    async fn pause(&self, name: &str) -> Result<()>;
+    // set pipeline state to NULL
+    async fn stop(&self, name: &str) -> Result<()>;
+    async fn state(&self, name: &str) -> Result<GstPipelineState>;
+    async fn pipeline_names(&self) -> Result<Vec<String>>;
+    // description the pipeline was created with, None if it was created by another tool (or before descriptions were recorded)
+    async fn description(&self, name: &str) -> Result<Option<String>>;
+    async fn send_eos(&self, name: &str) -> Result<()>;
+    // pop the next bus message matching filter (gstd syntax, e.g. error+eos), waiting up to timeout
+    // messages which don't match filter are dropped, returns None if timeout elapsed
+    // each pipeline bus has a single reader: GstBusWatcher, or run_until_eos for pipelines it excludes
+    async fn read_bus(
+        &self,
+        name: &str,
+        filter: &str,
+        timeout: Duration,
+    ) -> Result<Option<BusMessage>>;
+}
+
+// gstd bus filter and timeout applied by GstdBackend::read_bus
+#[derive(Clone, Debug)]
+struct GstdBusWatch {
+    filter: String,
+    timeout: Duration,
+    watch: BusWatch,
+}
+
+// Pipelines created in gstd, shared by every process connected to gstd
+#[derive(Clone, Debug)]
+pub struct GstdBackend {
+    client: GstClient,
+    // gstd does not report pipeline descriptions, so they are recorded when pipelines are created
+    descriptions_file: PathBuf,
+    // gstd keeps one filter and timeout per pipeline bus, so they are applied once per pipeline by this process
+    bus_watches: Arc<Mutex<HashMap<String, GstdBusWatch>>>,
+}
+
+impl GstdBackend {
+    pub fn new(client: GstClient) -> Self {
+        Self {
+            client,
+            descriptions_file: DEFAULT_GSTD_DESCRIPTIONS_FILE.into(),
+            bus_watches: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn with_descriptions_file(self, descriptions_file: &Path) -> Self {
+        Self {
+            descriptions_file: descriptions_file.to_path_buf(),
+            ..self
+        }
+    }
+
+    // descriptions file I/O and its advisory lock block the calling thread, so they run with spawn_blocking
+    async fn read_descriptions(&self) -> Result<HashMap<String, String>> {
+        let descriptions_file = self.descriptions_file.clone();
+        Ok(tokio::task::spawn_blocking(move || read_descriptions_file(&descriptions_file)).await?)
+    }
+
+    // re-use the watch created by the first read_bus call for name, instead of re-applying filter and timeout
+    // a second reader with a different filter is rejected, because it would change the filter of the first reader
+    async fn bus_watch(&self, name: &str, filter: &str, timeout: Duration) -> Result<BusWatch> {
+        let existing = self.bus_watches.lock().unwrap().get(name).cloned();
+        if let Some(existing) = existing {
+            if existing.filter != filter || existing.timeout != timeout {
+                return Err(anyhow!(
+                    "Pipeline bus name={} is already read with filter={} timeout={:?}, refusing to apply filter={} timeout={:?}",
+                    name,
+                    existing.filter,
+                    existing.timeout,
+                    filter,
+                    timeout
+                ));
+            }
+            return Ok(existing.watch);
+        }
+        let watch = self
+            .client
+            .pipeline(name)
+            .bus()
+            .watch(filter, timeout.as_nanos() as u64)
+            .await?;
+        info!(
+            "Applied pipeline bus name={} filter={} timeout={:?}",
+            name, filter, timeout
+        );
+        self.bus_watches.lock().unwrap().insert(
+            name.to_string(),
+            GstdBusWatch {
+                filter: filter.to_string(),
+                timeout,
+                watch: watch.clone(),
+            },
+        );
+        Ok(watch)
+    }
+
+    // modify returns false if descriptions were not changed, see modify_descriptions_file
+    async fn modify_descriptions<F>(&self, modify: F) -> Result<()>
+    where
+        F: FnOnce(&mut HashMap<String, String>) -> bool + Send + 'static,
+    {
+        let descriptions_file = self.descriptions_file.clone();
+        tokio::task::spawn_blocking(move || modify_descriptions_file(&descriptions_file, modify))
+            .await?
+    }
+
+    async fn update_descriptions(&self, name: &str, description: Option<&str>) -> Result<()> {
+        let key = name.to_string();
+        let description = description.map(|description| description.to_string());
+        self.modify_descriptions(move |descriptions| match description {
+            Some(description) => {
+                descriptions.insert(key, description);
+                true
+            }
+            None => descriptions.remove(&key).is_some(),
+        })
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "Failed to write pipeline descriptions file={} name={} error={}",
+                self.descriptions_file.display(),
+                name,
+                e
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl PipelineBackend for GstdBackend {
+    fn kind(&self) -> PipelineBackendKind {
+        PipelineBackendKind::Gstd
+    }
+
+    async fn create(&self, name: &str, description: &str) -> Result<()> {
+        match self.client.pipeline(name).create(description).await {
+            Ok(_) => self.update_descriptions(name, Some(description)).await,
+            // keep the description recorded by the process which created the pipeline
+            // otherwise record description, so the existing pipeline isn't treated as changed and re-created
+            Err(gst_client::Error::BadStatus(reqwest::StatusCode::CONFLICT, body)) => {
+                info!(
+                    "Pipeline with name={} already exists, body={:?}",
+                    name, body
+                );
+                let key = name.to_string();
+                let description = description.to_string();
+                self.modify_descriptions(move |descriptions| {
+                    if descriptions.contains_key(&key) {
+                        return false;
+                    }
+                    descriptions.insert(key, description);
+                    true
+                })
+                .await
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, name: &str) -> Result<()> {
+        self.client.pipeline(name).delete().await?;
+        self.bus_watches.lock().unwrap().remove(name);
+        self.update_descriptions(name, None).await
+    }
+
+    async fn play(&self, name: &str) -> Result<()> {
+        self.client.pipeline(name).play().await?;
+        Ok(())
+    }
+
+    async fn pause(&self, name: &str) -> Result<()> {
+        self.client.pipeline(name).pause().await?;
+        Ok(())
+    }
+
+    async fn stop(&self, name: &str) -> Result<()> {
+        self.client.pipeline(name).stop().await?;
+        Ok(())
+    }
+
+    async fn state(&self, name: &str) -> Result<GstPipelineState> {
+        let res = self.client.pipeline(name).state().await?;
+        match res.response {
+            ResponseT::Property(prop) => match prop.value {
+                PropertyValue::String(state) => Ok(GstPipelineState::from(state.as_ref())),
+                other => Err(anyhow!(
+                    "Received invalid state={:?} for pipeline={}",
+                    other,
+                    name
+                )),
+            },
+            _ => Err(anyhow!(
+                "Received invalid response to GET /pipelines/{}/state",
+                name
+            )),
+        }
+    }
+
+    async fn pipeline_names(&self) -> Result<Vec<String>> {
+        let res = self.client.pipelines().await?;
+        match res.response {
+            ResponseT::Properties(props) => {
+                let names = props.node_names();
+                let listed = names.clone();
+                // pipelines missing from gstd were deleted by another tool, or gstd restarted
+                if let Err(e) = self
+                    .modify_descriptions(move |descriptions| {
+                        let count = descriptions.len();
+                        descriptions.retain(|name, _| listed.contains(name));
+                        descriptions.len() != count
+                    })
+                    .await
+                {
+                    warn!(
+                        "Failed to prune pipeline descriptions file={} error={}",
+                        self.descriptions_file.display(),
+                        e
+                    );
+                }
+                Ok(names)
+            }
+            _ => Err(anyhow!("Received invalid response to GET /pipelines")),
+        }
+    }
+
+    async fn description(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.read_descriptions().await?.remove(name))
+    }
+
+    async fn send_eos(&self, name: &str) -> Result<()> {
+        self.client.pipeline(name).emit_event_eos().await?;
+        Ok(())
+    }
+
+    async fn read_bus(
+        &self,
+        name: &str,
+        filter: &str,
+        timeout: Duration,
+    ) -> Result<Option<BusMessage>> {
+        let watch = self.bus_watch(name, filter, timeout).await?;
+        match watch.next().await {
+            Ok(message) => Ok(message),
+            Err(e) => {
+                // pipeline was deleted or gstd restarted, the next read applies filter and timeout again
+                self.bus_watches.lock().unwrap().remove(name);
+                Err(e.into())
+            }
+        }
+    }
+}
+
+// Pipelines running inside this process, e.g. a single service running the whole pipeline graph
+// interpipesink/interpipesrc only connect pipelines created by the same process
+#[derive(Debug)]
+pub struct InProcessBackend {
+    pipelines: Mutex<HashMap<String, gst::Pipeline>>,
+    descriptions: Mutex<HashMap<String, String>>,
+}
+
+impl InProcessBackend {
+    pub fn new() -> Result<Self> {
+        gst::init()?;
+        Ok(Self {
+            pipelines: Mutex::new(HashMap::new()),
+            descriptions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn pipeline(&self, name: &str) -> Result<gst::Pipeline> {
+        self.pipelines
+            .lock()
+            .unwrap()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Pipeline name={} does not exist", name))
+    }
+
+    fn set_state(&self, name: &str, state: gst::State) -> Result<()> {
+        let pipeline = self.pipeline(name)?;
+        pipeline.set_state(state).map_err(|e| {
+            anyhow!(
+                "Failed to set pipeline={} state={:?} error={}",
+                name,
+                state,
+                e
+            )
+        })?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl PipelineBackend for InProcessBackend {
+    fn kind(&self) -> PipelineBackendKind {
+        PipelineBackendKind::InProcess
+    }
+
+    async fn create(&self, name: &str, description: &str) -> Result<()> {
+        if self.pipelines.lock().unwrap().contains_key(name) {
+            info!("Pipeline with name={} already exists", name);
+            return Ok(());
+        }
+        let pipeline = gst::parse_launch(description)?
+            .downcast::<gst::Pipeline>()
+            .map_err(|_| anyhow!("Description of pipeline={} is not a pipeline", name))?;
+        pipeline.set_property("name", name);
+        self.pipelines
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), pipeline);
+        self.descriptions
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), description.to_string());
+        Ok(())
+    }
+
+    async fn delete(&self, name: &str) -> Result<()> {
+        let pipeline = self
+            .pipelines
+            .lock()
+            .unwrap()
+            .remove(name)
+            .ok_or_else(|| anyhow!("Pipeline name={} does not exist", name))?;
+        self.descriptions.lock().unwrap().remove(name);
+        // release devices and streaming threads, like gstd does when deleting a pipeline
+        pipeline.set_state(gst::State::Null)?;
+        Ok(())
+    }
+
+    async fn play(&self, name: &str) -> Result<()> {
+        self.set_state(name, gst::State::Playing)
+    }
+
+    async fn pause(&self, name: &str) -> Result<()> {
+        self.set_state(name, gst::State::Paused)
+    }
+
+    async fn stop(&self, name: &str) -> Result<()> {
+        self.set_state(name, gst::State::Null)
+    }
+
+    async fn state(&self, name: &str) -> Result<GstPipelineState> {
+        let pipeline = self.pipeline(name)?;
+        Ok(GstPipelineState::from(pipeline.current_state()))
+    }
+
+    async fn pipeline_names(&self) -> Result<Vec<String>> {
+        let mut names: Vec<String> = self.pipelines.lock().unwrap().keys().cloned().collect();
+        names.sort();
+        Ok(names)
+    }
+
+    async fn description(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.descriptions.lock().unwrap().get(name).cloned())
+    }
+
+    async fn send_eos(&self, name: &str) -> Result<()> {
+        let pipeline = self.pipeline(name)?;
+        match pipeline.send_event(gst::event::Eos::new()) {
+            true => Ok(()),
+            false => Err(anyhow!("Pipeline={} did not handle EOS event", name)),
+        }
+    }
+
+    async fn read_bus(
+        &self,
+        name: &str,
+        filter: &str,
+        timeout: Duration,
+    ) -> Result<Option<BusMessage>> {
+        let pipeline = self.pipeline(name)?;
+        let bus = pipeline
+            .bus()
+            .ok_or_else(|| anyhow!("Pipeline={} does not have a bus", name))?;
+        let types = message_types(filter)?;
+        let timeout = gst::ClockTime::from_nseconds(timeout.as_nanos() as u64);
+        // bus reads block the calling thread
+        let msg = tokio::task::spawn_blocking(move || match types.is_empty() {
+            true => bus.timed_pop(timeout),
+            false => bus.timed_pop_filtered(timeout, &types),
+        })
+        .await?;
+        Ok(msg.map(|msg| bus_message(&msg)))
+    }
+}
+
+fn read_descriptions_file(descriptions_file: &Path) -> HashMap<String, String> {
+    match fs::read(descriptions_file) {
+        Ok(contents) => serde_json::from_slice(&contents).unwrap_or_else(|e| {
+            warn!(
+                "Failed to parse pipeline descriptions file={} error={}",
+                descriptions_file.display(),
+                e
+            );
+            HashMap::new()
+        }),
+        Err(_) => HashMap::new(),
+    }
+}
+
+// read-modify-write descriptions_file under an advisory lock, because it's shared by the CLI, nats-apps and the reconciler
+// the file is replaced with rename, so read_descriptions_file never observes a partial write
+fn modify_descriptions_file<F>(descriptions_file: &Path, modify: F) -> Result<()>
+where
+    F: FnOnce(&mut HashMap<String, String>) -> bool,
+{
+    let dir = descriptions_file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(dir)?;
+    let lock_file = descriptions_file.with_extension("lock");
+    let _lock = FileLock::lock(
+        &lock_file,
+        true,
+        FileOptions::new().write(true).create(true),
+    )?;
+    let mut descriptions = read_descriptions_file(descriptions_file);
+    if !modify(&mut descriptions) {
+        return Ok(());
+    }
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(&mut tmp, &descriptions)?;
+    tmp.as_file().sync_all()?;
+    tmp.persist(descriptions_file)?;
+    Ok(())
+}
+
+impl From<gst::State> for GstPipelineState {
+    fn from(state: gst::State) -> Self {
+        match state {
+            gst::State::Playing => GstPipelineState::Playing,
+            gst::State::Paused => GstPipelineState::Paused,
+            gst::State::Ready => GstPipelineState::Ready,
+            _ => GstPipelineState::Null,
+        }
+    }
+}
+
+// message types of a gstd bus filter, e.g. error+eos. An empty filter matches every message type
+// like gstd, a filter containing an unknown message type is rejected instead of matching every message
+pub fn message_types(filter: &str) -> Result<Vec<gst::MessageType>> {
+    if filter.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    filter
+        .split('+')
+        .map(|name| match name.trim().replace('_', "-").as_str() {
+            "error" => Ok(gst::MessageType::Error),
+            "warning" => Ok(gst::MessageType::Warning),
+            "info" => Ok(gst::MessageType::Info),
+            "eos" => Ok(gst::MessageType::Eos),
+            "state-changed" => Ok(gst::MessageType::StateChanged),
+            "element" => Ok(gst::MessageType::Element),
+            "qos" => Ok(gst::MessageType::Qos),
+            "latency" => Ok(gst::MessageType::Latency),
+            "stream-status" => Ok(gst::MessageType::StreamStatus),
+            "async-done" => Ok(gst::MessageType::AsyncDone),
+            _ => Err(anyhow!(
+                "Unsupported bus message type={:?} in filter={}",
+                name,
+                filter
+            )),
+        })
+        .collect()
+}
+
+// MessageType debug names are CamelCase, gst_message_type_get_name() names are kebab-case
+fn to_kebab_case(name: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            result.push('-');
+        }
+        result.extend(c.to_lowercase());
+    }
+    result
+}
+
+fn state_name(state: gst::State) -> &'static str {
+    match state {
+        gst::State::VoidPending => "VOID_PENDING",
+        gst::State::Null => "NULL",
+        gst::State::Ready => "READY",
+        gst::State::Paused => "PAUSED",
+        gst::State::Playing => "PLAYING",
+    }
+}
+
+fn clock_time_ns(time: Option<gst::ClockTime>) -> u64 {
+    time.map(|t| t.nseconds()).unwrap_or(u64::MAX)
+}
+
+fn value_to_json(value: &gst::glib::Value) -> serde_json::Value {
+    if let Ok(v) = value.get::<bool>() {
+        return v.into();
+    }
+    if let Ok(v) = value.get::<i32>() {
+        return v.into();
+    }
+    if let Ok(v) = value.get::<u32>() {
+        return v.into();
+    }
+    if let Ok(v) = value.get::<i64>() {
+        return v.into();
+    }
+    if let Ok(v) = value.get::<u64>() {
+        return v.into();
+    }
+    if let Ok(v) = value.get::<f64>() {
+        return v.into();
+    }
+    if let Ok(v) = value.get::<String>() {
+        return v.into();
+    }
+    // formatted like gstd, e.g. (GstFileSink) sink
+    if let Ok(obj) = value.get::<gst::Object>() {
+        return format!("({}) {}", obj.type_().name(), obj.name()).into();
+    }
+    value
+        .serialize()
+        .map(|v| serde_json::Value::String(v.to_string()))
+        .unwrap_or(serde_json::Value::Null)
+}
+
+fn structure_to_json(s: &gst::StructureRef) -> serde_json::Map<String, serde_json::Value> {
+    s.iter()
+        .map(|(field, value)| (field.to_string(), value_to_json(value)))
+        .collect()
+}
+
+// convert a message into gstd's bus message format, so consumers don't depend on the backend
+pub fn bus_message(msg: &gst::Message) -> BusMessage {
+    let mut fields = serde_json::Map::new();
+    let r#type = match msg.view() {
+        gst::MessageView::Error(err) => {
+            let error = err.error();
+            fields.insert("message".into(), error.message().into());
+            fields.insert("debug".into(), err.debug().map(|d| d.to_string()).into());
+            fields.insert("domain".into(), error.domain().as_str().to_string().into());
+            "error".to_string()
+        }
+        gst::MessageView::Warning(warning) => {
+            let error = warning.error();
+            fields.insert("message".into(), error.message().into());
+            fields.insert(
+                "debug".into(),
+                warning.debug().map(|d| d.to_string()).into(),
+            );
+            fields.insert("domain".into(), error.domain().as_str().to_string().into());
+            "warning".to_string()
+        }
+        gst::MessageView::StateChanged(sc) => {
+            fields.insert("old-state".into(), state_name(sc.old()).into());
+            fields.insert("new-state".into(), state_name(sc.current()).into());
+            fields.insert("pending-state".into(), state_name(sc.pending()).into());
+            "state-changed".to_string()
+        }
+        gst::MessageView::Qos(qos) => {
+            let (live, running_time, stream_time, _timestamp, duration) = qos.get();
+            let (jitter, proportion, quality) = qos.values();
+            fields.insert("live".into(), live.into());
+            fields.insert("running-time".into(), clock_time_ns(running_time).into());
+            fields.insert("stream-time".into(), clock_time_ns(stream_time).into());
+            fields.insert("duration".into(), clock_time_ns(duration).into());
+            fields.insert("jitter".into(), jitter.into());
+            fields.insert("proportion".into(), proportion.into());
+            fields.insert("quality".into(), quality.into());
+            "qos".to_string()
+        }
+        _ => {
+            // element messages nest structure fields under the structure name, like gstd
+            if let Some(s) = msg.structure() {
+                fields.insert(
+                    s.name().to_string(),
+                    serde_json::Value::Object(structure_to_json(s)),
+                );
+            }
+            to_kebab_case(&format!("{:?}", msg.type_()))
+        }
+    };
+    // gstreamer-rs has no getter for GST_MESSAGE_TIMESTAMP
+    let timestamp: Option<gst::ClockTime> = unsafe { from_glib((*msg.as_ptr()).timestamp) };
+    BusMessage {
+        r#type,
+        source: msg
+            .src()
+            .map(|src| src.name().to_string())
+            .unwrap_or_default(),
+        timestamp: timestamp
+            .map(|ts| ts.to_string())
+            .unwrap_or_else(|| CLOCK_TIME_NONE.to_string()),
+        seqnum: msg.seqnum().into_glib() as i64,
+        fields,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gst_client::gstd_types::BusMessageType;
+
+    #[test]
+    fn test_backend_kind() {
+        assert_eq!(
+            PipelineBackendKind::from_str("in_process").unwrap(),
+            PipelineBackendKind::InProcess
+        );
+        assert_eq!(
+            PipelineBackendKind::from_str("gstd").unwrap(),
+            PipelineBackendKind::Gstd
+        );
+        assert!(PipelineBackendKind::from_str("gst-launch").is_err());
+        assert_eq!(PipelineBackendKind::InProcess.to_string(), "in-process");
+    }
+
+    #[test]
+    fn test_message_types() {
+        assert_eq!(
+            message_types("error+state_changed+eos").unwrap(),
+            vec![
+                gst::MessageType::Error,
+                gst::MessageType::StateChanged,
+                gst::MessageType::Eos
+            ]
+        );
+        assert!(message_types("").unwrap().is_empty());
+        // a filter of only unknown names must not match every message type
+        assert!(message_types("errors").is_err());
+        assert!(message_types("error+bogus").is_err());
+        assert!(message_types("error+").is_err());
+        assert_eq!(to_kebab_case("StreamStatus"), "stream-status");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_gstd_descriptions_concurrent_updates() {
+        let dir = std::env::temp_dir().join(format!(
+            "printnanny-gstd-descriptions-test-{}",
+            std::process::id()
+        ));
+        let descriptions_file = dir.join("gstd-pipelines.json");
+        let backend = GstdBackend::new(GstClient::build("http://127.0.0.1:5001").unwrap())
+            .with_descriptions_file(&descriptions_file);
+
+        // every process shares descriptions_file, so concurrent writers must not lose entries
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let backend = backend.clone();
+                tokio::spawn(async move {
+                    backend
+                        .update_descriptions(
+                            &format!("camera{}", i),
+                            Some("videotestsrc ! fakesink"),
+                        )
+                        .await
+                        .unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        let descriptions = backend.read_descriptions().await.unwrap();
+        assert_eq!(descriptions.len(), 8);
+
+        backend.update_descriptions("camera0", None).await.unwrap();
+        assert!(!backend
+            .read_descriptions()
+            .await
+            .unwrap()
+            .contains_key("camera0"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_in_process_backend() {
+        let backend = InProcessBackend::new().unwrap();
+        let name = "test_in_process";
+        backend
+            .create(name, "videotestsrc num-buffers=5 ! fakesink")
+            .await
+            .unwrap();
+        // creating an existing pipeline is a no-op, like gstd
+        backend
+            .create(name, "videotestsrc ! fakesink")
+            .await
+            .unwrap();
+        assert_eq!(backend.pipeline_names().await.unwrap(), vec![name]);
+        assert_eq!(
+            backend.description(name).await.unwrap().as_deref(),
+            Some("videotestsrc num-buffers=5 ! fakesink")
+        );
+
+        backend.play(name).await.unwrap();
+        let mut eos = false;
+        for _ in 0..10 {
+            if let Some(msg) = backend
+                .read_bus(name, "eos+error", Duration::from_secs(1))
+                .await
+                .unwrap()
+            {
+                assert_eq!(msg.message_type(), BusMessageType::Eos, "{:?}", msg);
+                eos = true;
+                break;
+            }
+        }
+        assert!(eos);
+        assert_eq!(
+            backend.state(name).await.unwrap(),
+            GstPipelineState::Playing
+        );
+
+        backend.stop(name).await.unwrap();
+        assert_eq!(backend.state(name).await.unwrap(), GstPipelineState::Null);
+        backend.delete(name).await.unwrap();
+        assert!(backend.pipeline_names().await.unwrap().is_empty());
+        assert_eq!(backend.description(name).await.unwrap(), None);
+        assert!(backend.state(name).await.is_err());
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use futures_util::StreamExt;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use gst_client::gstd_types::{BusMessage, BusMessageType};
+use printnanny_nats_client::client::wait_for_nats_client;
+
+use crate::backend::{message_types, PipelineBackendKind};
+use crate::factory::{PrintNannyPipelineFactory, EXPORT_PIPELINE, TIMELAPSE_ASSEMBLE_PIPELINE};
+
+// published by GstBusWatcher, which is the only reader of pipeline buses
+pub const BUS_SUBJECT_PATTERN: &str = "pi.{pi_id}.gst.{pipeline}.bus.{message_type}";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PipelineBusMessage {
+    pub pipeline: String,
+    pub message: BusMessage,
+}
+
+impl PipelineBusMessage {
+    pub fn subject(&self, pi_id: &str) -> String {
+        BUS_SUBJECT_PATTERN
+            .replace("{pi_id}", pi_id)
+            .replace("{pipeline}", &subject_token(&self.pipeline))
+            .replace(
+                "{message_type}",
+                &subject_token(self.message.message_type().as_str()),
+            )
+    }
+
+    // subject matching message_type messages of every pipeline
+    pub fn wildcard_subject(pi_id: &str, message_type: &BusMessageType) -> String {
+        BUS_SUBJECT_PATTERN
+            .replace("{pi_id}", pi_id)
+            .replace("{pipeline}", "*")
+            .replace("{message_type}", &subject_token(message_type.as_str()))
+    }
+}
+
+// "." separates NATS subject tokens, "*" and ">" are wildcards
+fn subject_token(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '.' | '*' | '>' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+// receive message_type messages of every pipeline published by GstBusWatcher, until the NATS subscription closes
+// messages are dropped if the receiver falls more than buffer messages behind
+pub async fn subscribe_bus_messages(
+    nats_server_uri: &str,
+    nats_creds: &Option<PathBuf>,
+    pi_id: &str,
+    message_type: &BusMessageType,
+    buffer: usize,
+) -> Result<mpsc::Receiver<PipelineBusMessage>> {
+    let nats_client = wait_for_nats_client(nats_server_uri, nats_creds, false, 2000).await?;
+    let subject = PipelineBusMessage::wildcard_subject(pi_id, message_type);
+    let mut subscriber = nats_client
+        .subscribe(subject.clone())
+        .await
+        .map_err(|e| anyhow!("Failed to subscribe to subject={} error={}", subject, e))?;
+    info!("Subscribed to bus messages subject={}", subject);
+
+    let (sender, receiver) = mpsc::channel(buffer.max(1));
+    tokio::spawn(async move {
+        while let Some(message) = subscriber.next().await {
+            let msg: PipelineBusMessage = match serde_json::from_slice(&message.payload) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    error!(
+                        "Failed to parse bus message subject={} error={}",
+                        message.subject, e
+                    );
+                    continue;
+                }
+            };
+            match sender.try_send(msg) {
+                Ok(()) => (),
+                Err(mpsc::error::TrySendError::Full(msg)) => warn!(
+                    "Bus subscriber is full, dropping message pipeline={} type={}",
+                    msg.pipeline, msg.message.r#type
+                ),
+                // receiver was dropped
+                Err(mpsc::error::TrySendError::Closed(_)) => break,
+            }
+        }
+        warn!("Bus message subscription closed subject={}", subject);
+    });
+    Ok(receiver)
+}
+
+pub const DEFAULT_BUS_FILTER: &str = "error+warning+eos+state-changed+element";
+// pipelines which read their own bus, see PrintNannyPipelineFactory::run_until_eos
+// also matches names namespaced by camera (nozzle_timelapse_assemble) or recording (export_{video_recording_id})
+pub const DEFAULT_EXCLUDED_PIPELINES: [&str; 2] = [TIMELAPSE_ASSEMBLE_PIPELINE, EXPORT_PIPELINE];
+
+// bus reads block for up to 5 seconds, so watchers notice deleted pipelines
+const BUS_TIMEOUT: Duration = Duration::from_secs(5);
+const PIPELINE_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug)]
+struct BusSubscription {
+    // None matches every pipeline
+    pipeline: Option<String>,
+    // empty matches every message type
+    message_types: Vec<BusMessageType>,
+    sender: mpsc::Sender<PipelineBusMessage>,
+}
+
+impl BusSubscription {
+    fn matches(&self, msg: &PipelineBusMessage) -> bool {
+        let pipeline_matches = match &self.pipeline {
+            Some(pipeline) => pipeline == &msg.pipeline,
+            None => true,
+        };
+        pipeline_matches
+            && (self.message_types.is_empty()
+                || self.message_types.contains(&msg.message.message_type()))
+    }
+}
+
+// Watches the bus of every pipeline, publishing messages to pi.{pi_id}.gst.{pipeline}.bus.{message_type}
+// gstd delivers each bus message to a single reader, so handlers subscribe to the watcher instead of reading pipeline buses
+// runs in printnanny-nats-gstmultifile for gstd pipelines, and in the process running the in-process backend otherwise
+pub struct GstBusWatcher {
+    factory: PrintNannyPipelineFactory,
+    hostname: String,
+    filter: String,
+    excluded_pipelines: Vec<String>,
+    nats_client: Option<async_nats::Client>,
+    subscriptions: Mutex<Vec<BusSubscription>>,
+}
+
+impl GstBusWatcher {
+    pub fn new(factory: PrintNannyPipelineFactory, hostname: &str) -> Self {
+        Self {
+            factory,
+            hostname: hostname.to_string(),
+            filter: DEFAULT_BUS_FILTER.to_string(),
+            excluded_pipelines: DEFAULT_EXCLUDED_PIPELINES
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            nats_client: None,
+            subscriptions: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn with_nats_client(mut self, nats_client: async_nats::Client) -> Self {
+        self.nats_client = Some(nats_client);
+        self
+    }
+
+    pub fn with_filter(mut self, filter: &str) -> Self {
+        self.filter = filter.to_string();
+        self
+    }
+
+    // receive messages of message_types (all types if empty) from pipeline (all pipelines if None)
+    // messages are dropped if the receiver falls more than buffer messages behind
+    pub fn subscribe(
+        &self,
+        pipeline: Option<&str>,
+        message_types: &[BusMessageType],
+        buffer: usize,
+    ) -> mpsc::Receiver<PipelineBusMessage> {
+        let (sender, receiver) = mpsc::channel(buffer.max(1));
+        self.subscriptions.lock().unwrap().push(BusSubscription {
+            pipeline: pipeline.map(|p| p.to_string()),
+            message_types: message_types.to_vec(),
+            sender,
+        });
+        receiver
+    }
+
+    fn is_excluded(&self, pipeline: &str) -> bool {
+        self.excluded_pipelines.iter().any(|excluded| {
+            pipeline == excluded
+                || pipeline.starts_with(&format!("{excluded}_"))
+                || pipeline.ends_with(&format!("_{excluded}"))
+        })
+    }
+
+    async fn publish(&self, msg: &PipelineBusMessage) {
+        let nats_client = match &self.nats_client {
+            Some(nats_client) => nats_client,
+            None => return,
+        };
+        let subject = msg.subject(&self.hostname);
+        match serde_json::to_vec(msg) {
+            Ok(payload) => {
+                if let Err(e) = nats_client.publish(subject.clone(), payload.into()).await {
+                    error!("Failed to publish subject={} error={}", subject, e);
+                }
+            }
+            Err(e) => error!("Failed to serialize bus message error={}", e),
+        }
+    }
+
+    fn dispatch(&self, msg: &PipelineBusMessage) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.retain(|subscription| {
+            if !subscription.matches(msg) {
+                return true;
+            }
+            match subscription.sender.try_send(msg.clone()) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    warn!(
+                        "Bus subscriber is full, dropping message pipeline={} type={}",
+                        msg.pipeline, msg.message.r#type
+                    );
+                    true
+                }
+                // receiver was dropped
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            }
+        });
+    }
+
+    async fn watch_pipeline(self: Arc<Self>, pipeline: String) -> Result<()> {
+        let backend = self.factory.backend().clone();
+        info!(
+            "Watching bus pipeline={} filter={} backend={}",
+            &pipeline,
+            &self.filter,
+            backend.kind()
+        );
+        loop {
+            let message = match backend
+                .read_bus(&pipeline, &self.filter, BUS_TIMEOUT)
+                .await?
+            {
+                Some(message) => message,
+                None => continue,
+            };
+            debug!(
+                "Received bus message pipeline={} msg={:?}",
+                &pipeline, &message
+            );
+            let msg = PipelineBusMessage {
+                pipeline: pipeline.clone(),
+                message,
+            };
+            self.publish(&msg).await;
+            self.dispatch(&msg);
+        }
+    }
+
+    // watch pipelines as they are created, until the process exits
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        // in-process buses are filtered by message_types, so reject an unknown filter once instead of failing every watch
+        if self.factory.backend().kind() == PipelineBackendKind::InProcess {
+            message_types(&self.filter)?;
+        }
+        let mut watchers: HashMap<String, JoinHandle<()>> = HashMap::new();
+        loop {
+            watchers.retain(|_, handle| !handle.is_finished());
+            match self.factory.pipeline_names().await {
+                Ok(pipelines) => {
+                    for pipeline in pipelines {
+                        if self.is_excluded(&pipeline) || watchers.contains_key(&pipeline) {
+                            continue;
+                        }
+                        let watcher = self.clone();
+                        let name = pipeline.clone();
+                        let handle = tokio::spawn(async move {
+                            // pipeline was deleted or gstd restarted, pipeline is watched again on the next refresh if it exists
+                            if let Err(e) = watcher.watch_pipeline(name.clone()).await {
+                                warn!("Stopped watching bus pipeline={} error={}", name, e);
+                            }
+                        });
+                        watchers.insert(pipeline, handle);
+                    }
+                }
+                Err(e) => warn!("Failed to list pipelines error={}", e),
+            }
+            tokio::time::sleep(PIPELINE_REFRESH_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gst_client::gstd_types::BusMessage;
+
+    fn bus_message(pipeline: &str, message_type: &str) -> PipelineBusMessage {
+        PipelineBusMessage {
+            pipeline: pipeline.into(),
+            message: BusMessage {
+                r#type: message_type.into(),
+                source: "pipeline0".into(),
+                timestamp: "0:00:01.000000000".into(),
+                seqnum: 1,
+                fields: serde_json::Map::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_bus_message_subject() {
+        assert_eq!(
+            bus_message("h264_record", "state_changed").subject("octopi"),
+            "pi.octopi.gst.h264_record.bus.state-changed"
+        );
+        assert_eq!(
+            bus_message("camera.0 main", "eos").subject("octopi"),
+            "pi.octopi.gst.camera_0_main.bus.eos"
+        );
+    }
+
+    #[test]
+    fn test_excluded_pipelines() {
+        let watcher = GstBusWatcher::new(PrintNannyPipelineFactory::default(), "octopi");
+        assert!(watcher.is_excluded("timelapse_assemble"));
+        assert!(watcher.is_excluded("nozzle_timelapse_assemble"));
+        assert!(watcher.is_excluded("export_1"));
+        assert!(!watcher.is_excluded("timelapse"));
+        assert!(!watcher.is_excluded("h264_record"));
+    }
+
+    #[test]
+    fn test_bus_subscription() {
+        let factory = PrintNannyPipelineFactory::default();
+        let watcher = GstBusWatcher::new(factory, "octopi");
+        let mut errors = watcher.subscribe(None, &[BusMessageType::Error], 1);
+        let mut recording = watcher.subscribe(Some("h264_record"), &[], 10);
+        let closed = watcher.subscribe(None, &[], 1);
+        drop(closed);
+
+        watcher.dispatch(&bus_message("camera", "error"));
+        watcher.dispatch(&bus_message("h264_record", "element"));
+        watcher.dispatch(&bus_message("h264_record", "error"));
+
+        assert_eq!(errors.try_recv().unwrap().pipeline, "camera");
+        // subscriber buffer was full
+        assert!(errors.try_recv().is_err());
+        assert_eq!(recording.try_recv().unwrap().message.r#type, "element");
+        assert_eq!(recording.try_recv().unwrap().message.r#type, "error");
+        // dropped receiver is unsubscribed
+        assert_eq!(watcher.subscriptions.lock().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,160 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+
+use printnanny_edge_db::video_recording::VideoRecordingPart;
+
+// VideoRecordingPart files of one recording, ordered by buffer_index
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordingExport {
+    pub video_recording_id: String,
+    pub parts: Vec<PathBuf>,
+    // parts which were uploaded or removed by the retention manager
+    pub missing: usize,
+    pub output: PathBuf,
+}
+
+impl RecordingExport {
+    pub fn new(
+        sqlite_connection: &str,
+        video_recording_id: &str,
+        export_dir: &Path,
+    ) -> Result<Self> {
+        let rows = VideoRecordingPart::get_parts_by_video_recording_id(
+            sqlite_connection,
+            video_recording_id,
+        )?;
+        let total = rows.len();
+        let parts = ordered_parts(rows);
+        let missing = total - parts.len();
+        if missing > 0 {
+            warn!(
+                "Exporting VideoRecording id={} without missing parts count={}",
+                video_recording_id, missing
+            );
+        }
+        let extension = match parts.first() {
+            Some(part) => part
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("ts")
+                .to_string(),
+            None => {
+                return Err(anyhow!(
+                    "VideoRecording id={} has no local parts to export",
+                    video_recording_id
+                ))
+            }
+        };
+        Ok(Self {
+            video_recording_id: video_recording_id.to_string(),
+            parts,
+            missing,
+            output: export_dir.join(format!("{video_recording_id}.{extension}")),
+        })
+    }
+
+    pub fn extension(&self) -> &str {
+        self.output
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+    }
+
+    // mpegts parts are self-contained, so they can be concatenated byte-for-byte
+    // other containers are remuxed by PrintNannyPipelineFactory::export_video_recording
+    pub fn is_concatenable(&self) -> bool {
+        self.extension() == "ts"
+    }
+
+    // output is up to date if it was written after the newest part
+    pub fn is_current(&self) -> bool {
+        let output_modified = match self.output.metadata().and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(_) => return false,
+        };
+        self.parts.iter().all(|part| {
+            part.metadata()
+                .and_then(|m| m.modified())
+                .map(|modified| modified <= output_modified)
+                .unwrap_or(false)
+        })
+    }
+
+    pub fn concat(&self) -> Result<PathBuf> {
+        if let Some(parent) = self.output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // write to a temporary file, so a partial export is never served
+        let tmp = self.output.with_extension("tmp");
+        let mut writer = fs::File::create(&tmp)?;
+        for part in self.parts.iter() {
+            let mut reader = fs::File::open(part)?;
+            io::copy(&mut reader, &mut writer)?;
+        }
+        writer.sync_all()?;
+        fs::rename(&tmp, &self.output)?;
+        info!(
+            "Exported VideoRecording id={} parts={} output={}",
+            self.video_recording_id,
+            self.parts.len(),
+            self.output.display()
+        );
+        Ok(self.output.clone())
+    }
+}
+
+// local part files ordered by buffer_index, skipping deleted parts
+pub fn ordered_parts(mut rows: Vec<VideoRecordingPart>) -> Vec<PathBuf> {
+    rows.sort_by_key(|row| row.buffer_index);
+    rows.into_iter()
+        .filter(|row| !row.deleted)
+        .map(|row| PathBuf::from(row.file_name))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_concat() {
+        let dir =
+            std::env::temp_dir().join(format!("printnanny-export-test-{}", std::process::id()));
+        let recording = dir.join("recording");
+        fs::create_dir_all(&recording).unwrap();
+        fs::write(recording.join("0.ts"), b"first").unwrap();
+        fs::write(recording.join("1.ts"), b"second").unwrap();
+        fs::write(recording.join("10.ts"), b"third").unwrap();
+
+        let row = |index: i64, deleted: bool| VideoRecordingPart {
+            id: format!("recording__{index}"),
+            buffer_index: index,
+            deleted,
+            file_name: recording.join(format!("{index}.ts")).display().to_string(),
+            video_recording_id: "recording".into(),
+            ..VideoRecordingPart::default()
+        };
+        let rows = vec![row(10, false), row(0, false), row(2, false), row(1, true)];
+        let parts = ordered_parts(rows);
+        // 1.ts is deleted and 2.ts does not exist
+        assert_eq!(parts, vec![recording.join("0.ts"), recording.join("10.ts")]);
+
+        let export = RecordingExport {
+            video_recording_id: "recording".into(),
+            parts,
+            missing: 2,
+            output: dir.join("exports/recording.ts"),
+        };
+        assert!(export.is_concatenable());
+        assert!(!export.is_current());
+        let output = export.concat().unwrap();
+        assert_eq!(fs::read(output).unwrap(), b"firstthird");
+        assert!(export.is_current());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,15 +1,30 @@
+use std::collections::HashMap;
 use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use clap::ArgMatches;
-use gst_client::reqwest;
-use gst_client::GstClient;
+use gst_client::gstd_types::BusMessageBody;
+use gst_client::{GstClient, GstClientConfig};
 use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
 use tokio::time::{sleep, Duration};
 
-use printnanny_settings::cam::VideoStreamSettings;
+use printnanny_settings::cam::{
+    CameraSrcElement, DetectionOverlaySettings, GstElementSettings, GstElementTarget,
+    H264EncoderElement, JpegEncoderElement, PipelineNodeKind, RecordingFormatSettings,
+    TimelapseTrigger, VideoConvertElement, VideoSource, VideoStreamSettings,
+};
+use printnanny_settings::model::ModelManifest;
 use printnanny_settings::printnanny::PrintNannySettings;
-use printnanny_settings::printnanny_os_models::CameraSettings;
+
+use crate::backend::{GstdBackend, InProcessBackend, PipelineBackend, PipelineBackendKind};
+use crate::export::RecordingExport;
+use crate::graph::{PipelineGraph, PipelineGraphDiff, PipelineNode};
+use crate::preroll::PrerollBuffer;
+use crate::timelapse::TimelapseDir;
+use crate::validate::{PipelineRenderReport, RenderedPipeline};
 
 pub const CAMERA_PIPELINE: &str = "camera";
 pub const H264_ENCODING_PIPELINE: &str = "h264_encode";
@@ -19,17 +34,40 @@ pub const BB_PIPELINE: &str = "bounding_boxes";
 pub const DF_WINDOW_PIPELINE: &str = "df";
 pub const SNAPSHOT_PIPELINE: &str = "snapshot";
 pub const HLS_PIPELINE: &str = "hls";
+pub const PREROLL_PIPELINE: &str = "preroll";
 pub const H264_RECORDING_PIPELINE: &str = "h264_record";
 pub const H264_SPLITMUXSINK: &str = "h264_splitmuxsink";
+pub const TIMELAPSE_PIPELINE: &str = "timelapse";
+pub const TIMELAPSE_ASSEMBLE_PIPELINE: &str = "timelapse_assemble";
+pub const EXPORT_PIPELINE: &str = "export";
+// recording pipeline of any camera, e.g. h264_record or {camera_id}_h264_record
+pub fn is_recording_pipeline_name(name: &str) -> bool {
+    name == H264_RECORDING_PIPELINE || name.ends_with(&format!("_{H264_RECORDING_PIPELINE}"))
+}
+
+// maximum time to wait for a timelapse to be assembled
+const TIMELAPSE_ASSEMBLE_TIMEOUT: Duration = Duration::from_secs(600);
+// maximum time to wait for a recording to be remuxed
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(1800);
+// run_until_eos bus reads block for up to 1 second
+const EOS_BUS_TIMEOUT: Duration = Duration::from_secs(1);
+const DEFAULT_GSTD_ADDRESS: &str = "127.0.0.1";
+const DEFAULT_GSTD_PORT: i32 = 5002;
 
 #[derive(Clone, Debug)]
 pub struct PrintNannyPipelineFactory {
     pub address: String,
     pub port: i32,
     pub uri: String,
+    // pooled gstd client, shared by every clone of the factory
+    client: GstClient,
+    backend: Arc<dyn PipelineBackend>,
+    // elements selected for "auto" GstElementSettings, resolved once when the factory is constructed
+    elements: GstElementSettings,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum GstPipelineState {
     Paused,
     Playing,
@@ -51,52 +89,109 @@ impl From<&str> for GstPipelineState {
 
 impl Default for PrintNannyPipelineFactory {
     fn default() -> Self {
-        let address = "127.0.0.1".to_string();
-        let port = 5002;
-        let uri = Self::uri(&address, port);
-        Self { address, port, uri }
+        // the default uri is always valid, so this only fails if the http client's TLS backend can't be initialized
+        Self::new(DEFAULT_GSTD_ADDRESS.to_string(), DEFAULT_GSTD_PORT)
+            .expect("Failed to build default PrintNannyPipelineFactory")
     }
 }
 
-impl From<&ArgMatches> for PrintNannyPipelineFactory {
-    fn from(args: &ArgMatches) -> Self {
-        let defaults = PrintNannyPipelineFactory::default();
-        let address = args.value_of("http-address").unwrap_or(&defaults.address);
-        let port: i32 = args.value_of_t("http-port").unwrap_or(defaults.port);
+impl TryFrom<&ArgMatches> for PrintNannyPipelineFactory {
+    type Error = anyhow::Error;
+
+    fn try_from(args: &ArgMatches) -> Result<Self> {
+        let address = args
+            .value_of("http-address")
+            .unwrap_or(DEFAULT_GSTD_ADDRESS);
+        let port: i32 = args.value_of_t("http-port").unwrap_or(DEFAULT_GSTD_PORT);
         Self::new(address.to_string(), port)
     }
 }
 
 impl PrintNannyPipelineFactory {
-    pub fn new(address: String, port: i32) -> Self {
+    // pipelines are created in gstd listening on address:port
+    pub fn new(address: String, port: i32) -> Result<Self> {
+        Self::with_config(address, port, GstClientConfig::default())
+    }
+
+    // pipelines are created in gstd, using a client with connection pool, timeouts and retries configured by config
+    pub fn with_config(address: String, port: i32, config: GstClientConfig) -> Result<Self> {
         let uri = Self::uri(&address, port);
-        Self { address, port, uri }
+        let client = GstClient::build_with_config(&uri, config)?;
+        let backend = Arc::new(GstdBackend::new(client.clone()));
+        Ok(Self {
+            address,
+            port,
+            uri,
+            client,
+            backend,
+            elements: GstElementTarget::Host.elements(),
+        })
+    }
+
+    // select the backend at runtime. address:port is only used by the gstd backend
+    pub fn with_backend_kind(
+        address: String,
+        port: i32,
+        kind: PipelineBackendKind,
+    ) -> Result<Self> {
+        let factory = Self::new(address, port)?;
+        match kind {
+            PipelineBackendKind::Gstd => Ok(factory),
+            PipelineBackendKind::InProcess => {
+                Ok(factory.with_backend(Arc::new(InProcessBackend::new()?)))
+            }
+        }
+    }
+
+    pub fn with_backend(self, backend: Arc<dyn PipelineBackend>) -> Self {
+        Self { backend, ..self }
+    }
+
+    // render "auto" elements for target instead of this host's element registry
+    pub fn with_elements(self, target: GstElementTarget) -> Self {
+        Self {
+            elements: target.elements(),
+            ..self
+        }
+    }
+
+    // replace "auto" elements of each camera with the elements resolved when the factory was constructed
+    fn resolve_elements(&self, cameras: &[VideoStreamSettings]) -> Vec<VideoStreamSettings> {
+        cameras
+            .iter()
+            .map(|settings| Self::resolve_camera_elements(settings, &self.elements))
+            .collect()
+    }
+
+    fn resolve_camera_elements(
+        settings: &VideoStreamSettings,
+        elements: &GstElementSettings,
+    ) -> VideoStreamSettings {
+        let mut settings = settings.clone();
+        settings.elements = settings.elements.resolve_with(elements);
+        settings
     }
+
+    pub fn backend(&self) -> &Arc<dyn PipelineBackend> {
+        &self.backend
+    }
+
     pub fn uri(address: &str, port: i32) -> String {
         format!("http://{}:{}", address, port)
     }
 
-    pub fn gst_client(&self) -> GstClient {
-        GstClient::build(&self.uri).expect("Failed to build GstClient")
+    pub fn gst_client(&self) -> &GstClient {
+        &self.client
     }
 
     pub async fn pipeline_state(&self, pipeline_name: &str) -> GstPipelineState {
-        let client = self.gst_client();
-        match client.pipeline(pipeline_name).state().await {
-            Ok(state_res) => match state_res.response {
-                gst_client::gstd_types::ResponseT::Property(prop) => match prop.value {
-                    gst_client::gstd_types::PropertyValue::String(state) => {
-                        GstPipelineState::from(state.as_ref())
-                    }
-                    _ => GstPipelineState::Null,
-                },
-                _ => GstPipelineState::Null,
-            },
+        match self.backend.state(pipeline_name).await {
+            Ok(state) => state,
             Err(e) => {
                 // H264_RECORDING_PIPELINE state is polled every N seconds, 404ing when pipeline doesn't exist
                 // log these at the debug! level, and all other pipelines at the error! level
                 match pipeline_name {
-                    H264_RECORDING_PIPELINE => debug!(
+                    name if is_recording_pipeline_name(name) => debug!(
                         "Error getting gst pipeline state name={} error={}",
                         pipeline_name, e
                     ),
@@ -118,43 +213,30 @@ impl PrintNannyPipelineFactory {
         format!("{pipeline_name}_sink")
     }
 
-    async fn delete_pipeline(&self, pipeline_name: &str) -> Result<gst_client::Response> {
-        let client = GstClient::build(&self.uri).expect("Failed to build GstClient");
-        let pipeline = client.pipeline(pipeline_name);
-        Ok(pipeline.delete().await?)
+    async fn delete_pipeline(&self, pipeline_name: &str) -> Result<()> {
+        self.backend.delete(pipeline_name).await
     }
 
-    async fn make_pipeline(
-        &self,
-        pipeline_name: &str,
-        description: &str,
-    ) -> Result<gst_client::resources::Pipeline> {
+    // create pipeline_name, succeeding if it already exists
+    async fn make_pipeline(&self, pipeline_name: &str, description: &str) -> Result<()> {
         info!(
             "Creating {} pipeline with description: {}",
             pipeline_name, &description
         );
-        let client = GstClient::build(&self.uri).expect("Failed to build GstClient");
-        let pipeline = client.pipeline(pipeline_name);
-        match pipeline.create(description).await {
-            Ok(result) => {
-                info!("Created pipeline={}: {:?}", pipeline_name, result);
+        match self.backend.create(pipeline_name, description).await {
+            Ok(()) => {
+                info!(
+                    "Created pipeline={} backend={}",
+                    pipeline_name,
+                    self.backend.kind()
+                );
                 Ok(())
             }
             Err(e) => {
                 error!("Error creating pipeline name={} error={}", pipeline_name, e);
-                match e {
-                    gst_client::Error::BadStatus(reqwest::StatusCode::CONFLICT, ref body) => {
-                        info!(
-                            "Pipeline with name={} already exists, body={:?}",
-                            pipeline_name, body
-                        );
-                        Ok(())
-                    }
-                    _ => Err(e),
-                }
+                Err(e)
             }
-        }?;
-        Ok(pipeline)
+        }
     }
 
     // wait for pipeline to be available
@@ -174,30 +256,90 @@ impl PrintNannyPipelineFactory {
         Ok(())
     }
 
-    async fn make_camera_pipeline(
-        &self,
-        pipeline_name: &str,
+    fn camera_src_description(
+        elements: &GstElementSettings,
         settings: &VideoStreamSettings,
-    ) -> Result<gst_client::resources::Pipeline> {
+    ) -> String {
+        // decoded media is converted, scaled and rate-limited to match gst_camera_caps
+        let decoded = "videoconvert ! videoscale ! videorate";
+        match settings.video_source() {
+            // multifilesrc loop=true replays the file's bytes, so MediaVideoSource::validate only allows looping mpegts files
+            VideoSource::File(media) if media.loop_playback => format!(
+                "multifilesrc location={location} loop=true ! decodebin ! {decoded}",
+                location = media.file_location()
+            ),
+            VideoSource::File(media) | VideoSource::Uri(media) => {
+                format!("uridecodebin uri={uri} ! {decoded}", uri = media.gst_uri())
+            }
+            VideoSource::CSI(_) | VideoSource::USB(_) => match elements.camera_src {
+                CameraSrcElement::Libcamera => format!(
+                    "libcamerasrc camera-name={camera_name}",
+                    camera_name = settings.camera.device_name
+                ),
+                CameraSrcElement::VideoTest => "videotestsrc is-live=true pattern=ball".into(),
+            },
+        }
+    }
+
+    // descriptions are rendered from resolved GstElementSettings, see PrintNannyPipelineFactory::resolve_elements
+    // an unresolved "auto" element renders the Raspberry Pi hardware element
+    fn video_convert_description(elements: &GstElementSettings) -> &'static str {
+        match elements.video_convert {
+            VideoConvertElement::V4l2 | VideoConvertElement::Auto => "v4l2convert",
+            VideoConvertElement::VideoConvert => "videoconvert",
+        }
+    }
+
+    fn jpeg_encoder_description(elements: &GstElementSettings) -> &'static str {
+        match elements.jpeg_encoder {
+            JpegEncoderElement::V4l2 | JpegEncoderElement::Auto => "v4l2jpegenc",
+            JpegEncoderElement::Jpeg => "jpegenc",
+        }
+    }
+
+    // encode raw video to h264, ending with h264parse element
+    fn h264_encoder_description(elements: &GstElementSettings, h264parse_name: &str) -> String {
+        let convert = Self::video_convert_description(elements);
+        match elements.h264_encoder {
+            // v4l2h264enc doesn't set level/profile in caps, which are required by downstream rtp and hls pipelines
+            H264EncoderElement::V4l2 | H264EncoderElement::Auto => format!(
+                "v4l2h264enc extra-controls=controls,repeat_sequence_header=1 \
+                ! h264parse name={h264parse_name} \
+                ! capssetter caps=video/x-h264,level=(string)4,profile=(string)high"
+            ),
+            H264EncoderElement::X264 => format!(
+                "{convert} \
+                ! x264enc tune=zerolatency speed-preset=ultrafast key-int-max=60 byte-stream=true \
+                ! h264parse name={h264parse_name}"
+            ),
+            H264EncoderElement::OpenH264 => format!(
+                "{convert} \
+                ! openh264enc gop-size=60 complexity=low \
+                ! h264parse name={h264parse_name}"
+            ),
+        }
+    }
+
+    fn camera_pipeline_description(pipeline_name: &str, settings: &VideoStreamSettings) -> String {
         let interpipesink = Self::to_interpipesink_name(pipeline_name);
         let caps = settings.gst_camera_caps();
+        let elements = &settings.elements;
+        let src = Self::camera_src_description(elements, settings);
+        let convert = Self::video_convert_description(elements);
 
-        let description = format!(
-            "libcamerasrc camera-name={camera_name} \
+        format!(
+            "{src} \
             ! capsfilter caps={caps} \
-            ! v4l2convert \
+            ! {convert} \
             ! interpipesink name={interpipesink} sync=true async=false",
-            camera_name = settings.camera.device_name,
-        );
-        self.make_pipeline(pipeline_name, &description).await
+        )
     }
 
-    async fn make_jpeg_snapshot_pipeline(
-        &self,
+    fn jpeg_snapshot_pipeline_description(
         pipeline_name: &str,
         listen_to: &str,
         settings: &VideoStreamSettings,
-    ) -> Result<gst_client::resources::Pipeline> {
+    ) -> String {
         let interpipesrc = Self::to_interpipesrc_name(pipeline_name);
         let listen_to = Self::to_interpipesink_name(listen_to);
 
@@ -205,56 +347,118 @@ impl PrintNannyPipelineFactory {
 
         let max_buffers = 30;
         let caps = settings.gst_camera_caps();
-        let description = format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=false max-buffers={max_buffers} leaky-type=2 caps={caps} \
-            ! v4l2jpegenc ! multifilesink location={filesink_location} max-files={max_buffers}",
-        );
-        self.make_pipeline(pipeline_name, &description).await
+        let jpegenc = Self::jpeg_encoder_description(&settings.elements);
+        format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=false max-buffers={max_buffers} leaky-type=2 caps={caps} \
+            ! {jpegenc} ! multifilesink location={filesink_location} max-files={max_buffers}",
+        )
     }
 
-    async fn make_h264_encode_pipeline(
-        &self,
+    // always-on ring buffer of mpegts fragments, see PrerollBuffer
+    fn preroll_pipeline_description(
         pipeline_name: &str,
         listen_to: &str,
         settings: &VideoStreamSettings,
-    ) -> Result<gst_client::resources::Pipeline> {
+    ) -> String {
+        let interpipesrc = Self::to_interpipesrc_name(pipeline_name);
         let listen_to = Self::to_interpipesink_name(listen_to);
+
+        let location = PrerollBuffer::new(&settings.preroll.path).location();
+        let max_files = settings.preroll.max_files();
+        let max_size_time = settings.preroll.fragment_secs.max(1) as u64 * 1_000_000_000;
+
+        format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=true format=3 stream-sync=passthrough-ts \
+            ! queue \
+            ! splitmuxsink muxer=mpegtsmux name={pipeline_name}_splitmuxsink max-files={max_files} location={location} max-size-time={max_size_time} send-keyframe-requests=false")
+    }
+
+    // writes jpeg frames to TimelapseDir.frames (interval trigger) or TimelapseDir.latest (layer trigger)
+    fn timelapse_pipeline_description(
+        pipeline_name: &str,
+        listen_to: &str,
+        dir: &TimelapseDir,
+        settings: &VideoStreamSettings,
+    ) -> String {
         let interpipesrc = Self::to_interpipesrc_name(pipeline_name);
-        let interpipesink = Self::to_interpipesink_name(pipeline_name);
+        let listen_to = Self::to_interpipesink_name(listen_to);
 
-        let caps: String = settings.gst_camera_caps();
-        let description = format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=true caps={caps} \
-            ! v4l2h264enc extra-controls=controls,repeat_sequence_header=1 \
-            ! h264parse name={pipeline_name}_h264parse \
-            ! capssetter caps=video/x-h264,level=(string)4,profile=(string)high \
-            ! interpipesink name={interpipesink} sync=false async=false forward-events=true forward-eos=true",
+        let caps = settings.gst_camera_caps();
+        let jpegenc = Self::jpeg_encoder_description(&settings.elements);
+        let sink = match settings.timelapse.trigger {
+            TimelapseTrigger::Interval => format!(
+                "capsfilter caps=video/x-raw,framerate=1/{interval} \
+                ! {jpegenc} ! multifilesink location={location}",
+                interval = settings.timelapse.interval_secs,
+                location = dir.frames_location()
+            ),
+            // keep a rolling window of 1 frame/second, copied to frames/ by TimelapseDir::capture_latest_frame
+            TimelapseTrigger::Layer => format!(
+                "capsfilter caps=video/x-raw,framerate=1/1 \
+                ! {jpegenc} ! multifilesink location={location} max-files=3",
+                location = dir.latest_location()
+            ),
+        };
+        format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=false max-buffers=1 leaky-type=2 format=3 caps={caps} \
+            ! videorate drop-only=true skip-to-first=true \
+            ! {sink}",
+        )
+    }
+
+    // encode TimelapseDir.frames into TimelapseDir.output
+    fn timelapse_assemble_pipeline_description(
+        pipeline_name: &str,
+        dir: &TimelapseDir,
+        settings: &VideoStreamSettings,
+    ) -> String {
+        let framerate = settings.timelapse.framerate;
+        let location = dir.frames_location();
+        let output = dir.output().display().to_string();
+        let encoder = Self::h264_encoder_description(
+            &settings.elements,
+            &format!("{pipeline_name}_h264parse"),
         );
-        self.make_pipeline(pipeline_name, &description).await
+        format!(
+            "multifilesrc location={location} index=0 caps=image/jpeg,framerate={framerate}/1 \
+            ! jpegdec \
+            ! {encoder} \
+            ! mp4mux ! filesink location={output}",
+        )
     }
 
-    async fn make_rtp_pipeline(
-        &self,
+    fn h264_encode_pipeline_description(
         pipeline_name: &str,
         listen_to: &str,
         settings: &VideoStreamSettings,
-    ) -> Result<gst_client::resources::Pipeline> {
+    ) -> String {
         let listen_to = Self::to_interpipesink_name(listen_to);
         let interpipesrc = Self::to_interpipesrc_name(pipeline_name);
+        let interpipesink = Self::to_interpipesink_name(pipeline_name);
 
-        let port = settings.rtp.video_udp_port;
+        let caps: String = settings.gst_camera_caps();
+        let encoder = Self::h264_encoder_description(
+            &settings.elements,
+            &format!("{pipeline_name}_h264parse"),
+        );
+        format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=true caps={caps} \
+            ! {encoder} \
+            ! interpipesink name={interpipesink} sync=false async=false forward-events=true forward-eos=true",
+        )
+    }
+
+    fn rtp_pipeline_description(pipeline_name: &str, listen_to: &str, port: i32) -> String {
+        let listen_to = Self::to_interpipesink_name(listen_to);
+        let interpipesrc = Self::to_interpipesrc_name(pipeline_name);
 
-        let description = format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=true format=3 \
+        format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=true format=3 \
             ! rtph264pay config-interval=1 aggregate-mode=zero-latency pt=96 \
             ! queue2 \
-            ! udpsink port={port}");
-        self.make_pipeline(pipeline_name, &description).await
+            ! udpsink port={port}")
     }
 
-    async fn make_hls_pipeline(
-        &self,
+    fn hls_pipeline_description(
         pipeline_name: &str,
         listen_to: &str,
         settings: &VideoStreamSettings,
-    ) -> Result<gst_client::resources::Pipeline> {
+    ) -> String {
         let listen_to = Self::to_interpipesink_name(listen_to);
         let interpipesrc = Self::to_interpipesrc_name(pipeline_name);
 
@@ -275,53 +479,56 @@ impl PrintNannyPipelineFactory {
         let framerate_n = settings.camera.framerate_n;
         let target_duration = (60 / framerate_n) + 1; // v4l2-ctl --list-ctrls-menu -d 11 -> h264_i_frame_period default sends a key unit every 60 frames
 
-        let description = format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=true format=3 \
-            ! hlssink2 playlist-length=8 max-files=10 target-duration={target_duration} location={hls_segments_location} playlist-location={hls_playlist_location} playlist-root={hls_playlist_root} send-keyframe-requests=false");
-        self.make_pipeline(pipeline_name, &description).await
+        format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=true format=3 \
+            ! hlssink2 playlist-length=8 max-files=10 target-duration={target_duration} location={hls_segments_location} playlist-location={hls_playlist_location} playlist-root={hls_playlist_root} send-keyframe-requests=false")
     }
 
-    async fn make_inference_pipeline(
-        &self,
+    fn inference_pipeline_description(
         pipeline_name: &str,
         listen_to: &str,
         settings: &VideoStreamSettings,
-    ) -> Result<gst_client::resources::Pipeline> {
+        model: &ModelManifest,
+    ) -> String {
         let listen_to = Self::to_interpipesink_name(listen_to);
         let interpipesrc = Self::to_interpipesrc_name(pipeline_name);
         let interpipesink = Self::to_interpipesink_name(pipeline_name);
 
-        let tensor_format = "RGB"; // model expects pixel data to be in RGB format
         let caps: String = settings.gst_camera_caps();
 
-        let detection_settings = &*settings.detection;
-        let tensor_width = detection_settings.tensor_width;
-        let tensor_height = detection_settings.tensor_height;
-        let tflite_model_file = detection_settings.model_file.as_str();
+        // input tensor shape, pixel format and normalization are described by the model manifest
+        let tensor_format = model.input.format.as_str();
+        let tensor_width = model.input.width;
+        let tensor_height = model.input.height;
+        let tensor_transform = model.input.transform_option();
+        let tensor_dimensions = model.input.tensor_dimensions();
+        let framework = model.framework.as_str();
+        let model_file = model.model_file.display();
 
         let max_buffers = 3;
-        let description = format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=false max-buffers={max_buffers} leaky-type=2 caps={caps} \
-            ! v4l2convert ! videoscale ! capsfilter caps=video/x-raw,format={tensor_format},width={tensor_width},height={tensor_height} \
+        let convert = Self::video_convert_description(&settings.elements);
+        format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=false max-buffers={max_buffers} leaky-type=2 caps={caps} \
+            ! {convert} ! videoscale ! capsfilter caps=video/x-raw,format={tensor_format},width={tensor_width},height={tensor_height} \
             ! tensor_converter \
-            ! tensor_transform mode=arithmetic option=typecast:uint8,add:0,div:1 \
-            ! capsfilter caps=other/tensors,format=static \
-            ! tensor_filter framework=tensorflow2-lite model={tflite_model_file} \
+            ! tensor_transform mode=arithmetic option={tensor_transform} \
+            ! capsfilter caps=other/tensors,format=static,num_tensors=1,dimensions={tensor_dimensions} \
+            ! tensor_filter framework={framework} model={model_file} \
             ! interpipesink name={interpipesink} sync=false async=false",
-        );
-
-        self.make_pipeline(pipeline_name, &description).await
+        )
     }
 
-    async fn make_bounding_box_pipeline(
-        &self,
+    fn bounding_box_pipeline_description(
         pipeline_name: &str,
         listen_to: &str,
+        port: i32,
         settings: &VideoStreamSettings,
-    ) -> Result<gst_client::resources::Pipeline> {
+        model: &ModelManifest,
+    ) -> String {
         let listen_to = Self::to_interpipesink_name(listen_to);
         let interpipesrc = Self::to_interpipesrc_name(pipeline_name);
 
-        let port = settings.rtp.overlay_udp_port;
-        let detection = &(*settings.detection);
+        // mobilenet-ssd-postprocess compares the threshold in percent against raw scores
+        let score_threshold =
+            (model.score_threshold(settings.detection.nms_threshold) * 100_f32).round() as i32;
 
         // let colorimetry = "bt709";
 
@@ -337,58 +544,253 @@ impl PrintNannyPipelineFactory {
         //    (5): percent          - GST_FORMAT_PERCENT
         let caps: String = settings.gst_tensor_decoder_caps();
         let camera = &*settings.camera;
+        let elements = &settings.elements;
+        let convert = Self::video_convert_description(elements);
+        let encoder = Self::h264_encoder_description(elements, "bb_h264parse");
 
-        let description = format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=true accept-eos-event=false is-live=true allow-renegotiation=true \
-            ! tensor_decoder name=bb_tensor_decoder mode=bounding_boxes option1=mobilenet-ssd-postprocess option2={tflite_label_file} option3=0:1:2:3,{nms_threshold} option4={video_width}:{video_height} option5={tensor_width}:{tensor_height} \
+        format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=true accept-eos-event=false is-live=true allow-renegotiation=true \
+            ! tensor_decoder name=bb_tensor_decoder mode=bounding_boxes option1={layout} option2={label_file} option3={output_tensors},{score_threshold} option4={video_width}:{video_height} option5={tensor_width}:{tensor_height} \
             ! queue \
-            ! v4l2convert \
+            ! {convert} \
             ! capsfilter caps={caps} \
-            ! v4l2h264enc extra-controls=controls,repeat_sequence_header=1 \
-            ! h264parse \
-            ! capssetter caps=video/x-h264,level=(string)4,profile=(string)high \
+            ! {encoder} \
             ! rtph264pay config-interval=1 aggregate-mode=zero-latency pt=96 \
             ! udpsink port={port}",
-            nms_threshold=detection.nms_threshold,
-            tflite_label_file=detection.label_file,
-            tensor_height=detection.tensor_height,
-            tensor_width=detection.tensor_width,
+            layout=model.output.layout,
+            label_file=model.label_file.display(),
+            output_tensors=model.output.tensors,
+            tensor_height=model.input.height,
+            tensor_width=model.input.width,
             video_width=camera.width,
             video_height=camera.height,
 
-        );
-        self.make_pipeline(pipeline_name, &description).await
+        )
     }
 
-    async fn make_df_pipeline(
-        &self,
+    // bounding_box_overlay label text and per-class color properties, e.g. colors=nozzle:00BFFF,spaghetti:FF0000
+    fn overlay_properties(overlay: &DetectionOverlaySettings) -> String {
+        // property values are split on whitespace, so label text can't contain spaces
+        let labels: Vec<String> = overlay
+            .labels
+            .iter()
+            .map(|(label, text)| format!("{label}:{}", text.replace(char::is_whitespace, "-")))
+            .collect();
+        let colors: Vec<String> = overlay
+            .colors
+            .iter()
+            .map(|(label, color)| format!("{label}:{}", color.trim_start_matches('#')))
+            .collect();
+        let mut properties = vec![
+            format!(
+                "default-color={}",
+                overlay.default_color.trim_start_matches('#')
+            ),
+            format!("line-width={}", overlay.line_width),
+            format!("show-labels={}", overlay.show_labels),
+            format!("show-scores={}", overlay.show_scores),
+        ];
+        if !labels.is_empty() {
+            properties.push(format!("labels={}", labels.join(",")));
+        }
+        if !colors.is_empty() {
+            properties.push(format!("colors={}", colors.join(",")));
+        }
+        properties.join(" ")
+    }
+
+    // DetectionOverlayMode::Composite: draw bounding boxes onto camera frames, producing the annotated stream h264_encode listens to
+    // sink_0 receives camera frames, sink_1 receives transparent RGBA frames drawn by bounding_box_overlay
+    fn composite_bounding_box_pipeline_description(
+        pipeline_name: &str,
+        listen_to: &str,
+        frames_from: &str,
+        settings: &VideoStreamSettings,
+        model: &ModelManifest,
+    ) -> String {
+        let listen_to = Self::to_interpipesink_name(listen_to);
+        let frames_from = Self::to_interpipesink_name(frames_from);
+        let interpipesrc = Self::to_interpipesrc_name(pipeline_name);
+        let frames_interpipesrc = Self::to_interpipesrc_name(&format!("{pipeline_name}_frames"));
+        let interpipesink = Self::to_interpipesink_name(pipeline_name);
+        let compositor = format!("{pipeline_name}_compositor");
+
+        let camera = &*settings.camera;
+        let caps: String = settings.gst_camera_caps();
+        let convert = Self::video_convert_description(&settings.elements);
+        let score_threshold = model.score_threshold(settings.detection.nms_threshold);
+        let overlay_properties = Self::overlay_properties(&settings.detection_overlay);
+
+        format!("interpipesrc name={frames_interpipesrc} listen-to={frames_from} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=true caps={caps} \
+            ! queue \
+            ! {compositor}.sink_0 \
+            interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=false max-buffers=1 leaky-type=2 \
+            ! tensor_decoder name={pipeline_name}_tensor_decoder mode=custom-code option1=printnanny_bb_dataframe_decoder \
+            ! bounding_box_overlay name={pipeline_name}_overlay width={width} height={height} label-file={label_file} score-threshold={score_threshold} {overlay_properties} \
+            ! queue \
+            ! {compositor}.sink_1 \
+            compositor name={compositor} ignore-inactive-pads=true \
+            ! {convert} \
+            ! capsfilter caps={caps} \
+            ! interpipesink name={interpipesink} sync=false async=false forward-events=true forward-eos=true",
+            width=camera.width,
+            height=camera.height,
+            label_file=model.label_file.display(),
+        )
+    }
+
+    fn df_pipeline_description(
         pipeline_name: &str,
         listen_to: &str,
         settings: &VideoStreamSettings,
-    ) -> Result<gst_client::resources::Pipeline> {
+        model: &ModelManifest,
+    ) -> String {
         let listen_to = Self::to_interpipesink_name(listen_to);
         let interpipesrc = Self::to_interpipesrc_name(pipeline_name);
         let detection = &(*settings.detection);
 
-        let nms_threshold = detection.nms_threshold as f32 / 100_f32;
+        let score_threshold = model.score_threshold(detection.nms_threshold);
+        let labels = model.label_list();
         let nats_server_uri = detection.nats_server_uri.as_str();
 
-        let description = format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=false \
+        format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=false is-live=true allow-renegotiation=false \
             ! tensor_decoder name=df_tensor_decoder mode=custom-code option1=printnanny_bb_dataframe_decoder \
-            ! dataframe_agg filter-threshold={nms_threshold} output-type=json \
-            ! nats_sink nats-address={nats_server_uri}");
-        self.make_pipeline(pipeline_name, &description).await
+            ! dataframe_agg filter-threshold={score_threshold} labels={labels} output-type=json \
+            ! nats_sink nats-address={nats_server_uri}")
     }
-    async fn make_recording_pipeline(
+
+    // render gst-launch description of a PipelineGraph node, using model for inference, bounding_boxes and df nodes
+    pub fn pipeline_description(
+        node: &PipelineNode,
+        settings: &VideoStreamSettings,
+        model: &ModelManifest,
+    ) -> String {
+        let name = node.name.as_str();
+        let listen_to = node.listen_to.as_deref().unwrap_or_default();
+        match node.kind {
+            PipelineNodeKind::Camera => Self::camera_pipeline_description(name, settings),
+            PipelineNodeKind::H264Encode => {
+                Self::h264_encode_pipeline_description(name, listen_to, settings)
+            }
+            PipelineNodeKind::Rtp => {
+                let port = node.udp_port.unwrap_or(settings.rtp.video_udp_port);
+                Self::rtp_pipeline_description(name, listen_to, port)
+            }
+            PipelineNodeKind::Hls => Self::hls_pipeline_description(name, listen_to, settings),
+            PipelineNodeKind::Inference => {
+                Self::inference_pipeline_description(name, listen_to, settings, model)
+            }
+            PipelineNodeKind::BoundingBoxes => match &node.frames_from {
+                Some(frames_from) => Self::composite_bounding_box_pipeline_description(
+                    name,
+                    listen_to,
+                    frames_from,
+                    settings,
+                    model,
+                ),
+                None => {
+                    let port = node.udp_port.unwrap_or(settings.rtp.overlay_udp_port);
+                    Self::bounding_box_pipeline_description(name, listen_to, port, settings, model)
+                }
+            },
+            PipelineNodeKind::Dataframe => {
+                Self::df_pipeline_description(name, listen_to, settings, model)
+            }
+            PipelineNodeKind::Snapshot => {
+                Self::jpeg_snapshot_pipeline_description(name, listen_to, settings)
+            }
+            PipelineNodeKind::Preroll => {
+                Self::preroll_pipeline_description(name, listen_to, settings)
+            }
+        }
+    }
+
+    // VideoStreamSettings of the camera which owns node
+    fn node_settings<'a>(
+        node: &PipelineNode,
+        cameras: &'a [VideoStreamSettings],
+    ) -> Result<&'a VideoStreamSettings> {
+        cameras
+            .iter()
+            .find(|c| c.camera_id == node.camera_id)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Pipeline name={} references unknown camera_id={}",
+                    node.name,
+                    node.camera_id
+                )
+            })
+    }
+
+    // render every pipeline in the graph described by cameras without contacting gstd, then validate the rendered descriptions
+    // "auto" elements are rendered for target, so output reviewed on another machine matches the target device
+    pub fn render_pipelines(
+        cameras: &[VideoStreamSettings],
+        target: GstElementTarget,
+    ) -> Result<PipelineRenderReport> {
+        let elements = target.elements();
+        let cameras: Vec<VideoStreamSettings> = cameras
+            .iter()
+            .map(|settings| Self::resolve_camera_elements(settings, &elements))
+            .collect();
+        let graph = PipelineGraph::from_cameras(&cameras)?;
+        let mut pipelines = vec![];
+        for node in graph.nodes().iter() {
+            let settings = Self::node_settings(node, &cameras)?;
+            let model = settings.detection_model()?;
+            pipelines.push(RenderedPipeline {
+                name: node.name.clone(),
+                kind: node.kind,
+                enabled: node.enabled,
+                listen_to: node.listen_to.clone(),
+                description: Self::pipeline_description(node, settings, &model),
+            });
+        }
+        Ok(PipelineRenderReport::new(pipelines))
+    }
+
+    async fn make_graph_pipeline(
         &self,
+        node: &PipelineNode,
+        settings: &VideoStreamSettings,
+    ) -> Result<()> {
+        // splitmuxsink does not create its output directory
+        if node.kind == PipelineNodeKind::Preroll {
+            fs::create_dir_all(&settings.preroll.path)?;
+        }
+        let model = settings.detection_model()?;
+        let description = Self::pipeline_description(node, settings, &model);
+        self.make_pipeline(&node.name, &description).await
+    }
+
+    // splitmuxsink container, segment and retention policy are configured by RecordingFormatSettings
+    fn recording_pipeline_description(
         pipeline_name: &str,
         listen_to: &str,
-        filename: &str,
+        dir: &str,
         filesink_name: &str,
-        _camera: &CameraSettings,
-    ) -> Result<gst_client::resources::Pipeline> {
+        format: &RecordingFormatSettings,
+    ) -> String {
         let interpipesrc = Self::to_interpipesrc_name(pipeline_name);
         let listen_to = Self::to_interpipesink_name(listen_to);
 
+        let location = format.location(dir);
+        let properties = format.splitmuxsink_properties();
+
+        format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=true is-live=true allow-renegotiation=true format=3 stream-sync=passthrough-ts \
+            ! queue \
+            ! splitmuxsink name={filesink_name} {properties} location={location} send-keyframe-requests=false")
+    }
+
+    async fn make_recording_pipeline(
+        &self,
+        pipeline_name: &str,
+        listen_to: &str,
+        filename: &str,
+        filesink_name: &str,
+        format: &RecordingFormatSettings,
+    ) -> Result<()> {
+        format.validate()?;
+
         // ensure directory exists
         match fs::create_dir_all(filename) {
             Ok(_) => {
@@ -399,86 +801,381 @@ impl PrintNannyPipelineFactory {
             }
         };
 
-        let location = format!("{filename}/%d.mp4");
-        let max_files = 50;
-
-        let max_bytes = 10000000; // 10MB (bytes)
-
-        let description = format!("interpipesrc name={interpipesrc} listen-to={listen_to} accept-events=false accept-eos-event=true is-live=true allow-renegotiation=true format=3 stream-sync=passthrough-ts \
-            ! queue \
-            ! splitmuxsink muxer=mpegtsmux name={filesink_name} max-files={max_files} location={location} max-size-bytes={max_bytes} send-keyframe-requests=false");
+        let description = Self::recording_pipeline_description(
+            pipeline_name,
+            listen_to,
+            filename,
+            filesink_name,
+            format,
+        );
         self.make_pipeline(pipeline_name, &description).await
     }
 
     pub async fn stop_pipeline(&self, pipeline_name: &str) -> Result<()> {
         info!("Attempting to stop Gstreamer pipeline: {}", &pipeline_name);
-        let client = GstClient::build(&self.uri).expect("Failed to build GstClient");
-        let pipeline = client.pipeline(pipeline_name);
-        pipeline.stop().await?;
+        self.backend.stop(pipeline_name).await?;
         info!("Success! Stopped Gstreamer pipeline: {}", &pipeline_name);
         Ok(())
     }
 
     pub async fn start_pipeline(&self, pipeline_name: &str) -> Result<()> {
         info!("Attempting to start Gstreamer pipeline: {}", &pipeline_name);
-        let client = GstClient::build(&self.uri).expect("Failed to build GstClient");
-        let pipeline = client.pipeline(pipeline_name);
-        pipeline.pause().await?;
-        pipeline.play().await?;
+        self.backend.pause(pipeline_name).await?;
+        self.backend.play(pipeline_name).await?;
         info!("Success! Started Gstreamer pipeline: {}", &pipeline_name);
         Ok(())
     }
 
-    pub async fn sync_optional_pipelines(&self, settings: VideoStreamSettings) -> Result<()> {
-        let hls_pipeline = self
-            .make_hls_pipeline(HLS_PIPELINE, H264_ENCODING_PIPELINE, &settings)
-            .await?;
-        let hls_settings = &*(settings).hls;
-        if hls_settings.enabled {
-            hls_pipeline.pause().await?;
-            hls_pipeline.play().await?;
-        } else {
-            hls_pipeline.stop().await?;
+    // names of all pipelines created in the backend
+    pub async fn pipeline_names(&self) -> Result<Vec<String>> {
+        self.backend.pipeline_names().await
+    }
+
+    pub(crate) async fn stop_and_delete_pipeline(&self, pipeline_name: &str) -> Result<()> {
+        warn!("Stopping pipeline: {}", pipeline_name);
+        self.backend.stop(pipeline_name).await?;
+        warn!("Deleting pipeline: {}", pipeline_name);
+        self.backend.delete(pipeline_name).await?;
+        Ok(())
+    }
+
+    // create graph nodes in topological order, then transition all to PAUSED and PLAYING
+    pub(crate) async fn create_graph_pipelines(
+        &self,
+        nodes: &[PipelineNode],
+        cameras: &[VideoStreamSettings],
+    ) -> Result<()> {
+        let cameras = self.resolve_elements(cameras);
+        for node in nodes.iter() {
+            let settings = Self::node_settings(node, &cameras)?;
+            self.make_graph_pipeline(node, settings).await?;
         }
 
+        for node in nodes.iter() {
+            info!("Setting pipeline name={} state=PAUSED", node.name);
+            self.backend.pause(&node.name).await?;
+        }
+
+        for node in nodes.iter() {
+            info!("Setting pipeline name={} state=PLAYING", node.name);
+            self.backend.play(&node.name).await?;
+        }
         Ok(())
     }
 
-    pub async fn start_video_recording_pipeline(&self, filename: &str) -> Result<()> {
-        let settings = PrintNannySettings::new().await?;
-        let camera = *settings.video_stream.camera;
+    // description each pipeline was created with, None if the backend didn't record one
+    async fn pipeline_descriptions(&self) -> Result<HashMap<String, Option<String>>> {
+        let mut descriptions = HashMap::new();
+        for pipeline_name in self.pipeline_names().await? {
+            let description = self.backend.description(&pipeline_name).await?;
+            descriptions.insert(pipeline_name, description);
+        }
+        Ok(descriptions)
+    }
 
-        match self.delete_pipeline(H264_RECORDING_PIPELINE).await {
-            Ok(_) => info!("Deleted existing pipeline={H264_RECORDING_PIPELINE}",),
-            Err(e) => info!(
-                "Failed to delete pipeline={H264_RECORDING_PIPELINE} error={}",
-                e
-            ),
+    // render the description of every enabled node in graph
+    fn rendered_descriptions(
+        graph: &PipelineGraph,
+        cameras: &[VideoStreamSettings],
+    ) -> Result<HashMap<String, String>> {
+        let mut rendered = HashMap::new();
+        for node in graph.enabled_nodes() {
+            let settings = Self::node_settings(node, cameras)?;
+            let model = settings.detection_model()?;
+            rendered.insert(
+                node.name.clone(),
+                Self::pipeline_description(node, settings, &model),
+            );
+        }
+        Ok(rendered)
+    }
+
+    // compare graph with the backend's pipelines, including the description each pipeline was created with
+    pub(crate) async fn diff_graph(
+        &self,
+        graph: &PipelineGraph,
+        cameras: &[VideoStreamSettings],
+    ) -> Result<PipelineGraphDiff> {
+        let actual = self.pipeline_descriptions().await?;
+        let rendered = Self::rendered_descriptions(graph, &self.resolve_elements(cameras))?;
+        Ok(graph.diff_descriptions(&actual, &rendered))
+    }
+
+    // delete disabled nodes, re-create changed nodes and create missing nodes
+    // unchanged pipelines and pipelines outside of the graph (e.g. h264_record, timelapse) are left running
+    async fn apply_graph(
+        &self,
+        graph: &PipelineGraph,
+        cameras: &[VideoStreamSettings],
+    ) -> Result<PipelineGraphDiff> {
+        let diff = self.diff_graph(graph, cameras).await?;
+        info!(
+            "Syncing pipeline graph create={:?} delete={:?} changed={:?} unchanged={:?} unmanaged={:?}",
+            diff.create.iter().map(|n| &n.name).collect::<Vec<_>>(),
+            diff.delete,
+            diff.changed.iter().map(|n| &n.name).collect::<Vec<_>>(),
+            diff.unchanged,
+            diff.unmanaged
+        );
+
+        // downstream pipelines are deleted first
+        for pipeline_name in diff
+            .delete
+            .iter()
+            .chain(diff.changed.iter().rev().map(|n| &n.name))
+        {
+            self.stop_and_delete_pipeline(pipeline_name).await?;
+        }
+        let nodes: Vec<PipelineNode> = graph
+            .enabled_nodes()
+            .filter(|node| {
+                diff.create.iter().any(|n| n.name == node.name)
+                    || diff.changed.iter().any(|n| n.name == node.name)
+            })
+            .cloned()
+            .collect();
+        self.create_graph_pipelines(&nodes, cameras).await?;
+        Ok(diff)
+    }
+
+    // converge running pipelines with the PipelineGraph described by cameras, without restarting unchanged pipelines
+    pub async fn sync_optional_pipelines(&self, cameras: &[VideoStreamSettings]) -> Result<()> {
+        let graph = PipelineGraph::from_cameras(cameras)?;
+        self.apply_graph(&graph, cameras).await?;
+        Ok(())
+    }
+
+    // converge the pipelines of camera_id, leaving other cameras, recording and timelapse pipelines running
+    pub async fn sync_camera_pipelines(
+        &self,
+        cameras: &[VideoStreamSettings],
+        camera_id: &str,
+    ) -> Result<()> {
+        let graph = PipelineGraph::from_cameras(cameras)?.camera_graph(camera_id);
+        self.apply_graph(&graph, cameras).await?;
+        Ok(())
+    }
+
+    // record the encoder pipeline of the camera described by settings, e.g. {camera_id}_h264_record listening to {camera_id}_h264_encode
+    pub async fn start_video_recording_pipeline(
+        &self,
+        settings: &VideoStreamSettings,
+        filename: &str,
+    ) -> Result<()> {
+        let pipeline_name = settings.namespaced_pipeline_name(H264_RECORDING_PIPELINE);
+        let listen_to = settings.namespaced_pipeline_name(H264_ENCODING_PIPELINE);
+        match self.delete_pipeline(&pipeline_name).await {
+            Ok(_) => info!("Deleted existing pipeline={}", pipeline_name),
+            Err(e) => info!("Failed to delete pipeline={} error={}", pipeline_name, e),
         };
 
-        let pipeline = self
-            .make_recording_pipeline(
-                H264_RECORDING_PIPELINE,
-                H264_ENCODING_PIPELINE,
-                filename,
-                H264_SPLITMUXSINK,
-                &camera,
-            )
-            .await?;
-        pipeline.pause().await?;
-        pipeline.play().await?;
+        self.make_recording_pipeline(
+            &pipeline_name,
+            &listen_to,
+            filename,
+            H264_SPLITMUXSINK,
+            &settings.recording_format,
+        )
+        .await?;
+        self.backend.pause(&pipeline_name).await?;
+        self.backend.play(&pipeline_name).await?;
         Ok(())
     }
 
-    pub async fn stop_video_recording_pipeline(&self) -> Result<()> {
-        let client = GstClient::build(&self.uri).expect("Failed to build GstClient");
-        let pipeline = client.pipeline(H264_RECORDING_PIPELINE);
-        pipeline.emit_event_eos().await?;
-        info!("Sent EOS signal to pipeline name={H264_RECORDING_PIPELINE}");
-        pipeline.stop().await?;
-        info!("Stopped pipeline name={H264_RECORDING_PIPELINE}");
-        pipeline.delete().await?;
-        info!("Deleted pipeline name={H264_RECORDING_PIPELINE}");
+    // recording pipelines of all cameras which exist in the backend
+    pub async fn recording_pipeline_names(&self) -> Result<Vec<String>> {
+        Ok(self
+            .pipeline_names()
+            .await?
+            .into_iter()
+            .filter(|name| is_recording_pipeline_name(name))
+            .collect())
+    }
+
+    // start capturing timelapse frames from the camera pipeline described by settings
+    pub async fn start_timelapse_pipeline(
+        &self,
+        dir: &TimelapseDir,
+        settings: &VideoStreamSettings,
+    ) -> Result<()> {
+        let pipeline_name = settings.namespaced_pipeline_name(TIMELAPSE_PIPELINE);
+        let listen_to = settings.namespaced_pipeline_name(CAMERA_PIPELINE);
+        match self.delete_pipeline(&pipeline_name).await {
+            Ok(_) => info!("Deleted existing pipeline={}", pipeline_name),
+            Err(e) => info!("Failed to delete pipeline={} error={}", pipeline_name, e),
+        };
+        dir.create_all()?;
+        let settings = Self::resolve_camera_elements(settings, &self.elements);
+        let description =
+            Self::timelapse_pipeline_description(&pipeline_name, &listen_to, dir, &settings);
+        self.make_pipeline(&pipeline_name, &description).await?;
+        self.backend.pause(&pipeline_name).await?;
+        self.backend.play(&pipeline_name).await?;
+        Ok(())
+    }
+
+    pub async fn stop_timelapse_pipeline(&self, settings: &VideoStreamSettings) -> Result<()> {
+        let pipeline_name = settings.namespaced_pipeline_name(TIMELAPSE_PIPELINE);
+        self.stop_and_delete_pipeline(&pipeline_name).await
+    }
+
+    // encode captured frames into an mp4, waiting for the assemble pipeline to reach EOS
+    pub async fn assemble_timelapse(
+        &self,
+        dir: &TimelapseDir,
+        settings: &VideoStreamSettings,
+    ) -> Result<std::path::PathBuf> {
+        let frame_count = dir.frame_count()?;
+        if frame_count == 0 {
+            return Err(anyhow!(
+                "Timelapse dir={} does not contain any frames",
+                dir.dir.display()
+            ));
+        }
+        let pipeline_name = settings.namespaced_pipeline_name(TIMELAPSE_ASSEMBLE_PIPELINE);
+        let settings = Self::resolve_camera_elements(settings, &self.elements);
+        let description =
+            Self::timelapse_assemble_pipeline_description(&pipeline_name, dir, &settings);
+        info!(
+            "Assembling timelapse frame_count={} output={}",
+            frame_count,
+            dir.output().display()
+        );
+        self.run_until_eos(&pipeline_name, &description, TIMELAPSE_ASSEMBLE_TIMEOUT)
+            .await?;
+        Ok(dir.output())
+    }
+
+    // play a finite pipeline, waiting for EOS or error before deleting it
+    async fn run_until_eos(
+        &self,
+        pipeline_name: &str,
+        description: &str,
+        timeout: Duration,
+    ) -> Result<()> {
+        self.make_pipeline(pipeline_name, description).await?;
+        let result = match self.backend.play(pipeline_name).await {
+            Ok(()) => self.wait_for_eos(pipeline_name, timeout).await,
+            Err(e) => Err(e),
+        };
+        // delete the pipeline even if it failed, so failed runs don't accumulate in gstd
+        if let Err(e) = self.stop_and_delete_pipeline(pipeline_name).await {
+            match result {
+                Ok(()) => return Err(e),
+                Err(_) => warn!(
+                    "Failed to delete pipeline={} after it failed error={}",
+                    pipeline_name, e
+                ),
+            }
+        }
+        result
+    }
+
+    // read the bus of pipeline_name until EOS or ERROR, or timeout elapses
+    async fn wait_for_eos(&self, pipeline_name: &str, timeout: Duration) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if tokio::time::Instant::now() > deadline {
+                return Err(anyhow!(
+                    "Timed out waiting for EOS on pipeline={}",
+                    pipeline_name
+                ));
+            }
+            let msg = match self
+                .backend
+                .read_bus(pipeline_name, "eos+error", EOS_BUS_TIMEOUT)
+                .await?
+            {
+                Some(msg) => msg,
+                None => continue,
+            };
+            match msg.body() {
+                Ok(BusMessageBody::Eos) => return Ok(()),
+                Ok(BusMessageBody::Error(error)) => {
+                    return Err(anyhow!("Pipeline={} failed error={}", pipeline_name, error));
+                }
+                Ok(_) => (),
+                Err(e) => warn!(
+                    "Failed to parse bus message pipeline={} type={} error={}",
+                    pipeline_name, msg.r#type, e
+                ),
+            }
+        }
+    }
+
+    // remux parts of a non-mpegts recording into RecordingExport.output
+    fn export_pipeline_description(
+        pipeline_name: &str,
+        export: &RecordingExport,
+    ) -> Result<String> {
+        let (demuxer, muxer) = match export.extension() {
+            "mp4" => ("qtdemux", "mp4mux"),
+            "mkv" => ("matroskademux", "matroskamux"),
+            other => {
+                return Err(anyhow!(
+                    "Unsupported recording container extension={}",
+                    other
+                ))
+            }
+        };
+        let concat = format!("{pipeline_name}_concat");
+        let output = export.output.display().to_string();
+        let sources: Vec<String> = export
+            .parts
+            .iter()
+            .map(|part| {
+                format!(
+                    "filesrc location={} ! {demuxer} ! {concat}.",
+                    part.display()
+                )
+            })
+            .collect();
+        Ok(format!(
+            "concat name={concat} ! h264parse ! {muxer} ! filesink location={output} {}",
+            sources.join(" ")
+        ))
+    }
+
+    // concatenate a VideoRecording's local parts into one playable file, reusing an up-to-date export
+    pub async fn export_video_recording(&self, export: &RecordingExport) -> Result<PathBuf> {
+        if export.is_current() {
+            info!(
+                "Reusing VideoRecording export id={} output={}",
+                export.video_recording_id,
+                export.output.display()
+            );
+            return Ok(export.output.clone());
+        }
+        if export.is_concatenable() {
+            return export.concat();
+        }
+        if let Some(parent) = export.output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let pipeline_name = format!("{}_{}", EXPORT_PIPELINE, export.video_recording_id);
+        let description = Self::export_pipeline_description(&pipeline_name, export)?;
+        self.run_until_eos(&pipeline_name, &description, EXPORT_TIMEOUT)
+            .await?;
+        info!(
+            "Exported VideoRecording id={} parts={} output={}",
+            export.video_recording_id,
+            export.parts.len(),
+            export.output.display()
+        );
+        Ok(export.output.clone())
+    }
+
+    pub async fn stop_video_recording_pipeline(
+        &self,
+        settings: &VideoStreamSettings,
+    ) -> Result<()> {
+        let pipeline_name = settings.namespaced_pipeline_name(H264_RECORDING_PIPELINE);
+        self.backend.send_eos(&pipeline_name).await?;
+        info!("Sent EOS signal to pipeline name={}", pipeline_name);
+        self.backend.stop(&pipeline_name).await?;
+        info!("Stopped pipeline name={}", pipeline_name);
+        self.backend.delete(&pipeline_name).await?;
+        info!("Deleted pipeline name={}", pipeline_name);
         Ok(())
     }
 
@@ -491,90 +1188,526 @@ impl PrintNannyPipelineFactory {
             settings.save().await;
         }
 
-        self.stop_pipelines().await?;
+        // only the default camera is hotplugged, additional cameras are configured explicitly
+        let cameras = settings.video_streams();
+        let graph = PipelineGraph::from_cameras(&cameras)?;
 
-        let video_settings = settings.video_stream;
+        // pipelines are only re-created if their rendered description changed
+        let diff = self.apply_graph(&graph, &cameras).await?;
+        for pipeline_name in diff.unchanged.iter() {
+            if self.pipeline_state(pipeline_name).await != GstPipelineState::Playing {
+                self.start_pipeline(pipeline_name).await?;
+            }
+        }
+        Ok(())
+    }
 
-        let camera_pipeline = self
-            .make_camera_pipeline(CAMERA_PIPELINE, &video_settings)
-            .await?;
+    // re-create pipelines of camera_id rendered from the active detection model, leaving camera and encoder pipelines running
+    // downstream interpipesrc elements re-connect to the re-created interpipesink by name
+    pub async fn restart_model_pipelines(
+        &self,
+        cameras: &[VideoStreamSettings],
+        camera_id: &str,
+    ) -> Result<Vec<String>> {
+        let graph = PipelineGraph::from_cameras(cameras)?;
+        let nodes: Vec<PipelineNode> = graph
+            .enabled_nodes()
+            .filter(|node| node.camera_id == camera_id && node.kind.uses_detection_model())
+            .cloned()
+            .collect();
+        let actual = self.pipeline_names().await?;
+        // downstream pipelines are deleted first
+        for node in nodes.iter().rev() {
+            if actual.contains(&node.name) {
+                self.stop_and_delete_pipeline(&node.name).await?;
+            }
+        }
+        self.create_graph_pipelines(&nodes, cameras).await?;
+        let names = nodes.into_iter().map(|node| node.name).collect();
+        info!(
+            "Restarted model pipelines camera_id={} pipelines={:?}",
+            camera_id, names
+        );
+        Ok(names)
+    }
 
-        let h264_pipeline = self
-            .make_h264_encode_pipeline(H264_ENCODING_PIPELINE, CAMERA_PIPELINE, &video_settings)
-            .await?;
+    pub async fn stop_pipelines(&self) -> Result<()> {
+        warn!("Stopping gstreamer pipelines");
+        for pipeline_name in self.pipeline_names().await? {
+            self.stop_and_delete_pipeline(&pipeline_name).await?;
+        }
+        Ok(())
+    }
+}
 
-        let rtp_pipeline = self
-            .make_rtp_pipeline(RTP_PIPELINE, H264_ENCODING_PIPELINE, &video_settings)
-            .await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use printnanny_settings::cam::{
+        DetectionOverlayMode, MediaVideoSource, PipelineNodeKind, PipelineNodeSettings,
+        RecordingContainer, DEFAULT_CAMERA_ID,
+    };
+
+    use crate::validate::parse_description;
+
+    #[test]
+    fn test_render_default_pipelines() {
+        let settings = VideoStreamSettings::default();
+        let report = PrintNannyPipelineFactory::render_pipelines(
+            std::slice::from_ref(&settings),
+            GstElementTarget::RaspberryPi,
+        )
+        .unwrap();
+        assert_eq!(report.pipelines.len(), settings.pipelines.len());
+        assert!(report.is_valid(), "{:#?}", report.issues);
+    }
 
-        let inference_pipeline = self
-            .make_inference_pipeline(INFERENCE_PIPELINE, CAMERA_PIPELINE, &video_settings)
-            .await?;
+    #[test]
+    fn test_render_udp_port_override() {
+        let mut settings = VideoStreamSettings::default();
+        settings.pipelines.push(PipelineNodeSettings {
+            name: "rtp_2".into(),
+            udp_port: Some(20003),
+            ..PipelineNodeSettings::new(PipelineNodeKind::Rtp, Some(PipelineNodeKind::H264Encode))
+        });
+        let report = PrintNannyPipelineFactory::render_pipelines(
+            std::slice::from_ref(&settings),
+            GstElementTarget::RaspberryPi,
+        )
+        .unwrap();
+        let rtp_2 = report.pipelines.iter().find(|p| p.name == "rtp_2").unwrap();
+        assert!(rtp_2.description.contains("udpsink port=20003"));
+        assert!(report.is_valid(), "{:#?}", report.issues);
+    }
 
-        let bb_pipeline = self
-            .make_bounding_box_pipeline(BB_PIPELINE, INFERENCE_PIPELINE, &video_settings)
-            .await?;
+    #[test]
+    fn test_render_media_sources() {
+        let mut settings = VideoStreamSettings {
+            media: Some(MediaVideoSource {
+                uri: "/home/printnanny/failed-print.ts".into(),
+                loop_playback: true,
+            }),
+            ..Default::default()
+        };
+        let report = PrintNannyPipelineFactory::render_pipelines(
+            std::slice::from_ref(&settings),
+            GstElementTarget::RaspberryPi,
+        )
+        .unwrap();
+        assert!(report.is_valid(), "{:#?}", report.issues);
+        assert!(report.pipelines[0]
+            .description
+            .starts_with("multifilesrc location=/home/printnanny/failed-print.ts loop=true"));
+
+        settings.media = Some(MediaVideoSource::new("rtsp://192.168.1.2:554/stream"));
+        let report = PrintNannyPipelineFactory::render_pipelines(
+            std::slice::from_ref(&settings),
+            GstElementTarget::RaspberryPi,
+        )
+        .unwrap();
+        assert!(report.is_valid(), "{:#?}", report.issues);
+        assert!(report.pipelines[0]
+            .description
+            .starts_with("uridecodebin uri=rtsp://192.168.1.2:554/stream"));
+    }
 
-        let df_pipeline = self
-            .make_df_pipeline(DF_WINDOW_PIPELINE, INFERENCE_PIPELINE, &video_settings)
-            .await?;
+    #[test]
+    fn test_render_software_elements() {
+        let settings = VideoStreamSettings {
+            elements: GstElementSettings {
+                camera_src: CameraSrcElement::VideoTest,
+                video_convert: VideoConvertElement::VideoConvert,
+                h264_encoder: H264EncoderElement::X264,
+                jpeg_encoder: JpegEncoderElement::Jpeg,
+            },
+            ..Default::default()
+        };
+        let report = PrintNannyPipelineFactory::render_pipelines(
+            std::slice::from_ref(&settings),
+            GstElementTarget::RaspberryPi,
+        )
+        .unwrap();
+        assert!(report.is_valid(), "{:#?}", report.issues);
+        for pipeline in report.pipelines.iter() {
+            assert!(
+                !pipeline.description.contains("v4l2"),
+                "{}",
+                pipeline.description
+            );
+            assert!(!pipeline.description.contains("libcamerasrc"));
+        }
+    }
 
-        let snapshot_pipeline = self
-            .make_jpeg_snapshot_pipeline(SNAPSHOT_PIPELINE, CAMERA_PIPELINE, &video_settings)
-            .await?;
+    #[test]
+    fn test_render_elements_target() {
+        // "auto" elements are rendered for the target, independent of the host's element registry
+        let settings = VideoStreamSettings::default();
+        let cameras = std::slice::from_ref(&settings);
+        let rpi =
+            PrintNannyPipelineFactory::render_pipelines(cameras, GstElementTarget::RaspberryPi)
+                .unwrap();
+        let software =
+            PrintNannyPipelineFactory::render_pipelines(cameras, GstElementTarget::Software)
+                .unwrap();
+        let description = |report: &PipelineRenderReport, name: &str| {
+            report
+                .pipelines
+                .iter()
+                .find(|p| p.name == name)
+                .unwrap()
+                .description
+                .clone()
+        };
+        assert!(description(&rpi, H264_ENCODING_PIPELINE).contains("v4l2h264enc"));
+        assert!(description(&software, H264_ENCODING_PIPELINE).contains("x264enc"));
+        assert!(description(&software, SNAPSHOT_PIPELINE).contains("jpegenc"));
+        assert!(!description(&software, SNAPSHOT_PIPELINE).contains("v4l2jpegenc"));
+
+        // explicitly configured elements are kept
+        let mut settings = VideoStreamSettings::default();
+        settings.elements.h264_encoder = H264EncoderElement::OpenH264;
+        let report = PrintNannyPipelineFactory::render_pipelines(
+            std::slice::from_ref(&settings),
+            GstElementTarget::RaspberryPi,
+        )
+        .unwrap();
+        assert!(description(&report, H264_ENCODING_PIPELINE).contains("openh264enc"));
+    }
 
-        let mut pipelines = vec![
-            camera_pipeline,
-            h264_pipeline,
-            rtp_pipeline,
-            inference_pipeline,
-            bb_pipeline,
-            df_pipeline,
-            snapshot_pipeline,
+    #[test]
+    fn test_render_multiple_cameras() {
+        let cameras = vec![
+            VideoStreamSettings::default(),
+            VideoStreamSettings::for_camera("nozzle", 1),
         ];
+        let report =
+            PrintNannyPipelineFactory::render_pipelines(&cameras, GstElementTarget::RaspberryPi)
+                .unwrap();
+        assert!(report.is_valid(), "{:#?}", report.issues);
+        assert_eq!(
+            report.pipelines.len(),
+            cameras.iter().map(|c| c.pipelines.len()).sum::<usize>()
+        );
+        let rtp = report
+            .pipelines
+            .iter()
+            .find(|p| p.name == "nozzle_rtp")
+            .unwrap();
+        assert!(rtp
+            .description
+            .contains("listen-to=nozzle_h264_encode_sink"));
+        assert!(rtp
+            .description
+            .contains(&format!("udpsink port={}", cameras[1].rtp.video_udp_port)));
+        let hls = report
+            .pipelines
+            .iter()
+            .find(|p| p.name == "nozzle_hls")
+            .unwrap();
+        assert!(hls.description.contains("/nozzle/"));
+        assert!(report
+            .pipelines
+            .iter()
+            .any(|p| p.name == "camera" && p.description.contains("name=camera_sink")));
+        assert_eq!(cameras[0].camera_id, DEFAULT_CAMERA_ID);
+    }
 
-        let hls_settings = &*(video_settings).hls;
+    #[test]
+    fn test_render_model_pipelines() {
+        let settings = VideoStreamSettings::default();
+        let graph = PipelineGraph::from_settings(&settings).unwrap();
+        let render = |name: &str, model: &ModelManifest| {
+            PrintNannyPipelineFactory::pipeline_description(
+                graph.get(name).unwrap(),
+                &settings,
+                model,
+            )
+        };
 
-        if hls_settings.enabled {
-            let hls_pipeline = self
-                .make_hls_pipeline(HLS_PIPELINE, H264_ENCODING_PIPELINE, &video_settings)
-                .await?;
-            pipelines.push(hls_pipeline);
+        // without an active registry model, pipelines are rendered from DetectionSettings
+        let bundled = settings.detection_model().unwrap();
+        let df = render(DF_WINDOW_PIPELINE, &bundled);
+        assert!(df.contains("filter-threshold=0.66 labels=nozzle,adhesion,spaghetti,print,raft"));
+        let bb = render(BB_PIPELINE, &bundled);
+        assert!(bb.contains("option1=mobilenet-ssd-postprocess option2=/usr/share/printnanny/model/labels.txt option3=0:1:2:3,66"));
+
+        let mut model = bundled.clone();
+        model.name = "printnanny-v2".into();
+        model.model_file = "/models/printnanny-v2/model.tflite".into();
+        model.label_file = "/models/printnanny-v2/labels.txt".into();
+        model.labels.push("blob".into());
+        model.input.width = 416;
+        model.input.height = 416;
+        model.input.dtype = "float32".into();
+        model.input.add = -127.5;
+        model.input.div = 127.5;
+        model.output.tensors = "1:3:0:2".into();
+        model.score_scale = 0.5;
+
+        let inference = render(INFERENCE_PIPELINE, &model);
+        assert!(inference.contains("format=RGB,width=416,height=416"));
+        assert!(inference.contains("option=typecast:float32,add:-127.5,div:127.5"));
+        assert!(inference.contains("num_tensors=1,dimensions=3:416:416:1"));
+        assert!(inference.contains(
+            "tensor_filter framework=tensorflow2-lite model=/models/printnanny-v2/model.tflite"
+        ));
+        let bb = render(BB_PIPELINE, &model);
+        assert!(bb.contains("option2=/models/printnanny-v2/labels.txt option3=1:3:0:2,132"));
+        assert!(bb.contains("option5=416:416"));
+        let df = render(DF_WINDOW_PIPELINE, &model);
+        assert!(
+            df.contains("filter-threshold=1.32 labels=nozzle,adhesion,spaghetti,print,raft,blob")
+        );
+        for description in [inference, bb, df] {
+            let elements = parse_description(&description).unwrap();
+            assert!(!elements.is_empty(), "{description}");
         }
+    }
 
-        for pipeline in pipelines.iter() {
-            info!("Setting pipeline name={} state=PAUSED", pipeline.name);
-            pipeline.pause().await?;
-        }
+    #[test]
+    fn test_render_composite_overlay() {
+        let mut settings = VideoStreamSettings::default();
+        settings.detection_overlay.mode = DetectionOverlayMode::Composite;
+        settings
+            .detection_overlay
+            .labels
+            .insert("spaghetti".into(), "Failed print".into());
+        let report = PrintNannyPipelineFactory::render_pipelines(
+            std::slice::from_ref(&settings),
+            GstElementTarget::RaspberryPi,
+        )
+        .unwrap();
+        assert!(report.is_valid(), "{:#?}", report.issues);
+        let find = |name: &str| report.pipelines.iter().find(|p| p.name == name).unwrap();
+
+        let bb = &find("bounding_boxes").description;
+        assert!(bb.contains("listen-to=camera_sink"));
+        assert!(bb.contains("listen-to=tflite_inference_sink"));
+        assert!(bb.contains("labels=spaghetti:Failed-print"));
+        assert!(bb.contains(
+            "colors=adhesion:FFA500,nozzle:00BFFF,print:00FF00,raft:FFFF00,spaghetti:FF0000"
+        ));
+        assert!(bb.contains("interpipesink name=bounding_boxes_sink"));
+        assert!(!bb.contains("udpsink"));
+        let elements = parse_description(bb).unwrap();
+        assert!(elements.iter().any(|e| e.factory == "compositor"));
+
+        assert!(find("h264_encode")
+            .description
+            .contains("listen-to=bounding_boxes_sink"));
+        assert!(find("rtp")
+            .description
+            .contains("listen-to=h264_encode_sink"));
+    }
+
+    #[test]
+    fn test_recording_pipeline_name() {
+        let nozzle = VideoStreamSettings {
+            camera_id: "nozzle".into(),
+            ..VideoStreamSettings::default()
+        };
+        let name = nozzle.namespaced_pipeline_name(H264_RECORDING_PIPELINE);
+        assert_eq!(name, "nozzle_h264_record");
+        assert!(is_recording_pipeline_name(&name));
+        assert!(is_recording_pipeline_name(H264_RECORDING_PIPELINE));
+        assert!(!is_recording_pipeline_name(
+            &nozzle.namespaced_pipeline_name(H264_ENCODING_PIPELINE)
+        ));
+    }
 
-        for pipeline in pipelines {
-            info!("Setting pipeline name={} state=PLAYING", pipeline.name);
-            pipeline.play().await?;
+    #[test]
+    fn test_render_recording_containers() {
+        let dir = "/home/printnanny/.local/share/printnanny/video/1";
+        for (container, muxer, location) in [
+            (
+                RecordingContainer::Ts,
+                "muxer=mpegtsmux",
+                "location=/home/printnanny/.local/share/printnanny/video/1/%d.ts",
+            ),
+            (
+                RecordingContainer::Mp4,
+                "muxer=mp4mux",
+                "location=/home/printnanny/.local/share/printnanny/video/1/%d.mp4",
+            ),
+            (
+                RecordingContainer::FragmentedMp4,
+                "muxer-factory=mp4mux",
+                "location=/home/printnanny/.local/share/printnanny/video/1/%d.mp4",
+            ),
+            (
+                RecordingContainer::Mkv,
+                "muxer=matroskamux",
+                "location=/home/printnanny/.local/share/printnanny/video/1/%d.mkv",
+            ),
+        ] {
+            let format = RecordingFormatSettings {
+                container,
+                ..RecordingFormatSettings::default()
+            };
+            let description = PrintNannyPipelineFactory::recording_pipeline_description(
+                H264_RECORDING_PIPELINE,
+                H264_ENCODING_PIPELINE,
+                dir,
+                H264_SPLITMUXSINK,
+                &format,
+            );
+            assert!(description.contains(muxer), "{}", description);
+            assert!(description.contains(location), "{}", description);
+            assert!(parse_description(&description).is_ok());
         }
 
-        Ok(())
+        let format = RecordingFormatSettings {
+            segment_bytes: 0,
+            segment_secs: 60,
+            max_files: 0,
+            filename_template: "segment%05d".into(),
+            ..RecordingFormatSettings::default()
+        };
+        assert!(format.validate().is_ok());
+        let format_v2 = RecordingFormatSettings {
+            filename_template: "v2_segment%05d_part".into(),
+            ..format.clone()
+        };
+        assert!(format_v2.validate().is_ok());
+        let description = PrintNannyPipelineFactory::recording_pipeline_description(
+            H264_RECORDING_PIPELINE,
+            H264_ENCODING_PIPELINE,
+            dir,
+            H264_SPLITMUXSINK,
+            &format,
+        );
+        assert!(description.contains("max-size-time=60000000000 location="));
+        assert!(!description.contains("max-files"));
+        assert!(!description.contains("max-size-bytes"));
+        assert!(description.contains("/video/1/segment%05d.ts"));
+
+        for template in [
+            "",
+            "%s",
+            "%d/%d",
+            "part",
+            "%d.part",
+            "seg%05d_v2",
+            "v2%d",
+            "%d-1",
+        ] {
+            let format = RecordingFormatSettings {
+                filename_template: template.into(),
+                ..RecordingFormatSettings::default()
+            };
+            assert!(format.validate().is_err(), "{}", template);
+        }
     }
 
-    pub async fn stop_pipelines(&self) -> Result<()> {
-        warn!("Stopping gstreamer pipelines");
-        let client = GstClient::build(&self.uri).expect("Failed to build GstClient");
-        let res = client.pipelines().await?;
-
-        match res.response {
-            gst_client::gstd_types::ResponseT::Properties(props) => {
-                if let Some(nodes) = props.nodes {
-                    for node in nodes {
-                        let pipeline = client.pipeline(&node.name);
-                        warn!("Stopping pipeline: {}", &node.name);
-                        pipeline.stop().await?;
-                        warn!("Deleting pipeline: {}", &node.name);
-                        pipeline.delete().await?;
-                    }
-                }
-            }
-            _ => unimplemented!("Received invalid response to GET /pipelines"),
+    #[test]
+    fn test_render_export_pipeline() {
+        let dir = std::path::Path::new("/home/printnanny/.local/share/printnanny/video");
+        let mut export = RecordingExport {
+            video_recording_id: "1".into(),
+            parts: vec![dir.join("1/0.mp4"), dir.join("1/1.mp4")],
+            missing: 0,
+            output: dir.join("exports/1.mp4"),
         };
+        let description =
+            PrintNannyPipelineFactory::export_pipeline_description(EXPORT_PIPELINE, &export)
+                .unwrap();
+        assert_eq!(
+            description,
+            "concat name=export_concat ! h264parse ! mp4mux ! filesink location=/home/printnanny/.local/share/printnanny/video/exports/1.mp4 \
+            filesrc location=/home/printnanny/.local/share/printnanny/video/1/0.mp4 ! qtdemux ! export_concat. \
+            filesrc location=/home/printnanny/.local/share/printnanny/video/1/1.mp4 ! qtdemux ! export_concat."
+        );
 
-        Ok(())
+        export.output = dir.join("exports/1.ts");
+        assert!(export.is_concatenable());
+        assert!(
+            PrintNannyPipelineFactory::export_pipeline_description(EXPORT_PIPELINE, &export)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_render_preroll_pipeline() {
+        let mut nozzle = VideoStreamSettings::for_camera("nozzle", 1);
+        nozzle.preroll.enabled = true;
+        let cameras = vec![VideoStreamSettings::default(), nozzle];
+        let report =
+            PrintNannyPipelineFactory::render_pipelines(&cameras, GstElementTarget::RaspberryPi)
+                .unwrap();
+        assert!(report.is_valid(), "{:#?}", report.issues);
+        // pre-roll is opt-in
+        let find = |name: &str| report.pipelines.iter().find(|p| p.name == name).unwrap();
+        assert!(!find("preroll").enabled);
+        let preroll = find("nozzle_preroll");
+        assert!(preroll.enabled);
+        assert!(preroll
+            .description
+            .contains("listen-to=nozzle_h264_encode_sink"));
+        assert!(preroll.description.contains(&format!(
+            "max-files={} location=/var/run/printnanny-preroll/nozzle/%05d.ts max-size-time=4000000000",
+            cameras[1].preroll.max_files()
+        )));
+    }
+
+    #[test]
+    fn test_render_timelapse_pipelines() {
+        let mut settings = VideoStreamSettings::default();
+        let dir = TimelapseDir::new("/home/printnanny/.local/share/printnanny/timelapse/1");
+        let description = PrintNannyPipelineFactory::timelapse_pipeline_description(
+            TIMELAPSE_PIPELINE,
+            CAMERA_PIPELINE,
+            &dir,
+            &settings,
+        );
+        assert!(description.contains("framerate=1/10"));
+        assert!(description.contains(
+            "location=/home/printnanny/.local/share/printnanny/timelapse/1/frames/%05d.jpg"
+        ));
+
+        settings.timelapse.trigger = TimelapseTrigger::Layer;
+        let description = PrintNannyPipelineFactory::timelapse_pipeline_description(
+            TIMELAPSE_PIPELINE,
+            CAMERA_PIPELINE,
+            &dir,
+            &settings,
+        );
+        assert!(description.contains("/timelapse/1/latest/%05d.jpg max-files=3"));
+
+        let rendered = vec![
+            RenderedPipeline {
+                name: CAMERA_PIPELINE.into(),
+                kind: PipelineNodeKind::Camera,
+                enabled: true,
+                listen_to: None,
+                description: PrintNannyPipelineFactory::camera_pipeline_description(
+                    CAMERA_PIPELINE,
+                    &settings,
+                ),
+            },
+            RenderedPipeline {
+                name: TIMELAPSE_PIPELINE.into(),
+                kind: PipelineNodeKind::Snapshot,
+                enabled: true,
+                listen_to: Some(CAMERA_PIPELINE.into()),
+                description,
+            },
+            RenderedPipeline {
+                name: TIMELAPSE_ASSEMBLE_PIPELINE.into(),
+                kind: PipelineNodeKind::H264Encode,
+                enabled: true,
+                listen_to: None,
+                description: PrintNannyPipelineFactory::timelapse_assemble_pipeline_description(
+                    TIMELAPSE_ASSEMBLE_PIPELINE,
+                    &dir,
+                    &settings,
+                ),
+            },
+        ];
+        let issues: Vec<_> = PipelineRenderReport::new(rendered)
+            .errors()
+            .cloned()
+            .collect();
+        // frames are written by the timelapse pipeline, so missing input files are only warnings
+        assert!(issues.is_empty(), "{:#?}", issues);
     }
 }
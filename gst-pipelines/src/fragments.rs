@@ -0,0 +1,126 @@
+use std::fs;
+
+use anyhow::{anyhow, Result};
+use log::{error, info};
+use tokio::sync::mpsc;
+
+use gst_client::gstd_types::{
+    BusMessageBody, GstSplitMuxSinkFragmentMessage, GST_SPLIT_MUX_SINK_FRAGMENT_MESSAGE_CLOSED,
+    GST_SPLIT_MUX_SINK_FRAGMENT_MESSAGE_OPENED,
+};
+use printnanny_edge_db::video_recording::{
+    parse_video_recording_id, parse_video_recording_index, NewVideoRecordingPart,
+    VideoRecordingPart,
+};
+
+use crate::bus::PipelineBusMessage;
+
+fn part_row_id(location: &str) -> Result<String> {
+    VideoRecordingPart::row_id_from_filename(location).ok_or_else(|| {
+        anyhow!(
+            "Failed to parse VideoRecordingPart id from location={}",
+            location
+        )
+    })
+}
+
+// Insert local VideoRecordingPart row
+fn handle_fragment_opened(
+    fragment: GstSplitMuxSinkFragmentMessage,
+    sqlite_connection: &str,
+) -> Result<VideoRecordingPart> {
+    // parse recording id from fragment
+    let video_recording_id = parse_video_recording_id(&fragment.location).ok_or_else(|| {
+        anyhow!(
+            "Failed to parse VideoRecording id from location={}",
+            &fragment.location
+        )
+    })?;
+
+    let index = parse_video_recording_index(&fragment.location).ok_or_else(|| {
+        anyhow!(
+            "Failed to parse VideoRecordingPart index from location={}",
+            &fragment.location
+        )
+    })?;
+
+    let size = fs::metadata(&fragment.location)?.len() as i64;
+
+    let row_id = part_row_id(&fragment.location)?;
+
+    let row = NewVideoRecordingPart {
+        id: &row_id,
+        buffer_index: &index,
+        buffer_runningtime: &(fragment.running_time as i64),
+        deleted: &false,
+        file_name: &fragment.location,
+        video_recording_id: &video_recording_id,
+        size: &size,
+        // splitmuxsink is still writing the fragment, it's queued for upload by handle_fragment_closed
+        closed: &false,
+    };
+    match VideoRecordingPart::insert(sqlite_connection, row) {
+        Ok(()) => info!(
+            "Inserted VideoRecordingPart video_recording_id={} id={} file_name={}",
+            &video_recording_id, &row_id, &fragment.location
+        ),
+        Err(e) => error!("Failed to insert VideoRecordingPart row, error={}", e),
+    }
+    let result = VideoRecordingPart::get_by_id(sqlite_connection, &row_id)?;
+    Ok(result)
+}
+
+// mark VideoRecordingPart closed, the upload queue (printnanny_services::video_recording_sync) uploads it
+fn handle_fragment_closed(
+    fragment: GstSplitMuxSinkFragmentMessage,
+    sqlite_connection: &str,
+) -> Result<VideoRecordingPart> {
+    let row_id = part_row_id(&fragment.location)?;
+    let size = fs::metadata(&fragment.location)?.len() as i64;
+    VideoRecordingPart::mark_closed(sqlite_connection, &row_id, size)?;
+    let row = VideoRecordingPart::get_by_id(sqlite_connection, &row_id)?;
+    Ok(row)
+}
+
+// handle splitmuxsink fragment messages of the recording pipeline, received from GstBusWatcher, until receiver closes
+pub async fn handle_fragment_messages(
+    mut receiver: mpsc::Receiver<PipelineBusMessage>,
+    sqlite_connection: &str,
+) {
+    while let Some(msg) = receiver.recv().await {
+        let element = match msg.message.body() {
+            Ok(BusMessageBody::Element(element)) => element,
+            _ => continue,
+        };
+        match element.name.as_deref() {
+            Some(GST_SPLIT_MUX_SINK_FRAGMENT_MESSAGE_OPENED) => {
+                info!(
+                    "Handling msg on gstreamer pipeline bus name={} msg={:?}",
+                    msg.pipeline, msg
+                );
+                // insert filesink msg row
+                let result = element
+                    .parse::<GstSplitMuxSinkFragmentMessage>()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|fragment| handle_fragment_opened(fragment, sqlite_connection));
+                if let Err(e) = result {
+                    error!("Failed to insert VideoRecordingPart row error={}", e)
+                }
+            }
+            Some(GST_SPLIT_MUX_SINK_FRAGMENT_MESSAGE_CLOSED) => {
+                info!(
+                    "Handling msg on gstreamer pipeline bus name={} msg={:?}",
+                    msg.pipeline, msg
+                );
+                let result = element
+                    .parse::<GstSplitMuxSinkFragmentMessage>()
+                    .map_err(anyhow::Error::from)
+                    .and_then(|fragment| handle_fragment_closed(fragment, sqlite_connection));
+                if let Err(e) = result {
+                    error!("Failed to mark VideoRecordingPart row closed error={}", e)
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,639 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::{anyhow, Result};
+
+use printnanny_settings::cam::{
+    DetectionOverlayMode, PipelineNodeKind, PipelineNodeSettings, VideoStreamSettings,
+    DEFAULT_CAMERA_ID,
+};
+
+// A gstd pipeline in PipelineGraph
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PipelineNode {
+    pub name: String,
+    // VideoStreamSettings.camera_id used to render this pipeline
+    pub camera_id: String,
+    pub kind: PipelineNodeKind,
+    // false if the node (or any upstream node) is disabled
+    pub enabled: bool,
+    pub listen_to: Option<String>,
+    pub udp_port: Option<i32>,
+    // camera frames composited with bounding boxes, set on bounding_boxes nodes which h264_encode listens to
+    pub frames_from: Option<String>,
+}
+
+// interpipesink (upstream) -> interpipesrc (downstream) connection between two pipelines
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PipelineEdge {
+    pub upstream: String,
+    pub downstream: String,
+}
+
+// Result of comparing a PipelineGraph against the pipelines reported by gstd
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PipelineGraphDiff {
+    // enabled nodes missing from gstd, in creation order
+    pub create: Vec<PipelineNode>,
+    // disabled nodes present in gstd, downstream pipelines first
+    pub delete: Vec<String>,
+    // enabled nodes already present in gstd
+    pub unchanged: Vec<String>,
+    // enabled nodes present in gstd, which were created with a different (or unknown) description, in creation order
+    pub changed: Vec<PipelineNode>,
+    // pipelines present in gstd, which are not part of the graph (e.g. h264_record)
+    pub unmanaged: Vec<String>,
+}
+
+impl PipelineGraphDiff {
+    pub fn is_empty(&self) -> bool {
+        self.create.is_empty() && self.delete.is_empty() && self.changed.is_empty()
+    }
+}
+
+// Directed graph of gstd pipelines, sorted so that every node appears after the node it listens to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PipelineGraph {
+    nodes: Vec<PipelineNode>,
+}
+
+impl PipelineGraph {
+    pub fn new(nodes: &[PipelineNodeSettings]) -> Result<Self> {
+        let nodes: Vec<(String, PipelineNodeSettings)> = nodes
+            .iter()
+            .map(|node| (DEFAULT_CAMERA_ID.to_string(), node.clone()))
+            .collect();
+        Self::build(&nodes)
+    }
+
+    // nodes are (camera_id, node) pairs
+    fn build(camera_nodes: &[(String, PipelineNodeSettings)]) -> Result<Self> {
+        let nodes: Vec<&PipelineNodeSettings> = camera_nodes.iter().map(|(_, n)| n).collect();
+        let camera_ids: HashMap<&str, &str> = camera_nodes
+            .iter()
+            .map(|(camera_id, n)| (n.name.as_str(), camera_id.as_str()))
+            .collect();
+        let mut by_name: HashMap<&str, &PipelineNodeSettings> = HashMap::new();
+        for node in nodes.iter().copied() {
+            if node.name.is_empty() || node.name.contains(char::is_whitespace) {
+                return Err(anyhow!("Invalid pipeline name={:?}", node.name));
+            }
+            if by_name.insert(node.name.as_str(), node).is_some() {
+                return Err(anyhow!("Duplicate pipeline name={}", node.name));
+            }
+        }
+
+        // count upstream edges for each node and validate listen_to
+        let mut in_degree: HashMap<&str, usize> = HashMap::new();
+        let mut downstream: HashMap<&str, Vec<&str>> = HashMap::new();
+        for node in nodes.iter() {
+            match (&node.kind, &node.listen_to) {
+                (PipelineNodeKind::Camera, Some(listen_to)) => {
+                    return Err(anyhow!(
+                        "Camera pipeline name={} cannot listen_to={}",
+                        node.name,
+                        listen_to
+                    ))
+                }
+                (PipelineNodeKind::Camera, None) => {
+                    in_degree.insert(&node.name, 0);
+                }
+                (_, None) => {
+                    return Err(anyhow!(
+                        "Pipeline name={} kind={:?} requires listen_to",
+                        node.name,
+                        node.kind
+                    ))
+                }
+                (_, Some(listen_to)) => {
+                    if !by_name.contains_key(listen_to.as_str()) {
+                        return Err(anyhow!(
+                            "Pipeline name={} listens to unknown pipeline={}",
+                            node.name,
+                            listen_to
+                        ));
+                    }
+                    in_degree.insert(&node.name, 1);
+                    downstream
+                        .entry(listen_to.as_str())
+                        .or_default()
+                        .push(&node.name);
+                }
+            }
+        }
+
+        // Kahn's algorithm, preserving declaration order between siblings
+        let mut queue: VecDeque<&str> = nodes
+            .iter()
+            .filter(|n| in_degree[n.name.as_str()] == 0)
+            .map(|n| n.name.as_str())
+            .collect();
+        let mut sorted: Vec<PipelineNode> = Vec::with_capacity(nodes.len());
+        let mut enabled: HashMap<&str, bool> = HashMap::new();
+        while let Some(name) = queue.pop_front() {
+            let node = by_name[name];
+            let upstream_enabled = match &node.listen_to {
+                Some(listen_to) => enabled[listen_to.as_str()],
+                None => true,
+            };
+            enabled.insert(name, node.enabled && upstream_enabled);
+            sorted.push(PipelineNode {
+                name: node.name.clone(),
+                camera_id: camera_ids[name].to_string(),
+                kind: node.kind,
+                enabled: node.enabled && upstream_enabled,
+                listen_to: node.listen_to.clone(),
+                udp_port: node.udp_port,
+                frames_from: Self::frames_from(node, &nodes, &by_name)?,
+            });
+            if let Some(children) = downstream.get(name) {
+                queue.extend(children.iter());
+            }
+        }
+
+        if sorted.len() != nodes.len() {
+            let sorted_names: HashSet<&str> = sorted.iter().map(|n| n.name.as_str()).collect();
+            let cycle: Vec<&str> = nodes
+                .iter()
+                .map(|n| n.name.as_str())
+                .filter(|n| !sorted_names.contains(n))
+                .collect();
+            return Err(anyhow!("Pipeline graph contains a cycle: {:?}", cycle));
+        }
+
+        Ok(Self { nodes: sorted })
+    }
+
+    // A bounding_boxes node listened to by h264_encode composites boxes onto the frames its inference node listens to
+    fn frames_from(
+        node: &PipelineNodeSettings,
+        nodes: &[&PipelineNodeSettings],
+        by_name: &HashMap<&str, &PipelineNodeSettings>,
+    ) -> Result<Option<String>> {
+        let composited = node.kind == PipelineNodeKind::BoundingBoxes
+            && nodes.iter().any(|n| {
+                n.kind == PipelineNodeKind::H264Encode && n.listen_to.as_ref() == Some(&node.name)
+            });
+        if !composited {
+            return Ok(None);
+        }
+        let inference = node
+            .listen_to
+            .as_deref()
+            .and_then(|listen_to| by_name.get(listen_to));
+        match inference.and_then(|n| n.listen_to.clone()) {
+            Some(frames) => Ok(Some(frames)),
+            None => Err(anyhow!(
+                "Pipeline name={} is composited by h264_encode, but its upstream pipeline does not listen to camera frames",
+                node.name
+            )),
+        }
+    }
+
+    // DetectionOverlayMode::Composite retargets h264_encode from the camera to the first enabled bounding_boxes node
+    // Returns (camera, bounding_boxes) names, or None if inference or bounding_boxes is disabled
+    fn composite_overlay(nodes: &[PipelineNodeSettings]) -> Option<(String, String)> {
+        nodes
+            .iter()
+            .filter(|n| n.kind == PipelineNodeKind::BoundingBoxes && n.enabled)
+            .find_map(|bb| {
+                let inference = nodes
+                    .iter()
+                    .find(|n| n.enabled && bb.listen_to.as_ref() == Some(&n.name))?;
+                let camera = inference.listen_to.clone()?;
+                Some((camera, bb.name.clone()))
+            })
+    }
+
+    // VideoStreamSettings.pipelines namespaced by camera_id, honoring HlsSettings.enabled, SnapshotSettings.enabled, PrerollSettings.enabled
+    // and DetectionOverlaySettings.mode
+    fn camera_nodes(settings: &VideoStreamSettings) -> Vec<(String, PipelineNodeSettings)> {
+        let composite = match settings.detection_overlay.mode {
+            DetectionOverlayMode::Composite => Self::composite_overlay(&settings.pipelines),
+            DetectionOverlayMode::Stream => None,
+        };
+        settings
+            .pipelines
+            .iter()
+            .map(|node| {
+                let listen_to = match &composite {
+                    Some((camera, bb))
+                        if node.kind == PipelineNodeKind::H264Encode
+                            && node.listen_to.as_ref() == Some(camera) =>
+                    {
+                        Some(bb)
+                    }
+                    _ => node.listen_to.as_ref(),
+                };
+                let enabled = match node.kind {
+                    PipelineNodeKind::Hls => node.enabled && settings.hls.enabled,
+                    PipelineNodeKind::Snapshot => node.enabled && settings.snapshot.enabled,
+                    PipelineNodeKind::Preroll => node.enabled && settings.preroll.enabled,
+                    _ => node.enabled,
+                };
+                let node = PipelineNodeSettings {
+                    name: settings.namespaced_pipeline_name(&node.name),
+                    listen_to: listen_to
+                        .map(|listen_to| settings.namespaced_pipeline_name(listen_to)),
+                    enabled,
+                    ..node.clone()
+                };
+                (settings.camera_id.clone(), node)
+            })
+            .collect()
+    }
+
+    // Build graph from a single camera's VideoStreamSettings.pipelines
+    pub fn from_settings(settings: &VideoStreamSettings) -> Result<Self> {
+        Self::build(&Self::camera_nodes(settings))
+    }
+
+    // Build graph containing the pipelines of every camera
+    pub fn from_cameras(cameras: &[VideoStreamSettings]) -> Result<Self> {
+        let mut camera_ids: HashSet<&str> = HashSet::new();
+        let mut udp_ports: HashMap<i32, &str> = HashMap::new();
+        for camera in cameras.iter() {
+            let camera_id = camera.camera_id.as_str();
+            if camera_id.is_empty()
+                || !camera_id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return Err(anyhow!("Invalid camera_id={:?}", camera_id));
+            }
+            if !camera_ids.insert(camera_id) {
+                return Err(anyhow!("Duplicate camera_id={}", camera_id));
+            }
+            camera.validate()?;
+            for port in [camera.rtp.video_udp_port, camera.rtp.overlay_udp_port] {
+                if let Some(other) = udp_ports.insert(port, camera_id) {
+                    if other != camera_id {
+                        return Err(anyhow!(
+                            "UDP port={} is used by camera_id={} and camera_id={}",
+                            port,
+                            other,
+                            camera_id
+                        ));
+                    }
+                }
+            }
+        }
+        let nodes: Vec<(String, PipelineNodeSettings)> =
+            cameras.iter().flat_map(Self::camera_nodes).collect();
+        let graph = Self::build(&nodes)?;
+
+        // PipelineNodeSettings.udp_port overrides must not collide with another camera's ports, or another pipeline's port
+        let mut node_ports: HashMap<i32, String> = HashMap::new();
+        for node in graph.enabled_nodes() {
+            let rtp = match cameras.iter().find(|c| c.camera_id == node.camera_id) {
+                Some(camera) => &camera.rtp,
+                None => continue,
+            };
+            let port = match (node.kind, &node.frames_from) {
+                (PipelineNodeKind::Rtp, _) => node.udp_port.unwrap_or(rtp.video_udp_port),
+                (PipelineNodeKind::BoundingBoxes, None) => {
+                    node.udp_port.unwrap_or(rtp.overlay_udp_port)
+                }
+                _ => continue,
+            };
+            if let Some(other) = udp_ports.get(&port) {
+                if *other != node.camera_id {
+                    return Err(anyhow!(
+                        "UDP port={} of pipeline={} is used by camera_id={}",
+                        port,
+                        node.name,
+                        other
+                    ));
+                }
+            }
+            if let Some(other) = node_ports.insert(port, node.name.clone()) {
+                return Err(anyhow!(
+                    "UDP port={} is used by pipeline={} and pipeline={}",
+                    port,
+                    other,
+                    node.name
+                ));
+            }
+        }
+        Ok(graph)
+    }
+
+    pub fn nodes(&self) -> &[PipelineNode] {
+        &self.nodes
+    }
+
+    pub fn enabled_nodes(&self) -> impl Iterator<Item = &PipelineNode> {
+        self.nodes.iter().filter(|n| n.enabled)
+    }
+
+    // nodes of camera_id, pipelines of other cameras are unmanaged when diffing the returned graph
+    pub fn camera_graph(&self, camera_id: &str) -> PipelineGraph {
+        PipelineGraph {
+            nodes: self
+                .nodes
+                .iter()
+                .filter(|n| n.camera_id == camera_id)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PipelineNode> {
+        self.nodes.iter().find(|n| n.name == name)
+    }
+
+    pub fn edges(&self) -> Vec<PipelineEdge> {
+        self.nodes
+            .iter()
+            .flat_map(|n| {
+                n.listen_to
+                    .iter()
+                    .chain(n.frames_from.iter())
+                    .map(|upstream| PipelineEdge {
+                        upstream: upstream.clone(),
+                        downstream: n.name.clone(),
+                    })
+            })
+            .collect()
+    }
+
+    // Compare graph with the names of pipelines reported by gstd
+    pub fn diff(&self, actual: &[String]) -> PipelineGraphDiff {
+        let actual_set: HashSet<&str> = actual.iter().map(|v| v.as_str()).collect();
+
+        let create = self
+            .enabled_nodes()
+            .filter(|n| !actual_set.contains(n.name.as_str()))
+            .cloned()
+            .collect();
+        let unchanged = self
+            .enabled_nodes()
+            .filter(|n| actual_set.contains(n.name.as_str()))
+            .map(|n| n.name.clone())
+            .collect();
+        let delete = self
+            .nodes
+            .iter()
+            .rev()
+            .filter(|n| !n.enabled && actual_set.contains(n.name.as_str()))
+            .map(|n| n.name.clone())
+            .collect();
+        let unmanaged = actual
+            .iter()
+            .filter(|name| self.get(name).is_none())
+            .cloned()
+            .collect();
+
+        PipelineGraphDiff {
+            create,
+            delete,
+            unchanged,
+            changed: vec![],
+            unmanaged,
+        }
+    }
+
+    // Compare graph with the pipelines reported by gstd, where actual maps pipeline names to the description they were created with
+    // rendered maps enabled node names to their current description; nodes with a different or unknown description are changed
+    pub fn diff_descriptions(
+        &self,
+        actual: &HashMap<String, Option<String>>,
+        rendered: &HashMap<String, String>,
+    ) -> PipelineGraphDiff {
+        let mut names: Vec<String> = actual.keys().cloned().collect();
+        names.sort();
+        let mut diff = self.diff(&names);
+        let (unchanged, changed): (Vec<String>, Vec<String>) = diff
+            .unchanged
+            .into_iter()
+            .partition(|name| match (actual.get(name), rendered.get(name)) {
+                (Some(Some(created)), Some(rendered)) => created == rendered,
+                _ => false,
+            });
+        diff.unchanged = unchanged;
+        diff.changed = self
+            .enabled_nodes()
+            .filter(|n| changed.contains(&n.name))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use printnanny_settings::cam::{default_pipelines, VideoStreamSettings};
+
+    fn names(nodes: &[PipelineNode]) -> Vec<&str> {
+        nodes.iter().map(|n| n.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_default_graph_order() {
+        let graph = PipelineGraph::new(&default_pipelines()).unwrap();
+        let nodes = graph.nodes();
+        for node in nodes.iter() {
+            if let Some(listen_to) = &node.listen_to {
+                let upstream = nodes.iter().position(|n| &n.name == listen_to).unwrap();
+                let downstream = nodes.iter().position(|n| n.name == node.name).unwrap();
+                assert!(upstream < downstream);
+            }
+        }
+        assert_eq!(nodes[0].name, "camera");
+        assert_eq!(graph.edges().len(), nodes.len() - 1);
+    }
+
+    #[test]
+    fn test_disabled_upstream_disables_downstream() {
+        let mut pipelines = default_pipelines();
+        for node in pipelines.iter_mut() {
+            if node.kind == PipelineNodeKind::Inference {
+                node.enabled = false;
+            }
+        }
+        let graph = PipelineGraph::new(&pipelines).unwrap();
+        assert!(!graph.get("bounding_boxes").unwrap().enabled);
+        assert!(!graph.get("df").unwrap().enabled);
+        assert!(graph.get("rtp").unwrap().enabled);
+    }
+
+    #[test]
+    fn test_extra_rtp_node() {
+        let mut pipelines = default_pipelines();
+        pipelines.push(PipelineNodeSettings {
+            name: "rtp_2".into(),
+            udp_port: Some(20003),
+            ..PipelineNodeSettings::new(PipelineNodeKind::Rtp, Some(PipelineNodeKind::H264Encode))
+        });
+        let graph = PipelineGraph::new(&pipelines).unwrap();
+        assert_eq!(graph.get("rtp_2").unwrap().udp_port, Some(20003));
+    }
+
+    #[test]
+    fn test_invalid_graphs() {
+        let mut unknown = default_pipelines();
+        unknown[1].listen_to = Some("missing".into());
+        assert!(PipelineGraph::new(&unknown).is_err());
+
+        let mut duplicate = default_pipelines();
+        duplicate.push(duplicate[2].clone());
+        assert!(PipelineGraph::new(&duplicate).is_err());
+
+        let cycle = vec![
+            PipelineNodeSettings {
+                name: "a".into(),
+                listen_to: Some("b".into()),
+                ..PipelineNodeSettings::new(PipelineNodeKind::Rtp, None)
+            },
+            PipelineNodeSettings {
+                name: "b".into(),
+                listen_to: Some("a".into()),
+                ..PipelineNodeSettings::new(PipelineNodeKind::Rtp, None)
+            },
+        ];
+        assert!(PipelineGraph::new(&cycle).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut pipelines = default_pipelines();
+        for node in pipelines.iter_mut() {
+            if node.kind == PipelineNodeKind::Hls {
+                node.enabled = false;
+            }
+        }
+        let graph = PipelineGraph::new(&pipelines).unwrap();
+        let actual = vec![
+            "camera".to_string(),
+            "hls".to_string(),
+            "h264_record".to_string(),
+        ];
+        let diff = graph.diff(&actual);
+        assert_eq!(
+            names(&diff.create),
+            vec![
+                "h264_encode",
+                "tflite_inference",
+                "snapshot",
+                "rtp",
+                "preroll",
+                "bounding_boxes",
+                "df"
+            ]
+        );
+        assert_eq!(diff.delete, vec!["hls".to_string()]);
+        assert_eq!(diff.unchanged, vec!["camera".to_string()]);
+        assert_eq!(diff.unmanaged, vec!["h264_record".to_string()]);
+    }
+
+    #[test]
+    fn test_diff_descriptions() {
+        let graph = PipelineGraph::new(&default_pipelines()).unwrap();
+        let rendered: HashMap<String, String> = graph
+            .enabled_nodes()
+            .map(|n| (n.name.clone(), format!("{} description", n.name)))
+            .collect();
+        let mut actual: HashMap<String, Option<String>> = rendered
+            .iter()
+            .map(|(name, description)| (name.clone(), Some(description.clone())))
+            .collect();
+        actual.insert("h264_record".into(), None);
+        let diff = graph.diff_descriptions(&actual, &rendered);
+        assert!(diff.is_empty());
+        assert_eq!(diff.unmanaged, vec!["h264_record".to_string()]);
+
+        actual.insert("rtp".into(), Some("rtp old description".into()));
+        actual.insert("camera".into(), None);
+        actual.remove("df");
+        let diff = graph.diff_descriptions(&actual, &rendered);
+        assert_eq!(names(&diff.changed), vec!["camera", "rtp"]);
+        assert_eq!(names(&diff.create), vec!["df"]);
+        assert!(!diff.unchanged.contains(&"rtp".to_string()));
+        assert!(diff.unchanged.contains(&"h264_encode".to_string()));
+        assert_eq!(diff.unmanaged, vec!["h264_record".to_string()]);
+    }
+
+    #[test]
+    fn test_composite_overlay_graph() {
+        let mut settings = VideoStreamSettings::default();
+        settings.detection_overlay.mode = DetectionOverlayMode::Composite;
+        let graph = PipelineGraph::from_settings(&settings).unwrap();
+        let h264_encode = graph.get("h264_encode").unwrap();
+        assert_eq!(h264_encode.listen_to.as_deref(), Some("bounding_boxes"));
+        assert!(h264_encode.enabled);
+        let bb = graph.get("bounding_boxes").unwrap();
+        assert_eq!(bb.frames_from.as_deref(), Some("camera"));
+        assert_eq!(graph.edges().len(), graph.nodes().len());
+        let position = |name: &str| graph.nodes().iter().position(|n| n.name == name).unwrap();
+        assert!(position("bounding_boxes") < position("h264_encode"));
+        assert!(position("h264_encode") < position("rtp"));
+
+        // h264_encode keeps listening to the camera when inference is disabled
+        for node in settings.pipelines.iter_mut() {
+            if node.kind == PipelineNodeKind::Inference {
+                node.enabled = false;
+            }
+        }
+        let graph = PipelineGraph::from_settings(&settings).unwrap();
+        let h264_encode = graph.get("h264_encode").unwrap();
+        assert_eq!(h264_encode.listen_to.as_deref(), Some("camera"));
+        assert!(graph.get("rtp").unwrap().enabled);
+        assert_eq!(graph.get("bounding_boxes").unwrap().frames_from, None);
+    }
+
+    #[test]
+    fn test_multi_camera_graph() {
+        let cameras = vec![
+            VideoStreamSettings::default(),
+            VideoStreamSettings::for_camera("nozzle", 1),
+        ];
+        let graph = PipelineGraph::from_cameras(&cameras).unwrap();
+        assert_eq!(graph.nodes().len(), 2 * default_pipelines().len());
+        let node = graph.get("nozzle_rtp").unwrap();
+        assert_eq!(node.camera_id, "nozzle");
+        assert_eq!(node.listen_to.as_deref(), Some("nozzle_h264_encode"));
+        assert_eq!(graph.get("rtp").unwrap().camera_id, DEFAULT_CAMERA_ID);
+
+        let nozzle = graph.camera_graph("nozzle");
+        assert_eq!(nozzle.nodes().len(), default_pipelines().len());
+        assert!(nozzle.nodes().iter().all(|n| n.camera_id == "nozzle"));
+        let actual: Vec<String> = graph.nodes().iter().map(|n| n.name.clone()).collect();
+        let diff = nozzle.diff(&actual);
+        assert!(diff.unmanaged.contains(&"camera".to_string()));
+        assert!(!diff.unchanged.contains(&"camera".to_string()));
+
+        let duplicate = vec![
+            VideoStreamSettings::for_camera("nozzle", 1),
+            VideoStreamSettings::for_camera("nozzle", 2),
+        ];
+        assert!(PipelineGraph::from_cameras(&duplicate).is_err());
+
+        let port_conflict = vec![
+            VideoStreamSettings::default(),
+            VideoStreamSettings::for_camera("nozzle", 0),
+        ];
+        assert!(PipelineGraph::from_cameras(&port_conflict).is_err());
+
+        // udp_port overrides are checked against every camera's ports
+        let mut nozzle = VideoStreamSettings::for_camera("nozzle", 1);
+        nozzle.pipelines.push(PipelineNodeSettings {
+            name: "rtp_2".into(),
+            udp_port: Some(VideoStreamSettings::default().rtp.video_udp_port),
+            ..PipelineNodeSettings::new(PipelineNodeKind::Rtp, Some(PipelineNodeKind::H264Encode))
+        });
+        let override_conflict = vec![VideoStreamSettings::default(), nozzle.clone()];
+        assert!(PipelineGraph::from_cameras(&override_conflict).is_err());
+
+        // and against other pipelines of the same camera
+        nozzle.pipelines.last_mut().unwrap().udp_port = Some(nozzle.rtp.video_udp_port);
+        let same_camera_conflict = vec![VideoStreamSettings::default(), nozzle.clone()];
+        assert!(PipelineGraph::from_cameras(&same_camera_conflict).is_err());
+
+        nozzle.pipelines.last_mut().unwrap().udp_port = Some(20003);
+        let cameras = vec![VideoStreamSettings::default(), nozzle];
+        assert!(PipelineGraph::from_cameras(&cameras).is_ok());
+
+        let invalid = vec![VideoStreamSettings::for_camera("nozzle.cam", 1)];
+        assert!(PipelineGraph::from_cameras(&invalid).is_err());
+    }
+}
@@ -1,3 +1,12 @@
+pub mod backend;
+pub mod bus;
+pub mod export;
 pub mod factory;
+pub mod fragments;
+pub mod graph;
+pub mod preroll;
+pub mod reconciler;
+pub mod timelapse;
+pub mod validate;
 
 pub use gst_client;
@@ -0,0 +1,196 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use log::info;
+
+// splitmuxsink location pattern; fragment ids increase monotonically while max-files deletes the oldest fragments
+const FRAGMENT_PATTERN: &str = "%05d.ts";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrerollFragment {
+    pub path: PathBuf,
+    pub index: i64,
+    // last write to the fragment, approximately the end of the fragment's time span
+    pub modified: SystemTime,
+    pub size: u64,
+}
+
+// Ring buffer of mpegts fragments written by the preroll pipeline
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrerollBuffer {
+    pub dir: PathBuf,
+}
+
+impl PrerollBuffer {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn location(&self) -> String {
+        self.dir.join(FRAGMENT_PATTERN).display().to_string()
+    }
+
+    // fragments ordered by index
+    pub fn fragments(&self) -> Result<Vec<PrerollFragment>> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut result = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map(|ext| ext != "ts").unwrap_or(true) {
+                continue;
+            }
+            let index = match path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<i64>().ok())
+            {
+                Some(index) => index,
+                None => continue,
+            };
+            let metadata = path.metadata()?;
+            result.push(PrerollFragment {
+                path,
+                index,
+                modified: metadata.modified()?,
+                size: metadata.len(),
+            });
+        }
+        result.sort_by_key(|f| f.index);
+        Ok(result)
+    }
+
+    // copy fragments overlapping [start, end] to dest/{n}.ts, numbered from 0
+    // the newest fragment is still being written by splitmuxsink, so it is never committed
+    pub fn commit(
+        &self,
+        dest: &Path,
+        start: SystemTime,
+        end: SystemTime,
+        fragment_duration: Duration,
+    ) -> Result<Vec<PrerollFragment>> {
+        let mut fragments = self.fragments()?;
+        fragments.pop();
+        fs::create_dir_all(dest)?;
+        let mut result = vec![];
+        for (index, fragment) in select_fragments(&fragments, start, end, fragment_duration)
+            .into_iter()
+            .enumerate()
+        {
+            let path = dest.join(format!("{index}.ts"));
+            fs::copy(&fragment.path, &path)?;
+            result.push(PrerollFragment {
+                path,
+                index: index as i64,
+                ..fragment.clone()
+            });
+        }
+        info!(
+            "Committed pre-roll fragments count={} src={} dest={}",
+            result.len(),
+            self.dir.display(),
+            dest.display()
+        );
+        Ok(result)
+    }
+}
+
+// fragments (ordered by index) overlapping [start, end]
+// a fragment spans from the previous fragment's modified time to its own, or fragment_duration for the oldest fragment
+pub fn select_fragments(
+    fragments: &[PrerollFragment],
+    start: SystemTime,
+    end: SystemTime,
+    fragment_duration: Duration,
+) -> Vec<&PrerollFragment> {
+    let mut result = vec![];
+    let mut previous: Option<SystemTime> = None;
+    for fragment in fragments.iter() {
+        let fragment_start = previous.unwrap_or_else(|| {
+            fragment
+                .modified
+                .checked_sub(fragment_duration)
+                .unwrap_or(fragment.modified)
+        });
+        if fragment.modified >= start && fragment_start <= end {
+            result.push(fragment);
+        }
+        previous = Some(fragment.modified);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(index: i64, modified: SystemTime) -> PrerollFragment {
+        PrerollFragment {
+            path: PathBuf::from(format!("/var/run/printnanny-preroll/{index:05}.ts")),
+            index,
+            modified,
+            size: 1024,
+        }
+    }
+
+    #[test]
+    fn test_select_fragments() {
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let secs = |n: u64| t0 + Duration::from_secs(n);
+        // fragments ending at t0+4, t0+8, ... t0+24
+        let fragments: Vec<PrerollFragment> =
+            (1..=6).map(|i| fragment(i, secs(4 * i as u64))).collect();
+
+        let selected = select_fragments(&fragments, secs(9), secs(15), Duration::from_secs(4));
+        let indexes: Vec<i64> = selected.iter().map(|f| f.index).collect();
+        assert_eq!(indexes, vec![3, 4]);
+
+        // window before the oldest fragment includes the oldest fragment
+        let selected = select_fragments(&fragments, secs(0), secs(1), Duration::from_secs(4));
+        let indexes: Vec<i64> = selected.iter().map(|f| f.index).collect();
+        assert_eq!(indexes, vec![1]);
+
+        let selected = select_fragments(&fragments, secs(30), secs(40), Duration::from_secs(4));
+        assert!(selected.is_empty());
+    }
+
+    #[test]
+    fn test_commit_preroll_buffer() {
+        let dir =
+            std::env::temp_dir().join(format!("printnanny-preroll-test-{}", std::process::id()));
+        let buffer = PrerollBuffer::new(dir.join("buffer"));
+        let dest = dir.join("recording");
+        assert!(buffer.fragments().unwrap().is_empty());
+
+        fs::create_dir_all(&buffer.dir).unwrap();
+        for index in 7..10 {
+            fs::write(buffer.dir.join(format!("{index:05}.ts")), b"fragment").unwrap();
+        }
+        fs::write(buffer.dir.join("playlist.m3u8"), b"").unwrap();
+        assert_eq!(buffer.fragments().unwrap().len(), 3);
+
+        let now = SystemTime::now();
+        let committed = buffer
+            .commit(
+                &dest,
+                now - Duration::from_secs(60),
+                now,
+                Duration::from_secs(4),
+            )
+            .unwrap();
+        // newest fragment is still being written
+        assert_eq!(committed.len(), 2);
+        assert_eq!(committed[1].path, dest.join("1.ts"));
+        assert!(dest.join("1.ts").exists());
+        assert_eq!(
+            buffer.location(),
+            dir.join("buffer/%05d.ts").display().to_string()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
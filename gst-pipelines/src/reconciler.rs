@@ -0,0 +1,682 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::time::{Duration, Instant};
+
+use gst_client::gstd_types::BusMessageBody;
+use printnanny_settings::cam::VideoStreamSettings;
+use printnanny_settings::printnanny::PrintNannySettings;
+
+use crate::bus::PipelineBusMessage;
+use crate::factory::{GstPipelineState, PrintNannyPipelineFactory};
+use crate::graph::{PipelineGraph, PipelineNode};
+
+// Exponential backoff applied to pipelines that fail to (re)start
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(2),
+            max: Duration::from_secs(300),
+        }
+    }
+}
+
+impl Backoff {
+    // delay before retry number `attempt` (starting at 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(31);
+        self.initial
+            .checked_mul(1 << exp)
+            .unwrap_or(self.max)
+            .min(self.max)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "transition", rename_all = "snake_case")]
+pub enum PipelineTransition {
+    Created,
+    Deleted,
+    StateChanged {
+        from: Option<GstPipelineState>,
+        to: GstPipelineState,
+    },
+    Errored {
+        message: String,
+    },
+    Recreated {
+        attempt: u32,
+    },
+    // pipeline was not PLAYING, e.g. play failed after pause succeeded
+    Replayed {
+        attempt: u32,
+    },
+    // rendered description changed, e.g. camera, encoder or model settings
+    Reconfigured,
+    BackoffScheduled {
+        attempt: u32,
+        retry_in_ms: u64,
+        error: String,
+    },
+}
+
+// Structured event emitted by PipelineReconciler for every observed or applied pipeline transition
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineReconcileEvent {
+    pub pipeline: String,
+    // unix timestamp (milliseconds)
+    pub ts: u64,
+    #[serde(flatten)]
+    pub transition: PipelineTransition,
+}
+
+impl PipelineReconcileEvent {
+    pub fn new(pipeline: &str, transition: PipelineTransition) -> Self {
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        Self {
+            pipeline: pipeline.to_string(),
+            ts,
+            transition,
+        }
+    }
+}
+
+// Kept after a successful recreate, so a pipeline failing again at runtime keeps backing off
+// Removed once the pipeline has stayed PLAYING without bus errors for PipelineReconciler's healthy period
+#[derive(Clone, Debug)]
+struct RetryState {
+    attempt: u32,
+    retry_at: Instant,
+    healthy_since: Option<Instant>,
+    // set after a replay, so a pipeline still not PLAYING once the retry is due is recreated
+    replayed: bool,
+}
+
+// Action taken on an existing pipeline once its backoff has expired
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Repair {
+    Replay,
+    Recreate,
+}
+
+// Long-running loop, which converges backend pipelines to the PipelineGraph described by PrintNannySettings
+pub struct PipelineReconciler {
+    factory: PrintNannyPipelineFactory,
+    interval: Duration,
+    backoff: Backoff,
+    healthy_period: Duration,
+    observed: HashMap<String, GstPipelineState>,
+    retries: HashMap<String, RetryState>,
+    pending_bus_errors: HashMap<String, String>,
+    sender: Option<UnboundedSender<PipelineReconcileEvent>>,
+    bus_errors: Option<mpsc::Receiver<PipelineBusMessage>>,
+}
+
+impl PipelineReconciler {
+    pub fn new(factory: PrintNannyPipelineFactory, interval: Duration) -> Self {
+        Self {
+            factory,
+            interval,
+            backoff: Backoff::default(),
+            healthy_period: Duration::from_secs(60),
+            observed: HashMap::new(),
+            retries: HashMap::new(),
+            pending_bus_errors: HashMap::new(),
+            sender: None,
+            bus_errors: None,
+        }
+    }
+
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    // reset a pipeline's backoff after it stays PLAYING with no bus error for healthy_period
+    pub fn with_healthy_period(mut self, healthy_period: Duration) -> Self {
+        self.healthy_period = healthy_period;
+        self
+    }
+
+    // forward every PipelineReconcileEvent to sender, in addition to logging it
+    pub fn with_event_sender(mut self, sender: UnboundedSender<PipelineReconcileEvent>) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    // recreate pipelines reporting ERROR messages received from GstBusWatcher, see bus::subscribe_bus_messages
+    // pipeline buses are never read by the reconciler, because gstd delivers each bus message to a single reader
+    pub fn with_bus_errors(mut self, receiver: mpsc::Receiver<PipelineBusMessage>) -> Self {
+        self.bus_errors = Some(receiver);
+        self
+    }
+
+    pub async fn run(&mut self) -> Result<()> {
+        info!(
+            "Starting PipelineReconciler interval={:?} backoff={:?} healthy_period={:?} backend={}",
+            self.interval,
+            self.backoff,
+            self.healthy_period,
+            self.factory.backend().kind()
+        );
+        let mut interval = tokio::time::interval(self.interval);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.reconcile().await {
+                error!("Pipeline reconcile failed error={}", e);
+            }
+        }
+    }
+
+    // run a single reconcile pass, returning the transitions observed or applied
+    pub async fn reconcile(&mut self) -> Result<Vec<PipelineReconcileEvent>> {
+        let settings = PrintNannySettings::new().await?;
+        let cameras = settings.video_streams();
+        let graph = PipelineGraph::from_cameras(&cameras)?;
+        let diff = self.factory.diff_graph(&graph, &cameras).await?;
+
+        let mut bus_errors = std::mem::take(&mut self.pending_bus_errors);
+        bus_errors.extend(self.drain_bus_errors());
+        let mut events = vec![];
+        let now = Instant::now();
+        for pipeline_name in diff.delete.iter() {
+            if self.in_backoff(pipeline_name, now) {
+                continue;
+            }
+            let result = self.factory.stop_and_delete_pipeline(pipeline_name).await;
+            if result.is_ok() {
+                self.forget(pipeline_name);
+            }
+            events.extend(self.handle_result(
+                pipeline_name,
+                result,
+                PipelineTransition::Deleted,
+                Instant::now(),
+            ));
+        }
+
+        for node in graph.enabled_nodes() {
+            if self.in_backoff(&node.name, now) {
+                // handle bus errors received while backing off once the retry is due
+                if let Some(message) = bus_errors.remove(&node.name) {
+                    self.pending_bus_errors.insert(node.name.clone(), message);
+                }
+                continue;
+            }
+            if diff.create.iter().any(|n| n.name == node.name) {
+                let result = self.start(node, &cameras).await;
+                let transition = match self.retries.contains_key(&node.name) {
+                    true => PipelineTransition::Recreated {
+                        attempt: self.next_attempt(&node.name),
+                    },
+                    false => PipelineTransition::Created,
+                };
+                events.extend(self.handle_result(&node.name, result, transition, Instant::now()));
+                continue;
+            }
+
+            if diff.changed.iter().any(|n| n.name == node.name) {
+                info!("Reconfiguring pipeline name={}", node.name);
+                let result = self.recreate(node, &cameras).await;
+                events.extend(self.handle_result(
+                    &node.name,
+                    result,
+                    PipelineTransition::Reconfigured,
+                    Instant::now(),
+                ));
+                continue;
+            }
+
+            let state = self.factory.pipeline_state(&node.name).await;
+            let previous = self.observed.insert(node.name.clone(), state.clone());
+            if previous.as_ref() != Some(&state) {
+                events.push(self.emit(
+                    &node.name,
+                    PipelineTransition::StateChanged {
+                        from: previous,
+                        to: state.clone(),
+                    },
+                ));
+            }
+
+            let bus_error = bus_errors.remove(&node.name);
+            if let Some(message) = &bus_error {
+                events.push(self.emit(
+                    &node.name,
+                    PipelineTransition::Errored {
+                        message: message.clone(),
+                    },
+                ));
+            }
+
+            let attempt = self.next_attempt(&node.name);
+            match self.repair(&node.name, &state, bus_error.is_some()) {
+                Some(Repair::Recreate) => {
+                    warn!(
+                        "Recreating pipeline name={} state={:?} error={:?}",
+                        node.name, state, bus_error
+                    );
+                    let result = self.recreate(node, &cameras).await;
+                    events.extend(self.handle_result(
+                        &node.name,
+                        result,
+                        PipelineTransition::Recreated { attempt },
+                        Instant::now(),
+                    ));
+                }
+                Some(Repair::Replay) => {
+                    warn!("Replaying pipeline name={} state={:?}", node.name, state);
+                    let result = self.factory.start_pipeline(&node.name).await;
+                    events.extend(self.handle_result(
+                        &node.name,
+                        result,
+                        PipelineTransition::Replayed { attempt },
+                        Instant::now(),
+                    ));
+                }
+                None => self.observe_healthy(&node.name, now),
+            }
+        }
+        Ok(events)
+    }
+
+    fn next_attempt(&self, pipeline_name: &str) -> u32 {
+        self.retries
+            .get(pipeline_name)
+            .map(|r| r.attempt)
+            .unwrap_or(0)
+            + 1
+    }
+
+    // pipelines are expected to be PLAYING once their backoff has expired
+    // a pipeline which isn't is replayed first, then recreated if it's still not PLAYING when the next retry is due
+    fn repair(
+        &self,
+        pipeline_name: &str,
+        state: &GstPipelineState,
+        bus_error: bool,
+    ) -> Option<Repair> {
+        let replayed = self
+            .retries
+            .get(pipeline_name)
+            .map(|retry| retry.replayed)
+            .unwrap_or(false);
+        match state {
+            _ if bus_error => Some(Repair::Recreate),
+            GstPipelineState::Playing => None,
+            GstPipelineState::Null => Some(Repair::Recreate),
+            _ if replayed => Some(Repair::Recreate),
+            _ => Some(Repair::Replay),
+        }
+    }
+
+    fn in_backoff(&self, pipeline_name: &str, now: Instant) -> bool {
+        self.retries
+            .get(pipeline_name)
+            .map(|retry| retry.retry_at > now)
+            .unwrap_or(false)
+    }
+
+    // reset backoff once the pipeline has been PLAYING with no bus error for healthy_period
+    fn observe_healthy(&mut self, pipeline_name: &str, now: Instant) {
+        let healthy_period = self.healthy_period;
+        if let Some(retry) = self.retries.get_mut(pipeline_name) {
+            let healthy_since = *retry.healthy_since.get_or_insert(now);
+            if now.saturating_duration_since(healthy_since) >= healthy_period {
+                info!(
+                    "Pipeline name={} healthy for {:?}, resetting backoff attempt={}",
+                    pipeline_name, healthy_period, retry.attempt
+                );
+                self.retries.remove(pipeline_name);
+            }
+        }
+    }
+
+    fn schedule_retry(&mut self, pipeline_name: &str, attempt: u32, now: Instant) -> Duration {
+        let delay = self.backoff.delay(attempt);
+        self.retries.insert(
+            pipeline_name.to_string(),
+            RetryState {
+                attempt,
+                retry_at: now + delay,
+                healthy_since: None,
+                replayed: false,
+            },
+        );
+        delay
+    }
+
+    async fn recreate(
+        &mut self,
+        node: &PipelineNode,
+        cameras: &[VideoStreamSettings],
+    ) -> Result<()> {
+        self.factory.stop_and_delete_pipeline(&node.name).await?;
+        self.forget(&node.name);
+        self.start(node, cameras).await
+    }
+
+    async fn start(&mut self, node: &PipelineNode, cameras: &[VideoStreamSettings]) -> Result<()> {
+        self.factory
+            .create_graph_pipelines(std::slice::from_ref(node), cameras)
+            .await
+    }
+
+    fn handle_result(
+        &mut self,
+        pipeline_name: &str,
+        result: Result<()>,
+        transition: PipelineTransition,
+        now: Instant,
+    ) -> Option<PipelineReconcileEvent> {
+        match result {
+            Ok(()) => {
+                match &transition {
+                    // a recreated pipeline may fail again at runtime, so the next recreate still waits for backoff
+                    PipelineTransition::Recreated { attempt } => {
+                        self.schedule_retry(pipeline_name, *attempt, now);
+                    }
+                    PipelineTransition::Replayed { attempt } => {
+                        self.schedule_retry(pipeline_name, *attempt, now);
+                        if let Some(retry) = self.retries.get_mut(pipeline_name) {
+                            retry.replayed = true;
+                        }
+                    }
+                    _ => {
+                        self.retries.remove(pipeline_name);
+                    }
+                }
+                Some(self.emit(pipeline_name, transition))
+            }
+            Err(e) => {
+                let attempt = self.next_attempt(pipeline_name);
+                let delay = self.schedule_retry(pipeline_name, attempt, now);
+                Some(self.emit(
+                    pipeline_name,
+                    PipelineTransition::BackoffScheduled {
+                        attempt,
+                        retry_in_ms: delay.as_millis() as u64,
+                        error: e.to_string(),
+                    },
+                ))
+            }
+        }
+    }
+
+    // last ERROR message received for each pipeline since the previous pass
+    fn drain_bus_errors(&mut self) -> HashMap<String, String> {
+        let mut errors = HashMap::new();
+        let receiver = match &mut self.bus_errors {
+            Some(receiver) => receiver,
+            None => return errors,
+        };
+        while let Ok(msg) = receiver.try_recv() {
+            match msg.message.body() {
+                Ok(BusMessageBody::Error(error)) => {
+                    errors.insert(msg.pipeline, error.message);
+                }
+                Ok(_) => (),
+                Err(e) => error!(
+                    "Failed to parse bus message pipeline={} error={}",
+                    msg.pipeline, e
+                ),
+            }
+        }
+        errors
+    }
+
+    fn forget(&mut self, pipeline_name: &str) {
+        self.observed.remove(pipeline_name);
+    }
+
+    fn emit(&self, pipeline_name: &str, transition: PipelineTransition) -> PipelineReconcileEvent {
+        let event = PipelineReconcileEvent::new(pipeline_name, transition);
+        match serde_json::to_string(&event) {
+            Ok(json) => info!("pipeline_reconcile_event={}", json),
+            Err(e) => error!("Failed to serialize {:?} error={}", event, e),
+        }
+        if let Some(sender) = &self.sender {
+            if let Err(e) = sender.send(event.clone()) {
+                error!("Failed to send PipelineReconcileEvent error={}", e);
+            }
+        }
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Backoff {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+        };
+        assert_eq!(backoff.delay(1), Duration::from_secs(1));
+        assert_eq!(backoff.delay(2), Duration::from_secs(2));
+        assert_eq!(backoff.delay(4), Duration::from_secs(8));
+        assert_eq!(backoff.delay(10), Duration::from_secs(60));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_recreate_backoff_grows_until_healthy() {
+        let mut reconciler =
+            PipelineReconciler::new(PrintNannyPipelineFactory::default(), Duration::from_secs(1))
+                .with_backoff(Backoff {
+                    initial: Duration::from_secs(1),
+                    max: Duration::from_secs(60),
+                })
+                .with_healthy_period(Duration::from_secs(30));
+
+        // create succeeds every time, but the pipeline reports a bus error again once the retry is due
+        let mut now = Instant::now();
+        let mut delays = vec![];
+        for expected_attempt in 1..=4 {
+            assert!(!reconciler.in_backoff("camera", now));
+            let attempt = reconciler.next_attempt("camera");
+            assert_eq!(attempt, expected_attempt);
+            let event = reconciler
+                .handle_result(
+                    "camera",
+                    Ok(()),
+                    PipelineTransition::Recreated { attempt },
+                    now,
+                )
+                .unwrap();
+            assert_eq!(event.transition, PipelineTransition::Recreated { attempt });
+            assert!(reconciler.in_backoff("camera", now));
+            let retry_at = reconciler.retries["camera"].retry_at;
+            delays.push(retry_at - now);
+            now = retry_at;
+        }
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                Duration::from_secs(4),
+                Duration::from_secs(8)
+            ]
+        );
+
+        // backoff is only reset after the pipeline stays PLAYING for the healthy period
+        reconciler.observe_healthy("camera", now);
+        reconciler.observe_healthy("camera", now + Duration::from_secs(29));
+        assert_eq!(reconciler.next_attempt("camera"), 5);
+        reconciler.observe_healthy("camera", now + Duration::from_secs(30));
+        assert_eq!(reconciler.next_attempt("camera"), 1);
+    }
+
+    #[test]
+    fn test_not_playing_is_replayed_then_recreated() {
+        let mut reconciler =
+            PipelineReconciler::new(PrintNannyPipelineFactory::default(), Duration::from_secs(1))
+                .with_backoff(Backoff {
+                    initial: Duration::from_secs(1),
+                    max: Duration::from_secs(60),
+                });
+        let now = Instant::now();
+        assert_eq!(
+            reconciler.repair("camera", &GstPipelineState::Playing, false),
+            None
+        );
+        assert_eq!(
+            reconciler.repair("camera", &GstPipelineState::Playing, true),
+            Some(Repair::Recreate)
+        );
+        assert_eq!(
+            reconciler.repair("camera", &GstPipelineState::Null, false),
+            Some(Repair::Recreate)
+        );
+
+        // e.g. play failed after pause succeeded
+        assert_eq!(
+            reconciler.repair("camera", &GstPipelineState::Paused, false),
+            Some(Repair::Replay)
+        );
+        let attempt = reconciler.next_attempt("camera");
+        reconciler.handle_result(
+            "camera",
+            Ok(()),
+            PipelineTransition::Replayed { attempt },
+            now,
+        );
+        assert!(reconciler.in_backoff("camera", now));
+
+        // still not PLAYING once the retry is due
+        assert_eq!(
+            reconciler.repair("camera", &GstPipelineState::Ready, false),
+            Some(Repair::Recreate)
+        );
+        let attempt = reconciler.next_attempt("camera");
+        assert_eq!(attempt, 2);
+        reconciler.handle_result(
+            "camera",
+            Ok(()),
+            PipelineTransition::Recreated { attempt },
+            now + Duration::from_secs(1),
+        );
+        assert_eq!(
+            reconciler.repair("camera", &GstPipelineState::Paused, false),
+            Some(Repair::Replay)
+        );
+    }
+
+    #[test]
+    fn test_failed_delete_schedules_backoff() {
+        let mut reconciler =
+            PipelineReconciler::new(PrintNannyPipelineFactory::default(), Duration::from_secs(1));
+        let now = Instant::now();
+        let event = reconciler
+            .handle_result(
+                "camera_old",
+                Err(anyhow::anyhow!("connection refused")),
+                PipelineTransition::Deleted,
+                now,
+            )
+            .unwrap();
+        assert_eq!(
+            event.transition,
+            PipelineTransition::BackoffScheduled {
+                attempt: 1,
+                retry_in_ms: 2000,
+                error: "connection refused".into(),
+            }
+        );
+        assert!(reconciler.in_backoff("camera_old", now));
+
+        let event = reconciler
+            .handle_result(
+                "camera_old",
+                Ok(()),
+                PipelineTransition::Deleted,
+                now + Duration::from_secs(2),
+            )
+            .unwrap();
+        assert_eq!(event.transition, PipelineTransition::Deleted);
+        assert!(!reconciler.retries.contains_key("camera_old"));
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let event = PipelineReconcileEvent {
+            pipeline: "camera".into(),
+            ts: 1,
+            transition: PipelineTransition::StateChanged {
+                from: Some(GstPipelineState::Playing),
+                to: GstPipelineState::Null,
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "pipeline": "camera",
+                "ts": 1,
+                "transition": "state_changed",
+                "from": "playing",
+                "to": "null",
+            })
+        );
+    }
+
+    #[test]
+    fn test_drain_bus_errors() {
+        let bus_message = |pipeline: &str, message_type: &str, message: &str| {
+            let mut fields = serde_json::Map::new();
+            fields.insert("message".into(), message.into());
+            PipelineBusMessage {
+                pipeline: pipeline.into(),
+                message: gst_client::gstd_types::BusMessage {
+                    r#type: message_type.into(),
+                    source: "pipeline0".into(),
+                    timestamp: "0:00:01.000000000".into(),
+                    seqnum: 1,
+                    fields,
+                },
+            }
+        };
+        let (sender, receiver) = mpsc::channel(10);
+        let mut reconciler =
+            PipelineReconciler::new(PrintNannyPipelineFactory::default(), Duration::from_secs(1))
+                .with_bus_errors(receiver);
+        sender
+            .try_send(bus_message("camera", "error", "first"))
+            .unwrap();
+        sender
+            .try_send(bus_message(
+                "camera",
+                "error",
+                "Internal data stream error.",
+            ))
+            .unwrap();
+        sender
+            .try_send(bus_message(
+                "rtp",
+                "warning",
+                "Can't record audio fast enough",
+            ))
+            .unwrap();
+
+        let errors = reconciler.drain_bus_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors["camera"], "Internal data stream error.");
+        assert!(reconciler.drain_bus_errors().is_empty());
+    }
+}
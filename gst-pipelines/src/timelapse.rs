@@ -0,0 +1,154 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use log::info;
+
+// multifilesink/multifilesrc location pattern, frames must be numbered contiguously from 0
+const FRAME_PATTERN: &str = "%05d.jpg";
+
+// Layout of a timelapse directory:
+//   frames/00000.jpg .. frames/NNNNN.jpg   frames assembled into timelapse.mp4
+//   latest/                                 most recent camera frames, copied to frames/ on layer change
+//   timelapse.mp4
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimelapseDir {
+    pub dir: PathBuf,
+}
+
+impl TimelapseDir {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn frames(&self) -> PathBuf {
+        self.dir.join("frames")
+    }
+
+    pub fn latest(&self) -> PathBuf {
+        self.dir.join("latest")
+    }
+
+    pub fn output(&self) -> PathBuf {
+        self.dir.join("timelapse.mp4")
+    }
+
+    pub fn frames_location(&self) -> String {
+        self.frames().join(FRAME_PATTERN).display().to_string()
+    }
+
+    pub fn latest_location(&self) -> String {
+        self.latest().join(FRAME_PATTERN).display().to_string()
+    }
+
+    pub fn frame_path(&self, index: i64) -> PathBuf {
+        self.frames().join(format!("{index:05}.jpg"))
+    }
+
+    pub fn create_all(&self) -> Result<()> {
+        fs::create_dir_all(self.frames())?;
+        fs::create_dir_all(self.latest())?;
+        Ok(())
+    }
+
+    fn jpegs(dir: &Path) -> Result<Vec<PathBuf>> {
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut result: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().map(|ext| ext == "jpg").unwrap_or(false))
+            .collect();
+        result.sort();
+        Ok(result)
+    }
+
+    pub fn frame_count(&self) -> Result<i64> {
+        Ok(Self::jpegs(&self.frames())?.len() as i64)
+    }
+
+    // copy the most recent complete frame in latest/ to frames/{index}.jpg
+    // multifilesink may still be writing the newest file, so the second-newest file is preferred
+    pub fn capture_latest_frame(&self, index: i64) -> Result<Option<PathBuf>> {
+        let mut latest = Self::jpegs(&self.latest())?;
+        latest.sort_by_key(|p| {
+            p.metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or(std::time::UNIX_EPOCH)
+        });
+        let src = match latest.len() {
+            0 => return Ok(None),
+            1 => &latest[0],
+            n => &latest[n - 2],
+        };
+        let dest = self.frame_path(index);
+        fs::copy(src, &dest)?;
+        info!(
+            "Captured timelapse frame src={} dest={}",
+            src.display(),
+            dest.display()
+        );
+        Ok(Some(dest))
+    }
+}
+
+// M240 is the conventional "trigger camera" command, inserted by slicers' layer change gcode
+pub fn is_camera_trigger(gcode: &str) -> bool {
+    gcode
+        .split_whitespace()
+        .next()
+        .map(|cmd| cmd.eq_ignore_ascii_case("M240"))
+        .unwrap_or(false)
+}
+
+// parse Z height from a G0/G1 move, e.g. "G1 Z0.4 F3000"
+pub fn parse_layer_z(gcode: &str) -> Option<f64> {
+    // strip trailing comment
+    let gcode = gcode.split(';').next().unwrap_or_default();
+    let mut tokens = gcode.split_whitespace();
+    match tokens.next() {
+        Some(cmd) if cmd.eq_ignore_ascii_case("G0") || cmd.eq_ignore_ascii_case("G1") => (),
+        _ => return None,
+    }
+    tokens
+        .find_map(|t| t.strip_prefix('Z').or_else(|| t.strip_prefix('z')))
+        .and_then(|z| z.parse::<f64>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_layer_z() {
+        assert_eq!(parse_layer_z("G1 Z0.4 F3000"), Some(0.4));
+        assert_eq!(parse_layer_z("G0 X10 Y10 Z2 ; travel"), Some(2.0));
+        assert_eq!(parse_layer_z("G1 X10 Y10 E0.5"), None);
+        assert_eq!(parse_layer_z("G28 Z"), None);
+        assert_eq!(parse_layer_z("M117 Z1.0"), None);
+        assert!(is_camera_trigger("M240"));
+        assert!(is_camera_trigger("m240 ; layer change"));
+        assert!(!is_camera_trigger("M2400"));
+    }
+
+    #[test]
+    fn test_capture_latest_frame() {
+        let dir =
+            std::env::temp_dir().join(format!("printnanny-timelapse-test-{}", std::process::id()));
+        let timelapse = TimelapseDir::new(&dir);
+        timelapse.create_all().unwrap();
+        assert_eq!(timelapse.capture_latest_frame(0).unwrap(), None);
+
+        fs::write(timelapse.latest().join("00000.jpg"), b"frame").unwrap();
+        let frame = timelapse.capture_latest_frame(0).unwrap().unwrap();
+        assert_eq!(frame, timelapse.frame_path(0));
+        assert_eq!(timelapse.frame_count().unwrap(), 1);
+        assert_eq!(
+            timelapse.frames_location(),
+            dir.join("frames/%05d.jpg").display().to_string()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use printnanny_settings::cam::PipelineNodeKind;
+
+// gstd pipeline description rendered without contacting gstd
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderedPipeline {
+    pub name: String,
+    pub kind: PipelineNodeKind,
+    pub enabled: bool,
+    pub listen_to: Option<String>,
+    pub description: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationSeverity {
+    // e.g. a model file missing on the machine rendering the pipeline, which may exist on the printer
+    Warning,
+    // description would be rejected by gstd or could never link
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineValidationIssue {
+    pub pipeline: String,
+    pub severity: ValidationSeverity,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineRenderReport {
+    pub pipelines: Vec<RenderedPipeline>,
+    pub issues: Vec<PipelineValidationIssue>,
+}
+
+impl PipelineRenderReport {
+    pub fn new(pipelines: Vec<RenderedPipeline>) -> Self {
+        let issues = validate_pipelines(&pipelines);
+        Self { pipelines, issues }
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &PipelineValidationIssue> {
+        self.issues
+            .iter()
+            .filter(|i| i.severity == ValidationSeverity::Error)
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+}
+
+// Element parsed from a gst-launch style description, e.g. "udpsink port=20001"
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsedElement {
+    pub factory: String,
+    pub properties: Vec<(String, String)>,
+}
+
+impl ParsedElement {
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+// pad reference starting a branch or linking into a named element, e.g. "comp.sink_0" or "t."
+fn is_pad_reference(token: &str) -> bool {
+    !token.contains('=') && token.contains('.')
+}
+
+// Split a description into elements, returning an error for empty links (e.g. "a ! ! b")
+// pad references are skipped, so "... ! comp.sink_0 videotestsrc ! comp.sink_1 compositor name=comp" has 3 elements
+pub fn parse_description(description: &str) -> Result<Vec<ParsedElement>, String> {
+    let mut elements: Vec<ParsedElement> = vec![];
+    for (i, segment) in description.split('!').enumerate() {
+        let mut element: Option<ParsedElement> = None;
+        let mut linked = false;
+        for token in segment.split_whitespace() {
+            if is_pad_reference(token) {
+                elements.extend(element.take());
+                linked = true;
+                continue;
+            }
+            match (&mut element, token.split_once('=')) {
+                (None, _) => {
+                    element = Some(ParsedElement {
+                        factory: token.to_string(),
+                        properties: vec![],
+                    })
+                }
+                (Some(element), Some((key, value))) if !key.is_empty() && !value.is_empty() => {
+                    element
+                        .properties
+                        .push((key.to_string(), value.to_string()))
+                }
+                (Some(element), _) => {
+                    return Err(format!(
+                        "Invalid property {token:?} on element {}",
+                        element.factory
+                    ))
+                }
+            }
+        }
+        match element {
+            Some(element) => elements.push(element),
+            None if linked => (),
+            None => return Err(format!("Empty element at position {i}")),
+        }
+    }
+    Ok(elements)
+}
+
+fn is_caps_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'))
+}
+
+// Validate a caps string, e.g. video/x-raw,width=640,height=480,format=YUY2
+pub fn validate_caps(caps: &str) -> Result<(), String> {
+    if caps.is_empty() {
+        return Err("Caps are empty".into());
+    }
+    if caps.contains(char::is_whitespace) {
+        return Err(format!("Caps contain whitespace: {caps}"));
+    }
+    let mut fields = caps.split(',');
+    let media_type = fields.next().unwrap_or_default();
+    // strip caps features, e.g. video/x-raw(memory:DMABuf)
+    let media_type = match media_type.split_once('(') {
+        Some((media_type, features)) if features.ends_with(')') => media_type,
+        Some(_) => return Err(format!("Invalid caps features in {caps}")),
+        None => media_type,
+    };
+    match media_type.split_once('/') {
+        Some((kind, subtype)) if is_caps_token(kind) && is_caps_token(subtype) => (),
+        _ => return Err(format!("Invalid media type {media_type:?} in {caps}")),
+    }
+    for field in fields {
+        let (key, value) = match field.split_once('=') {
+            Some(kv) => kv,
+            None => return Err(format!("Caps field {field:?} is missing a value")),
+        };
+        if !is_caps_token(key) {
+            return Err(format!("Invalid caps field name {key:?} in {caps}"));
+        }
+        let value = match value.strip_prefix('(') {
+            Some(typed) => match typed.split_once(')') {
+                Some((_type, value)) => value,
+                None => return Err(format!("Unterminated caps field type in {field:?}")),
+            },
+            None => value,
+        };
+        if value.is_empty() {
+            return Err(format!("Caps field {key:?} has an empty value"));
+        }
+    }
+    Ok(())
+}
+
+fn issue(
+    pipeline: &RenderedPipeline,
+    severity: ValidationSeverity,
+    message: String,
+) -> PipelineValidationIssue {
+    PipelineValidationIssue {
+        pipeline: pipeline.name.clone(),
+        severity,
+        message,
+    }
+}
+
+// Output locations must be absolute, and their parent directory should exist
+fn validate_output_location(
+    pipeline: &RenderedPipeline,
+    key: &str,
+    location: &str,
+) -> Option<PipelineValidationIssue> {
+    let path = Path::new(location);
+    if !path.is_absolute() {
+        return Some(issue(
+            pipeline,
+            ValidationSeverity::Error,
+            format!("{key}={location} is not an absolute path"),
+        ));
+    }
+    match path.parent() {
+        Some(parent) if !parent.exists() => Some(issue(
+            pipeline,
+            ValidationSeverity::Warning,
+            format!("{key}={location} parent directory does not exist"),
+        )),
+        _ => None,
+    }
+}
+
+// Input files (media, models, labels) must be absolute, and should exist
+fn validate_input_file(
+    pipeline: &RenderedPipeline,
+    key: &str,
+    location: &str,
+) -> Option<PipelineValidationIssue> {
+    let path = Path::new(location);
+    if !path.is_absolute() {
+        return Some(issue(
+            pipeline,
+            ValidationSeverity::Error,
+            format!("{key}={location} is not an absolute path"),
+        ));
+    }
+    if !path.exists() {
+        return Some(issue(
+            pipeline,
+            ValidationSeverity::Warning,
+            format!("{key}={location} does not exist"),
+        ));
+    }
+    None
+}
+
+// Validate caps strings, interpipe names and file locations of enabled pipelines
+pub fn validate_pipelines(pipelines: &[RenderedPipeline]) -> Vec<PipelineValidationIssue> {
+    let mut issues = vec![];
+    let mut parsed: Vec<(&RenderedPipeline, Vec<ParsedElement>)> = vec![];
+    for pipeline in pipelines.iter().filter(|p| p.enabled) {
+        match parse_description(&pipeline.description) {
+            Ok(elements) => parsed.push((pipeline, elements)),
+            Err(e) => issues.push(issue(pipeline, ValidationSeverity::Error, e)),
+        }
+    }
+
+    let mut sinks: HashMap<&str, &str> = HashMap::new();
+    let mut srcs: HashSet<&str> = HashSet::new();
+    for (pipeline, elements) in parsed.iter() {
+        for element in elements.iter() {
+            let name = element.property("name");
+            match (element.factory.as_str(), name) {
+                ("interpipesink", Some(name)) => {
+                    if let Some(other) = sinks.insert(name, &pipeline.name) {
+                        issues.push(issue(
+                            pipeline,
+                            ValidationSeverity::Error,
+                            format!("interpipesink name={name} is already used by {other}"),
+                        ));
+                    }
+                }
+                ("interpipesrc", Some(name)) => {
+                    let unique = srcs.insert(name);
+                    if !unique {
+                        issues.push(issue(
+                            pipeline,
+                            ValidationSeverity::Error,
+                            format!("interpipesrc name={name} is not unique"),
+                        ));
+                    }
+                }
+                ("interpipesink", None) | ("interpipesrc", None) => issues.push(issue(
+                    pipeline,
+                    ValidationSeverity::Error,
+                    format!("{} requires a name property", element.factory),
+                )),
+                _ => (),
+            }
+        }
+    }
+
+    for (pipeline, elements) in parsed.iter() {
+        for element in elements.iter() {
+            if element.factory == "interpipesrc" {
+                match element.property("listen-to") {
+                    Some(listen_to) if !sinks.contains_key(listen_to) => issues.push(issue(
+                        pipeline,
+                        ValidationSeverity::Error,
+                        format!("interpipesrc listen-to={listen_to} does not match any enabled interpipesink"),
+                    )),
+                    Some(_) => (),
+                    None => issues.push(issue(
+                        pipeline,
+                        ValidationSeverity::Error,
+                        "interpipesrc requires a listen-to property".into(),
+                    )),
+                }
+            }
+
+            if let Some(caps) = element.property("caps") {
+                // capsfilter and interpipesrc caps are required to be parseable by gst_caps_from_string
+                if let Err(e) = validate_caps(caps) {
+                    issues.push(issue(pipeline, ValidationSeverity::Error, e));
+                }
+            }
+
+            let output_keys: &[&str] = match element.factory.as_str() {
+                "multifilesink" | "filesink" | "splitmuxsink" => &["location"],
+                "hlssink2" => &["location", "playlist-location"],
+                _ => &[],
+            };
+            for key in output_keys {
+                if let Some(location) = element.property(key) {
+                    issues.extend(validate_output_location(pipeline, key, location));
+                }
+            }
+
+            let input_keys: &[&str] = match (element.factory.as_str(), element.property("mode")) {
+                ("filesrc", _) | ("multifilesrc", _) => &["location"],
+                ("tensor_filter", _) => &["model"],
+                ("tensor_decoder", Some("bounding_boxes")) => &["option2"],
+                ("bounding_box_overlay", _) => &["label-file"],
+                _ => &[],
+            };
+            for key in input_keys {
+                if let Some(location) = element.property(key) {
+                    issues.extend(validate_input_file(pipeline, key, location));
+                }
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered(name: &str, description: &str) -> RenderedPipeline {
+        RenderedPipeline {
+            name: name.into(),
+            kind: PipelineNodeKind::Rtp,
+            enabled: true,
+            listen_to: None,
+            description: description.into(),
+        }
+    }
+
+    #[test]
+    fn test_validate_caps() {
+        assert!(validate_caps("video/x-raw,width=640,height=480,format=YUY2").is_ok());
+        assert!(validate_caps("video/x-h264,level=(string)4,profile=(string)high").is_ok());
+        assert!(validate_caps("video/x-raw(memory:DMABuf),framerate=16/1").is_ok());
+        assert!(validate_caps("other/tensors,format=static").is_ok());
+        assert!(validate_caps("").is_err());
+        assert!(validate_caps("video,width=640").is_err());
+        assert!(validate_caps("video/x-raw,width=").is_err());
+        assert!(validate_caps("video/x-raw,width").is_err());
+        assert!(validate_caps("video/x-raw,level=(string4").is_err());
+    }
+
+    #[test]
+    fn test_parse_description() {
+        let elements = parse_description("videotestsrc ! udpsink port=20001").unwrap();
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[1].property("port"), Some("20001"));
+        assert!(parse_description("videotestsrc ! ! udpsink").is_err());
+        assert!(parse_description("videotestsrc ! udpsink port").is_err());
+
+        let elements = parse_description(
+            "videotestsrc ! comp.sink_0 videotestsrc pattern=ball ! comp.sink_1 compositor name=comp ! fakesink",
+        )
+        .unwrap();
+        let factories: Vec<&str> = elements.iter().map(|e| e.factory.as_str()).collect();
+        assert_eq!(
+            factories,
+            vec!["videotestsrc", "videotestsrc", "compositor", "fakesink"]
+        );
+        assert_eq!(elements[1].property("pattern"), Some("ball"));
+        assert!(parse_description("videotestsrc ! comp.sink_0 fakesink sync").is_err());
+    }
+
+    #[test]
+    fn test_validate_interpipe_names() {
+        let pipelines = vec![
+            rendered(
+                "camera",
+                "videotestsrc ! interpipesink name=camera_sink sync=true",
+            ),
+            rendered(
+                "rtp",
+                "interpipesrc name=rtp_src listen-to=missing_sink ! fakesink",
+            ),
+        ];
+        let issues = validate_pipelines(&pipelines);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].pipeline, "rtp");
+        assert_eq!(issues[0].severity, ValidationSeverity::Error);
+    }
+
+    #[test]
+    fn test_validate_file_locations() {
+        let pipelines = vec![rendered(
+            "snapshot",
+            "videotestsrc ! jpegenc ! multifilesink location=snapshot-%d.jpg",
+        )];
+        let report = PipelineRenderReport::new(pipelines);
+        assert!(!report.is_valid());
+
+        let pipelines = vec![rendered(
+            "snapshot",
+            "videotestsrc ! jpegenc ! multifilesink location=/does-not-exist/snapshot-%d.jpg",
+        )];
+        let report = PipelineRenderReport::new(pipelines);
+        assert!(report.is_valid());
+        assert_eq!(report.issues[0].severity, ValidationSeverity::Warning);
+    }
+}
@@ -0,0 +1,170 @@
+use std::num::ParseFloatError;
+
+use polars::prelude::*;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AggregateError {
+    #[error("Percentile {value} must be between 0 and 100")]
+    InvalidPercentile { value: f64 },
+    #[error("Failed to parse percentile from: {percentiles}")]
+    ParseFloatError {
+        percentiles: String,
+        source: ParseFloatError,
+    },
+    #[error("Duplicate class label {label}, aggregate column names must be unique")]
+    DuplicateLabel { label: String },
+}
+
+// Parse comma-separated class labels in class id order, for example "nozzle,adhesion,spaghetti"
+pub fn parse_labels(labels: &str) -> Vec<String> {
+    labels
+        .split(',')
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty())
+        .collect()
+}
+
+// Parse a label file with one class label per line, where the line number is the class id
+// empty lines in the middle of the file keep their class id, trailing empty lines are ignored
+pub fn parse_label_file(content: &str) -> Vec<String> {
+    let mut labels: Vec<String> = content
+        .lines()
+        .map(|label| label.trim().to_string())
+        .collect();
+    while labels.last().map(|label| label.is_empty()).unwrap_or(false) {
+        labels.pop();
+    }
+    labels
+}
+
+// Labels are aggregate column name prefixes, so each label may only be used by one class id
+// empty labels are unused class ids
+pub fn validate_labels(labels: &[String]) -> Result<(), AggregateError> {
+    let mut seen = std::collections::HashSet::new();
+    for label in labels.iter().filter(|label| !label.is_empty()) {
+        if !seen.insert(label) {
+            return Err(AggregateError::DuplicateLabel {
+                label: label.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Parse comma-separated percentiles between 0 and 100, for example "50,90,99.5"
+pub fn parse_percentiles(percentiles: &str) -> Result<Vec<f64>, AggregateError> {
+    percentiles
+        .split(',')
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let value = p
+                .parse::<f64>()
+                .map_err(|source| AggregateError::ParseFloatError {
+                    percentiles: percentiles.to_string(),
+                    source,
+                })?;
+            match (0.0..=100.0).contains(&value) {
+                true => Ok(value),
+                false => Err(AggregateError::InvalidPercentile { value }),
+            }
+        })
+        .collect()
+}
+
+// Column name suffix of a percentile, for example 50 -> p50 and 99.5 -> p99_5
+pub fn percentile_suffix(percentile: f64) -> String {
+    format!("p{}", percentile).replace('.', "_")
+}
+
+// Windowed aggregations of detection scores per class label, named {label}__{stat}
+// stats are count, mean, std, min, max and one column per percentile, e.g. spaghetti__p90
+pub fn class_aggregations(labels: &[String], percentiles: &[f64], ddof: u8) -> Vec<Expr> {
+    let mut aggs = vec![];
+    for (class_id, label) in labels.iter().enumerate() {
+        // empty lines of a label file are unused class ids
+        if label.is_empty() {
+            continue;
+        }
+        let scores =
+            col("detection_scores").filter(col("detection_classes").eq(lit(class_id as i32)));
+        aggs.push(scores.clone().count().alias(&format!("{label}__count")));
+        aggs.push(scores.clone().mean().alias(&format!("{label}__mean")));
+        aggs.push(scores.clone().std(ddof).alias(&format!("{label}__std")));
+        aggs.push(scores.clone().min().alias(&format!("{label}__min")));
+        aggs.push(scores.clone().max().alias(&format!("{label}__max")));
+        for percentile in percentiles.iter() {
+            aggs.push(
+                scores
+                    .clone()
+                    .quantile(lit(percentile / 100.0), QuantileInterpolOptions::Linear)
+                    .alias(&format!("{label}__{}", percentile_suffix(*percentile))),
+            );
+        }
+    }
+    aggs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_labels() {
+        assert_eq!(
+            parse_labels("nozzle, adhesion,,spaghetti"),
+            vec!["nozzle", "adhesion", "spaghetti"]
+        );
+        assert_eq!(
+            parse_label_file("nozzle\n\nspaghetti\n\n"),
+            vec!["nozzle", "", "spaghetti"]
+        );
+        assert!(validate_labels(&parse_label_file("nozzle\n\n\nspaghetti")).is_ok());
+        assert!(validate_labels(&parse_labels("nozzle,spaghetti,nozzle")).is_err());
+    }
+
+    #[test]
+    fn test_parse_percentiles() {
+        assert_eq!(
+            parse_percentiles("50, 90,99.5").unwrap(),
+            vec![50.0, 90.0, 99.5]
+        );
+        assert!(parse_percentiles("").unwrap().is_empty());
+        assert!(parse_percentiles("101").is_err());
+        assert!(parse_percentiles("p90").is_err());
+        assert_eq!(percentile_suffix(90.0), "p90");
+        assert_eq!(percentile_suffix(99.5), "p99_5");
+    }
+
+    #[test]
+    fn test_class_aggregations() {
+        let df = df!(
+            "detection_classes" => [0, 0, 0, 2, 2],
+            "detection_scores" => [0.2_f32, 0.4, 0.6, 0.9, 0.7]
+        )
+        .unwrap();
+        let labels = parse_label_file("nozzle\n\nblob\n");
+        let aggs = class_aggregations(&labels, &[50.0], 0);
+        // unused class id 1 doesn't produce columns
+        assert_eq!(aggs.len(), 12);
+
+        let result = df.lazy().select(aggs).collect().unwrap();
+        let row = result.get(0).unwrap();
+        let value = |name: &str| {
+            let index = result
+                .get_column_names()
+                .iter()
+                .position(|column| *column == name)
+                .unwrap();
+            row[index].clone()
+        };
+        assert_eq!(value("nozzle__count"), AnyValue::UInt32(3));
+        assert_eq!(value("nozzle__min"), AnyValue::Float32(0.2));
+        assert_eq!(value("nozzle__max"), AnyValue::Float32(0.6));
+        assert_eq!(value("blob__count"), AnyValue::UInt32(2));
+        assert_eq!(value("blob__max"), AnyValue::Float32(0.9));
+        assert!(result.column("nozzle__p50").is_ok());
+        assert!(result.column("blob__std").is_ok());
+    }
+}
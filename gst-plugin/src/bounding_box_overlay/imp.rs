@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use gst::glib;
+use gst::prelude::*;
+use gst::subclass::prelude::*;
+use gst_base::subclass::prelude::*;
+use once_cell::sync::Lazy;
+use polars::prelude::*;
+
+use crate::overlay::{parse_key_values, Color, Detection, OverlayStyle, RgbaFrame};
+
+static CAT: Lazy<gst::DebugCategory> = Lazy::new(|| {
+    gst::DebugCategory::new(
+        "bounding_box_overlay",
+        gst::DebugColorFlags::empty(),
+        Some("PrintNanny bounding box overlay"),
+    )
+});
+
+const DEFAULT_WIDTH: i32 = 640;
+const DEFAULT_HEIGHT: i32 = 480;
+const DEFAULT_LABEL_FILE: &str = "/usr/share/printnanny/model/labels.txt";
+const DEFAULT_COLOR: &str = "FFFFFF";
+const DEFAULT_LINE_WIDTH: u32 = 2;
+const DEFAULT_SHOW_LABELS: bool = true;
+const DEFAULT_SHOW_SCORES: bool = true;
+const DEFAULT_SCORE_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, Clone)]
+struct Settings {
+    width: i32,
+    height: i32,
+    label_file: String,
+    labels: String,
+    colors: String,
+    default_color: String,
+    line_width: u32,
+    show_labels: bool,
+    show_scores: bool,
+    score_threshold: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            label_file: DEFAULT_LABEL_FILE.into(),
+            labels: "".into(),
+            colors: "".into(),
+            default_color: DEFAULT_COLOR.into(),
+            line_width: DEFAULT_LINE_WIDTH,
+            show_labels: DEFAULT_SHOW_LABELS,
+            show_scores: DEFAULT_SHOW_SCORES,
+            score_threshold: DEFAULT_SCORE_THRESHOLD,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    info: Option<gst_video::VideoInfo>,
+    style: OverlayStyle,
+}
+
+#[derive(Default)]
+pub struct BoundingBoxOverlay {
+    settings: Mutex<Settings>,
+    state: Mutex<State>,
+}
+
+impl BoundingBoxOverlay {
+    fn parse_color(&self, color: &str) -> Option<Color> {
+        match Color::parse(color) {
+            Ok(color) => Some(color),
+            Err(e) => {
+                gst::warning!(CAT, imp: self, "Ignoring color={} error={}", color, e);
+                None
+            }
+        }
+    }
+
+    // OverlayStyle from element properties, reading class labels from label-file
+    fn style(&self, settings: &Settings) -> Result<OverlayStyle, gst::ErrorMessage> {
+        let labels = std::fs::read_to_string(&settings.label_file).map_err(|err| {
+            gst::error_msg!(
+                gst::ResourceError::OpenRead,
+                [
+                    "Failed to read label-file {} with error: {}",
+                    &settings.label_file,
+                    err.to_string()
+                ]
+            )
+        })?;
+        let colors: HashMap<String, Color> = parse_key_values(&settings.colors)
+            .into_iter()
+            .filter_map(|(label, color)| self.parse_color(&color).map(|color| (label, color)))
+            .collect();
+        Ok(OverlayStyle {
+            labels: labels.lines().map(|l| l.trim().to_string()).collect(),
+            label_text: parse_key_values(&settings.labels),
+            colors,
+            default_color: self
+                .parse_color(&settings.default_color)
+                .unwrap_or(Color::WHITE),
+            line_width: settings.line_width as usize,
+            show_labels: settings.show_labels,
+            show_scores: settings.show_scores,
+            score_threshold: settings.score_threshold,
+        })
+    }
+
+    // printnanny_bb_dataframe_decoder preserves the mobilenet-ssd-postprocess box layout, which is [ymin, xmin, ymax, xmax]
+    // so detection_boxes_x0 holds ymin, detection_boxes_y0 holds xmin, etc
+    fn detections(df: &DataFrame) -> PolarsResult<Vec<Detection>> {
+        let f32_column = |name: &str| -> PolarsResult<Vec<f32>> {
+            Ok(df
+                .column(name)?
+                .f32()?
+                .into_iter()
+                .map(|v| v.unwrap_or_default())
+                .collect())
+        };
+        let ymin = f32_column("detection_boxes_x0")?;
+        let xmin = f32_column("detection_boxes_y0")?;
+        let ymax = f32_column("detection_boxes_x1")?;
+        let xmax = f32_column("detection_boxes_y1")?;
+        let scores = f32_column("detection_scores")?;
+        let classes: Vec<i32> = df
+            .column("detection_classes")?
+            .i32()?
+            .into_iter()
+            .map(|v| v.unwrap_or_default())
+            .collect();
+
+        Ok((0..df.height())
+            .map(|i| Detection {
+                x0: xmin[i],
+                y0: ymin[i],
+                x1: xmax[i],
+                y1: ymax[i],
+                class: classes[i],
+                score: scores[i],
+            })
+            .collect())
+    }
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for BoundingBoxOverlay {
+    const NAME: &'static str = "BoundingBoxOverlay";
+    type Type = super::BoundingBoxOverlay;
+    type ParentType = gst_base::BaseTransform;
+}
+
+impl ObjectImpl for BoundingBoxOverlay {
+    fn properties() -> &'static [glib::ParamSpec] {
+        static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+            vec![
+                glib::ParamSpecInt::builder("width")
+                    .nick("Width")
+                    .blurb("Width of output frames, should match the camera frames the overlay is composited onto")
+                    .minimum(1)
+                    .default_value(DEFAULT_WIDTH)
+                    .build(),
+                glib::ParamSpecInt::builder("height")
+                    .nick("Height")
+                    .blurb("Height of output frames, should match the camera frames the overlay is composited onto")
+                    .minimum(1)
+                    .default_value(DEFAULT_HEIGHT)
+                    .build(),
+                glib::ParamSpecString::builder("label-file")
+                    .nick("Label File")
+                    .blurb("Class labels, one per line, indexed by detection class")
+                    .default_value(DEFAULT_LABEL_FILE)
+                    .build(),
+                glib::ParamSpecString::builder("labels")
+                    .nick("Labels")
+                    .blurb("Display text drawn instead of class labels, e.g. spaghetti:Failed-print,nozzle:Nozzle")
+                    .build(),
+                glib::ParamSpecString::builder("colors")
+                    .nick("Colors")
+                    .blurb("Box colors of class labels in RRGGBB format, e.g. spaghetti:FF0000,nozzle:00BFFF")
+                    .build(),
+                glib::ParamSpecString::builder("default-color")
+                    .nick("Default Color")
+                    .blurb("Box color of class labels missing from colors, in RRGGBB format")
+                    .default_value(DEFAULT_COLOR)
+                    .build(),
+                glib::ParamSpecUInt::builder("line-width")
+                    .nick("Line Width")
+                    .blurb("Box line width in pixels, scaled with frame height")
+                    .minimum(1)
+                    .default_value(DEFAULT_LINE_WIDTH)
+                    .build(),
+                glib::ParamSpecBoolean::builder("show-labels")
+                    .nick("Show Labels")
+                    .blurb("Draw class label above each box")
+                    .default_value(DEFAULT_SHOW_LABELS)
+                    .build(),
+                glib::ParamSpecBoolean::builder("show-scores")
+                    .nick("Show Scores")
+                    .blurb("Draw detection score above each box")
+                    .default_value(DEFAULT_SHOW_SCORES)
+                    .build(),
+                glib::ParamSpecFloat::builder("score-threshold")
+                    .nick("Score Threshold")
+                    .blurb("Skip detections where detection_score is below threshold. Float between 0 - 1")
+                    .minimum(0.0)
+                    .maximum(1.0)
+                    .default_value(DEFAULT_SCORE_THRESHOLD)
+                    .build(),
+            ]
+        });
+
+        PROPERTIES.as_ref()
+    }
+
+    fn set_property(&self, _id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+        let mut settings = self.settings.lock().unwrap();
+
+        match pspec.name() {
+            "width" => {
+                settings.width = value.get::<i32>().expect("type checked upstream");
+            }
+            "height" => {
+                settings.height = value.get::<i32>().expect("type checked upstream");
+            }
+            "label-file" => {
+                settings.label_file = value.get::<String>().expect("type checked upstream");
+            }
+            "labels" => {
+                settings.labels = value
+                    .get::<Option<String>>()
+                    .expect("type checked upstream")
+                    .unwrap_or_default();
+            }
+            "colors" => {
+                settings.colors = value
+                    .get::<Option<String>>()
+                    .expect("type checked upstream")
+                    .unwrap_or_default();
+            }
+            "default-color" => {
+                settings.default_color = value.get::<String>().expect("type checked upstream");
+            }
+            "line-width" => {
+                settings.line_width = value.get::<u32>().expect("type checked upstream");
+            }
+            "show-labels" => {
+                settings.show_labels = value.get::<bool>().expect("type checked upstream");
+            }
+            "show-scores" => {
+                settings.show_scores = value.get::<bool>().expect("type checked upstream");
+            }
+            "score-threshold" => {
+                settings.score_threshold = value.get::<f32>().expect("type checked upstream");
+            }
+            _ => unimplemented!(
+                "bounding_box_overlay does not implement property: {}",
+                pspec.name()
+            ),
+        };
+    }
+
+    fn property(&self, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+        let settings = self.settings.lock().unwrap();
+
+        match pspec.name() {
+            "width" => settings.width.to_value(),
+            "height" => settings.height.to_value(),
+            "label-file" => settings.label_file.to_value(),
+            "labels" => settings.labels.to_value(),
+            "colors" => settings.colors.to_value(),
+            "default-color" => settings.default_color.to_value(),
+            "line-width" => settings.line_width.to_value(),
+            "show-labels" => settings.show_labels.to_value(),
+            "show-scores" => settings.show_scores.to_value(),
+            "score-threshold" => settings.score_threshold.to_value(),
+            _ => unimplemented!(
+                "bounding_box_overlay does not implement property: {}",
+                pspec.name()
+            ),
+        }
+    }
+}
+
+impl GstObjectImpl for BoundingBoxOverlay {}
+
+impl ElementImpl for BoundingBoxOverlay {
+    fn metadata() -> Option<&'static gst::subclass::ElementMetadata> {
+        static ELEMENT_METADATA: Lazy<gst::subclass::ElementMetadata> = Lazy::new(|| {
+            gst::subclass::ElementMetadata::new(
+                "PrintNanny bounding box overlay",
+                "Filter/Effect/Video",
+                "Draw detection bounding boxes and labels onto transparent RGBA frames",
+                "Leigh Johnson <leigh@printnanny.ai>",
+            )
+        });
+
+        Some(&*ELEMENT_METADATA)
+    }
+
+    fn pad_templates() -> &'static [gst::PadTemplate] {
+        static PAD_TEMPLATES: Lazy<Vec<gst::PadTemplate>> = Lazy::new(|| {
+            // arrow streaming ipc messages from printnanny_bb_dataframe_decoder
+            let caps = gst::Caps::new_any();
+            let sink_pad_template = gst::PadTemplate::new(
+                "sink",
+                gst::PadDirection::Sink,
+                gst::PadPresence::Always,
+                &caps,
+            )
+            .unwrap();
+
+            let caps = gst_video::VideoCapsBuilder::new()
+                .format(gst_video::VideoFormat::Rgba)
+                .build();
+            let src_pad_template = gst::PadTemplate::new(
+                "src",
+                gst::PadDirection::Src,
+                gst::PadPresence::Always,
+                &caps,
+            )
+            .unwrap();
+
+            vec![src_pad_template, sink_pad_template]
+        });
+
+        PAD_TEMPLATES.as_ref()
+    }
+}
+
+impl BaseTransformImpl for BoundingBoxOverlay {
+    const MODE: gst_base::subclass::BaseTransformMode =
+        gst_base::subclass::BaseTransformMode::NeverInPlace;
+    const PASSTHROUGH_ON_SAME_CAPS: bool = false;
+    const TRANSFORM_IP_ON_PASSTHROUGH: bool = false;
+
+    fn start(&self) -> Result<(), gst::ErrorMessage> {
+        let settings = self.settings.lock().unwrap().clone();
+        let style = self.style(&settings)?;
+        gst::info!(
+            CAT,
+            imp: self,
+            "Started with labels={:?} colors={:?}",
+            style.labels,
+            style.colors
+        );
+        self.state.lock().unwrap().style = style;
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), gst::ErrorMessage> {
+        *self.state.lock().unwrap() = State::default();
+        gst::info!(CAT, imp: self, "Stopped");
+        Ok(())
+    }
+
+    // sink caps are fixed to the width and height properties, carrying over the tensor framerate
+    fn transform_caps(
+        &self,
+        direction: gst::PadDirection,
+        caps: &gst::Caps,
+        filter: Option<&gst::Caps>,
+    ) -> Option<gst::Caps> {
+        let other_caps = match direction {
+            gst::PadDirection::Sink => {
+                let settings = self.settings.lock().unwrap();
+                let framerate = caps
+                    .structure(0)
+                    .and_then(|s| s.get::<gst::Fraction>("framerate").ok())
+                    .unwrap_or_else(|| gst::Fraction::new(0, 1));
+                gst_video::VideoCapsBuilder::new()
+                    .format(gst_video::VideoFormat::Rgba)
+                    .width(settings.width)
+                    .height(settings.height)
+                    .framerate(framerate)
+                    .build()
+            }
+            _ => gst::Caps::new_any(),
+        };
+        gst::debug!(
+            CAT,
+            imp: self,
+            "Transformed caps from {} to {} in direction {:?}",
+            caps,
+            other_caps,
+            direction
+        );
+        match filter {
+            Some(filter) => {
+                Some(filter.intersect_with_mode(&other_caps, gst::CapsIntersectMode::First))
+            }
+            None => Some(other_caps),
+        }
+    }
+
+    // input buffers are variable-sized arrow messages, output buffers are always one RGBA frame
+    fn transform_size(
+        &self,
+        direction: gst::PadDirection,
+        _caps: &gst::Caps,
+        _size: usize,
+        othercaps: &gst::Caps,
+    ) -> Option<usize> {
+        match direction {
+            gst::PadDirection::Sink => gst_video::VideoInfo::from_caps(othercaps)
+                .ok()
+                .map(|info| info.size()),
+            _ => None,
+        }
+    }
+
+    fn set_caps(&self, incaps: &gst::Caps, outcaps: &gst::Caps) -> Result<(), gst::LoggableError> {
+        let info = gst_video::VideoInfo::from_caps(outcaps)
+            .map_err(|_| gst::loggable_error!(CAT, "Failed to parse output caps {}", outcaps))?;
+        gst::debug!(
+            CAT,
+            imp: self,
+            "Configured for caps {} to {}",
+            incaps,
+            outcaps
+        );
+        self.state.lock().unwrap().info = Some(info);
+        Ok(())
+    }
+
+    fn transform(
+        &self,
+        inbuf: &gst::Buffer,
+        outbuf: &mut gst::BufferRef,
+    ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        let state = self.state.lock().unwrap();
+        let element = self.obj();
+        let info = state.info.as_ref().ok_or_else(|| {
+            gst::element_error!(
+                element,
+                gst::CoreError::Negotiation,
+                ["Have no output caps"]
+            );
+            gst::FlowError::NotNegotiated
+        })?;
+
+        let cursor = inbuf.clone().into_cursor_readable();
+        let detections = IpcStreamReader::new(cursor)
+            .finish()
+            .and_then(|df| Self::detections(&df))
+            .map_err(|err| {
+                gst::element_error!(
+                    element,
+                    gst::StreamError::Decode,
+                    ["Failed to decode detections from Arrow IPC Stream: {}", err]
+                );
+                gst::FlowError::Error
+            })?;
+        gst::trace!(CAT, imp: self, "Drawing detections {:?}", detections);
+
+        let mut map = outbuf.map_writable().map_err(|_| {
+            gst::element_error!(
+                element,
+                gst::CoreError::Failed,
+                ["Failed to map output buffer writable"]
+            );
+            gst::FlowError::Error
+        })?;
+        let mut frame = RgbaFrame::new(
+            map.as_mut_slice(),
+            info.width() as usize,
+            info.height() as usize,
+        );
+        frame.clear();
+        frame.draw_detections(&detections, &state.style);
+
+        Ok(gst::FlowSuccess::Ok)
+    }
+}
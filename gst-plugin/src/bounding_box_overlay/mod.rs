@@ -0,0 +1,19 @@
+use gst::glib;
+use gst::prelude::*;
+
+mod imp;
+
+// Draws detections decoded by printnanny_bb_dataframe_decoder onto transparent RGBA frames,
+// which are composited onto camera frames by the compositor element
+glib::wrapper! {
+    pub struct BoundingBoxOverlay(ObjectSubclass<imp::BoundingBoxOverlay>) @extends gst_base::BaseTransform, gst::Element, gst::Object;
+}
+
+pub fn register(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
+    gst::Element::register(
+        Some(plugin),
+        "bounding_box_overlay",
+        gst::Rank::None,
+        BoundingBoxOverlay::static_type(),
+    )
+}
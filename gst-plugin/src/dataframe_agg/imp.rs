@@ -8,6 +8,9 @@ use once_cell::sync::Lazy;
 use polars::prelude::*;
 
 use super::DataframeOutputType;
+use crate::aggregate::{
+    class_aggregations, parse_label_file, parse_labels, parse_percentiles, validate_labels,
+};
 use crate::ipc::{dataframe_to_arrow_streaming_ipc_message, dataframe_to_json_bytearray};
 
 static CAT: Lazy<gst::DebugCategory> = Lazy::new(|| {
@@ -29,9 +32,16 @@ const DEFAULT_SCORE_THRESHOLD: f32 = 0.5;
 const DEFAULT_DDOF: u8 = 0; // delta degrees of freedom, used in std dev calculation. divisor = N - ddof, where N is the number of element in the set
 const DEFAULT_WINDOW_TRUNCATE: bool = false;
 const DEFAULT_WINDOW_INCLUDE_BOUNDARIES: bool = true;
+// class labels of the bundled model, in class id order
+const DEFAULT_LABELS: &str = "nozzle,adhesion,spaghetti,print,raft";
+const DEFAULT_LABEL_FILE: &str = "";
+const DEFAULT_PERCENTILES: &str = "50,90";
 
 struct State {
     dataframe: DataFrame,
+    // class labels indexed by detection_classes value, read from label-file or labels when the element starts
+    labels: Vec<String>,
+    percentiles: Vec<f64>,
 }
 
 impl Default for State {
@@ -55,7 +65,11 @@ impl Default for State {
             "rt" => rt
         )
         .expect("Failed to initialize dataframe");
-        Self { dataframe }
+        Self {
+            dataframe,
+            labels: parse_labels(DEFAULT_LABELS),
+            percentiles: vec![],
+        }
     }
 }
 
@@ -70,6 +84,9 @@ struct Settings {
     window_offset: String,
     window_truncate: bool,
     window_include_boundaries: bool,
+    labels: String,
+    label_file: String,
+    percentiles: String,
 }
 
 impl Default for Settings {
@@ -85,6 +102,9 @@ impl Default for Settings {
             window_offset: DEFAULT_WINDOW_OFFSET.into(),
             window_truncate: DEFAULT_WINDOW_TRUNCATE,
             window_include_boundaries: DEFAULT_WINDOW_INCLUDE_BOUNDARIES,
+            labels: DEFAULT_LABELS.into(),
+            label_file: DEFAULT_LABEL_FILE.into(),
+            percentiles: DEFAULT_PERCENTILES.into(),
         }
     }
 }
@@ -101,6 +121,50 @@ impl DataframeAgg {
         Ok(())
     }
 
+    // resolve class labels and percentiles from element properties, label-file takes precedence over labels
+    // called on the NULL to READY transition, so changes to labels, label-file and percentiles apply the next time the element starts
+    fn start(&self) -> Result<(), gst::ErrorMessage> {
+        let settings = self.settings.lock().unwrap();
+        let labels = match settings.label_file.as_str() {
+            "" => parse_labels(&settings.labels),
+            label_file => {
+                let content = std::fs::read_to_string(label_file).map_err(|err| {
+                    gst::error_msg!(
+                        gst::ResourceError::OpenRead,
+                        [
+                            "Failed to read label-file {} with error: {}",
+                            label_file,
+                            err.to_string()
+                        ]
+                    )
+                })?;
+                parse_label_file(&content)
+            }
+        };
+        if labels.iter().all(|label| label.is_empty()) {
+            return Err(gst::error_msg!(
+                gst::LibraryError::Settings,
+                ["dataframe_agg requires at least one class label"]
+            ));
+        }
+        validate_labels(&labels)
+            .map_err(|err| gst::error_msg!(gst::LibraryError::Settings, ["{}", err.to_string()]))?;
+        let percentiles = parse_percentiles(&settings.percentiles)
+            .map_err(|err| gst::error_msg!(gst::LibraryError::Settings, ["{}", err.to_string()]))?;
+        gst::info!(
+            CAT,
+            imp: self,
+            "Aggregating labels={:?} percentiles={:?}",
+            labels,
+            percentiles
+        );
+
+        let mut state = self.state.lock().unwrap();
+        state.labels = labels;
+        state.percentiles = percentiles;
+        Ok(())
+    }
+
     // Called whenever an event arrives on the sink pad. It has to be handled accordingly and in
     // most cases has to be either passed to Pad::event_default() on this pad for default handling,
     // or Pad::push_event() on all pads with the opposite direction for direct forwarding.
@@ -211,6 +275,15 @@ impl DataframeAgg {
         };
 
         let localdf = state.dataframe.clone();
+        let mut aggs = vec![
+            col("rt").min().alias("rt__min"),
+            col("rt").max().alias("rt__max"),
+        ];
+        aggs.extend(class_aggregations(
+            &state.labels,
+            &state.percentiles,
+            settings.ddof,
+        ));
         // release state lock
         drop(state);
 
@@ -219,70 +292,7 @@ impl DataframeAgg {
         let mut windowed_df = localdf
             .lazy()
             .groupby_dynamic(vec![col("detection_classes")], group_options)
-            .agg([
-                col("rt").min().alias("rt__min"),
-                col("rt").max().alias("rt__max"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(0))
-                    .count()
-                    .alias("nozzle__count"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(0))
-                    .mean()
-                    .alias("nozzle__mean"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(0))
-                    .std(settings.ddof)
-                    .alias("nozzle__std"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(1))
-                    .count()
-                    .alias("adhesion__count"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(1))
-                    .mean()
-                    .alias("adhesion__mean"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(1))
-                    .std(settings.ddof)
-                    .alias("adhesion__std"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(2))
-                    .count()
-                    .alias("spaghetti__count"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(2))
-                    .mean()
-                    .alias("spaghetti__mean"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(2))
-                    .std(settings.ddof)
-                    .alias("spaghetti__std"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(3))
-                    .count()
-                    .alias("print__count"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(3))
-                    .mean()
-                    .alias("print__mean"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(3))
-                    .std(settings.ddof)
-                    .alias("print__std"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(4))
-                    .count()
-                    .alias("raft__count"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(4))
-                    .mean()
-                    .alias("raft__mean"),
-                col("detection_scores")
-                    .filter(col("detection_classes").eq(4))
-                    .std(settings.ddof)
-                    .alias("raft__std"),
-            ])
+            .agg(aggs)
             .collect()
             .map_err(|err| {
                 gst::error!(CAT, "Failed window/aggregate dataframes {}", err);
@@ -430,6 +440,21 @@ impl ObjectImpl for DataframeAgg {
                     .blurb("Delta degrees of freedom modifier, used in standard deviation and variance calculations")
                     .default_value(DEFAULT_DDOF as u32)
                     .build(),
+                glib::ParamSpecString::builder("labels")
+                    .nick("Labels")
+                    .blurb("Comma-separated class labels in class id order, used as aggregate column name prefixes. Read when the element starts (NULL to READY)")
+                    .default_value(DEFAULT_LABELS)
+                    .build(),
+                glib::ParamSpecString::builder("label-file")
+                    .nick("Label File")
+                    .blurb("Class labels, one per line, indexed by detection class. Overrides labels if set. Read when the element starts (NULL to READY)")
+                    .default_value(DEFAULT_LABEL_FILE)
+                    .build(),
+                glib::ParamSpecString::builder("percentiles")
+                    .nick("Percentiles")
+                    .blurb("Comma-separated detection score percentiles calculated per label, e.g. 50,90. Read when the element starts (NULL to READY)")
+                    .default_value(DEFAULT_PERCENTILES)
+                    .build(),
                 glib::ParamSpecEnum::builder::<DataframeOutputType>("output-type")
                     .nick("Output Format Type")
                     .blurb("Format of output buffer")
@@ -453,6 +478,9 @@ impl ObjectImpl for DataframeAgg {
             "window-offset" => settings.window_offset.to_value(),
             "window-truncate" => settings.window_truncate.to_value(),
             "window-include-boundaries" => settings.window_include_boundaries.to_value(),
+            "labels" => settings.labels.to_value(),
+            "label-file" => settings.label_file.to_value(),
+            "percentiles" => settings.percentiles.to_value(),
             _ => unimplemented!(),
         }
     }
@@ -494,6 +522,15 @@ impl ObjectImpl for DataframeAgg {
                 settings.window_include_boundaries =
                     value.get::<bool>().expect("type checked upstream");
             }
+            "labels" => {
+                settings.labels = value.get::<String>().expect("type checked upstream");
+            }
+            "label-file" => {
+                settings.label_file = value.get::<String>().expect("type checked upstream");
+            }
+            "percentiles" => {
+                settings.percentiles = value.get::<String>().expect("type checked upstream");
+            }
             _ => unimplemented!(),
         }
     }
@@ -551,6 +588,13 @@ impl ElementImpl for DataframeAgg {
         let element = self.obj();
         gst::trace!(CAT, obj: element, "Changing state {:?}", transition);
 
+        if transition == gst::StateChange::NullToReady {
+            self.start().map_err(|err| {
+                element.post_error_message(err);
+                gst::StateChangeError
+            })?;
+        }
+
         // Call the parent class' implementation of ::change_state()
         self.parent_change_state(transition)
     }
@@ -4,7 +4,7 @@ use gst::prelude::*;
 mod imp;
 
 // This enum may be used to control what type of output the dataframe aggregator produces
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
+#[derive(Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy, glib::Enum)]
 #[repr(u32)]
 #[enum_type(name = "GstDataframeAggOutput")]
 pub enum DataframeOutputType {
@@ -12,6 +12,7 @@ pub enum DataframeOutputType {
         name = "Arrow Streaming IPC: outputs the aggregate dataframe in arrow streaming ipc format",
         nick = "arrow-streaming-ipc"
     )]
+    #[default]
     ArrowStreamingIpc = 0,
     #[enum_value(
         name = "JSON: output the aggregate dataframe as JSON bytearray",
@@ -20,12 +21,6 @@ pub enum DataframeOutputType {
     Json = 1,
 }
 
-impl Default for DataframeOutputType {
-    fn default() -> Self {
-        Self::ArrowStreamingIpc
-    }
-}
-
 // The public Rust wrapper type for our element
 glib::wrapper! {
     pub struct DataframeAgg(ObjectSubclass<imp::DataframeAgg>) @extends gst::Bin, gst::Element, gst::Object;
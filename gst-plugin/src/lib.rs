@@ -1,14 +1,18 @@
 use gst::glib;
+mod bounding_box_overlay;
 mod dataframe_agg;
 mod dataframe_filesink;
 mod nats_sink;
 
+pub mod aggregate;
 pub mod error;
 pub mod ipc;
 pub mod nnstreamer;
+pub mod overlay;
 pub mod tensor;
 
 fn plugin_init(plugin: &gst::Plugin) -> Result<(), glib::BoolError> {
+    bounding_box_overlay::register(plugin)?;
     dataframe_filesink::register(plugin)?;
     dataframe_agg::register(plugin)?;
     nats_sink::register(plugin)?;
@@ -23,15 +23,13 @@ impl Default for Settings {
     }
 }
 
+#[derive(Default)]
 enum State {
+    #[default]
     Stopped,
-    Started { nc: nats::Connection },
-}
-
-impl Default for State {
-    fn default() -> State {
-        State::Stopped
-    }
+    Started {
+        nc: nats::Connection,
+    },
 }
 
 #[derive(Default)]
@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+use std::num::ParseIntError;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum OverlayError {
+    #[error("Failed to parse color {color}, expected RRGGBB hex format")]
+    InvalidColor { color: String },
+    #[error("Failed to parse integer from color hex digits")]
+    ParseIntError {
+        #[from]
+        source: ParseIntError,
+    },
+}
+
+// 5x7 glyphs, one byte per column, least significant bit is the top row
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+const GLYPHS: [(char, [u8; GLYPH_WIDTH]); 43] = [
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('%', [0x23, 0x13, 0x08, 0x64, 0x62]),
+    ('-', [0x08, 0x08, 0x08, 0x08, 0x08]),
+    ('.', [0x00, 0x60, 0x60, 0x00, 0x00]),
+    (':', [0x00, 0x36, 0x36, 0x00, 0x00]),
+    ('_', [0x40, 0x40, 0x40, 0x40, 0x40]),
+    ('?', [0x02, 0x01, 0x51, 0x09, 0x06]),
+    ('0', [0x3E, 0x51, 0x49, 0x45, 0x3E]),
+    ('1', [0x00, 0x42, 0x7F, 0x40, 0x00]),
+    ('2', [0x42, 0x61, 0x51, 0x49, 0x46]),
+    ('3', [0x21, 0x41, 0x45, 0x4B, 0x31]),
+    ('4', [0x18, 0x14, 0x12, 0x7F, 0x10]),
+    ('5', [0x27, 0x45, 0x45, 0x45, 0x39]),
+    ('6', [0x3C, 0x4A, 0x49, 0x49, 0x30]),
+    ('7', [0x01, 0x71, 0x09, 0x05, 0x03]),
+    ('8', [0x36, 0x49, 0x49, 0x49, 0x36]),
+    ('9', [0x06, 0x49, 0x49, 0x29, 0x1E]),
+    ('A', [0x7E, 0x11, 0x11, 0x11, 0x7E]),
+    ('B', [0x7F, 0x49, 0x49, 0x49, 0x36]),
+    ('C', [0x3E, 0x41, 0x41, 0x41, 0x22]),
+    ('D', [0x7F, 0x41, 0x41, 0x22, 0x1C]),
+    ('E', [0x7F, 0x49, 0x49, 0x49, 0x41]),
+    ('F', [0x7F, 0x09, 0x09, 0x09, 0x01]),
+    ('G', [0x3E, 0x41, 0x49, 0x49, 0x7A]),
+    ('H', [0x7F, 0x08, 0x08, 0x08, 0x7F]),
+    ('I', [0x00, 0x41, 0x7F, 0x41, 0x00]),
+    ('J', [0x20, 0x40, 0x41, 0x3F, 0x01]),
+    ('K', [0x7F, 0x08, 0x14, 0x22, 0x41]),
+    ('L', [0x7F, 0x40, 0x40, 0x40, 0x40]),
+    ('M', [0x7F, 0x02, 0x0C, 0x02, 0x7F]),
+    ('N', [0x7F, 0x04, 0x08, 0x10, 0x7F]),
+    ('O', [0x3E, 0x41, 0x41, 0x41, 0x3E]),
+    ('P', [0x7F, 0x09, 0x09, 0x09, 0x06]),
+    ('Q', [0x3E, 0x41, 0x51, 0x21, 0x5E]),
+    ('R', [0x7F, 0x09, 0x19, 0x29, 0x46]),
+    ('S', [0x46, 0x49, 0x49, 0x49, 0x31]),
+    ('T', [0x01, 0x01, 0x7F, 0x01, 0x01]),
+    ('U', [0x3F, 0x40, 0x40, 0x40, 0x3F]),
+    ('V', [0x1F, 0x20, 0x40, 0x20, 0x1F]),
+    ('W', [0x3F, 0x40, 0x38, 0x40, 0x3F]),
+    ('X', [0x63, 0x14, 0x08, 0x14, 0x63]),
+    ('Y', [0x07, 0x08, 0x70, 0x08, 0x07]),
+    ('Z', [0x61, 0x51, 0x49, 0x45, 0x43]),
+];
+
+// Opaque RGB color, drawn with alpha 255 onto a transparent frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color { r: 0, g: 0, b: 0 };
+    pub const WHITE: Color = Color {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+
+    // Parse RRGGBB, with or without a leading #
+    pub fn parse(color: &str) -> Result<Self, OverlayError> {
+        let hex = color.trim().trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(OverlayError::InvalidColor {
+                color: color.to_string(),
+            });
+        }
+        Ok(Self {
+            r: u8::from_str_radix(&hex[0..2], 16)?,
+            g: u8::from_str_radix(&hex[2..4], 16)?,
+            b: u8::from_str_radix(&hex[4..6], 16)?,
+        })
+    }
+
+    // black or white, whichever is readable on top of this color
+    pub fn contrast(&self) -> Self {
+        let luma = 299 * self.r as u32 + 587 * self.g as u32 + 114 * self.b as u32;
+        match luma > 128_000 {
+            true => Self::BLACK,
+            false => Self::WHITE,
+        }
+    }
+}
+
+// A single detection decoded by printnanny_bb_dataframe_decoder, with box coordinates normalized to 0-1
+#[derive(Clone, Debug, PartialEq)]
+pub struct Detection {
+    pub x0: f32,
+    pub y0: f32,
+    pub x1: f32,
+    pub y1: f32,
+    pub class: i32,
+    pub score: f32,
+}
+
+// Parse comma-separated key:value pairs, e.g. "nozzle:00BFFF,spaghetti:FF0000"
+pub fn parse_key_values(value: &str) -> HashMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .filter(|(k, _)| !k.is_empty())
+        .collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct OverlayStyle {
+    // class labels, indexed by detection class
+    pub labels: Vec<String>,
+    // display text drawn instead of the class label
+    pub label_text: HashMap<String, String>,
+    pub colors: HashMap<String, Color>,
+    pub default_color: Color,
+    pub line_width: usize,
+    pub show_labels: bool,
+    pub show_scores: bool,
+    pub score_threshold: f32,
+}
+
+impl Default for OverlayStyle {
+    fn default() -> Self {
+        Self {
+            labels: vec![],
+            label_text: HashMap::new(),
+            colors: HashMap::new(),
+            default_color: Color::WHITE,
+            line_width: 2,
+            show_labels: true,
+            show_scores: true,
+            score_threshold: 0.5,
+        }
+    }
+}
+
+impl OverlayStyle {
+    fn label(&self, class: i32) -> String {
+        usize::try_from(class)
+            .ok()
+            .and_then(|i| self.labels.get(i))
+            .cloned()
+            .unwrap_or_else(|| class.to_string())
+    }
+
+    pub fn color(&self, detection: &Detection) -> Color {
+        self.colors
+            .get(&self.label(detection.class))
+            .copied()
+            .unwrap_or(self.default_color)
+    }
+
+    // label text drawn above the box, e.g. "SPAGHETTI 87%"
+    pub fn caption(&self, detection: &Detection) -> Option<String> {
+        let label = self.label(detection.class);
+        let text = self.label_text.get(&label).cloned().unwrap_or(label);
+        let score = format!("{:.0}%", detection.score * 100.0);
+        match (self.show_labels, self.show_scores) {
+            (true, true) => Some(format!("{text} {score}")),
+            (true, false) => Some(text),
+            (false, true) => Some(score),
+            (false, false) => None,
+        }
+    }
+}
+
+// Transparent RGBA frame, which bounding boxes are drawn onto
+pub struct RgbaFrame<'a> {
+    data: &'a mut [u8],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> RgbaFrame<'a> {
+    // data must hold at least width * height * 4 bytes
+    pub fn new(data: &'a mut [u8], width: usize, height: usize) -> Self {
+        assert!(data.len() >= width * height * 4);
+        Self {
+            data,
+            width,
+            height,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.data.fill(0);
+    }
+
+    // fill the rectangle [x0, x1) x [y0, y1), clipped to the frame
+    fn fill_rect(&mut self, x0: usize, y0: usize, x1: usize, y1: usize, color: Color) {
+        let (x1, y1) = (x1.min(self.width), y1.min(self.height));
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let i = (y * self.width + x) * 4;
+                self.data[i..i + 4].copy_from_slice(&[color.r, color.g, color.b, 255]);
+            }
+        }
+    }
+
+    fn stroke_rect(
+        &mut self,
+        x0: usize,
+        y0: usize,
+        x1: usize,
+        y1: usize,
+        width: usize,
+        color: Color,
+    ) {
+        self.fill_rect(x0, y0, x1, y0 + width, color);
+        self.fill_rect(x0, y1.saturating_sub(width), x1, y1, color);
+        self.fill_rect(x0, y0, x0 + width, y1, color);
+        self.fill_rect(x1.saturating_sub(width), y0, x1, y1, color);
+    }
+
+    fn draw_text(&mut self, x: usize, y: usize, text: &str, scale: usize, color: Color) {
+        for (n, c) in text.chars().enumerate() {
+            let c = c.to_ascii_uppercase();
+            let glyph = GLYPHS
+                .iter()
+                .find(|(g, _)| *g == c)
+                .or_else(|| GLYPHS.iter().find(|(g, _)| *g == '?'))
+                .map(|(_, columns)| columns)
+                .unwrap();
+            let gx = x + n * (GLYPH_WIDTH + 1) * scale;
+            for (col, bits) in glyph.iter().enumerate() {
+                for row in 0..GLYPH_HEIGHT {
+                    if bits & (1 << row) != 0 {
+                        let px = gx + col * scale;
+                        let py = y + row * scale;
+                        self.fill_rect(px, py, px + scale, py + scale, color);
+                    }
+                }
+            }
+        }
+    }
+
+    // draw each detection above style.score_threshold as a box in its class color, with a caption above the box
+    pub fn draw_detections(&mut self, detections: &[Detection], style: &OverlayStyle) {
+        // scale captions with the frame, 1x for 480p
+        let scale = (self.height / 480).max(1);
+        let line_width = style.line_width.max(1) * scale;
+        let to_px = |v: f32, max: usize| ((v.clamp(0.0, 1.0) * max as f32) as usize).min(max);
+        for detection in detections
+            .iter()
+            .filter(|d| d.score >= style.score_threshold)
+        {
+            let (x0, x1) = (
+                to_px(detection.x0, self.width),
+                to_px(detection.x1, self.width),
+            );
+            let (y0, y1) = (
+                to_px(detection.y0, self.height),
+                to_px(detection.y1, self.height),
+            );
+            if x1 <= x0 || y1 <= y0 {
+                continue;
+            }
+            let color = style.color(detection);
+            self.stroke_rect(x0, y0, x1, y1, line_width, color);
+
+            if let Some(caption) = style.caption(detection) {
+                let padding = scale;
+                let caption_width = caption.len() * (GLYPH_WIDTH + 1) * scale + padding;
+                let caption_height = GLYPH_HEIGHT * scale + 2 * padding;
+                // draw caption inside the box when there's no room above it
+                let caption_y = match y0 >= caption_height {
+                    true => y0 - caption_height,
+                    false => y0,
+                };
+                self.fill_rect(
+                    x0,
+                    caption_y,
+                    x0 + caption_width,
+                    caption_y + caption_height,
+                    color,
+                );
+                self.draw_text(
+                    x0 + padding,
+                    caption_y + padding,
+                    &caption,
+                    scale,
+                    color.contrast(),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixel(data: &[u8], width: usize, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * width + x) * 4;
+        [data[i], data[i + 1], data[i + 2], data[i + 3]]
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(
+            Color::parse("#FF8000").unwrap(),
+            Color {
+                r: 255,
+                g: 128,
+                b: 0
+            }
+        );
+        assert_eq!(Color::parse("00bfff").unwrap().b, 255);
+        assert!(Color::parse("#FFF").is_err());
+        assert!(Color::parse("GGGGGG").is_err());
+        assert_eq!(Color::WHITE.contrast(), Color::BLACK);
+    }
+
+    #[test]
+    fn test_parse_key_values() {
+        let parsed = parse_key_values("nozzle:00BFFF, spaghetti:FF0000,invalid");
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed["spaghetti"], "FF0000");
+    }
+
+    #[test]
+    fn test_caption() {
+        let style = OverlayStyle {
+            labels: vec!["nozzle".into(), "adhesion".into(), "spaghetti".into()],
+            label_text: HashMap::from([("spaghetti".into(), "Failed-print".into())]),
+            ..OverlayStyle::default()
+        };
+        let detection = Detection {
+            x0: 0.0,
+            y0: 0.0,
+            x1: 1.0,
+            y1: 1.0,
+            class: 2,
+            score: 0.87,
+        };
+        assert_eq!(
+            style.caption(&detection).as_deref(),
+            Some("Failed-print 87%")
+        );
+        let unknown = Detection {
+            class: 9,
+            ..detection
+        };
+        assert_eq!(style.caption(&unknown).as_deref(), Some("9 87%"));
+    }
+
+    #[test]
+    fn test_draw_detections() {
+        let (width, height) = (64, 48);
+        let mut data = vec![0; width * height * 4];
+        let red = Color { r: 255, g: 0, b: 0 };
+        let style = OverlayStyle {
+            labels: vec!["nozzle".into(), "spaghetti".into()],
+            colors: HashMap::from([("spaghetti".into(), red)]),
+            show_labels: false,
+            show_scores: false,
+            line_width: 1,
+            ..OverlayStyle::default()
+        };
+        let detections = vec![
+            Detection {
+                x0: 0.25,
+                y0: 0.25,
+                x1: 0.75,
+                y1: 0.75,
+                class: 1,
+                score: 0.9,
+            },
+            // below score_threshold
+            Detection {
+                x0: 0.0,
+                y0: 0.0,
+                x1: 0.1,
+                y1: 0.1,
+                class: 0,
+                score: 0.1,
+            },
+        ];
+        let mut frame = RgbaFrame::new(&mut data, width, height);
+        frame.draw_detections(&detections, &style);
+
+        assert_eq!(pixel(&data, width, 16, 12), [255, 0, 0, 255]);
+        assert_eq!(pixel(&data, width, 47, 35), [255, 0, 0, 255]);
+        // inside of the box and filtered detections stay transparent
+        assert_eq!(pixel(&data, width, 32, 24), [0, 0, 0, 0]);
+        assert_eq!(pixel(&data, width, 0, 0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_draw_caption() {
+        let (width, height) = (64, 48);
+        let mut data = vec![0; width * height * 4];
+        let style = OverlayStyle {
+            labels: vec!["nozzle".into()],
+            show_scores: false,
+            ..OverlayStyle::default()
+        };
+        let detections = vec![Detection {
+            x0: 0.25,
+            y0: 0.5,
+            x1: 0.75,
+            y1: 0.9,
+            class: 0,
+            score: 0.9,
+        }];
+        let mut frame = RgbaFrame::new(&mut data, width, height);
+        frame.draw_detections(&detections, &style);
+
+        // caption background is drawn above the box in the default color, with black text
+        let caption_y = 24 - (GLYPH_HEIGHT + 2);
+        assert_eq!(pixel(&data, width, 16, caption_y), [255, 255, 255, 255]);
+        let text_pixels = (caption_y..24)
+            .flat_map(|y| (16..48).map(move |x| (x, y)))
+            .filter(|(x, y)| pixel(&data, width, *x, *y) == [0, 0, 0, 255])
+            .count();
+        assert!(text_pixels > 0);
+    }
+}
@@ -114,6 +114,46 @@ fn test_nnstreamer_callback() {
     assert_eq!(num_buffers, expected_buffers);
 }
 
+#[test]
+fn test_bounding_box_overlay() {
+    init();
+    let base_path: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let model_path: PathBuf = base_path.join("fixtures/model.tflite");
+    let label_path: PathBuf = base_path.join("fixtures/labels.txt");
+
+    let num_detections = 40;
+    let (width, height) = (640, 480);
+
+    let expected_buffers = 16;
+    let pipeline = format!(
+        "videotestsrc num-buffers={expected_buffers} \
+        ! capsfilter caps=video/x-raw,width={tensor_width},height={tensor_height},format=RGB \
+        ! videoscale \
+        ! videoconvert \
+        ! tensor_converter \
+        ! capsfilter caps=other/tensors,num_tensors=1,format=static \
+        ! tensor_filter framework=tensorflow2-lite model={model_file} output=4:{num_detections}:1:1,{num_detections}:1:1:1,{num_detections}:1:1:1,1:1:1:1 outputname=detection_boxes,detection_classes,detection_scores,num_detections outputtype=float32,float32,float32,float32 \
+        ! tensor_decoder mode=custom-code option1=printnanny_bb_dataframe_decoder \
+        ! bounding_box_overlay width={width} height={height} label-file={label_file} colors=spaghetti:FF0000,nozzle:00BFFF score-threshold=0.1",
+        expected_buffers = expected_buffers,
+        num_detections = num_detections,
+        tensor_width = 320,
+        tensor_height = 320,
+        model_file = model_path.display(),
+        label_file = label_path.display(),
+    );
+    let mut h = gst_check::Harness::new_parse(&pipeline);
+    h.play();
+
+    let mut num_buffers = 0;
+    while let Some(buffer) = h.pull_until_eos().unwrap() {
+        // one transparent RGBA frame per tensor buffer
+        assert_eq!(buffer.size(), width * height * 4);
+        num_buffers += 1;
+    }
+    assert_eq!(num_buffers, expected_buffers);
+}
+
 // TODO: test flakes on:
 // `Err` value: ComputeError(Borrowed("empty container given"))'
 #[ignore]
@@ -191,7 +231,8 @@ fn test_dataframe_agg() {
     let model_path: PathBuf = base_path.join("fixtures/model.tflite");
 
     let expected_buffers = 512;
-    let expected_columns = 21;
+    // detection_classes, window boundaries, rt, rt__min, rt__max and 7 aggregations of 5 labels
+    let expected_columns = 41;
     let num_detections = 40;
     let max_duration = "10s";
 
@@ -242,7 +283,7 @@ fn test_dataframe_agg() {
             .collect()
             .unwrap();
 
-        let ts_dif = match df.get(0).unwrap().get(0).unwrap() {
+        let ts_dif = match df.get(0).unwrap().first().unwrap() {
             AnyValue::Int64(v) => v.to_owned(),
             _ => 0,
         };
@@ -252,6 +293,65 @@ fn test_dataframe_agg() {
     assert!(num_buffers == expected_buffers);
 }
 
+#[test]
+fn test_dataframe_agg_labels() {
+    init();
+
+    let base_path: PathBuf = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let model_path: PathBuf = base_path.join("fixtures/model.tflite");
+
+    let expected_buffers = 16;
+    let num_detections = 40;
+
+    // aggregate columns are generated per label, instead of the bundled model's 5 classes
+    let pipeline_str = format!(
+        "videotestsrc num-buffers={expected_buffers} \
+        ! capsfilter caps=video/x-raw,width={tensor_width},height={tensor_height},format=RGB \
+        ! videoscale \
+        ! videoconvert \
+        ! tensor_converter \
+        ! capsfilter caps=other/tensors,num_tensors=1,format=static \
+        ! tensor_filter framework=tensorflow2-lite model={model_file} output=4:{num_detections}:1:1,{num_detections}:1:1:1,{num_detections}:1:1:1,1:1:1:1 outputname=detection_boxes,detection_classes,detection_scores,num_detections outputtype=float32,float32,float32,float32 \
+        ! tensor_decoder mode=custom-code option1=printnanny_bb_dataframe_decoder \
+        ! dataframe_agg filter-threshold=0.0001 window-interval=100ms window-period=100ms labels=nozzle,spaghetti percentiles=50,99.5",
+        expected_buffers = expected_buffers,
+        num_detections = num_detections,
+        tensor_width = 320,
+        tensor_height = 320,
+        model_file = model_path.display(),
+    );
+    let mut h = gst_check::Harness::new_parse(&pipeline_str);
+    h.play();
+
+    let mut num_buffers = 0;
+    while let Some(buffer) = h.pull_until_eos().unwrap() {
+        let cursor = buffer.as_cursor_readable();
+        let df = IpcStreamReader::new(cursor)
+            .finish()
+            .expect("Failed to extract dataframe");
+        let columns = df.get_column_names();
+        assert_eq!(columns.len(), 20);
+        for column in [
+            "nozzle__count",
+            "nozzle__mean",
+            "nozzle__std",
+            "nozzle__min",
+            "nozzle__max",
+            "nozzle__p50",
+            "nozzle__p99_5",
+            "spaghetti__count",
+            "spaghetti__mean",
+            "spaghetti__std",
+            "spaghetti__p99_5",
+        ] {
+            assert!(columns.contains(&column), "{:?}", columns);
+        }
+        assert!(!columns.contains(&"raft__mean"));
+        num_buffers += 1;
+    }
+    assert_eq!(num_buffers, expected_buffers);
+}
+
 // requires websocket-tcp-server bin to be running, ignore in CI but keep as development helper
 #[ignore]
 #[test]
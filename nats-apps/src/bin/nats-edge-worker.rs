@@ -1,10 +1,13 @@
 use anyhow::Result;
+use printnanny_gst_pipelines::factory::PrintNannyPipelineFactory;
 use printnanny_nats_apps::event::NatsEvent;
 use printnanny_nats_apps::request_reply::{NatsReply, NatsRequest};
 use printnanny_nats_client::subscriber::NatsSubscriber;
+use printnanny_services::recording_recovery;
+use printnanny_settings::printnanny::PrintNannySettings;
 
 use env_logger::Builder;
-use log::LevelFilter;
+use log::{error, info, LevelFilter};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -27,6 +30,13 @@ async fn main() -> Result<()> {
         _ => builder.filter_level(LevelFilter::Trace).init(),
     };
 
+    // the edge worker starts and stops recordings, so recordings interrupted by an unclean shutdown are recovered before it handles requests
+    let settings = PrintNannySettings::new().await?;
+    match recording_recovery::recover(&settings, &PrintNannyPipelineFactory::default()).await {
+        Ok(report) => info!("Recording recovery report={:?}", report),
+        Err(e) => error!("Recording recovery failed error={}", e),
+    }
+
     let worker = NatsSubscriber::<NatsEvent, NatsRequest, NatsReply>::new(&args);
 
     worker.run().await?;
@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use clap::{crate_authors, crate_description, Arg, Command};
+use env_logger::Builder;
+use futures_util::StreamExt;
+use git_version::git_version;
+use log::{debug, error, info, warn, LevelFilter};
+
+use printnanny_edge_db::print_job::PrintJob;
+use printnanny_nats_apps::failure::{FailureScorer, PRINT_QUALITY_ALERT_SUBJECT_PATTERN};
+use printnanny_nats_apps::preroll::DEFAULT_DETECTION_SUBJECT;
+use printnanny_nats_client::client::wait_for_nats_client;
+use printnanny_settings::printnanny::PrintNannySettings;
+use printnanny_settings::sys_info;
+
+const DEFAULT_NATS_URI: &str = "nats://localhost:4223";
+const GIT_VERSION: &str = git_version!();
+
+// subscribe to detection dataframes and publish debounced print quality alerts, handled by nats-edge-worker
+// nats_sink does not namespace detection subjects by camera, so only PrintNannySettings.video_stream is scored
+async fn run_failure_alert(
+    nats_server_uri: &str,
+    nats_creds: Option<PathBuf>,
+    subject: &str,
+    hostname: &str,
+) -> Result<()> {
+    let settings = PrintNannySettings::new().await?;
+    let sqlite_connection = settings.paths.db().display().to_string();
+    let failure_alert = settings.video_stream.failure_alert.clone();
+    let model = settings.video_stream.detection_model()?;
+    if !failure_alert.enabled {
+        warn!("Print failure alerts are disabled, failure_alert.enabled=false");
+        return Ok(());
+    }
+
+    let nats_client = wait_for_nats_client(nats_server_uri, &nats_creds, false, 2000).await?;
+    let mut subscriber = nats_client
+        .subscribe(subject.to_string())
+        .await
+        .map_err(|e| anyhow!("Failed to subscribe to subject={} error={}", subject, e))?;
+    let alert_subject = PRINT_QUALITY_ALERT_SUBJECT_PATTERN.replace("{pi_id}", hostname);
+    let mut scorer = FailureScorer::new(&failure_alert, &model);
+    info!(
+        "Subscribed to subject={} failure_label={} failure_threshold={} failure_windows={} missing_label={} missing_windows={} cooldown={:?}",
+        subject,
+        scorer.failure_label,
+        scorer.failure_threshold,
+        scorer.failure_windows,
+        scorer.missing_label,
+        scorer.missing_windows,
+        scorer.cooldown
+    );
+
+    while let Some(message) = subscriber.next().await {
+        match PrintJob::get_current(&sqlite_connection) {
+            Ok(job) => scorer.set_print_job(job.as_ref().map(|job| job.id.as_str())),
+            Err(e) => {
+                error!("Failed to read current print job error={}", e);
+                continue;
+            }
+        }
+        match scorer.check(&message.payload, Instant::now()) {
+            Ok(Some(alert)) => {
+                warn!(
+                    "Print failure confirmed rule={:?} label={} windows={} score={}, publishing subject={}",
+                    alert.rule, alert.label, alert.windows, alert.score, alert_subject
+                );
+                if let Err(e) = nats_client
+                    .publish(alert_subject.clone(), serde_json::to_vec(&alert)?.into())
+                    .await
+                {
+                    error!("Failed to publish subject={} error={}", alert_subject, e);
+                }
+            }
+            Ok(None) => debug!("Failure score={}", scorer.score()),
+            Err(e) => error!(
+                "Failed to parse detection dataframe subject={} error={}",
+                subject, e
+            ),
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut builder = Builder::new();
+    // edge worker subscribes to lowercased hostname pattern
+    // see https://github.com/bitsy-ai/printnanny-os/issues/238
+    let hostname = sys_info::hostname()?.to_lowercase();
+
+    let app = Command::new("nats-failure-alert")
+        .author(crate_authors!())
+        .about(crate_description!())
+        .version(GIT_VERSION)
+        .arg(
+            Arg::new("v")
+                .short('v')
+                .multiple_occurrences(true)
+                .help("Sets the level of verbosity. Info: -v Debug: -vv Trace: -vvv"),
+        )
+        .arg(
+            Arg::new("nats_server_uri")
+                .long("nats-server-uri")
+                .takes_value(true)
+                .default_value(DEFAULT_NATS_URI),
+        )
+        .arg(Arg::new("nats_creds").long("nats-creds").takes_value(true))
+        .arg(
+            Arg::new("subject")
+                .long("subject")
+                .takes_value(true)
+                .default_value(DEFAULT_DETECTION_SUBJECT),
+        )
+        .arg(
+            Arg::new("hostname")
+                .long("hostname")
+                .default_value(&hostname)
+                .takes_value(true),
+        );
+    let args = app.get_matches();
+    // Vary the output based on how many times the user used the "verbose" flag
+    // (i.e. 'printnanny v v v' or 'printnanny vvv' vs 'printnanny v'
+    let verbosity = args.occurrences_of("v");
+    match verbosity {
+        0 => {
+            builder.filter_level(LevelFilter::Warn).init();
+        }
+        1 => {
+            builder.filter_level(LevelFilter::Info).init();
+        }
+        2 => {
+            builder.filter_level(LevelFilter::Debug).init();
+        }
+        _ => builder.filter_level(LevelFilter::Trace).init(),
+    };
+
+    let nats_server_uri = args.value_of("nats_server_uri").unwrap();
+    let nats_creds = args.value_of("nats_creds").map(PathBuf::from);
+    let subject = args.value_of("subject").unwrap();
+    let hostname = args.value_of("hostname").unwrap().to_lowercase();
+
+    run_failure_alert(nats_server_uri, nats_creds, subject, &hostname).await
+}
@@ -0,0 +1,142 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use clap::{crate_authors, crate_description, Arg, Command};
+use env_logger::Builder;
+use futures_util::StreamExt;
+use git_version::git_version;
+use log::{error, info, warn, LevelFilter};
+
+use printnanny_nats_apps::preroll::{PrerollTrigger, DEFAULT_DETECTION_SUBJECT};
+use printnanny_nats_client::client::wait_for_nats_client;
+use printnanny_settings::printnanny::PrintNannySettings;
+use printnanny_settings::sys_info;
+
+const DEFAULT_NATS_URI: &str = "nats://localhost:4223";
+const GIT_VERSION: &str = git_version!();
+
+// subscribe to detection dataframes and request a pre-roll buffer commit when PrerollSettings.trigger_threshold is exceeded
+// nats_sink does not namespace detection subjects by camera, so only PrintNannySettings.video_stream is committed
+async fn run_preroll_trigger(
+    nats_server_uri: &str,
+    nats_creds: Option<PathBuf>,
+    subject: &str,
+    hostname: &str,
+) -> Result<()> {
+    let settings = PrintNannySettings::new().await?;
+    let preroll = settings.video_stream.preroll.clone();
+    let model = settings.video_stream.detection_model()?;
+    if !preroll.enabled || !preroll.trigger_enabled {
+        warn!(
+            "Pre-roll detection trigger is disabled, preroll.enabled={} preroll.trigger_enabled={}",
+            preroll.enabled, preroll.trigger_enabled
+        );
+        return Ok(());
+    }
+
+    let nats_client = wait_for_nats_client(nats_server_uri, &nats_creds, false, 2000).await?;
+    let mut subscriber = nats_client
+        .subscribe(subject.to_string())
+        .await
+        .map_err(|e| anyhow!("Failed to subscribe to subject={} error={}", subject, e))?;
+    let request_subject = format!("pi.{hostname}.command.camera.recording.commit");
+    let mut trigger = PrerollTrigger::new(&preroll, &model);
+    info!(
+        "Subscribed to subject={} label={} threshold={} cooldown={:?}",
+        subject, trigger.label, trigger.threshold, trigger.cooldown
+    );
+
+    while let Some(message) = subscriber.next().await {
+        match trigger.check(&message.payload, Instant::now()) {
+            Ok(Some(score)) => {
+                let request = trigger.commit_request(score);
+                warn!(
+                    "Detection score exceeded threshold label={} score={} threshold={}, requesting subject={}",
+                    trigger.label, score, trigger.threshold, request_subject
+                );
+                match nats_client
+                    .request(
+                        request_subject.clone(),
+                        serde_json::to_vec(&request)?.into(),
+                    )
+                    .await
+                {
+                    Ok(reply) => info!(
+                        "Received reply subject={} payload={}",
+                        request_subject,
+                        String::from_utf8_lossy(&reply.payload)
+                    ),
+                    Err(e) => error!("Failed to request subject={} error={}", request_subject, e),
+                }
+            }
+            Ok(None) => (),
+            Err(e) => error!(
+                "Failed to parse detection dataframe subject={} error={}",
+                subject, e
+            ),
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut builder = Builder::new();
+    // edge worker subscribes to lowercased hostname pattern
+    // see https://github.com/bitsy-ai/printnanny-os/issues/238
+    let hostname = sys_info::hostname()?.to_lowercase();
+
+    let app = Command::new("nats-preroll-trigger")
+        .author(crate_authors!())
+        .about(crate_description!())
+        .version(GIT_VERSION)
+        .arg(
+            Arg::new("v")
+                .short('v')
+                .multiple_occurrences(true)
+                .help("Sets the level of verbosity. Info: -v Debug: -vv Trace: -vvv"),
+        )
+        .arg(
+            Arg::new("nats_server_uri")
+                .long("nats-server-uri")
+                .takes_value(true)
+                .default_value(DEFAULT_NATS_URI),
+        )
+        .arg(Arg::new("nats_creds").long("nats-creds").takes_value(true))
+        .arg(
+            Arg::new("subject")
+                .long("subject")
+                .takes_value(true)
+                .default_value(DEFAULT_DETECTION_SUBJECT),
+        )
+        .arg(
+            Arg::new("hostname")
+                .long("hostname")
+                .default_value(&hostname)
+                .takes_value(true),
+        );
+    let args = app.get_matches();
+    // Vary the output based on how many times the user used the "verbose" flag
+    // (i.e. 'printnanny v v v' or 'printnanny vvv' vs 'printnanny v'
+    let verbosity = args.occurrences_of("v");
+    match verbosity {
+        0 => {
+            builder.filter_level(LevelFilter::Warn).init();
+        }
+        1 => {
+            builder.filter_level(LevelFilter::Info).init();
+        }
+        2 => {
+            builder.filter_level(LevelFilter::Debug).init();
+        }
+        _ => builder.filter_level(LevelFilter::Trace).init(),
+    };
+
+    let nats_server_uri = args.value_of("nats_server_uri").unwrap();
+    let nats_creds = args.value_of("nats_creds").map(PathBuf::from);
+    let subject = args.value_of("subject").unwrap();
+    let hostname = args.value_of("hostname").unwrap().to_lowercase();
+
+    run_preroll_trigger(nats_server_uri, nats_creds, subject, &hostname).await
+}
@@ -3,16 +3,32 @@ use std::fmt::Debug;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use bytes::Bytes;
-use log::info;
+use log::{error, info, warn};
 use printnanny_api_client::models;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::failure::PrintQualityAlert;
+use crate::recording_policy;
+
+use printnanny_edge_db::print_job::{PrintJob, PrintJobAlert, UpdatePrintJob};
+use printnanny_edge_db::timelapse::{Timelapse, UpdateTimelapse};
+use printnanny_edge_db::video_recording::{UpdateVideoRecording, VideoRecording};
+use printnanny_gst_pipelines::factory::PrintNannyPipelineFactory;
+use printnanny_gst_pipelines::timelapse::{is_camera_trigger, parse_layer_z, TimelapseDir};
 use printnanny_nats_client::event::NatsEventHandler;
 use printnanny_octoprint_models::{self, Job, JobProgress};
+use printnanny_services::printer_control::apply_failure_action;
 use printnanny_services::printnanny_api::ApiService;
+use printnanny_settings::cam::{TimelapseTrigger, VideoStreamSettings};
 use printnanny_settings::printnanny::PrintNannySettings;
-use tokio::io::AsyncWriteExt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PrintJobStatusKind {
+    Started,
+    Finished,
+    Updated,
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "subject_pattern")]
@@ -34,6 +50,9 @@ pub enum NatsEvent {
 
     #[serde(rename = "pi.{pi_id}.octoprint.event.gcode")]
     OctoPrintGcode(printnanny_octoprint_models::OctoPrintGcode),
+
+    #[serde(rename = "pi.{pi_id}.alerts.print_quality")]
+    PrintQualityAlert(PrintQualityAlert),
 }
 
 impl NatsEvent {
@@ -44,11 +63,20 @@ impl NatsEvent {
         Ok(())
     }
 
-    fn handle_octoprint_server_shutdown(
+    async fn handle_octoprint_server_shutdown(
         event: &printnanny_octoprint_models::OctoPrintServerStatusChanged,
     ) -> Result<()> {
         info!("handle_octoprint_server_shutdown event={:?}", event);
-        Ok(())
+        let settings = PrintNannySettings::new().await?;
+        let sqlite_connection = settings.paths.db().display().to_string();
+        let job = PrintJob::get_current(&sqlite_connection)?;
+        let recording = VideoRecording::get_current(&sqlite_connection)?;
+        let action = recording_policy::on_server_shutdown(
+            &settings.video_stream,
+            job.is_some(),
+            recording.is_some(),
+        );
+        recording_policy::apply(action, job.map(|job| job.id)).await
     }
 
     fn handle_octoprint_printer_status(
@@ -59,11 +87,127 @@ impl NatsEvent {
         Ok(())
     }
 
+    // JobStatusChanged payload carries the OctoPrint event name as "status" (or "event_type")
+    // and the gcode file as "path" (or job.file.name)
+    pub fn parse_job_status(value: &serde_json::Value) -> Option<(String, Option<String>)> {
+        let status = ["status", "event_type"]
+            .iter()
+            .find_map(|key| value.get(key).and_then(|v| v.as_str()))?
+            .to_string();
+        let gcode_file_name = value
+            .get("path")
+            .and_then(|v| v.as_str())
+            .or_else(|| {
+                value
+                    .pointer("/job/file/name")
+                    .or_else(|| value.pointer("/job/file/path"))
+                    .and_then(|v| v.as_str())
+            })
+            .map(|v| v.to_string());
+        Some((status, gcode_file_name))
+    }
+
+    pub fn job_status_kind(status: &str) -> PrintJobStatusKind {
+        let normalized: String = status
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        match normalized.trim_start_matches("print") {
+            "started" => PrintJobStatusKind::Started,
+            "done" | "failed" | "cancelled" | "canceled" | "error" => PrintJobStatusKind::Finished,
+            _ => PrintJobStatusKind::Updated,
+        }
+    }
+
     async fn handle_octoprint_job_status_changed(
         event: &printnanny_octoprint_models::JobStatusChanged,
     ) -> Result<()> {
         info!("handle_octoprint_job_status_changed event={:?}", event);
-        Ok(())
+        let (status, gcode_file_name) = match Self::parse_job_status(&serde_json::to_value(event)?)
+        {
+            Some(result) => result,
+            None => {
+                warn!("Failed to parse job status from event={:?}", event);
+                return Ok(());
+            }
+        };
+        let settings = PrintNannySettings::new().await?;
+        let sqlite_connection = settings.paths.db().display().to_string();
+        let kind = Self::job_status_kind(&status);
+        let recording = VideoRecording::get_current(&sqlite_connection)?;
+        let action =
+            recording_policy::on_job_status(&settings.video_stream, &kind, recording.is_some());
+
+        match kind {
+            PrintJobStatusKind::Started => {
+                // previous job did not report a terminal status
+                PrintJob::finish_all(&sqlite_connection, "Unknown")?;
+                let job = PrintJob::start_new(
+                    &sqlite_connection,
+                    gcode_file_name.as_deref(),
+                    &status,
+                    recording.as_ref().map(|r| r.id.as_str()),
+                )?;
+                if let Some(recording) = recording {
+                    VideoRecording::update(
+                        &sqlite_connection,
+                        &recording.id,
+                        UpdateVideoRecording {
+                            gcode_file_name: job.gcode_file_name.as_deref(),
+                            cloud_sync_done: None,
+                            dir: None,
+                            recording_start: None,
+                            recording_end: None,
+                        },
+                    )?;
+                    info!(
+                        "Linked PrintJob id={} to VideoRecording id={}",
+                        job.id, recording.id
+                    );
+                }
+            }
+            PrintJobStatusKind::Finished => {
+                if let Some(job) = PrintJob::get_current(&sqlite_connection)? {
+                    let now = chrono::Utc::now();
+                    PrintJob::update(
+                        &sqlite_connection,
+                        &job.id,
+                        UpdatePrintJob {
+                            status: Some(&status),
+                            job_end: Some(&now),
+                            video_recording_id: None,
+                        },
+                    )?;
+                }
+                // cancelled and failed jobs never report 100% progress, so the timelapse finishes with the job
+                if let Some(timelapse) = Timelapse::get_current(&sqlite_connection)? {
+                    if let Err(e) = Self::finish_timelapse(
+                        &settings.video_stream,
+                        &sqlite_connection,
+                        &timelapse,
+                    )
+                    .await
+                    {
+                        error!("Failed to finish timelapse id={} error={}", timelapse.id, e);
+                    }
+                }
+            }
+            PrintJobStatusKind::Updated => {
+                if let Some(job) = PrintJob::get_current(&sqlite_connection)? {
+                    PrintJob::update(
+                        &sqlite_connection,
+                        &job.id,
+                        UpdatePrintJob {
+                            status: Some(&status),
+                            ..UpdatePrintJob::default()
+                        },
+                    )?;
+                }
+            }
+        }
+        // a recording started by the policy is linked to the new PrintJob by ApiService::video_recordings_create
+        recording_policy::apply(action, None).await
     }
 
     async fn handle_octoprint_job_progress(
@@ -84,6 +228,21 @@ impl NatsEvent {
             .completion
             .expect("JobProgress.progress.completion expected to be some value, but got None");
 
+        // a timelapse failure shouldn't suppress progress snapshots or alerts
+        if let Err(e) = Self::handle_timelapse_progress(
+            &settings.video_stream,
+            &sqlite_connection,
+            completion,
+            event.path.as_ref().map(|v| v.to_string()),
+        )
+        .await
+        {
+            error!(
+                "Failed to handle timelapse progress completion={} error={}",
+                completion, e
+            );
+        }
+
         let api = ApiService::new(settings.cloud, sqlite_connection);
         api.camera_snapshot_create().await?;
 
@@ -93,43 +252,32 @@ impl NatsEvent {
         {
             let mut payload: HashMap<String, serde_json::Value> = std::collections::HashMap::new();
 
-            match &event.job {
-                Some(v) => {
-                    payload.insert("job".to_string(), serde_json::to_value::<Job>(*v.clone())?);
-                }
-                None => (),
-            };
-
-            match &event.storage {
-                Some(v) => {
-                    payload.insert(
-                        "job".to_string(),
-                        serde_json::to_value::<String>(v.to_string())?,
-                    );
-                }
-                None => (),
-            };
-
-            match &event.path {
-                Some(v) => {
-                    payload.insert(
-                        "path".to_string(),
-                        serde_json::to_value::<String>(v.to_string())?,
-                    );
-                }
-                None => (),
-            };
-
-            match &event.progress {
-                Some(v) => {
-                    payload.insert(
-                        "progress".to_string(),
-                        serde_json::to_value::<JobProgress>(*v.clone())?,
-                    );
-                }
-                None => (),
-            };
+            if let Some(v) = &event.job {
+                payload.insert("job".to_string(), serde_json::to_value::<Job>(*v.clone())?);
+            }
+
+            if let Some(v) = &event.storage {
+                payload.insert(
+                    "job".to_string(),
+                    serde_json::to_value::<String>(v.to_string())?,
+                );
+            }
+
+            if let Some(v) = &event.path {
+                payload.insert(
+                    "path".to_string(),
+                    serde_json::to_value::<String>(v.to_string())?,
+                );
+            }
+
+            if let Some(v) = &event.progress {
+                payload.insert(
+                    "progress".to_string(),
+                    serde_json::to_value::<JobProgress>(*v.clone())?,
+                );
+            }
 
+            let alert_payload = serde_json::to_string(&payload)?;
             let alert = api
                 .print_job_alert_create(
                     models::EventTypeEnum::PrintProgress,
@@ -138,13 +286,256 @@ impl NatsEvent {
                 )
                 .await?;
             info!("Success! Created PrintJobAlert id={}", alert.id);
+
+            if let Some(job) = PrintJob::get_current(&api.sqlite_connection)? {
+                PrintJobAlert::insert(
+                    &api.sqlite_connection,
+                    &job.id,
+                    &models::EventTypeEnum::PrintProgress.to_string(),
+                    Some(&alert.id),
+                    Some(&alert_payload),
+                )?;
+            }
         }
 
         Ok(())
     }
 
-    fn handle_octoprint_gcode(event: &printnanny_octoprint_models::OctoPrintGcode) -> Result<()> {
+    // a progress event at 0% starts a new timelapse, and 100% assembles the current timelapse
+    // a finished job (done, cancelled or failed) also assembles it, see handle_octoprint_job_status_changed
+    async fn handle_timelapse_progress(
+        video_stream: &VideoStreamSettings,
+        sqlite_connection: &str,
+        completion: f64,
+        gcode_file_name: Option<String>,
+    ) -> Result<()> {
+        if !video_stream.timelapse.enabled {
+            return Ok(());
+        }
+        let current = Timelapse::get_current(sqlite_connection)?;
+        match current {
+            Some(current) if completion == 0_f64 || completion >= 100_f64 => {
+                Self::finish_timelapse(video_stream, sqlite_connection, &current).await?;
+                if completion == 0_f64 {
+                    Self::start_timelapse(video_stream, sqlite_connection, gcode_file_name).await?;
+                }
+            }
+            // capture resumes if timelapse was enabled (or nats-edge-worker restarted) mid-print
+            None if completion < 100_f64 => {
+                Self::start_timelapse(video_stream, sqlite_connection, gcode_file_name).await?;
+            }
+            _ => (),
+        };
+        Ok(())
+    }
+
+    async fn start_timelapse(
+        video_stream: &VideoStreamSettings,
+        sqlite_connection: &str,
+        gcode_file_name: Option<String>,
+    ) -> Result<()> {
+        let recording = VideoRecording::get_current(sqlite_connection)?;
+        let timelapse = Timelapse::start_new(
+            sqlite_connection,
+            video_stream.timelapse.path.clone().into(),
+            video_stream.timelapse.trigger.as_str(),
+            gcode_file_name.as_deref(),
+            recording.as_ref().map(|r| r.id.as_str()),
+        )?;
+        let factory = PrintNannyPipelineFactory::default();
+        factory
+            .start_timelapse_pipeline(&TimelapseDir::new(&timelapse.dir), video_stream)
+            .await?;
+        info!(
+            "Started timelapse id={} trigger={}",
+            timelapse.id, timelapse.capture_trigger
+        );
+        Ok(())
+    }
+
+    async fn finish_timelapse(
+        video_stream: &VideoStreamSettings,
+        sqlite_connection: &str,
+        timelapse: &Timelapse,
+    ) -> Result<()> {
+        let factory = PrintNannyPipelineFactory::default();
+        if let Err(e) = factory.stop_timelapse_pipeline(video_stream).await {
+            warn!(
+                "Failed to stop timelapse pipeline id={} error={}",
+                timelapse.id, e
+            );
+        }
+        let dir = TimelapseDir::new(&timelapse.dir);
+        let frame_count = dir.frame_count()?;
+        let file_name = match factory.assemble_timelapse(&dir, video_stream).await {
+            Ok(output) => Some(output.display().to_string()),
+            Err(e) => {
+                error!(
+                    "Failed to assemble timelapse id={} error={}",
+                    timelapse.id, e
+                );
+                None
+            }
+        };
+        let now = chrono::Utc::now();
+        Timelapse::update(
+            sqlite_connection,
+            &timelapse.id,
+            UpdateTimelapse {
+                frame_count: Some(&frame_count),
+                last_z: None,
+                capture_end: Some(&now),
+                file_name: file_name.as_deref(),
+            },
+        )?;
+        info!(
+            "Finished timelapse id={} frame_count={} file_name={:?}",
+            timelapse.id, frame_count, file_name
+        );
+        Ok(())
+    }
+
+    // devices which never synced EmailAlertSettings from PrintNanny Cloud don't send print quality alerts
+    fn print_quality_alerts_enabled(sqlite_connection: &str) -> Result<bool> {
+        let email_alert_settings =
+            printnanny_edge_db::cloud::EmailAlertSettings::get_optional(sqlite_connection)?;
+        Ok(email_alert_settings
+            .map(|settings| settings.print_quality_enabled)
+            .unwrap_or(false))
+    }
+
+    // alerts are recorded against the current PrintJob, and sent to PrintNanny Cloud if EmailAlertSettings.print_quality_enabled
+    // the current PrintJob is then paused or cancelled if FailureActionSettings.enabled
+    async fn handle_print_quality_alert(event: &PrintQualityAlert) -> Result<()> {
+        warn!("handle_print_quality_alert event={:?}", event);
+        let settings = PrintNannySettings::new().await?;
+        let sqlite_connection = settings.paths.db().display().to_string();
+        let job = match PrintJob::get_current(&sqlite_connection)? {
+            Some(job) => job,
+            None => {
+                info!("No print job is running, ignoring print quality alert");
+                return Ok(());
+            }
+        };
+
+        let payload = event.payload()?;
+        let alert_payload = serde_json::to_string(&payload)?;
+        let cloud_alert_id = match Self::print_quality_alerts_enabled(&sqlite_connection)? {
+            true => {
+                let api = ApiService::new(settings.cloud, sqlite_connection.clone());
+                // the local alert is recorded and the printer action applied even if PrintNanny Cloud is unreachable
+                match api
+                    .print_job_alert_create(
+                        models::EventTypeEnum::PrintQuality,
+                        models::EventSourceEnum::Octoprint,
+                        Some(payload),
+                    )
+                    .await
+                {
+                    Ok(alert) => {
+                        info!("Success! Created PrintJobAlert id={}", alert.id);
+                        Some(alert.id)
+                    }
+                    Err(e) => {
+                        error!(
+                            "Failed to create PrintJobAlert in PrintNanny Cloud print_job_id={} error={}",
+                            job.id, e
+                        );
+                        None
+                    }
+                }
+            }
+            false => None,
+        };
+        let alert = PrintJobAlert::insert(
+            &sqlite_connection,
+            &job.id,
+            &models::EventTypeEnum::PrintQuality.to_string(),
+            cloud_alert_id.as_deref(),
+            Some(&alert_payload),
+        )?;
+
+        if let Some(action) = apply_failure_action(
+            &settings.failure_action,
+            &sqlite_connection,
+            &job.id,
+            Some(&alert.id),
+        )
+        .await?
+        {
+            warn!(
+                "Applied failure action={} printer_api={} status={} print_job_id={}",
+                action.action, action.printer_api, action.status, job.id
+            );
+        }
+        Ok(())
+    }
+
+    // OctoPrintGcode payload carries the command sent to the printer as "gcode" (or "cmd")
+    fn gcode_command(
+        event: &printnanny_octoprint_models::OctoPrintGcode,
+    ) -> Result<Option<String>> {
+        let value = serde_json::to_value(event)?;
+        Ok(["gcode", "cmd"]
+            .iter()
+            .find_map(|key| value.get(key).and_then(|v| v.as_str()))
+            .map(|v| v.to_string()))
+    }
+
+    async fn handle_octoprint_gcode(
+        event: &printnanny_octoprint_models::OctoPrintGcode,
+    ) -> Result<()> {
         info!("handle_octoprint_gcode event={:?}", event);
+        let gcode = match Self::gcode_command(event)? {
+            Some(gcode) => gcode,
+            None => return Ok(()),
+        };
+        let layer_z = parse_layer_z(&gcode);
+        if layer_z.is_none() && !is_camera_trigger(&gcode) {
+            return Ok(());
+        }
+
+        let settings = PrintNannySettings::new().await?;
+        let timelapse_settings = &settings.video_stream.timelapse;
+        if !timelapse_settings.enabled || timelapse_settings.trigger != TimelapseTrigger::Layer {
+            return Ok(());
+        }
+        let sqlite_connection = settings.paths.db().display().to_string();
+        let timelapse = match Timelapse::get_current(&sqlite_connection)? {
+            Some(timelapse) => timelapse,
+            None => return Ok(()),
+        };
+
+        // z-hops briefly raise Z above the current layer, so only a new maximum Z is treated as a layer change
+        let is_layer_change = match (layer_z, timelapse.last_z) {
+            (Some(z), Some(last_z)) => z > last_z,
+            (Some(_), None) => true,
+            (None, _) => true,
+        };
+        if !is_layer_change {
+            return Ok(());
+        }
+        let dir = TimelapseDir::new(&timelapse.dir);
+        match dir.capture_latest_frame(timelapse.frame_count)? {
+            Some(_) => {
+                let frame_count = timelapse.frame_count + 1;
+                let last_z = layer_z.or(timelapse.last_z);
+                Timelapse::update(
+                    &sqlite_connection,
+                    &timelapse.id,
+                    UpdateTimelapse {
+                        frame_count: Some(&frame_count),
+                        last_z: last_z.as_ref(),
+                        capture_end: None,
+                        file_name: None,
+                    },
+                )?;
+            }
+            None => warn!(
+                "No frames available for timelapse id={} dir={}",
+                timelapse.id, timelapse.dir
+            ),
+        }
         Ok(())
     }
 }
@@ -163,13 +554,11 @@ impl NatsEventHandler for NatsEvent {
                 )?))
             }
 
-            "pi.{pi_id}.octoprint.event.server.shutdown" => {
-                Ok(NatsEvent::OctoPrintServerStartup(serde_json::from_slice::<
-                    printnanny_octoprint_models::OctoPrintServerStatusChanged,
-                >(
-                    payload.as_ref()
-                )?))
-            }
+            "pi.{pi_id}.octoprint.event.server.shutdown" => Ok(NatsEvent::OctoPrintServerShutdown(
+                serde_json::from_slice::<printnanny_octoprint_models::OctoPrintServerStatusChanged>(
+                    payload.as_ref(),
+                )?,
+            )),
 
             "pi.{pi_id}.octoprint.event.printer.status" => {
                 Ok(NatsEvent::PrinterStatusChanged(serde_json::from_slice::<
@@ -203,6 +592,14 @@ impl NatsEventHandler for NatsEvent {
                 )?))
             }
 
+            "pi.{pi_id}.alerts.print_quality" => {
+                Ok(NatsEvent::PrintQualityAlert(serde_json::from_slice::<
+                    PrintQualityAlert,
+                >(
+                    payload.as_ref()
+                )?))
+            }
+
             _ => Err(anyhow!(
                 " NatsEventHandler not implemented for subject pattern {}",
                 subject_pattern
@@ -216,7 +613,7 @@ impl NatsEventHandler for NatsEvent {
                 Self::handle_octoprint_server_startup(event)
             }
             NatsEvent::OctoPrintServerShutdown(event) => {
-                Self::handle_octoprint_server_shutdown(event)
+                Self::handle_octoprint_server_shutdown(event).await
             }
 
             NatsEvent::PrinterStatusChanged(event) => Self::handle_octoprint_printer_status(event),
@@ -229,7 +626,69 @@ impl NatsEventHandler for NatsEvent {
                 Self::handle_octoprint_job_status_changed(event).await
             }
 
-            NatsEvent::OctoPrintGcode(event) => Self::handle_octoprint_gcode(event),
+            NatsEvent::OctoPrintGcode(event) => Self::handle_octoprint_gcode(event).await,
+
+            NatsEvent::PrintQualityAlert(event) => Self::handle_print_quality_alert(event).await,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_job_status() {
+        let value = serde_json::json!({"status": "PrintStarted", "path": "benchy.gcode"});
+        assert_eq!(
+            NatsEvent::parse_job_status(&value),
+            Some(("PrintStarted".into(), Some("benchy.gcode".into())))
+        );
+        let value = serde_json::json!({
+            "event_type": "PrintDone",
+            "job": {"file": {"name": "benchy.gcode"}}
+        });
+        assert_eq!(
+            NatsEvent::parse_job_status(&value),
+            Some(("PrintDone".into(), Some("benchy.gcode".into())))
+        );
+        assert_eq!(NatsEvent::parse_job_status(&serde_json::json!({})), None);
+
+        assert_eq!(
+            NatsEvent::job_status_kind("PrintStarted"),
+            PrintJobStatusKind::Started
+        );
+        assert_eq!(
+            NatsEvent::job_status_kind("print_cancelled"),
+            PrintJobStatusKind::Finished
+        );
+        assert_eq!(
+            NatsEvent::job_status_kind("PrintFailed"),
+            PrintJobStatusKind::Finished
+        );
+        assert_eq!(
+            NatsEvent::job_status_kind("PrintPaused"),
+            PrintJobStatusKind::Updated
+        );
+    }
+
+    #[test]
+    fn test_print_quality_alerts_without_email_alert_settings() {
+        let dir = std::env::temp_dir().join(format!(
+            "printnanny-email-alert-settings-test-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sqlite_connection = dir.join("db.sqlite").display().to_string();
+        printnanny_edge_db::connection::run_migrations(&sqlite_connection).unwrap();
+
+        // email_alert_settings is empty until the device syncs with PrintNanny Cloud
+        assert_eq!(
+            printnanny_edge_db::cloud::EmailAlertSettings::get_optional(&sqlite_connection)
+                .unwrap(),
+            None
+        );
+        assert!(!NatsEvent::print_quality_alerts_enabled(&sqlite_connection).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use printnanny_settings::cam::FailureAlertSettings;
+use printnanny_settings::model::ModelManifest;
+
+use crate::preroll::dataframe_rows;
+
+// one-way event handled by nats-edge-worker, see NatsEvent::PrintQualityAlert
+pub const PRINT_QUALITY_ALERT_SUBJECT_PATTERN: &str = "pi.{pi_id}.alerts.print_quality";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureRule {
+    // windowed mean score of FailureAlertSettings.failure_label exceeded failure_threshold
+    #[serde(rename = "detected")]
+    Detected,
+    // FailureAlertSettings.missing_label was not detected
+    #[serde(rename = "missing")]
+    Missing,
+}
+
+// Confirmed print failure, published to PRINT_QUALITY_ALERT_SUBJECT_PATTERN
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PrintQualityAlert {
+    pub rule: FailureRule,
+    pub label: String,
+    // consecutive windows matching the rule
+    pub windows: u32,
+    // failure score (0.0 - 1.0) when the alert was raised
+    pub score: f64,
+    // last windowed mean score of label, None for FailureRule::Missing
+    pub mean: Option<f64>,
+}
+
+impl PrintQualityAlert {
+    // PrintJobAlertRequest.payload
+    pub fn payload(&self) -> Result<HashMap<String, Value>> {
+        match serde_json::to_value(self)? {
+            Value::Object(map) => Ok(map.into_iter().collect()),
+            other => Err(anyhow!("Expected alert object, received {}", other)),
+        }
+    }
+}
+
+// Scores dataframe_agg windows against FailureAlertSettings rules
+// the failure score is the progress of the closest rule towards confirmation, 1.0 once a rule held for enough consecutive windows
+// windows are only scored while a print job is running, and scoring starts over for every print job
+#[derive(Clone, Debug)]
+pub struct FailureScorer {
+    pub failure_label: String,
+    // raw dataframe_agg detection score, see ModelManifest.score_scale
+    pub failure_threshold: f64,
+    pub failure_windows: u32,
+    pub missing_label: String,
+    pub missing_windows: u32,
+    pub cooldown: Duration,
+    failure_count: u32,
+    missing_count: u32,
+    last_mean: Option<f64>,
+    last_alert: Option<Instant>,
+    print_job_id: Option<String>,
+}
+
+impl FailureScorer {
+    // model is the active detection model, whose score_scale converts failure_threshold to a raw score
+    pub fn new(settings: &FailureAlertSettings, model: &ModelManifest) -> Self {
+        Self {
+            failure_label: settings.failure_label.clone(),
+            failure_threshold: model.score_threshold(settings.failure_threshold) as f64,
+            failure_windows: settings.failure_windows.max(1) as u32,
+            missing_label: settings.missing_label.clone(),
+            missing_windows: settings.missing_windows.max(1) as u32,
+            cooldown: Duration::from_secs(settings.cooldown_secs.max(0) as u64),
+            failure_count: 0,
+            missing_count: 0,
+            last_mean: None,
+            last_alert: None,
+            print_job_id: None,
+        }
+    }
+
+    // current PrintJob id, None while no print job is running
+    // windows from a previous print job, or from idle time between print jobs, don't count towards the next print job's alert
+    pub fn set_print_job(&mut self, print_job_id: Option<&str>) {
+        if self.print_job_id.as_deref() == print_job_id {
+            return;
+        }
+        self.print_job_id = print_job_id.map(|id| id.to_string());
+        self.failure_count = 0;
+        self.missing_count = 0;
+        self.last_mean = None;
+        self.last_alert = None;
+    }
+
+    // rows without a label's columns (e.g. the label isn't part of the active model) don't change that rule's count
+    fn update(&mut self, row: &Value) {
+        if let Some(mean) = row.get(format!("{}__mean", self.failure_label)) {
+            self.last_mean = mean.as_f64();
+            match self.last_mean {
+                Some(mean) if mean > self.failure_threshold => self.failure_count += 1,
+                _ => self.failure_count = 0,
+            }
+        }
+        if self.missing_label.is_empty() {
+            return;
+        }
+        let count = row.get(format!("{}__count", self.missing_label));
+        let mean = row.get(format!("{}__mean", self.missing_label));
+        if count.is_none() && mean.is_none() {
+            return;
+        }
+        let detected = count.and_then(|v| v.as_u64()).unwrap_or(0) > 0
+            || mean.and_then(|v| v.as_f64()).is_some();
+        match detected {
+            true => self.missing_count = 0,
+            false => self.missing_count += 1,
+        }
+    }
+
+    pub fn score(&self) -> f64 {
+        let failure = self.failure_count as f64 / self.failure_windows as f64;
+        let missing = match self.missing_label.is_empty() {
+            true => 0_f64,
+            false => self.missing_count as f64 / self.missing_windows as f64,
+        };
+        failure.max(missing).min(1_f64)
+    }
+
+    // scores the dataframe rows in payload, returning an alert if a rule is confirmed outside of the cooldown
+    // windows received while no print job is running are dropped, see set_print_job
+    pub fn check(&mut self, payload: &[u8], now: Instant) -> Result<Option<PrintQualityAlert>> {
+        let rows = dataframe_rows(payload)?;
+        if self.print_job_id.is_none() {
+            return Ok(None);
+        }
+        for row in rows.iter() {
+            self.update(row);
+        }
+        let score = self.score();
+        if score < 1_f64 {
+            return Ok(None);
+        }
+        if let Some(last_alert) = self.last_alert {
+            if now.duration_since(last_alert) < self.cooldown {
+                return Ok(None);
+            }
+        }
+        self.last_alert = Some(now);
+        let alert = match self.failure_count >= self.failure_windows {
+            true => PrintQualityAlert {
+                rule: FailureRule::Detected,
+                label: self.failure_label.clone(),
+                windows: self.failure_count,
+                score,
+                mean: self.last_mean,
+            },
+            false => PrintQualityAlert {
+                rule: FailureRule::Missing,
+                label: self.missing_label.clone(),
+                windows: self.missing_count,
+                score,
+                mean: None,
+            },
+        };
+        Ok(Some(alert))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use printnanny_settings::cam::VideoStreamSettings;
+
+    fn model() -> ModelManifest {
+        VideoStreamSettings::default().detection_model().unwrap()
+    }
+
+    #[test]
+    fn test_failure_scorer_score_scale() {
+        let settings = FailureAlertSettings {
+            failure_threshold: 50,
+            failure_windows: 1,
+            ..FailureAlertSettings::default()
+        };
+        // e.g. a quantized model reporting scores from 0 to 255
+        let model = ModelManifest {
+            score_scale: 1_f32 / 255_f32,
+            ..model()
+        };
+        let mut scorer = FailureScorer::new(&settings, &model);
+        assert!((scorer.failure_threshold - 127.5).abs() < 0.01);
+        scorer.set_print_job(Some("job1"));
+        let now = Instant::now();
+        assert_eq!(
+            scorer.check(br#"{"spaghetti__mean": 100.0}"#, now).unwrap(),
+            None
+        );
+        let alert = scorer
+            .check(br#"{"spaghetti__mean": 200.0}"#, now)
+            .unwrap()
+            .unwrap();
+        assert_eq!(alert.mean, Some(200.0));
+    }
+
+    #[test]
+    fn test_failure_scorer() {
+        let settings = FailureAlertSettings {
+            failure_windows: 2,
+            missing_windows: 3,
+            ..FailureAlertSettings::default()
+        };
+        let mut scorer = FailureScorer::new(&settings, &model());
+        scorer.set_print_job(Some("job1"));
+        let ok = br#"{"spaghetti__mean": 0.2, "nozzle__count": 4, "nozzle__mean": 0.9}"#;
+        let spaghetti = br#"{"spaghetti__mean": 0.8, "nozzle__count": 4, "nozzle__mean": 0.9}"#;
+        let no_nozzle = br#"{"spaghetti__mean": null, "nozzle__count": 0, "nozzle__mean": null}"#;
+
+        let now = Instant::now();
+        assert_eq!(scorer.check(spaghetti, now).unwrap(), None);
+        assert_eq!(scorer.score(), 0.5);
+        // consecutive windows are reset by a window below threshold
+        assert_eq!(scorer.check(ok, now).unwrap(), None);
+        assert_eq!(scorer.score(), 0.0);
+
+        let payload = format!(
+            "[{}, {}]",
+            String::from_utf8_lossy(spaghetti),
+            String::from_utf8_lossy(spaghetti)
+        );
+        let alert = scorer.check(payload.as_bytes(), now).unwrap().unwrap();
+        assert_eq!(alert.rule, FailureRule::Detected);
+        assert_eq!(alert.label, "spaghetti");
+        assert_eq!(alert.windows, 2);
+        assert_eq!(alert.mean, Some(0.8));
+        assert_eq!(alert.payload().unwrap()["rule"], "detected");
+        // cooldown
+        assert_eq!(scorer.check(spaghetti, now).unwrap(), None);
+
+        let later = now + scorer.cooldown + Duration::from_secs(1);
+        for _ in 0..2 {
+            assert_eq!(scorer.check(no_nozzle, later).unwrap(), None);
+        }
+        let alert = scorer.check(no_nozzle, later).unwrap().unwrap();
+        assert_eq!(alert.rule, FailureRule::Missing);
+        assert_eq!(alert.label, "nozzle");
+        assert_eq!(alert.windows, 3);
+        assert!(scorer.check(b"1.0", later).is_err());
+    }
+
+    #[test]
+    fn test_failure_scorer_print_job() {
+        let settings = FailureAlertSettings {
+            failure_windows: 2,
+            ..FailureAlertSettings::default()
+        };
+        let mut scorer = FailureScorer::new(&settings, &model());
+        let spaghetti = br#"{"spaghetti__mean": 0.8}"#;
+        let now = Instant::now();
+
+        // windows are dropped while no print job is running
+        for _ in 0..3 {
+            assert_eq!(scorer.check(spaghetti, now).unwrap(), None);
+        }
+        assert_eq!(scorer.score(), 0.0);
+
+        scorer.set_print_job(Some("job1"));
+        assert_eq!(scorer.check(spaghetti, now).unwrap(), None);
+        assert_eq!(scorer.score(), 0.5);
+        // same print job keeps counting
+        scorer.set_print_job(Some("job1"));
+        assert!(scorer.check(spaghetti, now).unwrap().is_some());
+
+        // the next print job starts over, without the previous print job's cooldown
+        scorer.set_print_job(Some("job2"));
+        assert_eq!(scorer.score(), 0.0);
+        assert_eq!(scorer.check(spaghetti, now).unwrap(), None);
+        assert!(scorer.check(spaghetti, now).unwrap().is_some());
+
+        scorer.set_print_job(None);
+        assert_eq!(scorer.score(), 0.0);
+        assert_eq!(scorer.check(spaghetti, now).unwrap(), None);
+        assert_eq!(scorer.score(), 0.0);
+    }
+}
@@ -0,0 +1,5 @@
+// GstBusWatcher is defined in printnanny-gst-pipelines, so the in-process backend can watch its own pipeline buses
+pub use printnanny_gst_pipelines::bus::{
+    GstBusWatcher, PipelineBusMessage, BUS_SUBJECT_PATTERN, DEFAULT_BUS_FILTER,
+    DEFAULT_EXCLUDED_PIPELINES,
+};
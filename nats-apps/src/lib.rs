@@ -1,2 +1,9 @@
+// handlers return the large settings and service errors, boxing them would change the public error types
+#![allow(clippy::result_large_err)]
+
 pub mod event;
+pub mod failure;
+pub mod gst_bus;
+pub mod preroll;
+pub mod recording_policy;
 pub mod request_reply;
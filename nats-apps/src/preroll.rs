@@ -0,0 +1,141 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use printnanny_settings::cam::PrerollSettings;
+use printnanny_settings::model::ModelManifest;
+
+use crate::request_reply::CameraRecordingCommitRequest;
+
+// dataframe_agg (output-type=json) rows published by the df pipeline's nats_sink
+pub const DEFAULT_DETECTION_SUBJECT: &str = "pi.qc.df";
+
+// dataframe_agg rows in window order; output-type=json publishes an array of rows, or a single row object
+pub fn dataframe_rows(payload: &[u8]) -> Result<Vec<Value>> {
+    match serde_json::from_slice::<Value>(payload)? {
+        Value::Array(rows) => Ok(rows),
+        row @ Value::Object(_) => Ok(vec![row]),
+        other => Err(anyhow!("Expected dataframe rows, received {}", other)),
+    }
+}
+
+// Requests a pre-roll buffer commit when the windowed mean score of PrerollSettings.trigger_label
+// exceeds PrerollSettings.trigger_threshold, at most once per PrerollSettings.trigger_cooldown_secs
+#[derive(Clone, Debug)]
+pub struct PrerollTrigger {
+    pub label: String,
+    // raw dataframe_agg detection score, see ModelManifest.score_scale
+    pub threshold: f64,
+    pub cooldown: Duration,
+    last_commit: Option<Instant>,
+}
+
+impl PrerollTrigger {
+    // model is the active detection model, whose score_scale converts trigger_threshold to a raw score
+    pub fn new(settings: &PrerollSettings, model: &ModelManifest) -> Self {
+        Self {
+            label: settings.trigger_label.clone(),
+            threshold: model.score_threshold(settings.trigger_threshold) as f64,
+            cooldown: Duration::from_secs(settings.trigger_cooldown_secs.max(0) as u64),
+            last_commit: None,
+        }
+    }
+
+    // maximum "{label}__mean" of the dataframe rows in payload, None if label was not detected
+    pub fn score(&self, payload: &[u8]) -> Result<Option<f64>> {
+        let column = format!("{}__mean", self.label);
+        let score = dataframe_rows(payload)?
+            .iter()
+            .filter_map(|row| row.get(&column).and_then(|v| v.as_f64()))
+            .fold(None, |max: Option<f64>, v| {
+                Some(max.map_or(v, |max| max.max(v)))
+            });
+        Ok(score)
+    }
+
+    // returns the score which exceeded threshold, if a commit should be requested
+    pub fn check(&mut self, payload: &[u8], now: Instant) -> Result<Option<f64>> {
+        let score = match self.score(payload)? {
+            Some(score) if score > self.threshold => score,
+            _ => return Ok(None),
+        };
+        if let Some(last_commit) = self.last_commit {
+            if now.duration_since(last_commit) < self.cooldown {
+                return Ok(None);
+            }
+        }
+        self.last_commit = Some(now);
+        Ok(Some(score))
+    }
+
+    pub fn commit_request(&self, score: f64) -> CameraRecordingCommitRequest {
+        CameraRecordingCommitRequest {
+            reason: Some(format!("{}__mean={:.2}", self.label, score)),
+            ..CameraRecordingCommitRequest::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use printnanny_settings::cam::VideoStreamSettings;
+
+    #[test]
+    fn test_preroll_trigger() {
+        let settings = PrerollSettings {
+            trigger_enabled: true,
+            ..PrerollSettings::default()
+        };
+        let model = VideoStreamSettings::default().detection_model().unwrap();
+        let mut trigger = PrerollTrigger::new(&settings, &model);
+        let below = br#"[{"rt__min": 0, "spaghetti__mean": 0.2, "nozzle__mean": 0.9}]"#;
+        let above =
+            br#"[{"rt__min": 0, "spaghetti__mean": null}, {"rt__min": 1, "spaghetti__mean": 0.8}]"#;
+
+        let now = Instant::now();
+        assert_eq!(trigger.check(below, now).unwrap(), None);
+        assert_eq!(trigger.check(above, now).unwrap(), Some(0.8));
+        // cooldown
+        assert_eq!(trigger.check(above, now).unwrap(), None);
+        assert_eq!(
+            trigger
+                .check(above, now + trigger.cooldown + Duration::from_secs(1))
+                .unwrap(),
+            Some(0.8)
+        );
+        assert!(trigger.check(b"1.0", now).is_err());
+        assert_eq!(
+            trigger.commit_request(0.8).reason.as_deref(),
+            Some("spaghetti__mean=0.80")
+        );
+    }
+
+    #[test]
+    fn test_preroll_trigger_score_scale() {
+        let settings = PrerollSettings {
+            trigger_enabled: true,
+            trigger_threshold: 50,
+            ..PrerollSettings::default()
+        };
+        let model = ModelManifest {
+            score_scale: 1_f32 / 255_f32,
+            ..VideoStreamSettings::default().detection_model().unwrap()
+        };
+        let mut trigger = PrerollTrigger::new(&settings, &model);
+        let now = Instant::now();
+        assert_eq!(
+            trigger
+                .check(br#"[{"spaghetti__mean": 100.0}]"#, now)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            trigger
+                .check(br#"[{"spaghetti__mean": 200.0}]"#, now)
+                .unwrap(),
+            Some(200.0)
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use log::{error, info, warn};
+
+use printnanny_edge_db::print_job::{PrintJob, UpdatePrintJob};
+use printnanny_edge_db::video_recording::{UpdateVideoRecording, VideoRecording};
+use printnanny_settings::cam::VideoStreamSettings;
+use printnanny_settings::printnanny::PrintNannySettings;
+
+use crate::event::PrintJobStatusKind;
+use crate::request_reply::{CameraRecordingStartRequest, CameraRecordingStopRequest, NatsRequest};
+
+// PrintJob status recorded when OctoPrint shut down before the job reported a terminal status
+pub const SERVER_SHUTDOWN_STATUS: &str = "ServerShutdown";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordingAction {
+    Start,
+    // stop after grace period, unless a new job started in the meantime
+    Stop { grace: Duration },
+    None,
+}
+
+// Starts and stops video recordings from OctoPrint job and server events, see RecordingPolicySettings
+pub fn on_job_status(
+    settings: &VideoStreamSettings,
+    kind: &PrintJobStatusKind,
+    recording_active: bool,
+) -> RecordingAction {
+    match kind {
+        // a job started during the grace period of the previous job continues the active recording
+        PrintJobStatusKind::Started if settings.recording.auto_start && !recording_active => {
+            RecordingAction::Start
+        }
+        PrintJobStatusKind::Finished if settings.recording_policy.auto_stop && recording_active => {
+            RecordingAction::Stop {
+                grace: Duration::from_secs(settings.recording_policy.stop_grace_secs.max(0) as u64),
+            }
+        }
+        _ => RecordingAction::None,
+    }
+}
+
+pub fn on_server_shutdown(
+    settings: &VideoStreamSettings,
+    job_active: bool,
+    recording_active: bool,
+) -> RecordingAction {
+    if settings.recording_policy.auto_stop && job_active && recording_active {
+        RecordingAction::Stop {
+            grace: Duration::from_secs(settings.recording_policy.shutdown_grace_secs.max(0) as u64),
+        }
+    } else {
+        RecordingAction::None
+    }
+}
+
+// stop recording_id after grace, if it is still the current recording and no other job is running
+// interrupted_job is marked finished with SERVER_SHUTDOWN_STATUS if it did not report a terminal status
+async fn stop_after_grace(
+    grace: Duration,
+    recording_id: String,
+    interrupted_job: Option<String>,
+) -> Result<()> {
+    tokio::time::sleep(grace).await;
+    let settings = PrintNannySettings::new().await?;
+    let sqlite_connection = settings.paths.db().display().to_string();
+
+    if let Some(job) = PrintJob::get_current(&sqlite_connection)? {
+        if Some(&job.id) != interrupted_job.as_ref() {
+            info!(
+                "PrintJob id={} started during grace period, continuing VideoRecording id={}",
+                job.id, recording_id
+            );
+            return Ok(());
+        }
+        let now = chrono::Utc::now();
+        PrintJob::update(
+            &sqlite_connection,
+            &job.id,
+            UpdatePrintJob {
+                status: Some(SERVER_SHUTDOWN_STATUS),
+                job_end: Some(&now),
+                video_recording_id: None,
+            },
+        )?;
+        warn!(
+            "PrintJob id={} did not finish before OctoPrint shutdown, marked status={}",
+            job.id, SERVER_SHUTDOWN_STATUS
+        );
+    }
+
+    match VideoRecording::get_current(&sqlite_connection)? {
+        Some(recording) if recording.id == recording_id => {
+            info!(
+                "Recording policy stopping VideoRecording id={} after grace_secs={}",
+                recording_id,
+                grace.as_secs()
+            );
+            if let Err(e) =
+                NatsRequest::handle_camera_recording_stop(&CameraRecordingStopRequest::default())
+                    .await
+            {
+                // finalization requires PrintNanny Cloud, end recording locally so the next job starts a new recording
+                error!(
+                    "Failed to stop VideoRecording id={} error={}",
+                    recording_id, e
+                );
+                let now = chrono::Utc::now();
+                VideoRecording::update(
+                    &sqlite_connection,
+                    &recording_id,
+                    UpdateVideoRecording {
+                        recording_end: Some(&now),
+                        cloud_sync_done: None,
+                        dir: None,
+                        recording_start: None,
+                        gcode_file_name: None,
+                    },
+                )?;
+            }
+        }
+        _ => info!(
+            "VideoRecording id={} was already stopped, nothing to do",
+            recording_id
+        ),
+    }
+    Ok(())
+}
+
+pub async fn apply(action: RecordingAction, interrupted_job: Option<String>) -> Result<()> {
+    match action {
+        RecordingAction::Start => {
+            info!("Recording policy starting VideoRecording");
+            NatsRequest::handle_camera_recording_start(&CameraRecordingStartRequest::default())
+                .await?;
+        }
+        RecordingAction::Stop { grace } => {
+            let settings = PrintNannySettings::new().await?;
+            let sqlite_connection = settings.paths.db().display().to_string();
+            let recording = match VideoRecording::get_current(&sqlite_connection)? {
+                Some(recording) => recording,
+                None => return Ok(()),
+            };
+            tokio::spawn(async move {
+                if let Err(e) = stop_after_grace(grace, recording.id, interrupted_job).await {
+                    error!("Recording policy failed to stop recording error={}", e);
+                }
+            });
+        }
+        RecordingAction::None => (),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_on_job_status() {
+        let settings = VideoStreamSettings::default();
+        let grace = Duration::from_secs(settings.recording_policy.stop_grace_secs as u64);
+        assert_eq!(
+            on_job_status(&settings, &PrintJobStatusKind::Started, false),
+            RecordingAction::Start
+        );
+        assert_eq!(
+            on_job_status(&settings, &PrintJobStatusKind::Started, true),
+            RecordingAction::None
+        );
+        assert_eq!(
+            on_job_status(&settings, &PrintJobStatusKind::Finished, true),
+            RecordingAction::Stop { grace }
+        );
+        assert_eq!(
+            on_job_status(&settings, &PrintJobStatusKind::Finished, false),
+            RecordingAction::None
+        );
+        assert_eq!(
+            on_job_status(&settings, &PrintJobStatusKind::Updated, true),
+            RecordingAction::None
+        );
+
+        let mut manual = settings.clone();
+        manual.recording.auto_start = false;
+        manual.recording_policy.auto_stop = false;
+        assert_eq!(
+            on_job_status(&manual, &PrintJobStatusKind::Started, false),
+            RecordingAction::None
+        );
+        assert_eq!(
+            on_job_status(&manual, &PrintJobStatusKind::Finished, true),
+            RecordingAction::None
+        );
+    }
+
+    #[test]
+    fn test_on_server_shutdown() {
+        let settings = VideoStreamSettings::default();
+        let grace = Duration::from_secs(settings.recording_policy.shutdown_grace_secs as u64);
+        assert_eq!(
+            on_server_shutdown(&settings, true, true),
+            RecordingAction::Stop { grace }
+        );
+        assert_eq!(
+            on_server_shutdown(&settings, false, true),
+            RecordingAction::None
+        );
+        assert_eq!(
+            on_server_shutdown(&settings, true, false),
+            RecordingAction::None
+        );
+    }
+}
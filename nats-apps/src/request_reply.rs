@@ -1,5 +1,6 @@
 use std::fmt::Debug;
-use std::time::SystemTime;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
 
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
@@ -7,13 +8,14 @@ use bytes::Bytes;
 use log::{error, info, warn};
 use printnanny_services::video_recording_sync::sync_all_video_recordings;
 use printnanny_settings::cam::CameraVideoSource;
+use printnanny_settings::error::PrintNannySettingsError;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 
 use printnanny_dbus::printnanny_os_models;
 use printnanny_dbus::printnanny_os_models::{
-    CameraRecordingLoadReply, CameraRecordingStarted, CameraRecordingStopped, CameraStatus,
-    CamerasLoadReply, CrashReportOsLogsReply, CrashReportOsLogsRequest, DeviceInfoLoadReply,
+    CameraRecordingStarted, CameraRecordingStopped, CameraStatus, CamerasLoadReply,
+    CrashReportOsLogsReply, CrashReportOsLogsRequest, DeviceInfoLoadReply,
     PrintNannyCloudAuthReply, PrintNannyCloudAuthRequest, PrintNannyCloudSyncReply, SettingsApp,
     SettingsFile, SettingsFileApplyReply, SettingsFileApplyRequest, SettingsFileLoadReply,
     SettingsFileRevertReply, SettingsFileRevertRequest, SystemdManagerDisableUnitsReply,
@@ -27,32 +29,166 @@ use printnanny_dbus::printnanny_os_models::{
 use printnanny_dbus::zbus;
 use printnanny_dbus::zbus_systemd;
 
+use printnanny_edge_db::print_job::{PrintJob, PrintJobAlert};
+
 use printnanny_settings::git2;
+use printnanny_settings::model::ModelManifest;
 use printnanny_settings::printnanny::PrintNannySettings;
 use printnanny_settings::vcs::VersionControlledSettings;
 
 use printnanny_services::printnanny_api::ApiService;
+use printnanny_services::retention::{self, RetentionStatus};
+use printnanny_services::video_recording_sync::{self, UploadQueueStatus};
 
+use printnanny_gst_pipelines::export::RecordingExport;
 use printnanny_gst_pipelines::factory::{
     GstPipelineState, PrintNannyPipelineFactory, H264_RECORDING_PIPELINE,
 };
+use printnanny_gst_pipelines::preroll::PrerollBuffer;
 
 use printnanny_nats_client::request_reply::NatsRequestHandler;
 
+// Start recording camera_id (PrintNannySettings.video_stream if None) into a new VideoRecording
+// only one recording is current at a time, so the recording pipelines of other cameras are stopped
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CameraRecordingStartRequest {
+    #[serde(default)]
+    pub camera_id: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraRecordingStartReply {
+    pub camera_id: String,
+    #[serde(flatten)]
+    pub started: CameraRecordingStarted,
+}
+
+// Stop recording camera_id, or whichever camera is recording if None
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CameraRecordingStopRequest {
+    #[serde(default)]
+    pub camera_id: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraRecordingStopReply {
+    pub camera_id: String,
+    #[serde(flatten)]
+    pub stopped: CameraRecordingStopped,
+}
+
+// Commit the pre-roll buffer of camera_id (PrintNannySettings.video_stream if None) into a new VideoRecording
+// preroll_secs and postroll_secs default to PrerollSettings values
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CameraRecordingCommitRequest {
+    #[serde(default)]
+    pub camera_id: Option<String>,
+    #[serde(default)]
+    pub preroll_secs: Option<i32>,
+    #[serde(default)]
+    pub postroll_secs: Option<i32>,
+    // logged with the committed recording, e.g. "spaghetti__mean=0.82"
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+// Load a VideoRecording with its parts, print jobs and alerts
+// an empty request loads the current recording
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CameraRecordingLoadRequest {
+    #[serde(default)]
+    pub video_recording_id: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PrintJobMetadata {
+    #[serde(flatten)]
+    pub job: PrintJob,
+    pub alerts: Vec<PrintJobAlert>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraRecordingLoadReply {
+    #[serde(flatten)]
+    pub recording: printnanny_os_models::CameraRecordingLoadReply,
+    // print jobs captured by recording, ordered by job_start
+    pub print_jobs: Vec<PrintJobMetadata>,
+}
+
+// Concatenate local parts of a VideoRecording into one playable file, see printnanny_gst_pipelines::export
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CameraRecordingExportRequest {
+    pub video_recording_id: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CameraRecordingExportReply {
+    pub video_recording_id: String,
+    pub file_name: String,
+    pub size: u64,
+    pub parts: usize,
+    // parts which were already uploaded or removed by the retention manager
+    pub missing: usize,
+    // served by printnanny-snapshot
+    pub download_path: String,
+}
+
+// List the detection models in the registry of camera_id (PrintNannySettings.video_stream if None)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetectionModelLoadRequest {
+    #[serde(default)]
+    pub camera_id: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DetectionModelLoadReply {
+    pub camera_id: String,
+    pub registry: String,
+    pub active: ModelManifest,
+    // valid manifests in the registry, ordered by name
+    pub models: Vec<ModelManifest>,
+}
+
+// Switch the detection model of camera_id (PrintNannySettings.video_stream if None) without restarting the camera
+// name is a ModelRegistry model, or None to use the model described by DetectionSettings
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetectionModelActivateRequest {
+    #[serde(default)]
+    pub camera_id: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DetectionModelActivateReply {
+    pub camera_id: String,
+    pub model: ModelManifest,
+    // re-created inference, bounding box and dataframe pipelines
+    pub restarted: Vec<String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "subject_pattern")]
 pub enum NatsRequest {
     // pi.{pi_id}.command.camera.recording.load
     #[serde(rename = "pi.{pi_id}.command.camera.recording.load")]
-    CameraRecordingLoadRequest,
+    CameraRecordingLoadRequest(CameraRecordingLoadRequest),
 
     // pi.{pi_id}.command.camera.recording.start
     #[serde(rename = "pi.{pi_id}.command.camera.recording.start")]
-    CameraRecordingStartRequest,
+    CameraRecordingStartRequest(CameraRecordingStartRequest),
 
     // pi.{pi_id}.command.camera.recording.stop
     #[serde(rename = "pi.{pi_id}.command.camera.recording.stop")]
-    CameraRecordingStopRequest,
+    CameraRecordingStopRequest(CameraRecordingStopRequest),
+
+    // pi.{pi_id}.command.camera.recording.commit
+    #[serde(rename = "pi.{pi_id}.command.camera.recording.commit")]
+    CameraRecordingCommitRequest(CameraRecordingCommitRequest),
+
+    // pi.{pi_id}.command.camera.recording.export
+    #[serde(rename = "pi.{pi_id}.command.camera.recording.export")]
+    CameraRecordingExportRequest(CameraRecordingExportRequest),
 
     // pi.{pi_id}.cameras.load
     #[serde(rename = "pi.{pi_id}.cameras.load")]
@@ -61,6 +197,16 @@ pub enum NatsRequest {
     #[serde(rename = "pi.{pi_id}.command.cloud.sync")]
     PrintNannyCloudSyncRequest,
 
+    // pi.{pi_id}.command.retention.*
+    #[serde(rename = "pi.{pi_id}.command.retention.status")]
+    RetentionStatusRequest,
+    #[serde(rename = "pi.{pi_id}.command.retention.enforce")]
+    RetentionEnforceRequest,
+
+    // pi.{pi_id}.command.upload_queue.status
+    #[serde(rename = "pi.{pi_id}.command.upload_queue.status")]
+    UploadQueueStatusRequest,
+
     // pi.{pi_id}.crash_reports.os
     #[serde(rename = "pi.{pi_id}.crash_reports.os")]
     CrashReportOsLogsRequest(CrashReportOsLogsRequest),
@@ -85,6 +231,20 @@ pub enum NatsRequest {
     CameraSettingsFileLoadRequest,
     #[serde(rename = "pi.{pi_id}.settings.camera.status")]
     CameraStatusRequest,
+    // pi.{pi_id}.settings.camera.{camera_id}.*
+    #[serde(rename = "pi.{pi_id}.settings.camera.{camera_id}.apply")]
+    CameraIdSettingsApplyRequest {
+        camera_id: String,
+        settings: VideoStreamSettings,
+    },
+    #[serde(rename = "pi.{pi_id}.settings.camera.{camera_id}.load")]
+    CameraIdSettingsLoadRequest { camera_id: String },
+
+    // pi.{pi_id}.settings.model.*
+    #[serde(rename = "pi.{pi_id}.settings.model.load")]
+    DetectionModelLoadRequest(DetectionModelLoadRequest),
+    #[serde(rename = "pi.{pi_id}.settings.model.activate")]
+    DetectionModelActivateRequest(DetectionModelActivateRequest),
 
     // pi.{pi_id}.dbus.org.freedesktop.systemd1.*
     #[serde(rename = "pi.{pi_id}.dbus.org.freedesktop.systemd1.Manager.DisableUnit")]
@@ -115,11 +275,19 @@ pub enum NatsReply {
 
     // pi.{pi_id}.command.camera.recording.start
     #[serde(rename = "pi.{pi_id}.command.camera.recording.start")]
-    CameraRecordingStartReply(CameraRecordingStarted),
+    CameraRecordingStartReply(CameraRecordingStartReply),
 
     // pi.{pi_id}.command.camera.recording.stop
     #[serde(rename = "pi.{pi_id}.command.camera.recording.stop")]
-    CameraRecordingStopReply(CameraRecordingStopped),
+    CameraRecordingStopReply(CameraRecordingStopReply),
+
+    // pi.{pi_id}.command.camera.recording.commit
+    #[serde(rename = "pi.{pi_id}.command.camera.recording.commit")]
+    CameraRecordingCommitReply(CameraRecordingStarted),
+
+    // pi.{pi_id}.command.camera.recording.export
+    #[serde(rename = "pi.{pi_id}.command.camera.recording.export")]
+    CameraRecordingExportReply(CameraRecordingExportReply),
 
     // pi.{pi_id}.cameras.load
     #[serde(rename = "pi.{pi_id}.cameras.load")]
@@ -128,6 +296,16 @@ pub enum NatsReply {
     #[serde(rename = "pi.{pi_id}.command.cloud.sync")]
     PrintNannyCloudSyncReply(PrintNannyCloudSyncReply),
 
+    // pi.{pi_id}.command.retention.*
+    #[serde(rename = "pi.{pi_id}.command.retention.status")]
+    RetentionStatusReply(RetentionStatus),
+    #[serde(rename = "pi.{pi_id}.command.retention.enforce")]
+    RetentionEnforceReply(RetentionStatus),
+
+    // pi.{pi_id}.command.upload_queue.status
+    #[serde(rename = "pi.{pi_id}.command.upload_queue.status")]
+    UploadQueueStatusReply(UploadQueueStatus),
+
     // pi.{pi_id}.crash_reports.os
     #[serde(rename = "pi.{pi_id}.crash_reports.os")]
     CrashReportOsLogsReply(CrashReportOsLogsReply),
@@ -152,6 +330,23 @@ pub enum NatsReply {
     CameraSettingsFileLoadReply(VideoStreamSettings),
     #[serde(rename = "pi.{pi_id}.settings.camera.status")]
     CameraStatusReply(CameraStatus),
+    // pi.{pi_id}.settings.camera.{camera_id}.*
+    #[serde(rename = "pi.{pi_id}.settings.camera.{camera_id}.apply")]
+    CameraIdSettingsApplyReply {
+        camera_id: String,
+        settings: VideoStreamSettings,
+    },
+    #[serde(rename = "pi.{pi_id}.settings.camera.{camera_id}.load")]
+    CameraIdSettingsLoadReply {
+        camera_id: String,
+        settings: VideoStreamSettings,
+    },
+
+    // pi.{pi_id}.settings.model.*
+    #[serde(rename = "pi.{pi_id}.settings.model.load")]
+    DetectionModelLoadReply(DetectionModelLoadReply),
+    #[serde(rename = "pi.{pi_id}.settings.model.activate")]
+    DetectionModelActivateReply(DetectionModelActivateReply),
 
     // pi.{pi_id}.dbus.org.freedesktop.systemd1.*
     #[serde(rename = "pi.{pi_id}.dbus.org.freedesktop.systemd1.Manager.DisableUnit")]
@@ -173,47 +368,123 @@ pub enum NatsReply {
     SystemdManagerStopUnitReply(SystemdManagerStopUnitReply),
 }
 
+// Extract (camera_id, action) from subject pattern pi.{pi_id}.settings.camera.{camera_id}.{action}
+// camera_id may not contain ".", so pi.{pi_id}.settings.camera.{load,apply,status} are not matched
+fn parse_camera_id_subject(subject_pattern: &str) -> Option<(&str, &str)> {
+    let suffix = subject_pattern.strip_prefix("pi.{pi_id}.settings.camera.")?;
+    match suffix.split_once('.') {
+        Some((camera_id, action)) if !camera_id.is_empty() && !action.contains('.') => {
+            Some((camera_id, action))
+        }
+        _ => None,
+    }
+}
+
 impl NatsRequest {
-    pub async fn handle_camera_recording_load() -> Result<NatsReply> {
+    pub async fn handle_camera_recording_load(
+        request: &CameraRecordingLoadRequest,
+    ) -> Result<NatsReply> {
         let settings = PrintNannySettings::new().await?;
         let sqlite_connection = settings.paths.db().display().to_string();
-        let current =
-            printnanny_edge_db::video_recording::VideoRecording::get_current(&sqlite_connection)?;
-        match current {
-            Some(current) => {
+        let recording = match &request.video_recording_id {
+            Some(video_recording_id) => Some(
+                printnanny_edge_db::video_recording::VideoRecording::get_by_id(
+                    &sqlite_connection,
+                    video_recording_id,
+                )?,
+            ),
+            None => printnanny_edge_db::video_recording::VideoRecording::get_current(
+                &sqlite_connection,
+            )?,
+        };
+        match recording {
+            Some(recording) => {
                 // get parts for recording
-                let parts =  printnanny_edge_db::video_recording::VideoRecordingPart::get_parts_by_video_recording_id(&sqlite_connection, &current.id)?.into_iter().map(|v| v.into()).collect();
+                let parts =  printnanny_edge_db::video_recording::VideoRecordingPart::get_parts_by_video_recording_id(&sqlite_connection, &recording.id)?.into_iter().map(|v| v.into()).collect();
+                let print_jobs =
+                    PrintJob::get_by_video_recording_id(&sqlite_connection, &recording.id)?
+                        .into_iter()
+                        .map(|job| {
+                            let alerts =
+                                PrintJobAlert::get_by_print_job_id(&sqlite_connection, &job.id)?;
+                            Ok(PrintJobMetadata { job, alerts })
+                        })
+                        .collect::<Result<Vec<PrintJobMetadata>>>()?;
                 Ok(NatsReply::CameraRecordingLoadReply(
                     CameraRecordingLoadReply {
-                        recording: Some(Box::new(current.into())),
-                        parts: Some(parts),
+                        recording: printnanny_os_models::CameraRecordingLoadReply {
+                            recording: Some(Box::new(recording.into())),
+                            parts: Some(parts),
+                        },
+                        print_jobs,
                     },
                 ))
             }
             None => Ok(NatsReply::CameraRecordingLoadReply(
                 CameraRecordingLoadReply {
-                    recording: None,
-                    parts: None,
+                    recording: printnanny_os_models::CameraRecordingLoadReply {
+                        recording: None,
+                        parts: None,
+                    },
+                    print_jobs: vec![],
                 },
             )),
         }
     }
 
-    pub async fn handle_camera_recording_start() -> Result<NatsReply> {
+    // settings of camera_id, PrintNannySettings.video_stream if None
+    fn get_video_stream(
+        settings: &PrintNannySettings,
+        camera_id: &Option<String>,
+    ) -> Result<printnanny_settings::cam::VideoStreamSettings> {
+        match camera_id {
+            Some(camera_id) => match settings.get_video_stream(camera_id) {
+                Some(video_stream) => Ok(video_stream.clone()),
+                None => Err(PrintNannySettingsError::CameraNotFound {
+                    camera_id: camera_id.clone(),
+                }
+                .into()),
+            },
+            None => Ok(settings.video_stream.clone()),
+        }
+    }
+
+    pub async fn handle_camera_recording_start(
+        request: &CameraRecordingStartRequest,
+    ) -> Result<NatsReply> {
         let settings = PrintNannySettings::new().await?;
+        let video_stream = Self::get_video_stream(&settings, &request.camera_id)?;
         let sqlite_connection = settings.paths.db().display().to_string();
+
+        // finish_all ends the current recording, so stop every camera still writing parts into it
+        let factory = PrintNannyPipelineFactory::default();
+        let running = factory.recording_pipeline_names().await?;
+        for camera in settings.video_streams() {
+            if camera.camera_id != video_stream.camera_id
+                && running.contains(&camera.namespaced_pipeline_name(H264_RECORDING_PIPELINE))
+            {
+                factory.stop_video_recording_pipeline(&camera).await?;
+            }
+        }
         printnanny_edge_db::video_recording::VideoRecording::finish_all(&sqlite_connection)?;
 
         let api = ApiService::new(settings.cloud, sqlite_connection);
-        let recording = api.video_recordings_create(settings.paths.video()).await?;
+        let recording = api
+            .video_recordings_create(settings.paths.video(), &video_stream)
+            .await?;
         Ok(NatsReply::CameraRecordingStartReply(
-            CameraRecordingStarted {
-                recording: Box::new(recording.into()),
+            CameraRecordingStartReply {
+                camera_id: video_stream.camera_id,
+                started: CameraRecordingStarted {
+                    recording: Box::new(recording.into()),
+                },
             },
         ))
     }
 
-    pub async fn handle_camera_recording_stop() -> Result<NatsReply> {
+    pub async fn handle_camera_recording_stop(
+        request: &CameraRecordingStopRequest,
+    ) -> Result<NatsReply> {
         let settings = PrintNannySettings::new().await?;
         let sqlite_connection = settings.paths.db().display().to_string();
 
@@ -222,8 +493,23 @@ impl NatsRequest {
             printnanny_edge_db::video_recording::VideoRecording::get_current(&sqlite_connection)?;
         let factory = PrintNannyPipelineFactory::default();
 
+        // without camera_id, stop the camera whose recording pipeline exists, falling back to the default camera
+        let video_stream = match &request.camera_id {
+            Some(_) => Self::get_video_stream(&settings, &request.camera_id)?,
+            None => {
+                let running = factory.recording_pipeline_names().await.unwrap_or_default();
+                settings
+                    .video_streams()
+                    .into_iter()
+                    .find(|camera| {
+                        running.contains(&camera.namespaced_pipeline_name(H264_RECORDING_PIPELINE))
+                    })
+                    .unwrap_or_else(|| settings.video_stream.clone())
+            }
+        };
+
         // send EOS signal to gstreamer
-        factory.stop_video_recording_pipeline().await?;
+        factory.stop_video_recording_pipeline(&video_stream).await?;
 
         // sync all video recording parts
         sync_all_video_recordings().await?;
@@ -240,12 +526,172 @@ impl NatsRequest {
         };
 
         Ok(NatsReply::CameraRecordingStopReply(
-            CameraRecordingStopped {
-                recording: recording.map(|v| Box::new(v.into())),
+            CameraRecordingStopReply {
+                camera_id: video_stream.camera_id,
+                stopped: CameraRecordingStopped {
+                    recording: recording.map(|v| Box::new(v.into())),
+                },
             },
         ))
     }
 
+    // commits pre-roll fragments after the post-roll window has been written, replying with the VideoRecording it will create
+    // the VideoRecording row is only inserted once its fragments are copied, with recording_end already set,
+    // so VideoRecording::get_current never returns a committed recording and a restart during post-roll leaves no row behind
+    pub async fn handle_camera_recording_commit(
+        request: &CameraRecordingCommitRequest,
+    ) -> Result<NatsReply> {
+        let settings = PrintNannySettings::new().await?;
+        let video_stream = Self::get_video_stream(&settings, &request.camera_id)?;
+        if !video_stream.preroll.enabled {
+            return Err(anyhow!(
+                "Pre-roll buffer is disabled for camera_id={}",
+                video_stream.camera_id
+            ));
+        }
+
+        let preroll_secs = request
+            .preroll_secs
+            .unwrap_or(video_stream.preroll.preroll_secs)
+            .max(0) as u64;
+        let postroll_secs = request
+            .postroll_secs
+            .unwrap_or(video_stream.preroll.postroll_secs)
+            .max(0) as u64;
+        let now = SystemTime::now();
+        let start = now - Duration::from_secs(preroll_secs);
+        let end = now + Duration::from_secs(postroll_secs);
+
+        let sqlite_connection = settings.paths.db().display().to_string();
+        // committed fragments would be a second current recording, overlapping the active recording's parts
+        if let Some(current) =
+            printnanny_edge_db::video_recording::VideoRecording::get_current(&sqlite_connection)?
+        {
+            return Err(anyhow!(
+                "Recording id={} is in progress, refusing to commit pre-roll buffer camera_id={}",
+                current.id,
+                video_stream.camera_id
+            ));
+        }
+        let api = ApiService::new(settings.cloud.clone(), sqlite_connection);
+        let recording = printnanny_edge_db::video_recording::VideoRecording::new_finished(
+            settings.paths.video(),
+            start.into(),
+            end.into(),
+        );
+        info!(
+            "Committing pre-roll buffer camera_id={} recording_id={} preroll_secs={} postroll_secs={} reason={:?}",
+            video_stream.camera_id, recording.id, preroll_secs, postroll_secs, request.reason
+        );
+
+        let committed = recording.clone();
+        tokio::spawn(async move {
+            if let Err(e) =
+                Self::commit_preroll_recording(api, &video_stream, &committed, start, end).await
+            {
+                error!(
+                    "Failed to commit pre-roll buffer recording_id={} error={}",
+                    committed.id, e
+                );
+            }
+        });
+
+        Ok(NatsReply::CameraRecordingCommitReply(
+            CameraRecordingStarted {
+                recording: Box::new(recording.into()),
+            },
+        ))
+    }
+
+    async fn commit_preroll_recording(
+        api: ApiService,
+        video_stream: &printnanny_settings::cam::VideoStreamSettings,
+        recording: &printnanny_edge_db::video_recording::VideoRecording,
+        start: SystemTime,
+        end: SystemTime,
+    ) -> Result<()> {
+        // wait until the fragment containing end has been closed by splitmuxsink
+        let fragment_duration =
+            Duration::from_secs(video_stream.preroll.fragment_secs.max(1) as u64);
+        let wait = end.duration_since(SystemTime::now()).unwrap_or_default() + fragment_duration;
+        tokio::time::sleep(wait).await;
+
+        let sqlite_connection = api.sqlite_connection.clone();
+        let buffer = PrerollBuffer::new(&video_stream.preroll.path);
+        let dir = Path::new(&recording.dir);
+        let result = match buffer.commit(dir, start, end, fragment_duration) {
+            Ok(fragments) => api
+                .video_recordings_create_finished(recording)
+                .await
+                .map(|_| fragments)
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        let fragments = match result {
+            Ok(fragments) => fragments,
+            Err(e) => {
+                // without a VideoRecording row referencing dir, copied fragments would never be uploaded or cleaned up
+                let inserted = printnanny_edge_db::video_recording::VideoRecording::get_by_id(
+                    &sqlite_connection,
+                    &recording.id,
+                )
+                .is_ok();
+                if inserted {
+                    return Err(e);
+                }
+                if let Err(remove_error) = fs::remove_dir_all(dir).await {
+                    warn!(
+                        "Failed to remove pre-roll recording dir={} error={}",
+                        dir.display(),
+                        remove_error
+                    );
+                }
+                return Err(e);
+            }
+        };
+        let first_modified = fragments.first().map(|f| f.modified);
+        for fragment in fragments.iter() {
+            let file_name = fragment.path.display().to_string();
+            let row_id =
+                printnanny_edge_db::video_recording::VideoRecordingPart::row_id_from_filename(
+                    &file_name,
+                )
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Failed to parse VideoRecordingPart id from file={}",
+                        file_name
+                    )
+                })?;
+            let running_time = first_modified
+                .and_then(|first| fragment.modified.duration_since(first).ok())
+                .unwrap_or_default()
+                .as_nanos() as i64;
+            let row = printnanny_edge_db::video_recording::NewVideoRecordingPart {
+                id: &row_id,
+                buffer_index: &fragment.index,
+                buffer_runningtime: &running_time,
+                deleted: &false,
+                file_name: &file_name,
+                video_recording_id: &recording.id,
+                size: &(fragment.size as i64),
+                closed: &true,
+            };
+            printnanny_edge_db::video_recording::VideoRecordingPart::insert(
+                &sqlite_connection,
+                row,
+            )?;
+        }
+
+        sync_all_video_recordings().await?;
+        api.video_recording_finalize(&recording.id).await?;
+        info!(
+            "Committed pre-roll buffer recording_id={} parts={}",
+            recording.id,
+            fragments.len()
+        );
+        Ok(())
+    }
+
     pub async fn handle_cloud_sync() -> Result<NatsReply> {
         let start = chrono::offset::Utc::now().to_rfc3339();
 
@@ -256,7 +702,7 @@ impl NatsRequest {
         // set optional pipelines to correct state
         let gst_pipelines = PrintNannyPipelineFactory::default();
         gst_pipelines
-            .sync_optional_pipelines(settings.video_stream)
+            .sync_optional_pipelines(&settings.video_streams())
             .await?;
         let end = chrono::offset::Utc::now().to_rfc3339();
 
@@ -265,6 +711,56 @@ impl NatsRequest {
         ))
     }
 
+    pub async fn handle_camera_recording_export(
+        request: &CameraRecordingExportRequest,
+    ) -> Result<NatsReply> {
+        let settings = PrintNannySettings::new().await?;
+        let sqlite_connection = settings.paths.db().display().to_string();
+        let export = RecordingExport::new(
+            &sqlite_connection,
+            &request.video_recording_id,
+            &settings.paths.video_exports(),
+        )?;
+        let factory = PrintNannyPipelineFactory::default();
+        let output = factory.export_video_recording(&export).await?;
+        let size = fs::metadata(&output).await?.len();
+        Ok(NatsReply::CameraRecordingExportReply(
+            CameraRecordingExportReply {
+                video_recording_id: export.video_recording_id.clone(),
+                file_name: output.display().to_string(),
+                size,
+                parts: export.parts.len(),
+                missing: export.missing,
+                download_path: format!(
+                    "/printnanny-snapshot/recordings/{}",
+                    export.video_recording_id
+                ),
+            },
+        ))
+    }
+
+    // report disk usage of recordings, snapshots and HLS segments, optionally enforcing RetentionSettings
+    pub async fn handle_retention(enforce: bool) -> Result<NatsReply> {
+        let settings = PrintNannySettings::new().await?;
+        let result = tokio::task::spawn_blocking(move || match enforce {
+            true => retention::enforce(&settings),
+            false => retention::status(&settings),
+        })
+        .await??;
+        match enforce {
+            true => Ok(NatsReply::RetentionEnforceReply(result)),
+            false => Ok(NatsReply::RetentionStatusReply(result)),
+        }
+    }
+
+    // report pending uploads and parts the upload queue gave up on after UploadQueueSettings.max_attempts
+    pub async fn handle_upload_queue_status() -> Result<NatsReply> {
+        let settings = PrintNannySettings::new().await?;
+        let result =
+            tokio::task::spawn_blocking(move || video_recording_sync::status(&settings)).await??;
+        Ok(NatsReply::UploadQueueStatusReply(result))
+    }
+
     // message messages sent to: "pi.{pi_id}.device_info.load"
     pub async fn handle_device_info_load() -> Result<NatsReply> {
         let settings = PrintNannySettings::new().await?;
@@ -373,10 +869,17 @@ impl NatsRequest {
         };
         let factory = PrintNannyPipelineFactory::default();
 
-        let recording = matches!(
-            factory.pipeline_state(H264_RECORDING_PIPELINE).await,
-            GstPipelineState::Playing
-        );
+        // recording if any camera's recording pipeline is playing
+        let mut recording = false;
+        for pipeline_name in factory.recording_pipeline_names().await.unwrap_or_default() {
+            if matches!(
+                factory.pipeline_state(&pipeline_name).await,
+                GstPipelineState::Playing
+            ) {
+                recording = true;
+                break;
+            }
+        }
 
         info!(
             "CameraStatus streaming={} recording={:#?}",
@@ -590,40 +1093,157 @@ impl NatsRequest {
         }
     }
 
-    pub async fn handle_camera_settings_load() -> Result<NatsReply> {
+    // load settings of camera_id, or PrintNannySettings.video_stream if camera_id is None
+    async fn load_camera_settings(
+        camera_id: Option<&str>,
+    ) -> Result<printnanny_settings::cam::VideoStreamSettings> {
         // "hotplug" prefers live connected devices or default/disconnected devices
         let mut settings = PrintNannySettings::new().await?;
-        let old_video_stream_settings = settings.video_stream.clone();
-        settings.video_stream = settings.video_stream.hotplug().await?;
-        if settings.video_stream != old_video_stream_settings {
-            warn!("handle_cameras_load detected a hotplug change in camera settings. Saving detected configuration");
-            let content = settings.to_toml_string()?;
-            let ts = SystemTime::now();
-            let commit_msg = format!("[HOTPLUG] Updated PrintNannySettings.camera @ {ts:?}");
-            settings.save_and_commit(&content, Some(commit_msg)).await?;
-            settings = PrintNannySettings::new().await?;
+        let camera_id = camera_id
+            .unwrap_or(&settings.video_stream.camera_id)
+            .to_string();
+        if camera_id == settings.video_stream.camera_id {
+            let old_video_stream_settings = settings.video_stream.clone();
+            settings.video_stream = settings.video_stream.hotplug().await?;
+            if settings.video_stream != old_video_stream_settings {
+                warn!("handle_cameras_load detected a hotplug change in camera settings. Saving detected configuration");
+                let content = settings.to_toml_string()?;
+                let ts = SystemTime::now();
+                let commit_msg = format!("[HOTPLUG] Updated PrintNannySettings.camera @ {ts:?}");
+                settings.save_and_commit(&content, Some(commit_msg)).await?;
+                settings = PrintNannySettings::new().await?;
+            }
         }
-        Ok(NatsReply::CameraSettingsFileLoadReply(
-            settings.video_stream.into(),
-        ))
+        match settings.get_video_stream(&camera_id) {
+            Some(video_stream) => Ok(video_stream.clone()),
+            None => Err(PrintNannySettingsError::CameraNotFound { camera_id }.into()),
+        }
+    }
+
+    // apply request to camera_id, or PrintNannySettings.video_stream if camera_id is None
+    async fn apply_camera_settings(
+        camera_id: Option<&str>,
+        request: &VideoStreamSettings,
+    ) -> Result<printnanny_settings::cam::VideoStreamSettings> {
+        info!("Received request: {:#?}", request);
+        let mut settings = PrintNannySettings::new().await?;
+        let camera_id = camera_id
+            .unwrap_or(&settings.video_stream.camera_id)
+            .to_string();
+        let video_stream = match settings.get_video_stream(&camera_id) {
+            // pipeline graph, elements and media source aren't part of the os_models::VideoStreamSettings request, keep configured values
+            Some(current) => current.merge_os_models(request.clone()),
+            None => return Err(PrintNannySettingsError::CameraNotFound { camera_id }.into()),
+        };
+        video_stream.validate()?;
+        settings.set_video_stream(video_stream.clone())?;
+        let content = settings.to_toml_string()?;
+        let ts = SystemTime::now();
+        let commit_msg =
+            format!("Updated PrintNannySettings.camera camera_id={camera_id} @ {ts:?}");
+        settings.save_and_commit(&content, Some(commit_msg)).await?;
+        // re-create changed pipelines of camera_id, without interrupting other cameras or an active recording
+        let factory: PrintNannyPipelineFactory = PrintNannyPipelineFactory::default();
+        factory
+            .sync_camera_pipelines(&settings.video_streams(), &camera_id)
+            .await?;
+        Ok(video_stream)
+    }
+
+    pub async fn handle_camera_settings_load() -> Result<NatsReply> {
+        let video_stream = Self::load_camera_settings(None).await?;
+        Ok(NatsReply::CameraSettingsFileLoadReply(video_stream.into()))
     }
 
     pub async fn handle_camera_settings_apply(request: &VideoStreamSettings) -> Result<NatsReply> {
+        let video_stream = Self::apply_camera_settings(None, request).await?;
+        Ok(NatsReply::CameraSettingsFileApplyReply(video_stream.into()))
+    }
+
+    // message messages sent to: "pi.{pi_id}.settings.camera.{camera_id}.load"
+    pub async fn handle_camera_id_settings_load(camera_id: &str) -> Result<NatsReply> {
+        let video_stream = Self::load_camera_settings(Some(camera_id)).await?;
+        Ok(NatsReply::CameraIdSettingsLoadReply {
+            camera_id: camera_id.to_string(),
+            settings: video_stream.into(),
+        })
+    }
+
+    // message messages sent to: "pi.{pi_id}.settings.camera.{camera_id}.apply"
+    pub async fn handle_camera_id_settings_apply(
+        camera_id: &str,
+        request: &VideoStreamSettings,
+    ) -> Result<NatsReply> {
+        let video_stream = Self::apply_camera_settings(Some(camera_id), request).await?;
+        Ok(NatsReply::CameraIdSettingsApplyReply {
+            camera_id: camera_id.to_string(),
+            settings: video_stream.into(),
+        })
+    }
+
+    // message messages sent to: "pi.{pi_id}.settings.model.load"
+    pub async fn handle_model_load(request: &DetectionModelLoadRequest) -> Result<NatsReply> {
+        let video_stream = Self::load_camera_settings(request.camera_id.as_deref()).await?;
+        let camera_id = video_stream.camera_id.clone();
+        let registry = video_stream.model.registry.clone();
+        // manifests are read with blocking I/O
+        let (active, models) = tokio::task::spawn_blocking(move || {
+            let active = video_stream.detection_model()?;
+            let models = video_stream.model.registry().list()?;
+            Ok::<_, PrintNannySettingsError>((active, models))
+        })
+        .await??;
+        Ok(NatsReply::DetectionModelLoadReply(
+            DetectionModelLoadReply {
+                camera_id,
+                registry,
+                active,
+                models,
+            },
+        ))
+    }
+
+    // message messages sent to: "pi.{pi_id}.settings.model.activate"
+    pub async fn handle_model_activate(
+        request: &DetectionModelActivateRequest,
+    ) -> Result<NatsReply> {
         info!("Received request: {:#?}", request);
         let mut settings = PrintNannySettings::new().await?;
+        let camera_id = request
+            .camera_id
+            .as_deref()
+            .unwrap_or(&settings.video_stream.camera_id)
+            .to_string();
+        let mut video_stream = match settings.get_video_stream(&camera_id) {
+            Some(current) => current.clone(),
+            None => return Err(PrintNannySettingsError::CameraNotFound { camera_id }.into()),
+        };
+        video_stream.model.active = request.name.clone();
+
+        // validate manifest before saving, so an invalid model never replaces the running model
+        let candidate = video_stream.clone();
+        let model = tokio::task::spawn_blocking(move || candidate.detection_model()).await??;
 
-        settings.video_stream = request.clone().into();
+        settings.set_video_stream(video_stream)?;
         let content = settings.to_toml_string()?;
         let ts = SystemTime::now();
-        let commit_msg = format!("Updated PrintNannySettings.camera @ {ts:?}");
+        let commit_msg = format!(
+            "Updated PrintNannySettings.camera camera_id={camera_id} model={} @ {ts:?}",
+            model.name
+        );
         settings.save_and_commit(&content, Some(commit_msg)).await?;
-        // stop gstreamer pipelines
+
+        // re-create model pipelines, camera and encoder pipelines keep running
         let factory: PrintNannyPipelineFactory = PrintNannyPipelineFactory::default();
-        factory.stop_pipelines().await?;
-        factory.start_pipelines().await?;
-        // start gstreamer pipelines
-        Ok(NatsReply::CameraSettingsFileApplyReply(
-            settings.video_stream.into(),
+        let restarted = factory
+            .restart_model_pipelines(&settings.video_streams(), &camera_id)
+            .await?;
+        Ok(NatsReply::DetectionModelActivateReply(
+            DetectionModelActivateReply {
+                camera_id,
+                model,
+                restarted,
+            },
         ))
     }
 
@@ -851,17 +1471,70 @@ impl NatsRequestHandler for NatsRequest {
     type Reply = NatsReply;
 
     fn deserialize_payload(subject_pattern: &str, payload: &Bytes) -> Result<Self::Request> {
+        if let Some((camera_id, action)) = parse_camera_id_subject(subject_pattern) {
+            let camera_id = camera_id.to_string();
+            return match action {
+                "apply" => Ok(NatsRequest::CameraIdSettingsApplyRequest {
+                    camera_id,
+                    settings: serde_json::from_slice::<VideoStreamSettings>(payload.as_ref())?,
+                }),
+                "load" => Ok(NatsRequest::CameraIdSettingsLoadRequest { camera_id }),
+                _ => Err(anyhow!(
+                    "NATS message handler not implemented for subject pattern {}",
+                    subject_pattern
+                )),
+            };
+        }
         match subject_pattern {
+            // payload is optional, an empty request starts recording the default camera
             "pi.{pi_id}.command.camera.recording.start" => {
-                Ok(NatsRequest::CameraRecordingStartRequest)
+                let request = match payload.is_empty() {
+                    true => CameraRecordingStartRequest::default(),
+                    false => {
+                        serde_json::from_slice::<CameraRecordingStartRequest>(payload.as_ref())?
+                    }
+                };
+                Ok(NatsRequest::CameraRecordingStartRequest(request))
             }
+            // payload is optional, an empty request stops whichever camera is recording
             "pi.{pi_id}.command.camera.recording.stop" => {
-                Ok(NatsRequest::CameraRecordingStopRequest)
+                let request = match payload.is_empty() {
+                    true => CameraRecordingStopRequest::default(),
+                    false => {
+                        serde_json::from_slice::<CameraRecordingStopRequest>(payload.as_ref())?
+                    }
+                };
+                Ok(NatsRequest::CameraRecordingStopRequest(request))
             }
+            // payload is optional, an empty request loads the current recording
             "pi.{pi_id}.command.camera.recording.load" => {
-                Ok(NatsRequest::CameraRecordingLoadRequest)
+                let request = match payload.is_empty() {
+                    true => CameraRecordingLoadRequest::default(),
+                    false => {
+                        serde_json::from_slice::<CameraRecordingLoadRequest>(payload.as_ref())?
+                    }
+                };
+                Ok(NatsRequest::CameraRecordingLoadRequest(request))
+            }
+            // payload is optional, an empty request commits the default camera's pre-roll buffer
+            "pi.{pi_id}.command.camera.recording.commit" => {
+                let request = match payload.is_empty() {
+                    true => CameraRecordingCommitRequest::default(),
+                    false => {
+                        serde_json::from_slice::<CameraRecordingCommitRequest>(payload.as_ref())?
+                    }
+                };
+                Ok(NatsRequest::CameraRecordingCommitRequest(request))
+            }
+            "pi.{pi_id}.command.camera.recording.export" => {
+                Ok(NatsRequest::CameraRecordingExportRequest(
+                    serde_json::from_slice::<CameraRecordingExportRequest>(payload.as_ref())?,
+                ))
             }
             "pi.{pi_id}.command.cloud.sync" => Ok(NatsRequest::PrintNannyCloudSyncRequest),
+            "pi.{pi_id}.command.retention.status" => Ok(NatsRequest::RetentionStatusRequest),
+            "pi.{pi_id}.command.retention.enforce" => Ok(NatsRequest::RetentionEnforceRequest),
+            "pi.{pi_id}.command.upload_queue.status" => Ok(NatsRequest::UploadQueueStatusRequest),
             "pi.{pi_id}.crash_reports.os" => Ok(NatsRequest::CrashReportOsLogsRequest(
                 serde_json::from_slice::<CrashReportOsLogsRequest>(payload.as_ref())?,
             )),
@@ -884,6 +1557,17 @@ impl NatsRequestHandler for NatsRequest {
             )),
             "pi.{pi_id}.settings.camera.load" => Ok(NatsRequest::CameraSettingsFileLoadRequest),
             "pi.{pi_id}.settings.camera.status" => Ok(NatsRequest::CameraStatusRequest),
+            // payload is optional, an empty request loads models of the default camera
+            "pi.{pi_id}.settings.model.load" => {
+                let request = match payload.is_empty() {
+                    true => DetectionModelLoadRequest::default(),
+                    false => serde_json::from_slice::<DetectionModelLoadRequest>(payload.as_ref())?,
+                };
+                Ok(NatsRequest::DetectionModelLoadRequest(request))
+            }
+            "pi.{pi_id}.settings.model.activate" => Ok(NatsRequest::DetectionModelActivateRequest(
+                serde_json::from_slice::<DetectionModelActivateRequest>(payload.as_ref())?,
+            )),
 
             "pi.{pi_id}.dbus.org.freedesktop.systemd1.Manager.DisableUnit" => {
                 Ok(NatsRequest::SystemdManagerDisableUnitsRequest(
@@ -931,13 +1615,32 @@ impl NatsRequestHandler for NatsRequest {
     async fn handle(&self) -> Result<Self::Reply> {
         match self {
             // pi.{pi_id}.command.camera.recording.start
-            NatsRequest::CameraRecordingStartRequest => Self::handle_camera_recording_start().await,
+            NatsRequest::CameraRecordingStartRequest(request) => {
+                Self::handle_camera_recording_start(request).await
+            }
             // pi.{pi_id}.command.camera.recording.stop
-            NatsRequest::CameraRecordingStopRequest => Self::handle_camera_recording_stop().await,
+            NatsRequest::CameraRecordingStopRequest(request) => {
+                Self::handle_camera_recording_stop(request).await
+            }
             // pi.{pi_id}.command.camera.recording.load
-            NatsRequest::CameraRecordingLoadRequest => Self::handle_camera_recording_load().await,
+            NatsRequest::CameraRecordingLoadRequest(request) => {
+                Self::handle_camera_recording_load(request).await
+            }
+            // pi.{pi_id}.command.camera.recording.commit
+            NatsRequest::CameraRecordingCommitRequest(request) => {
+                Self::handle_camera_recording_commit(request).await
+            }
+            // pi.{pi_id}.command.camera.recording.export
+            NatsRequest::CameraRecordingExportRequest(request) => {
+                Self::handle_camera_recording_export(request).await
+            }
             // pi.{pi_id}.command.cloud.sync
             NatsRequest::PrintNannyCloudSyncRequest => Self::handle_cloud_sync().await,
+            // pi.{pi_id}.command.retention.*
+            NatsRequest::RetentionStatusRequest => Self::handle_retention(false).await,
+            NatsRequest::RetentionEnforceRequest => Self::handle_retention(true).await,
+            // pi.{pi_id}.command.upload_queue.status
+            NatsRequest::UploadQueueStatusRequest => Self::handle_upload_queue_status().await,
             // pi.{pi_id}.cameras.load
             NatsRequest::CameraLoadRequest => Self::handle_cameras_load().await,
             // pi.{pi_id}.settings.camera.status
@@ -966,6 +1669,21 @@ impl NatsRequestHandler for NatsRequest {
             NatsRequest::CameraSettingsFileApplyRequest(request) => {
                 Self::handle_camera_settings_apply(request).await
             }
+            // pi.{pi_id}.settings.camera.{camera_id}.*
+            NatsRequest::CameraIdSettingsLoadRequest { camera_id } => {
+                Self::handle_camera_id_settings_load(camera_id).await
+            }
+            NatsRequest::CameraIdSettingsApplyRequest {
+                camera_id,
+                settings,
+            } => Self::handle_camera_id_settings_apply(camera_id, settings).await,
+            // pi.{pi_id}.settings.model.*
+            NatsRequest::DetectionModelLoadRequest(request) => {
+                Self::handle_model_load(request).await
+            }
+            NatsRequest::DetectionModelActivateRequest(request) => {
+                Self::handle_model_activate(request).await
+            }
             // pi.{pi_id}.dbus.org.freedesktop.systemd1.*
             NatsRequest::SystemdManagerDisableUnitsRequest(request) => {
                 Self::handle_disable_units_request(request).await
@@ -999,7 +1717,7 @@ mod tests {
     use tokio::runtime::Runtime;
 
     #[cfg(test)]
-    fn make_settings_repo(jail: &mut figment::Jail) -> () {
+    fn make_settings_repo(jail: &mut figment::Jail) {
         let output = jail.directory().to_str().unwrap();
         let moonraker_settings_file = jail.directory().join("settings/moonraker/moonraker.conf");
 
@@ -1055,6 +1773,209 @@ mod tests {
         );
         assert_eq!(subject, "pi.{pi_id}.settings.printnanny.cloud.auth")
     }
+
+    #[test]
+    fn test_deserialize_camera_id_subject() {
+        let request = NatsRequest::deserialize_payload(
+            "pi.{pi_id}.settings.camera.nozzle.load",
+            &Bytes::new(),
+        )
+        .unwrap();
+        match request {
+            NatsRequest::CameraIdSettingsLoadRequest { camera_id } => {
+                assert_eq!(camera_id, "nozzle")
+            }
+            _ => panic!("Expected NatsRequest::CameraIdSettingsLoadRequest"),
+        }
+
+        // legacy subjects without camera_id
+        let request =
+            NatsRequest::deserialize_payload("pi.{pi_id}.settings.camera.load", &Bytes::new())
+                .unwrap();
+        assert!(matches!(
+            request,
+            NatsRequest::CameraSettingsFileLoadRequest
+        ));
+        assert_eq!(
+            parse_camera_id_subject("pi.{pi_id}.settings.camera.status"),
+            None
+        );
+        assert!(NatsRequest::deserialize_payload(
+            "pi.{pi_id}.settings.camera.nozzle.revert",
+            &Bytes::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_deserialize_camera_recording_start_stop() {
+        let request = NatsRequest::deserialize_payload(
+            "pi.{pi_id}.command.camera.recording.start",
+            &Bytes::new(),
+        )
+        .unwrap();
+        match request {
+            NatsRequest::CameraRecordingStartRequest(request) => {
+                assert_eq!(request, CameraRecordingStartRequest::default())
+            }
+            _ => panic!("Expected NatsRequest::CameraRecordingStartRequest"),
+        }
+
+        let payload = Bytes::from(r#"{"camera_id": "nozzle"}"#);
+        let request =
+            NatsRequest::deserialize_payload("pi.{pi_id}.command.camera.recording.start", &payload)
+                .unwrap();
+        match request {
+            NatsRequest::CameraRecordingStartRequest(request) => {
+                assert_eq!(request.camera_id.as_deref(), Some("nozzle"))
+            }
+            _ => panic!("Expected NatsRequest::CameraRecordingStartRequest"),
+        }
+
+        let request =
+            NatsRequest::deserialize_payload("pi.{pi_id}.command.camera.recording.stop", &payload)
+                .unwrap();
+        match request {
+            NatsRequest::CameraRecordingStopRequest(request) => {
+                assert_eq!(request.camera_id.as_deref(), Some("nozzle"))
+            }
+            _ => panic!("Expected NatsRequest::CameraRecordingStopRequest"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_camera_recording_commit() {
+        let request = NatsRequest::deserialize_payload(
+            "pi.{pi_id}.command.camera.recording.commit",
+            &Bytes::new(),
+        )
+        .unwrap();
+        match request {
+            NatsRequest::CameraRecordingCommitRequest(request) => {
+                assert_eq!(request, CameraRecordingCommitRequest::default())
+            }
+            _ => panic!("Expected NatsRequest::CameraRecordingCommitRequest"),
+        }
+
+        let payload = Bytes::from(r#"{"camera_id": "nozzle", "preroll_secs": 30}"#);
+        let request = NatsRequest::deserialize_payload(
+            "pi.{pi_id}.command.camera.recording.commit",
+            &payload,
+        )
+        .unwrap();
+        match request {
+            NatsRequest::CameraRecordingCommitRequest(request) => {
+                assert_eq!(request.camera_id.as_deref(), Some("nozzle"));
+                assert_eq!(request.preroll_secs, Some(30));
+                assert_eq!(request.postroll_secs, None);
+            }
+            _ => panic!("Expected NatsRequest::CameraRecordingCommitRequest"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_camera_recording_export() {
+        let payload = Bytes::from(r#"{"video_recording_id": "66b3a3a0"}"#);
+        let request = NatsRequest::deserialize_payload(
+            "pi.{pi_id}.command.camera.recording.export",
+            &payload,
+        )
+        .unwrap();
+        match request {
+            NatsRequest::CameraRecordingExportRequest(request) => {
+                assert_eq!(request.video_recording_id, "66b3a3a0")
+            }
+            _ => panic!("Expected NatsRequest::CameraRecordingExportRequest"),
+        }
+        assert!(NatsRequest::deserialize_payload(
+            "pi.{pi_id}.command.camera.recording.export",
+            &Bytes::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_deserialize_camera_recording_load() {
+        let request = NatsRequest::deserialize_payload(
+            "pi.{pi_id}.command.camera.recording.load",
+            &Bytes::new(),
+        )
+        .unwrap();
+        match request {
+            NatsRequest::CameraRecordingLoadRequest(request) => {
+                assert_eq!(request.video_recording_id, None)
+            }
+            _ => panic!("Expected NatsRequest::CameraRecordingLoadRequest"),
+        }
+        let payload = Bytes::from(r#"{"video_recording_id": "66b3a3a0"}"#);
+        let request =
+            NatsRequest::deserialize_payload("pi.{pi_id}.command.camera.recording.load", &payload)
+                .unwrap();
+        match request {
+            NatsRequest::CameraRecordingLoadRequest(request) => {
+                assert_eq!(request.video_recording_id, Some("66b3a3a0".into()))
+            }
+            _ => panic!("Expected NatsRequest::CameraRecordingLoadRequest"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_model_requests() {
+        let request =
+            NatsRequest::deserialize_payload("pi.{pi_id}.settings.model.load", &Bytes::new())
+                .unwrap();
+        match request {
+            NatsRequest::DetectionModelLoadRequest(request) => assert_eq!(request.camera_id, None),
+            _ => panic!("Expected NatsRequest::DetectionModelLoadRequest"),
+        }
+        let payload = Bytes::from(r#"{"camera_id": "nozzle", "name": "printnanny-v2"}"#);
+        let request =
+            NatsRequest::deserialize_payload("pi.{pi_id}.settings.model.activate", &payload)
+                .unwrap();
+        match request {
+            NatsRequest::DetectionModelActivateRequest(request) => {
+                assert_eq!(request.camera_id, Some("nozzle".into()));
+                assert_eq!(request.name, Some("printnanny-v2".into()));
+            }
+            _ => panic!("Expected NatsRequest::DetectionModelActivateRequest"),
+        }
+        // an empty name re-activates the bundled model
+        let request = NatsRequest::deserialize_payload(
+            "pi.{pi_id}.settings.model.activate",
+            &Bytes::from("{}"),
+        )
+        .unwrap();
+        assert!(matches!(
+            request,
+            NatsRequest::DetectionModelActivateRequest(DetectionModelActivateRequest {
+                name: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_deserialize_retention() {
+        let request =
+            NatsRequest::deserialize_payload("pi.{pi_id}.command.retention.status", &Bytes::new())
+                .unwrap();
+        assert!(matches!(request, NatsRequest::RetentionStatusRequest));
+        let request =
+            NatsRequest::deserialize_payload("pi.{pi_id}.command.retention.enforce", &Bytes::new())
+                .unwrap();
+        assert!(matches!(request, NatsRequest::RetentionEnforceRequest));
+    }
+
+    #[test]
+    fn test_deserialize_upload_queue_status() {
+        let request = NatsRequest::deserialize_payload(
+            "pi.{pi_id}.command.upload_queue.status",
+            &Bytes::new(),
+        )
+        .unwrap();
+        assert!(matches!(request, NatsRequest::UploadQueueStatusRequest));
+    }
+
     #[test(tokio::test)]
     async fn test_device_info_load() {
         let request = NatsRequest::DeviceInfoLoadRequest;
@@ -1107,9 +2028,9 @@ mod tests {
             let reply = Runtime::new().unwrap().block_on(request.handle()).unwrap();
 
             if let NatsReply::CameraSettingsFileApplyReply(reply) = reply {
-                assert_eq!(reply.hls.enabled, false);
+                assert!(!reply.hls.enabled);
                 settings = runtime.block_on(PrintNannySettings::new()).unwrap();
-                assert_eq!(settings.video_stream.hls.enabled, false);
+                assert!(!settings.video_stream.hls.enabled);
             } else {
                 panic!("Expected NatsReply::CameraSettingsFileApplyReply")
             }
@@ -35,7 +35,7 @@ impl RpiCpuInfo {
         let mut hardware: Option<String> = None;
         let mut serial: Option<String> = None;
 
-        for line in reader.lines().flatten() {
+        for line in reader.lines().map_while(Result::ok) {
             if !line.is_empty() {
                 let mut s = line.split(':');
                 let key = s.next().unwrap();
@@ -1,7 +1,7 @@
 use std::fs::File;
 use std::io;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::Path;
 
 use log::{debug, error, warn};
 
@@ -12,7 +12,7 @@ use tokio::process::Command;
 use printnanny_settings::error::PrintNannySettingsError;
 use zip::write::FileOptions;
 
-async fn pip_freeze(venv: &PathBuf) -> io::Result<Vec<u8>> {
+async fn pip_freeze(venv: &Path) -> io::Result<Vec<u8>> {
     let pip = venv.join("bin/pip");
     let output = Command::new(pip).args(["freeze", "--all"]).output().await?;
     Ok(output.stdout)
@@ -47,6 +47,46 @@ pub enum VideoRecordingError {
 
     #[error(transparent)]
     IoError(#[from] std::io::Error),
+
+    #[error(transparent)]
+    RetentionError(#[from] RetentionError),
+
+    #[error("Failed to start recording pipeline for VideoRecording with id={id} error={error}")]
+    PipelineStartFailed { id: String, error: String },
+}
+
+#[derive(Error, Debug)]
+pub enum RetentionError {
+    #[error("Refusing to record, datafs is nearly full (used {datafs_used} of {datafs_size} bytes, max {max_used_percent}%)")]
+    DatafsFull {
+        datafs_size: i64,
+        datafs_used: i64,
+        max_used_percent: i32,
+    },
+
+    #[error(transparent)]
+    PrintNannySettingsError(#[from] PrintNannySettingsError),
+
+    #[error(transparent)]
+    SqliteDBError(#[from] diesel::result::Error),
+
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+#[derive(Error, Debug)]
+pub enum RecordingRecoveryError {
+    #[error("Refusing to recover recordings while pipeline={pipeline} is recording")]
+    RecordingInProgress { pipeline: String },
+
+    #[error(transparent)]
+    PrintNannySettingsError(#[from] PrintNannySettingsError),
+
+    #[error(transparent)]
+    SqliteDBError(#[from] diesel::result::Error),
+
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
 }
 
 #[derive(Error, Debug)]
@@ -56,6 +96,17 @@ pub enum VideoRecordingSyncError {
 
     #[error("mp4 upload url was not set for VideoRecording with id={id} file_name={file_name}")]
     UploadUrlNotSet { id: String, file_name: String },
+    #[error("PrintNanny Cloud did not confirm upload of VideoRecordingPart with id={id} file_name={file_name}")]
+    UploadNotConfirmed { id: String, file_name: String },
+    #[error(
+        "PrintNanny Cloud returned invalid {field}={value} for VideoRecordingPart with id={id}"
+    )]
+    InvalidTimestamp {
+        id: String,
+        field: String,
+        value: String,
+        source: chrono::ParseError,
+    },
     #[error(transparent)]
     ReqwestError(#[from] reqwest::Error),
 
@@ -198,6 +249,13 @@ pub enum ServiceError {
     #[error(transparent)]
     UrlParseError(#[from] url::ParseError),
 
+    #[error("Moonraker JSON-RPC {method} failed with code={code} message={message}")]
+    MoonrakerJsonRpcError {
+        method: String,
+        code: i64,
+        message: String,
+    },
+
     #[error(transparent)]
     SqliteDBError(#[from] diesel::result::Error),
 
@@ -1,3 +1,6 @@
+// ServiceError wraps the large settings and API client errors, boxing them would change the public error types
+#![allow(clippy::result_large_err)]
+
 pub mod cpuinfo;
 pub mod crash_report;
 pub mod error;
@@ -5,6 +8,9 @@ pub mod file;
 pub mod janus;
 pub mod metadata;
 pub mod octoprint;
+pub mod printer_control;
+pub mod recording_recovery;
+pub mod retention;
 pub mod video_recording_sync;
 
 pub mod os_release;
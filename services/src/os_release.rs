@@ -86,14 +86,14 @@ impl OsRelease {
             error: e,
         })?;
         let reader = BufReader::new(file);
-        Ok(OsRelease::from_iter(reader.lines().flatten()))
+        Ok(OsRelease::from_iter(reader.lines().map_while(Result::ok)))
     }
 
     /// Attempt to parse any `/etc/os-release`-like file.
     pub fn new_from<P: AsRef<Path>>(path: P) -> io::Result<OsRelease> {
         let file = open(path)?;
         let reader = BufReader::new(file);
-        Ok(OsRelease::from_iter(reader.lines().flatten()))
+        Ok(OsRelease::from_iter(reader.lines().map_while(Result::ok)))
     }
 }
 
@@ -0,0 +1,185 @@
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use reqwest::header;
+use serde_json::{json, Value};
+use url::Url;
+
+use printnanny_edge_db::octoprint::OctoPrintServer;
+use printnanny_edge_db::print_job::PrintJobAction;
+use printnanny_settings::printnanny::{FailureAction, FailureActionSettings, PrinterApi};
+
+use crate::error::ServiceError;
+use crate::octoprint::octoprint_api_client;
+
+// POST /api/job body
+// https://docs.octoprint.org/en/master/api/job.html#issue-a-job-command
+pub fn octoprint_job_command(action: FailureAction) -> Value {
+    match action {
+        FailureAction::Pause => json!({"command": "pause", "action": "pause"}),
+        FailureAction::Cancel => json!({"command": "cancel"}),
+    }
+}
+
+// https://moonraker.readthedocs.io/en/latest/web_api/#print-management
+pub fn moonraker_method(action: FailureAction) -> &'static str {
+    match action {
+        FailureAction::Pause => "printer.print.pause",
+        FailureAction::Cancel => "printer.print.cancel",
+    }
+}
+
+pub fn moonraker_jsonrpc_request(action: FailureAction, id: i64) -> Value {
+    json!({"jsonrpc": "2.0", "method": moonraker_method(action), "id": id})
+}
+
+// true if the last action was taken less than cooldown_secs before now
+pub fn in_cooldown(last: Option<&PrintJobAction>, now: DateTime<Utc>, cooldown_secs: i64) -> bool {
+    match last {
+        Some(last) => now - last.created_dt < Duration::seconds(cooldown_secs),
+        None => false,
+    }
+}
+
+async fn octoprint_job_action(
+    sqlite_connection: &str,
+    action: FailureAction,
+) -> Result<(), ServiceError> {
+    let octoprint_server = OctoPrintServer::get(sqlite_connection)?;
+    let api_client = octoprint_api_client(&octoprint_server)?;
+    // keep OctoPrint's base path, e.g. http://localhost/octoprint/api/job
+    let base_url = Url::parse(&format!(
+        "{}/",
+        octoprint_server.octoprint_url.trim_end_matches('/')
+    ))?;
+    let url = base_url.join("api/job")?;
+    api_client
+        .post(url.clone())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&octoprint_job_command(action))?)
+        .send()
+        .await?
+        .error_for_status()?;
+    info!("Sent OctoPrint job command action={} url={}", action, url);
+    Ok(())
+}
+
+async fn moonraker_job_action(
+    moonraker_url: &str,
+    action: FailureAction,
+) -> Result<(), ServiceError> {
+    let base_url = Url::parse(&format!("{}/", moonraker_url.trim_end_matches('/')))?;
+    let url = base_url.join("server/jsonrpc")?;
+    let request = moonraker_jsonrpc_request(action, Utc::now().timestamp());
+    let response = reqwest::Client::new()
+        .post(url.clone())
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&request)?)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let response: Value = serde_json::from_slice(&response)?;
+    if let Some(error) = response.get("error") {
+        return Err(ServiceError::MoonrakerJsonRpcError {
+            method: moonraker_method(action).to_string(),
+            code: error
+                .get("code")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default(),
+            message: error
+                .get("message")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+        });
+    }
+    info!(
+        "Sent Moonraker JSON-RPC method={} url={}",
+        moonraker_method(action),
+        url
+    );
+    Ok(())
+}
+
+// Pause or cancel the current print job in response to a confirmed print quality alert
+// returns None if actions are disabled or within FailureActionSettings.cooldown_secs of the last action taken on print_job_id
+// failed requests are recorded before the error is returned
+pub async fn apply_failure_action(
+    settings: &FailureActionSettings,
+    sqlite_connection: &str,
+    print_job_id: &str,
+    print_job_alert_id: Option<&str>,
+) -> Result<Option<PrintJobAction>, ServiceError> {
+    if !settings.enabled {
+        return Ok(None);
+    }
+    let last = PrintJobAction::get_last(sqlite_connection, print_job_id)?;
+    if in_cooldown(last.as_ref(), Utc::now(), settings.cooldown_secs) {
+        info!(
+            "Skipping failure action={}, last action was taken at {:?} cooldown_secs={}",
+            settings.action,
+            last.map(|last| last.created_dt),
+            settings.cooldown_secs
+        );
+        return Ok(None);
+    }
+
+    let result = match (settings.dry_run, settings.printer_api) {
+        (true, _) => {
+            warn!(
+                "Dry run, not sending failure action={} printer_api={} print_job_id={}",
+                settings.action, settings.printer_api, print_job_id
+            );
+            Ok(())
+        }
+        (false, PrinterApi::OctoPrint) => {
+            octoprint_job_action(sqlite_connection, settings.action).await
+        }
+        (false, PrinterApi::Moonraker) => {
+            moonraker_job_action(&settings.moonraker_url, settings.action).await
+        }
+    };
+    let error = result.as_ref().err().map(|e| e.to_string());
+    let action = PrintJobAction::insert(
+        sqlite_connection,
+        print_job_id,
+        print_job_alert_id,
+        &settings.action.to_string(),
+        &settings.printer_api.to_string(),
+        settings.dry_run,
+        error.as_deref(),
+    )?;
+    result?;
+    Ok(Some(action))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_action_requests() {
+        assert_eq!(
+            octoprint_job_command(FailureAction::Pause),
+            json!({"command": "pause", "action": "pause"})
+        );
+        assert_eq!(
+            octoprint_job_command(FailureAction::Cancel),
+            json!({"command": "cancel"})
+        );
+        assert_eq!(
+            moonraker_jsonrpc_request(FailureAction::Cancel, 1),
+            json!({"jsonrpc": "2.0", "method": "printer.print.cancel", "id": 1})
+        );
+
+        let now = Utc::now();
+        let last = PrintJobAction {
+            created_dt: now - Duration::seconds(60),
+            ..PrintJobAction::default()
+        };
+        assert!(!in_cooldown(None, now, 300));
+        assert!(in_cooldown(Some(&last), now, 300));
+        assert!(!in_cooldown(Some(&last), now, 30));
+    }
+}
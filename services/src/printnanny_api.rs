@@ -7,7 +7,7 @@ use std::io::BufReader;
 use std::path::{Path, PathBuf};
 
 use async_tempfile::TempFile;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde;
 use serde_json;
 use tokio::fs;
@@ -15,6 +15,7 @@ use tokio::fs;
 use tokio::io::AsyncWriteExt;
 
 // settings modules
+use printnanny_settings::cam::VideoStreamSettings;
 use printnanny_settings::error::PrintNannySettingsError;
 use printnanny_settings::printnanny::{PrintNannyApiConfig, PrintNannySettings};
 use printnanny_settings::sys_info;
@@ -36,10 +37,11 @@ use printnanny_gst_pipelines::factory::PrintNannyPipelineFactory;
 
 use crate::cpuinfo::RpiCpuInfo;
 use crate::crash_report::write_crash_report_zip;
-use crate::error::{IoError, ServiceError, VideoRecordingError};
+use crate::error::{IoError, RetentionError, ServiceError, VideoRecordingError};
 use crate::file::open;
 use crate::metadata;
 use crate::os_release::OsRelease;
+use crate::retention::check_recording_allowed;
 
 #[derive(Debug, Clone)]
 pub struct ApiService {
@@ -186,6 +188,7 @@ impl ApiService {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn crash_report_create(
         &self,
         description: Option<&str>,
@@ -194,7 +197,7 @@ impl ApiService {
         browser_logs: Option<PathBuf>,
         status: Option<models::CrashReportStatusEnum>,
         posthog_session: Option<&str>,
-        crash_report_paths: Vec<PathBuf>,
+        _crash_report_paths: Vec<PathBuf>,
     ) -> Result<models::CrashReport, ServiceError> {
         let file = tempfile::Builder::new()
             .prefix("crash-report")
@@ -247,7 +250,7 @@ impl ApiService {
     pub async fn crash_report_update(
         &self,
         id: &str,
-        crash_report_paths: Vec<PathBuf>,
+        _crash_report_paths: Vec<PathBuf>,
     ) -> Result<models::CrashReport, ServiceError> {
         let os_release = OsRelease::new()?;
         let file = tempfile::Builder::new()
@@ -329,17 +332,14 @@ impl ApiService {
         info!("Success! Updated SystemInfo model: {:?}", system_info);
 
         // sync PrintNanny Cloud OctoPrintServer model
-        match &edge_pi.octoprint_server_id {
-            Some(octoprint_server_id) => {
-                let octoprint_server = self
-                    .octoprint_server_update(octoprint_server_id, &edge_pi.id)
-                    .await?;
-                info!(
-                    "Success! Updated OctoPrintServer model: {:?}",
-                    octoprint_server
-                );
-            }
-            None => (),
+        if let Some(octoprint_server_id) = &edge_pi.octoprint_server_id {
+            let octoprint_server = self
+                .octoprint_server_update(octoprint_server_id, &edge_pi.id)
+                .await?;
+            info!(
+                "Success! Updated OctoPrintServer model: {:?}",
+                octoprint_server
+            );
         }
 
         // sync PrintNanny Cloud Pi model
@@ -544,31 +544,132 @@ impl ApiService {
         Ok(res)
     }
 
+    // start a new recording of the camera described by video_stream
     pub async fn video_recordings_create(
         &self,
         video_path: PathBuf,
+        video_stream: &VideoStreamSettings,
+    ) -> Result<printnanny_edge_db::video_recording::VideoRecording, VideoRecordingError> {
+        let settings = PrintNannySettings::new()
+            .await
+            .map_err(RetentionError::from)?;
+        check_recording_allowed(&settings)?;
+
+        let recording = self
+            .video_recordings_create_without_pipeline(video_path, Utc::now())
+            .await?;
+        info!("Attempting to start new recording id={}", &recording.id);
+
+        let factory = PrintNannyPipelineFactory::default();
+        if let Err(e) = factory
+            .start_video_recording_pipeline(video_stream, &recording.dir)
+            .await
+        {
+            // end the recording, so it isn't resumed as the current recording without a pipeline writing parts
+            let now = Utc::now();
+            printnanny_edge_db::video_recording::VideoRecording::update(
+                &self.sqlite_connection,
+                &recording.id,
+                printnanny_edge_db::video_recording::UpdateVideoRecording {
+                    recording_start: None,
+                    dir: None,
+                    cloud_sync_done: None,
+                    recording_end: Some(&now),
+                    gcode_file_name: None,
+                },
+            )?;
+            if let Err(finalize_error) = self.video_recording_finalize(&recording.id).await {
+                warn!(
+                    "Failed to finalize VideoRecording id={} error={}",
+                    &recording.id, finalize_error
+                );
+            }
+            return Err(VideoRecordingError::PipelineStartFailed {
+                id: recording.id,
+                error: e.to_string(),
+            });
+        }
+
+        info!(
+            "Gstreamer recording pipeline is now playing camera_id={}",
+            video_stream.camera_id
+        );
+        Ok(recording)
+    }
+
+    // create edge and cloud VideoRecording models, without starting the h264_record pipeline
+    pub async fn video_recordings_create_without_pipeline(
+        &self,
+        video_path: PathBuf,
+        recording_start: DateTime<Utc>,
     ) -> Result<printnanny_edge_db::video_recording::VideoRecording, VideoRecordingError> {
         let recording = printnanny_edge_db::video_recording::VideoRecording::start_new(
             &self.sqlite_connection,
             video_path,
         )?;
 
-        let now = Utc::now();
+        // associate recording with the print job in progress, if any
+        let print_job =
+            printnanny_edge_db::print_job::PrintJob::get_current(&self.sqlite_connection)?;
         let update = printnanny_edge_db::video_recording::UpdateVideoRecording {
-            recording_start: Some(&now),
+            recording_start: Some(&recording_start),
             dir: None,
             cloud_sync_done: None,
             recording_end: None,
-            gcode_file_name: None, // TODO
+            gcode_file_name: print_job
+                .as_ref()
+                .and_then(|job| job.gcode_file_name.as_deref()),
         };
         printnanny_edge_db::video_recording::VideoRecording::update(
             &self.sqlite_connection,
             &recording.id,
             update,
         )?;
+        self.video_recordings_link_and_create_cloud(&recording.id, print_job)
+            .await
+    }
+
+    // create edge and cloud VideoRecording models for a recording which already finished, see VideoRecording::new_finished
+    // used by recordings committed from the pre-roll buffer, after their parts were written
+    pub async fn video_recordings_create_finished(
+        &self,
+        recording: &printnanny_edge_db::video_recording::VideoRecording,
+    ) -> Result<printnanny_edge_db::video_recording::VideoRecording, VideoRecordingError> {
+        // associate recording with the print job in progress, if any
+        let print_job =
+            printnanny_edge_db::print_job::PrintJob::get_current(&self.sqlite_connection)?;
+        let recording = printnanny_edge_db::video_recording::VideoRecording {
+            gcode_file_name: print_job
+                .as_ref()
+                .and_then(|job| job.gcode_file_name.clone()),
+            ..recording.clone()
+        };
+        printnanny_edge_db::video_recording::VideoRecording::insert_finished(
+            &self.sqlite_connection,
+            &recording,
+        )?;
+        self.video_recordings_link_and_create_cloud(&recording.id, print_job)
+            .await
+    }
+
+    async fn video_recordings_link_and_create_cloud(
+        &self,
+        recording_id: &str,
+        print_job: Option<printnanny_edge_db::print_job::PrintJob>,
+    ) -> Result<printnanny_edge_db::video_recording::VideoRecording, VideoRecordingError> {
+        if let Some(print_job) = print_job {
+            printnanny_edge_db::print_job::PrintJob::update(
+                &self.sqlite_connection,
+                &print_job.id,
+                printnanny_edge_db::print_job::UpdatePrintJob {
+                    video_recording_id: Some(recording_id),
+                    ..printnanny_edge_db::print_job::UpdatePrintJob::default()
+                },
+            )?;
+        }
         let recording = printnanny_edge_db::video_recording::VideoRecording::get_by_id(
             &self.sqlite_connection,
-            &recording.id,
+            recording_id,
         )?;
 
         let result =
@@ -576,15 +677,6 @@ impl ApiService {
                 .await?;
 
         info!("Created PrintNanny Cloud VideoRecording {:?}", result);
-        info!("Attempting to start new recording id={}", &recording.id);
-
-        let factory = PrintNannyPipelineFactory::default();
-        factory
-            .start_video_recording_pipeline(&recording.dir)
-            .await
-            .expect("Failed to initialize connection to gstd");
-
-        info!("Gstreamer mp4 recording pipeline is now playing");
         Ok(recording)
     }
 
@@ -0,0 +1,344 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sysinfo::{System, SystemExt};
+
+use printnanny_edge_db::diesel;
+use printnanny_edge_db::print_job::PrintJob;
+use printnanny_edge_db::video_recording::{
+    parse_video_recording_index, NewVideoRecordingPart, UpdateVideoRecording, VideoRecording,
+    VideoRecordingPart,
+};
+use printnanny_gst_pipelines::factory::PrintNannyPipelineFactory;
+use printnanny_settings::printnanny::PrintNannySettings;
+
+use crate::error::RecordingRecoveryError;
+
+const TS_PACKET_SIZE: usize = 188;
+const TS_SYNC_BYTE: u8 = 0x47;
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    // recordings without recording_end, closed by recovery
+    pub recordings_finished: Vec<String>,
+    // part files without a VideoRecordingPart row, inserted into the upload queue
+    pub parts_inserted: usize,
+    pub parts_truncated: usize,
+    // partial segments without a complete packet
+    pub parts_removed: usize,
+    pub timestamp: String,
+}
+
+// length of data up to the end of the last complete MPEG-TS packet
+pub fn ts_valid_len(data: &[u8]) -> usize {
+    let mut len = data.len() / TS_PACKET_SIZE * TS_PACKET_SIZE;
+    // blocks which were allocated but never written are zero-filled after power loss
+    while len >= TS_PACKET_SIZE && data[len - TS_PACKET_SIZE] != TS_SYNC_BYTE {
+        len -= TS_PACKET_SIZE;
+    }
+    len
+}
+
+// truncate a partially written MPEG-TS segment to its last complete packet
+// returns the repaired size, or None if the segment does not contain a complete packet
+pub fn repair_ts_segment(path: &Path) -> io::Result<Option<u64>> {
+    let data = fs::read(path)?;
+    let valid = ts_valid_len(&data);
+    if valid == 0 {
+        return Ok(None);
+    }
+    if valid < data.len() {
+        let file = fs::OpenOptions::new().write(true).open(path)?;
+        file.set_len(valid as u64)?;
+        file.sync_all()?;
+    }
+    Ok(Some(valid as u64))
+}
+
+fn is_part_file(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    let has_index = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+        .map(|stem| stem.chars().any(|c| c.is_ascii_digit()))
+        .unwrap_or(false);
+    path.is_file() && matches!(extension, "ts" | "mp4" | "mkv") && has_index
+}
+
+// splitmuxsink fragments in a recording directory, ordered by index
+pub fn list_part_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut result = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !is_part_file(&path) {
+            continue;
+        }
+        match parse_video_recording_index(&path.display().to_string()) {
+            Some(index) => result.push((index, path)),
+            None => warn!(
+                "Ignoring part file without an index file={}",
+                path.display()
+            ),
+        }
+    }
+    result.sort_by_key(|(index, _)| *index);
+    Ok(result.into_iter().map(|(_, path)| path).collect())
+}
+
+fn recover_recording(
+    sqlite_connection: &str,
+    recording: &VideoRecording,
+    dir: &Path,
+    report: &mut RecoveryReport,
+) -> Result<(), RecordingRecoveryError> {
+    let dangling = recording.recording_end.is_none();
+    let mut parts = list_part_files(dir)?;
+
+    // the last fragment of a dangling recording was still open when the recording was interrupted
+    if let (true, Some(last)) = (dangling, parts.last().cloned()) {
+        if last.extension().map(|ext| ext == "ts").unwrap_or(false) {
+            let size = fs::metadata(&last)?.len();
+            match repair_ts_segment(&last)? {
+                Some(repaired) if repaired < size => {
+                    info!(
+                        "Truncated partial segment file={} size={} repaired_size={}",
+                        last.display(),
+                        size,
+                        repaired
+                    );
+                    report.parts_truncated += 1;
+                }
+                Some(_) => (),
+                None => {
+                    fs::remove_file(&last)?;
+                    VideoRecordingPart::mark_deleted_by_file_name(
+                        sqlite_connection,
+                        &last.display().to_string(),
+                    )?;
+                    info!(
+                        "Removed partial segment without a complete packet file={}",
+                        last.display()
+                    );
+                    report.parts_removed += 1;
+                    parts.pop();
+                }
+            }
+        } else {
+            warn!(
+                "Only MPEG-TS segments can be repaired, leaving partial segment file={}",
+                last.display()
+            );
+        }
+    }
+
+    // fragments whose fragment-opened message was never handled
+    for part in parts.iter() {
+        let file_name = part.display().to_string();
+        // list_part_files only returns files with an index
+        let (row_id, index) = match (
+            VideoRecordingPart::row_id_from_filename(&file_name),
+            parse_video_recording_index(&file_name),
+        ) {
+            (Some(row_id), Some(index)) => (row_id, index),
+            _ => {
+                warn!("Ignoring part file without an index file={}", file_name);
+                continue;
+            }
+        };
+        match VideoRecordingPart::get_by_id(sqlite_connection, &row_id) {
+            Ok(_) => continue,
+            Err(diesel::result::Error::NotFound) => (),
+            Err(e) => return Err(e.into()),
+        }
+        let size = fs::metadata(part)?.len() as i64;
+        let row = NewVideoRecordingPart {
+            id: &row_id,
+            size: &size,
+            buffer_index: &index,
+            buffer_runningtime: &0,
+            deleted: &false,
+            file_name: &file_name,
+            video_recording_id: &recording.id,
+            // recovery runs before the recording pipeline is started, so the fragment is no longer written
+            closed: &true,
+        };
+        VideoRecordingPart::insert(sqlite_connection, row)?;
+        info!(
+            "Inserted missing VideoRecordingPart video_recording_id={} id={} file_name={}",
+            &recording.id, &row_id, &file_name
+        );
+        report.parts_inserted += 1;
+    }
+
+    if dangling {
+        // recording ended when its last fragment was written
+        let recording_end: DateTime<Utc> = parts
+            .last()
+            .and_then(|part| part.metadata().and_then(|m| m.modified()).ok())
+            .map(DateTime::from)
+            .unwrap_or_else(Utc::now);
+        VideoRecording::update(
+            sqlite_connection,
+            &recording.id,
+            UpdateVideoRecording {
+                recording_end: Some(&recording_end),
+                cloud_sync_done: None,
+                dir: None,
+                recording_start: None,
+                gcode_file_name: None,
+            },
+        )?;
+        info!(
+            "Closed dangling VideoRecording id={} recording_end={}",
+            &recording.id, recording_end
+        );
+        report.recordings_finished.push(recording.id.clone());
+    }
+    Ok(())
+}
+
+// Reconcile video recordings after an unclean shutdown, e.g. power loss mid-recording
+// every recording without recording_end is treated as dangling, so recovery refuses to run while any camera's recording pipeline exists
+// recovered parts are uploaded by video_recording_sync's upload queue
+pub async fn recover(
+    settings: &PrintNannySettings,
+    factory: &PrintNannyPipelineFactory,
+) -> Result<RecoveryReport, RecordingRecoveryError> {
+    match factory.recording_pipeline_names().await {
+        Ok(mut names) if !names.is_empty() => {
+            return Err(RecordingRecoveryError::RecordingInProgress {
+                pipeline: names.remove(0),
+            });
+        }
+        Ok(_) => (),
+        // gstd is not running yet, so nothing is recording
+        Err(e) => warn!(
+            "Failed to list pipelines, assuming no recording is in progress error={}",
+            e
+        ),
+    }
+    let sqlite_connection = settings.paths.db().display().to_string();
+    let video_dir = settings.paths.video();
+    let exports_dir = settings.paths.video_exports();
+    let mut report = RecoveryReport::default();
+
+    if video_dir.exists() {
+        for entry in fs::read_dir(&video_dir)? {
+            let dir = entry?.path();
+            if !dir.is_dir() || dir == exports_dir {
+                continue;
+            }
+            let video_recording_id = match dir.file_name().and_then(|name| name.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let recording = match VideoRecording::get_by_id(&sqlite_connection, &video_recording_id)
+            {
+                Ok(recording) => recording,
+                Err(diesel::result::Error::NotFound) => {
+                    warn!(
+                        "Skipping directory without VideoRecording row dir={}",
+                        dir.display()
+                    );
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            recover_recording(&sqlite_connection, &recording, &dir, &mut report)?;
+        }
+    }
+
+    // dangling recordings whose directory was removed
+    VideoRecording::finish_all(&sqlite_connection)?;
+    // OctoPrint does not resume jobs after an unclean shutdown, but jobs started since boot may still be printing
+    let boot_time: DateTime<Utc> =
+        DateTime::from(UNIX_EPOCH + Duration::from_secs(System::new().boot_time()));
+    PrintJob::finish_started_before(&sqlite_connection, "Unknown", &boot_time)?;
+
+    report.timestamp = Utc::now().to_rfc3339();
+    info!(
+        "Recording recovery finished recordings={} parts_inserted={} parts_truncated={} parts_removed={}",
+        report.recordings_finished.len(),
+        report.parts_inserted,
+        report.parts_truncated,
+        report.parts_removed
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packets(count: usize) -> Vec<u8> {
+        let mut packet = [0xff; TS_PACKET_SIZE];
+        packet[0] = TS_SYNC_BYTE;
+        packet.repeat(count)
+    }
+
+    #[test]
+    fn test_ts_valid_len() {
+        assert_eq!(ts_valid_len(&[]), 0);
+        assert_eq!(ts_valid_len(&packets(3)), 3 * TS_PACKET_SIZE);
+
+        // partially written packet
+        let mut data = packets(3);
+        data.extend_from_slice(&[TS_SYNC_BYTE, 0xff, 0xff]);
+        assert_eq!(ts_valid_len(&data), 3 * TS_PACKET_SIZE);
+
+        // zero-filled block after power loss
+        let mut data = packets(2);
+        data.extend(vec![0; TS_PACKET_SIZE * 2 + 10]);
+        assert_eq!(ts_valid_len(&data), 2 * TS_PACKET_SIZE);
+
+        assert_eq!(ts_valid_len(&[0; TS_PACKET_SIZE]), 0);
+    }
+
+    #[test]
+    fn test_repair_ts_segment() {
+        let dir =
+            std::env::temp_dir().join(format!("printnanny-recovery-test-{}", std::process::id()));
+        let recording = dir.join("66b3a3a0");
+        fs::create_dir_all(&recording).unwrap();
+        let mut partial = packets(2);
+        partial.extend_from_slice(&[TS_SYNC_BYTE, 0xff]);
+        fs::write(recording.join("0.ts"), packets(4)).unwrap();
+        fs::write(recording.join("10.ts"), [0; 20]).unwrap();
+        fs::write(recording.join("2.ts"), &partial).unwrap();
+        fs::write(recording.join("playlist.m3u8"), b"").unwrap();
+
+        let parts = list_part_files(&recording).unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                recording.join("0.ts"),
+                recording.join("2.ts"),
+                recording.join("10.ts")
+            ]
+        );
+
+        assert_eq!(
+            repair_ts_segment(&recording.join("0.ts")).unwrap(),
+            Some(4 * TS_PACKET_SIZE as u64)
+        );
+        assert_eq!(
+            repair_ts_segment(&recording.join("2.ts")).unwrap(),
+            Some(2 * TS_PACKET_SIZE as u64)
+        );
+        assert_eq!(
+            fs::metadata(recording.join("2.ts")).unwrap().len(),
+            2 * TS_PACKET_SIZE as u64
+        );
+        assert_eq!(repair_ts_segment(&recording.join("10.ts")).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,361 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::Utc;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use printnanny_edge_db::video_recording::{VideoRecording, VideoRecordingPart};
+use printnanny_settings::printnanny::{PrintNannySettings, RetentionSettings};
+
+use crate::error::RetentionError;
+use crate::metadata;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetentionFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct RetentionStatus {
+    pub enabled: bool,
+    pub dirs: Vec<PathBuf>,
+    pub max_bytes: i64,
+    pub max_age_secs: i64,
+    // bytes used across dirs, after any deletions
+    pub used_bytes: u64,
+    pub file_count: usize,
+    pub deleted_files: usize,
+    pub deleted_bytes: u64,
+    // 0 if SystemInfo could not be read
+    pub datafs_size: i64,
+    pub datafs_used: i64,
+    pub recording_allowed: bool,
+    pub timestamp: String,
+}
+
+// directories bounded by RetentionSettings: video recordings, snapshots and HLS segments of every camera
+pub fn retention_dirs(settings: &PrintNannySettings) -> Vec<PathBuf> {
+    let mut result = vec![settings.paths.video(), settings.paths.snapshot_dir.clone()];
+    for video_stream in settings.video_streams() {
+        if let Some(dir) = Path::new(&video_stream.hls.segments).parent() {
+            let dir = dir.to_path_buf();
+            if !result.contains(&dir) {
+                result.push(dir);
+            }
+        }
+    }
+    result
+}
+
+// recursively list files under dirs, skipping paths under exclude and HLS playlists
+pub fn list_files(
+    dirs: &[PathBuf],
+    exclude: &[PathBuf],
+) -> Result<Vec<RetentionFile>, RetentionError> {
+    let mut result = vec![];
+    let mut stack: Vec<PathBuf> = dirs.iter().filter(|d| d.exists()).cloned().collect();
+    while let Some(dir) = stack.pop() {
+        if exclude.iter().any(|e| dir.starts_with(e)) {
+            continue;
+        }
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                stack.push(path);
+                continue;
+            }
+            if path.extension().map(|ext| ext == "m3u8").unwrap_or(false) {
+                continue;
+            }
+            result.push(RetentionFile {
+                path,
+                size: metadata.len(),
+                modified: metadata.modified()?,
+            });
+        }
+    }
+    Ok(result)
+}
+
+// files to delete: files older than max_age, then the oldest remaining files until total size is within max_bytes
+// protected files (e.g. recording parts pending upload) count towards max_bytes, but are never selected
+pub fn select_expired<'a>(
+    files: &'a [RetentionFile],
+    protected: &[PathBuf],
+    max_bytes: u64,
+    max_age: Option<Duration>,
+    now: SystemTime,
+) -> Vec<&'a RetentionFile> {
+    let mut sorted: Vec<&RetentionFile> = files.iter().collect();
+    sorted.sort_by_key(|f| f.modified);
+
+    let mut used: u64 = files.iter().map(|f| f.size).sum();
+    let mut result = vec![];
+    for file in sorted {
+        if protected.contains(&file.path) {
+            continue;
+        }
+        let expired = match max_age {
+            Some(max_age) => now
+                .duration_since(file.modified)
+                .map(|age| age > max_age)
+                .unwrap_or(false),
+            None => false,
+        };
+        if expired || used > max_bytes {
+            used -= file.size;
+            result.push(file);
+        }
+    }
+    result
+}
+
+// false if datafs usage exceeds datafs_max_used_percent, always true if datafs_size is unknown
+pub fn recording_allowed(settings: &RetentionSettings, datafs_size: i64, datafs_used: i64) -> bool {
+    if !settings.enabled || datafs_size <= 0 {
+        return true;
+    }
+    datafs_used * 100 <= datafs_size * settings.datafs_max_used_percent as i64
+}
+
+// returns (datafs_size, datafs_used), or (0, 0) if SystemInfo could not be read
+fn datafs_usage() -> (i64, i64) {
+    match metadata::system_info() {
+        Ok(system_info) => (system_info.datafs_size, system_info.datafs_used),
+        Err(e) => {
+            warn!(
+                "Failed to read SystemInfo, datafs usage is unknown error={}",
+                e
+            );
+            (0, 0)
+        }
+    }
+}
+
+// refuse to start a new recording when the data partition is nearly full
+pub fn check_recording_allowed(settings: &PrintNannySettings) -> Result<(), RetentionError> {
+    let (datafs_size, datafs_used) = datafs_usage();
+    if recording_allowed(&settings.retention, datafs_size, datafs_used) {
+        Ok(())
+    } else {
+        Err(RetentionError::DatafsFull {
+            datafs_size,
+            datafs_used,
+            max_used_percent: settings.retention.datafs_max_used_percent,
+        })
+    }
+}
+
+fn run(settings: &PrintNannySettings, dry_run: bool) -> Result<RetentionStatus, RetentionError> {
+    let retention = &settings.retention;
+    let sqlite_connection = settings.paths.db().display().to_string();
+    let dirs = retention_dirs(settings);
+
+    // never delete parts of the active recording
+    let exclude: Vec<PathBuf> = VideoRecording::get_current(&sqlite_connection)?
+        .map(|recording| vec![PathBuf::from(recording.dir)])
+        .unwrap_or_default();
+
+    let files = list_files(&dirs, &exclude)?;
+    let mut used_bytes: u64 = files.iter().map(|f| f.size).sum();
+    let mut file_count = files.len();
+    let mut deleted_files = 0;
+    let mut deleted_bytes = 0;
+
+    if retention.enabled && !dry_run {
+        let max_age = match retention.max_age_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs.max(0) as u64)),
+        };
+        // never delete parts which are not uploaded yet, unless the upload queue gave up on them
+        let pending_upload: Vec<PathBuf> = VideoRecordingPart::get_pending_upload_file_names(
+            &sqlite_connection,
+            settings.upload_queue.max_attempts,
+        )?
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+        let expired = select_expired(
+            &files,
+            &pending_upload,
+            retention.max_bytes.max(0) as u64,
+            max_age,
+            SystemTime::now(),
+        );
+        for file in expired {
+            if let Err(e) = fs::remove_file(&file.path) {
+                error!(
+                    "Failed to delete file path={} error={}",
+                    file.path.display(),
+                    e
+                );
+                continue;
+            }
+            debug!(
+                "Deleted file path={} size={}",
+                file.path.display(),
+                file.size
+            );
+            VideoRecordingPart::mark_deleted_by_file_name(
+                &sqlite_connection,
+                &file.path.display().to_string(),
+            )?;
+            deleted_files += 1;
+            deleted_bytes += file.size;
+        }
+        used_bytes -= deleted_bytes;
+        file_count -= deleted_files;
+        if deleted_files > 0 {
+            info!(
+                "Retention manager deleted files={} bytes={} used_bytes={} max_bytes={}",
+                deleted_files, deleted_bytes, used_bytes, retention.max_bytes
+            );
+        }
+    }
+
+    let (datafs_size, datafs_used) = datafs_usage();
+    Ok(RetentionStatus {
+        enabled: retention.enabled,
+        dirs,
+        max_bytes: retention.max_bytes,
+        max_age_secs: retention.max_age_secs,
+        used_bytes,
+        file_count,
+        deleted_files,
+        deleted_bytes,
+        datafs_size,
+        datafs_used,
+        recording_allowed: recording_allowed(retention, datafs_size, datafs_used),
+        timestamp: Utc::now().to_rfc3339(),
+    })
+}
+
+// delete files exceeding RetentionSettings budget and age policy
+pub fn enforce(settings: &PrintNannySettings) -> Result<RetentionStatus, RetentionError> {
+    run(settings, false)
+}
+
+// disk usage without deleting any files
+pub fn status(settings: &PrintNannySettings) -> Result<RetentionStatus, RetentionError> {
+    run(settings, true)
+}
+
+// enforce retention policy every RetentionSettings.interval_secs
+pub async fn run_retention_manager() -> Result<(), RetentionError> {
+    loop {
+        let settings = PrintNannySettings::new().await?;
+        let interval = Duration::from_secs(settings.retention.interval_secs.max(1) as u64);
+        if settings.retention.enabled {
+            match tokio::task::spawn_blocking(move || enforce(&settings)).await {
+                Ok(Ok(status)) => debug!("Retention manager status={:?}", status),
+                Ok(Err(e)) => error!("Retention manager failed error={}", e),
+                Err(e) => error!("Retention manager task failed error={}", e),
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64, modified: SystemTime) -> RetentionFile {
+        RetentionFile {
+            path: PathBuf::from(name),
+            size,
+            modified,
+        }
+    }
+
+    #[test]
+    fn test_select_expired() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100_000);
+        let files = vec![
+            file("new.ts", 100, now - Duration::from_secs(10)),
+            file("old.ts", 100, now - Duration::from_secs(5000)),
+            file("middle.ts", 100, now - Duration::from_secs(100)),
+        ];
+        let names = |selected: Vec<&RetentionFile>| -> Vec<String> {
+            selected
+                .iter()
+                .map(|f| f.path.display().to_string())
+                .collect()
+        };
+
+        assert!(select_expired(&files, &[], 300, None, now).is_empty());
+        assert_eq!(
+            names(select_expired(&files, &[], 250, None, now)),
+            vec!["old.ts"]
+        );
+        assert_eq!(
+            names(select_expired(&files, &[], 100, None, now)),
+            vec!["old.ts", "middle.ts"]
+        );
+        assert_eq!(
+            names(select_expired(
+                &files,
+                &[],
+                300,
+                Some(Duration::from_secs(60)),
+                now
+            )),
+            vec!["old.ts", "middle.ts"]
+        );
+        // pending upload, the next oldest file is deleted instead
+        assert_eq!(
+            names(select_expired(
+                &files,
+                &[PathBuf::from("old.ts")],
+                250,
+                None,
+                now
+            )),
+            vec!["middle.ts"]
+        );
+    }
+
+    #[test]
+    fn test_recording_allowed() {
+        let settings = RetentionSettings {
+            enabled: true,
+            ..RetentionSettings::default()
+        };
+        assert!(recording_allowed(&settings, 0, 0));
+        assert!(recording_allowed(&settings, 1000, 900));
+        assert!(!recording_allowed(&settings, 1000, 901));
+        let disabled = RetentionSettings::default();
+        assert!(!disabled.enabled);
+        assert!(recording_allowed(&disabled, 1000, 1000));
+    }
+
+    #[test]
+    fn test_list_files() {
+        let dir =
+            std::env::temp_dir().join(format!("printnanny-retention-test-{}", std::process::id()));
+        let active = dir.join("video/active");
+        fs::create_dir_all(&active).unwrap();
+        fs::create_dir_all(dir.join("hls")).unwrap();
+        fs::write(dir.join("video/0.ts"), b"video").unwrap();
+        fs::write(active.join("0.ts"), b"active").unwrap();
+        fs::write(dir.join("hls/segment00001.ts"), b"hls").unwrap();
+        fs::write(dir.join("hls/playlist.m3u8"), b"").unwrap();
+
+        let dirs = vec![dir.join("video"), dir.join("hls"), dir.join("missing")];
+        let mut files = list_files(&dirs, &[active]).unwrap();
+        files.sort_by_key(|f| f.path.clone());
+        let paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(
+            paths,
+            vec![dir.join("hls/segment00001.ts"), dir.join("video/0.ts")]
+        );
+        assert_eq!(files.iter().map(|f| f.size).sum::<u64>(), 8);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,78 +1,275 @@
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
-use log::{error, info};
-use tokio::task::JoinSet;
+use futures::stream::{self, StreamExt};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
 
 use crate::error::VideoRecordingSyncError;
 use crate::printnanny_api::ApiService;
 
 use printnanny_edge_db::video_recording;
-use printnanny_settings::printnanny::PrintNannySettings;
+use printnanny_settings::printnanny::{PrintNannySettings, UploadQueueSettings};
 
-pub async fn upload_video_recording_part(
-    row: video_recording::VideoRecordingPart,
-) -> Result<video_recording::VideoRecordingPart, VideoRecordingSyncError> {
-    // create/update cloud model
-    let settings = PrintNannySettings::new().await?;
-    let sqlite_connection = settings.paths.db().display().to_string();
+// VideoRecordingPart rows are the persistent upload queue: a part is pending until PrintNanny Cloud confirms receipt (sync_end is set)
+// videos_api::video_parts_create sends the whole file in one multipart request without a bandwidth limit, so a failed part is re-sent in full
+// RecordingFormatSettings.segment_bytes bounds how much is re-sent after a failure
+const UPLOAD_QUEUE_BATCH_SIZE: i64 = 100;
 
-    let api = ApiService::new(settings.cloud, sqlite_connection.clone());
-    let result = api.video_recording_part_create(&row).await?;
+// a part the upload queue gave up on after UploadQueueSettings.max_attempts failed uploads
+// it is no longer retried and retention may delete its file, in which case deleted is true
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FailedUpload {
+    pub id: String,
+    pub file_name: String,
+    pub video_recording_id: String,
+    pub sync_attempts: i64,
+    pub sync_error: Option<String>,
+    pub deleted: bool,
+}
 
-    let row = printnanny_edge_db::video_recording::VideoRecordingPart::get_by_id(
+impl From<video_recording::VideoRecordingPart> for FailedUpload {
+    fn from(row: video_recording::VideoRecordingPart) -> Self {
+        Self {
+            id: row.id,
+            file_name: row.file_name,
+            video_recording_id: row.video_recording_id,
+            sync_attempts: row.sync_attempts,
+            sync_error: row.sync_error,
+            deleted: row.deleted,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct UploadQueueStatus {
+    // parts waiting for upload or retry
+    pub pending: usize,
+    pub max_attempts: i64,
+    pub failed: Vec<FailedUpload>,
+    pub timestamp: String,
+}
+
+pub fn status(settings: &PrintNannySettings) -> Result<UploadQueueStatus, VideoRecordingSyncError> {
+    let sqlite_connection = settings.paths.db().display().to_string();
+    let max_attempts = settings.upload_queue.max_attempts;
+    let pending = video_recording::VideoRecordingPart::get_pending_upload_file_names(
         &sqlite_connection,
-        &row.id,
-    )?;
+        max_attempts,
+    )?
+    .len();
+    let failed =
+        video_recording::VideoRecordingPart::get_upload_failed(&sqlite_connection, max_attempts)?
+            .into_iter()
+            .map(FailedUpload::from)
+            .collect();
+    Ok(UploadQueueStatus {
+        pending,
+        max_attempts,
+        failed,
+        timestamp: Utc::now().to_rfc3339(),
+    })
+}
+
+// exponential backoff after attempts failed uploads: backoff_secs * 2^(attempts - 1), capped at backoff_max_secs
+pub fn backoff_delay(settings: &UploadQueueSettings, attempts: i64) -> Duration {
+    let exponent = (attempts - 1).clamp(0, 32) as u32;
+    let secs = settings
+        .backoff_secs
+        .max(0)
+        .saturating_mul(2_i64.saturating_pow(exponent))
+        .min(settings.backoff_max_secs.max(0));
+    Duration::from_secs(secs as u64)
+}
 
-    let sync_start_value = <chrono::DateTime<chrono::FixedOffset> as std::convert::Into<
-        DateTime<Utc>,
-    >>::into(DateTime::parse_from_rfc3339(&result.sync_start).unwrap());
-    let sync_end_value = <chrono::DateTime<chrono::FixedOffset> as std::convert::Into<
-        DateTime<Utc>,
-    >>::into(DateTime::parse_from_rfc3339(&result.sync_end).unwrap());
+// parse a timestamp returned by PrintNanny Cloud
+fn parse_cloud_timestamp(
+    id: &str,
+    field: &str,
+    value: &str,
+) -> Result<DateTime<Utc>, VideoRecordingSyncError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(Into::into)
+        .map_err(|source| VideoRecordingSyncError::InvalidTimestamp {
+            id: id.to_string(),
+            field: field.to_string(),
+            value: value.to_string(),
+            source,
+        })
+}
+
+async fn upload_confirmed(
+    api: &ApiService,
+    row: &video_recording::VideoRecordingPart,
+) -> Result<video_recording::VideoRecordingPart, VideoRecordingSyncError> {
+    let result = api.video_recording_part_create(row).await?;
 
-    let duration = sync_start_value.signed_duration_since(sync_end_value);
+    // only delete local file after PrintNanny Cloud confirms receipt of this part: the response must be for this part and carry sync_end
+    if result.id != row.id || result.sync_end.is_empty() {
+        return Err(VideoRecordingSyncError::UploadNotConfirmed {
+            id: row.id.clone(),
+            file_name: row.file_name.clone(),
+        });
+    }
+    let sync_start_value = parse_cloud_timestamp(&row.id, "sync_start", &result.sync_start)?;
+    let sync_end_value = parse_cloud_timestamp(&row.id, "sync_end", &result.sync_end)?;
+
+    let duration = sync_end_value.signed_duration_since(sync_start_value);
     info!(
         "Finished uploading VideoRecordingPart id={} in ms={}",
         &row.id,
         duration.num_milliseconds(),
     );
 
-    tokio::fs::remove_file(&row.file_name).await?;
-    info!(
-        "Deleted file VideoRecordingPart id={} file={}",
-        &row.id, &row.file_name
-    );
-    let row = printnanny_edge_db::video_recording::VideoRecordingPart::get_by_id(
-        &sqlite_connection,
-        &row.id,
-    )?;
+    let row = video_recording::VideoRecordingPart::get_by_id(&api.sqlite_connection, &row.id)?;
+    match tokio::fs::remove_file(&row.file_name).await {
+        Ok(()) => info!(
+            "Deleted file VideoRecordingPart id={} file={}",
+            &row.id, &row.file_name
+        ),
+        // upload is confirmed, so the part is not re-queued
+        Err(e) => warn!(
+            "Failed to delete file VideoRecordingPart id={} file={} error={}",
+            &row.id, &row.file_name, e
+        ),
+    }
     Ok(row)
 }
 
+async fn upload_part(
+    api: &ApiService,
+    settings: &UploadQueueSettings,
+    row: video_recording::VideoRecordingPart,
+) -> Result<video_recording::VideoRecordingPart, VideoRecordingSyncError> {
+    match upload_confirmed(api, &row).await {
+        Ok(row) => Ok(row),
+        Err(e) => {
+            let attempts = row.sync_attempts + 1;
+            let delay = backoff_delay(settings, attempts);
+            let next_attempt = Utc::now()
+                + chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::zero());
+            if settings.max_attempts > 0 && attempts >= settings.max_attempts {
+                error!(
+                    "Giving up on VideoRecordingPart id={} file={} after attempts={} error={}",
+                    &row.id, &row.file_name, attempts, e
+                );
+            } else {
+                warn!(
+                    "Failed to upload VideoRecordingPart id={} attempts={} next_attempt={} error={}",
+                    &row.id, attempts, next_attempt, e
+                );
+            }
+            video_recording::VideoRecordingPart::record_upload_failure(
+                &api.sqlite_connection,
+                &row.id,
+                &e.to_string(),
+                &next_attempt,
+            )?;
+            Err(e)
+        }
+    }
+}
+
+pub async fn upload_video_recording_part(
+    row: video_recording::VideoRecordingPart,
+) -> Result<video_recording::VideoRecordingPart, VideoRecordingSyncError> {
+    let settings = PrintNannySettings::new().await?;
+    let api = ApiService::from(&settings);
+    upload_part(&api, &settings.upload_queue, row).await
+}
+
+// upload all queued parts whose backoff has elapsed, at most UploadQueueSettings.concurrency at a time
 pub async fn sync_all_video_recordings() -> Result<(), VideoRecordingSyncError> {
     let settings = PrintNannySettings::new().await?;
-    let sqlite_connection = settings.paths.db().display().to_string();
-    // select all recording parts that have not been uploaded
-    let parts = video_recording::VideoRecordingPart::get_ready_for_cloud_sync(&sqlite_connection)?;
+    let api = ApiService::from(&settings);
+    let queue_settings = settings.upload_queue;
+    let concurrency = queue_settings.concurrency.max(1) as usize;
 
-    let count = parts.len();
-    info!("{} video recording parts ready for cloud sync", count);
+    let mut count = 0;
+    let mut failed = 0;
+    loop {
+        let parts = video_recording::VideoRecordingPart::get_upload_queue(
+            &api.sqlite_connection,
+            &Utc::now(),
+            queue_settings.max_attempts,
+            UPLOAD_QUEUE_BATCH_SIZE,
+        )?;
+        if parts.is_empty() {
+            break;
+        }
+        info!("{} video recording parts ready for cloud sync", parts.len());
 
-    let mut set = JoinSet::new();
-    for part in parts {
-        set.spawn(upload_video_recording_part(part));
-    }
+        let results: Vec<Result<video_recording::VideoRecordingPart, VideoRecordingSyncError>> =
+            stream::iter(parts)
+                .map(|part| upload_part(&api, &queue_settings, part))
+                .buffer_unordered(concurrency)
+                .collect()
+                .await;
 
-    while let Some(Ok(res)) = set.join_next().await {
-        match res {
-            Ok(part) => {
-                info!("Finished syncing video recording part.id={}", part.id);
-            }
-            Err(e) => {
-                error!("Error syncing video recording part error={}", e);
+        let mut batch_failed = 0;
+        for res in results.iter() {
+            match res {
+                Ok(part) => {
+                    info!("Finished syncing video recording part.id={}", part.id);
+                }
+                Err(e) => {
+                    batch_failed += 1;
+                    error!("Error syncing video recording part error={}", e);
+                }
             }
         }
+        count += results.len();
+        failed += batch_failed;
+        // every part in batch failed, retry on next pass instead of spinning on parts with zero backoff
+        if batch_failed == results.len() {
+            break;
+        }
+    }
+    info!(
+        "Finished syncing {} video recording parts, failed={}",
+        count, failed
+    );
+    let gave_up = video_recording::VideoRecordingPart::get_upload_failed(
+        &api.sqlite_connection,
+        queue_settings.max_attempts,
+    )?;
+    if !gave_up.is_empty() {
+        error!(
+            "{} video recording parts failed to upload after max_attempts={}",
+            gave_up.len(),
+            queue_settings.max_attempts
+        );
     }
-    info!("Finished syncing {} video recording parts", count);
     Ok(())
 }
+
+// drain upload queue every UploadQueueSettings.interval_secs
+pub async fn run_upload_queue() -> Result<(), VideoRecordingSyncError> {
+    loop {
+        let settings = PrintNannySettings::new().await?;
+        let interval = Duration::from_secs(settings.upload_queue.interval_secs.max(1) as u64);
+        if let Err(e) = sync_all_video_recordings().await {
+            error!("Upload queue failed error={}", e);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay() {
+        let settings = UploadQueueSettings {
+            backoff_secs: 10,
+            backoff_max_secs: 100,
+            ..UploadQueueSettings::default()
+        };
+        assert_eq!(backoff_delay(&settings, 1), Duration::from_secs(10));
+        assert_eq!(backoff_delay(&settings, 2), Duration::from_secs(20));
+        assert_eq!(backoff_delay(&settings, 4), Duration::from_secs(80));
+        assert_eq!(backoff_delay(&settings, 5), Duration::from_secs(100));
+        assert_eq!(backoff_delay(&settings, 1000), Duration::from_secs(100));
+    }
+}
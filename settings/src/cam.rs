@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::process::Output;
 
 use clap::ArgMatches;
@@ -10,11 +12,15 @@ use gst::prelude::DeviceExt;
 use gst::prelude::DeviceProviderExtManual;
 
 use crate::error::PrintNannySettingsError;
+use crate::model::{DetectionModelSettings, ModelManifest};
 
 const DEFAULT_COLORIMETRY: &str = "bt709";
 const DEFAULT_PIXEL_FORMAT: &str = "YUY2";
 const COMPAT_PIXEL_FORMATS: [&str; 1] = ["YUY2"];
 
+// camera_id of PrintNannySettings.video_stream, whose pipelines keep un-prefixed names (e.g. "camera")
+pub const DEFAULT_CAMERA_ID: &str = "default";
+
 #[derive(Debug, Clone, clap::ValueEnum, Deserialize, Serialize, PartialEq, Eq)]
 pub enum VideoSrcType {
     File,
@@ -298,9 +304,74 @@ impl CameraVideoSource {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
 pub struct MediaVideoSource {
+    // file path (or file:// uri) for VideoSource::File, rtsp:// or http(s):// uri for VideoSource::Uri
     pub uri: String,
+    // restart file playback from the beginning on end-of-stream
+    #[serde(default)]
+    pub loop_playback: bool,
+}
+
+impl MediaVideoSource {
+    const FILE_SCHEME: &'static str = "file://";
+    const URI_SCHEMES: [&'static str; 4] = ["rtsp://", "rtsps://", "http://", "https://"];
+    // multifilesrc loop=true replays the file's bytes, which only decodes for streamable containers
+    const LOOP_EXTENSIONS: [&'static str; 3] = ["ts", "m2ts", "mts"];
+
+    pub fn new(uri: &str) -> Self {
+        Self {
+            uri: uri.into(),
+            loop_playback: false,
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.uri.starts_with(Self::FILE_SCHEME) || self.uri.starts_with('/')
+    }
+
+    // true if device_name refers to a file or network stream, instead of a libcamera device
+    pub fn is_media_uri(device_name: &str) -> bool {
+        device_name.starts_with(Self::FILE_SCHEME)
+            || Self::URI_SCHEMES
+                .iter()
+                .any(|scheme| device_name.starts_with(scheme))
+    }
+
+    // absolute file path, without file:// scheme
+    pub fn file_location(&self) -> &str {
+        self.uri
+            .strip_prefix(Self::FILE_SCHEME)
+            .unwrap_or(self.uri.as_str())
+    }
+
+    // loop_playback is only supported for mpegts files, e.g. recordings made with RecordingContainer::Ts
+    pub fn validate(&self) -> Result<(), PrintNannySettingsError> {
+        if !self.loop_playback {
+            return Ok(());
+        }
+        let extension = Path::new(self.file_location())
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        let loopable = self.is_file()
+            && extension.map_or(false, |ext| Self::LOOP_EXTENSIONS.contains(&ext.as_str()));
+        if !loopable {
+            return Err(PrintNannySettingsError::InvalidValue {
+                value: format!("loop_playback uri={}", self.uri),
+            });
+        }
+        Ok(())
+    }
+
+    // uri with scheme, as expected by uridecodebin
+    pub fn gst_uri(&self) -> String {
+        if self.uri.starts_with('/') {
+            format!("{}{}", Self::FILE_SCHEME, self.uri)
+        } else {
+            self.uri.clone()
+        }
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
@@ -333,6 +404,14 @@ impl From<&CameraVideoSource> for printnanny_os_models::camera::Camera {
 
 impl From<printnanny_os_models::Camera> for VideoSource {
     fn from(camera: printnanny_os_models::Camera) -> VideoSource {
+        // CameraSourceType has no file/uri variants, so media sources are identified by device_name scheme
+        if MediaVideoSource::is_media_uri(&camera.device_name) {
+            let media = MediaVideoSource::new(&camera.device_name);
+            return match media.is_file() {
+                true => VideoSource::File(media),
+                false => VideoSource::Uri(media),
+            };
+        }
         match *camera.src_type {
             printnanny_os_models::CameraSourceType::Csi => {
                 VideoSource::CSI(CameraVideoSource {
@@ -374,14 +453,643 @@ impl From<VideoSource> for printnanny_os_models::Camera {
                 device_name: camera.device_name.clone(),
                 available_caps: camera.list_available_caps(),
             },
+            VideoSource::File(media) | VideoSource::Uri(media) => printnanny_os_models::Camera {
+                selected_caps: Box::new(CameraVideoSource::default_caps()),
+                // decoded media is converted to selected_caps, so no other caps are advertised
+                available_caps: vec![CameraVideoSource::default_caps()],
+                src_type: Box::new(printnanny_os_models::CameraSourceType::Usb),
+                index: -1,
+                label: media.uri.clone(),
+                device_name: media.gst_uri(),
+            },
+        }
+    }
+}
+
+// Kind of gstd pipeline, which determines the pipeline description rendered by PrintNannyPipelineFactory
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum PipelineNodeKind {
+    #[serde(rename = "camera")]
+    Camera,
+    #[serde(rename = "h264_encode")]
+    H264Encode,
+    #[serde(rename = "rtp")]
+    Rtp,
+    #[serde(rename = "hls")]
+    Hls,
+    #[serde(rename = "tflite_inference")]
+    Inference,
+    #[serde(rename = "bounding_boxes")]
+    BoundingBoxes,
+    #[serde(rename = "df")]
+    Dataframe,
+    #[serde(rename = "snapshot")]
+    Snapshot,
+    #[serde(rename = "preroll")]
+    Preroll,
+}
 
-            _ => todo!(),
+impl PipelineNodeKind {
+    // name of the default pipeline for this kind
+    pub fn default_name(&self) -> &'static str {
+        match self {
+            PipelineNodeKind::Camera => "camera",
+            PipelineNodeKind::H264Encode => "h264_encode",
+            PipelineNodeKind::Rtp => "rtp",
+            PipelineNodeKind::Hls => "hls",
+            PipelineNodeKind::Inference => "tflite_inference",
+            PipelineNodeKind::BoundingBoxes => "bounding_boxes",
+            PipelineNodeKind::Dataframe => "df",
+            PipelineNodeKind::Snapshot => "snapshot",
+            PipelineNodeKind::Preroll => "preroll",
         }
     }
+
+    // pipelines rendered from the active ModelManifest, re-created when the active model changes
+    pub fn uses_detection_model(&self) -> bool {
+        matches!(
+            self,
+            PipelineNodeKind::Inference
+                | PipelineNodeKind::BoundingBoxes
+                | PipelineNodeKind::Dataframe
+        )
+    }
+}
+
+// A single gstd pipeline in the PrintNanny Vision pipeline graph
+// Pipelines are connected by interpipesink (upstream) -> interpipesrc (downstream) edges
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct PipelineNodeSettings {
+    pub name: String,
+    pub kind: PipelineNodeKind,
+    pub enabled: bool,
+    // name of the upstream pipeline this pipeline's interpipesrc listens to
+    pub listen_to: Option<String>,
+    // overrides RtpSettings.video_udp_port (rtp) or RtpSettings.overlay_udp_port (bounding_boxes)
+    pub udp_port: Option<i32>,
+}
+
+impl PipelineNodeSettings {
+    pub fn new(kind: PipelineNodeKind, listen_to: Option<PipelineNodeKind>) -> Self {
+        Self {
+            name: kind.default_name().into(),
+            kind,
+            enabled: true,
+            listen_to: listen_to.map(|v| v.default_name().into()),
+            udp_port: None,
+        }
+    }
+}
+
+pub fn default_pipelines() -> Vec<PipelineNodeSettings> {
+    vec![
+        PipelineNodeSettings::new(PipelineNodeKind::Camera, None),
+        PipelineNodeSettings::new(
+            PipelineNodeKind::H264Encode,
+            Some(PipelineNodeKind::Camera),
+        ),
+        PipelineNodeSettings::new(PipelineNodeKind::Rtp, Some(PipelineNodeKind::H264Encode)),
+        PipelineNodeSettings::new(
+            PipelineNodeKind::Inference,
+            Some(PipelineNodeKind::Camera),
+        ),
+        PipelineNodeSettings::new(
+            PipelineNodeKind::BoundingBoxes,
+            Some(PipelineNodeKind::Inference),
+        ),
+        PipelineNodeSettings::new(
+            PipelineNodeKind::Dataframe,
+            Some(PipelineNodeKind::Inference),
+        ),
+        PipelineNodeSettings::new(PipelineNodeKind::Snapshot, Some(PipelineNodeKind::Camera)),
+        PipelineNodeSettings::new(PipelineNodeKind::Hls, Some(PipelineNodeKind::H264Encode)),
+        PipelineNodeSettings::new(
+            PipelineNodeKind::Preroll,
+            Some(PipelineNodeKind::H264Encode),
+        ),
+    ]
+}
+
+// returns true if a Gstreamer element factory with name is registered
+fn gst_element_available(name: &str) -> bool {
+    match gst::init() {
+        Ok(()) => gst::ElementFactory::find(name).is_some(),
+        Err(e) => {
+            error!("Failed to initialize gstreamer error={}", e);
+            false
+        }
+    }
+}
+
+// Resolve "auto" to the first available element, falling back to hardware element when none are registered
+fn resolve_gst_element<T: Copy>(choices: &[(T, &str)]) -> T {
+    for (choice, element) in choices.iter() {
+        if gst_element_available(element) {
+            debug!("Selected gstreamer element={}", element);
+            return *choice;
+        }
+    }
+    choices[0].0
+}
+
+// Source element of the camera pipeline
+#[derive(
+    Copy, Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub enum CameraSrcElement {
+    #[default]
+    #[serde(rename = "libcamerasrc")]
+    Libcamera,
+    // synthetic test pattern, used to run pipelines on machines without a camera
+    #[serde(rename = "videotestsrc")]
+    VideoTest,
+}
+
+#[derive(
+    Copy, Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub enum VideoConvertElement {
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "v4l2convert")]
+    V4l2,
+    #[serde(rename = "videoconvert")]
+    VideoConvert,
+}
+
+#[derive(
+    Copy, Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub enum H264EncoderElement {
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "v4l2h264enc")]
+    V4l2,
+    #[serde(rename = "x264enc")]
+    X264,
+    #[serde(rename = "openh264enc")]
+    OpenH264,
+}
+
+#[derive(
+    Copy, Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub enum JpegEncoderElement {
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "v4l2jpegenc")]
+    V4l2,
+    #[serde(rename = "jpegenc")]
+    Jpeg,
+}
+
+// Gstreamer elements used by PrintNannyPipelineFactory
+// "auto" is replaced by the elements of a GstElementTarget before pipelines are rendered, see GstElementSettings::resolve_with
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct GstElementSettings {
+    #[serde(default)]
+    pub camera_src: CameraSrcElement,
+    #[serde(default)]
+    pub video_convert: VideoConvertElement,
+    #[serde(default)]
+    pub h264_encoder: H264EncoderElement,
+    #[serde(default)]
+    pub jpeg_encoder: JpegEncoderElement,
+}
+
+impl GstElementSettings {
+    // replace "auto" elements by probing this host's Gstreamer element registry
+    // "auto" prefers Raspberry Pi V4L2 M2M elements, falling back to software elements found in the element registry
+    pub fn resolve(&self) -> Self {
+        let video_convert = match self.video_convert {
+            VideoConvertElement::Auto => resolve_gst_element(&[
+                (VideoConvertElement::V4l2, "v4l2convert"),
+                (VideoConvertElement::VideoConvert, "videoconvert"),
+            ]),
+            other => other,
+        };
+        let h264_encoder = match self.h264_encoder {
+            H264EncoderElement::Auto => resolve_gst_element(&[
+                (H264EncoderElement::V4l2, "v4l2h264enc"),
+                (H264EncoderElement::X264, "x264enc"),
+                (H264EncoderElement::OpenH264, "openh264enc"),
+            ]),
+            other => other,
+        };
+        let jpeg_encoder = match self.jpeg_encoder {
+            JpegEncoderElement::Auto => resolve_gst_element(&[
+                (JpegEncoderElement::V4l2, "v4l2jpegenc"),
+                (JpegEncoderElement::Jpeg, "jpegenc"),
+            ]),
+            other => other,
+        };
+        Self {
+            camera_src: self.camera_src,
+            video_convert,
+            h264_encoder,
+            jpeg_encoder,
+        }
+    }
+
+    // replace "auto" elements with target's elements, without probing the element registry
+    pub fn resolve_with(&self, target: &GstElementSettings) -> Self {
+        Self {
+            camera_src: self.camera_src,
+            video_convert: match self.video_convert {
+                VideoConvertElement::Auto => target.video_convert,
+                other => other,
+            },
+            h264_encoder: match self.h264_encoder {
+                H264EncoderElement::Auto => target.h264_encoder,
+                other => other,
+            },
+            jpeg_encoder: match self.jpeg_encoder {
+                JpegEncoderElement::Auto => target.jpeg_encoder,
+                other => other,
+            },
+        }
+    }
+}
+
+// Elements selected for "auto" GstElementSettings
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GstElementTarget {
+    // probe the Gstreamer element registry of the host rendering pipelines
+    #[default]
+    Host,
+    // Raspberry Pi V4L2 M2M elements, used to review pipelines rendered on another machine
+    RaspberryPi,
+    // software elements
+    Software,
+}
+
+impl GstElementTarget {
+    pub fn elements(&self) -> GstElementSettings {
+        match self {
+            GstElementTarget::Host => GstElementSettings::default().resolve(),
+            GstElementTarget::RaspberryPi => GstElementSettings {
+                camera_src: CameraSrcElement::Libcamera,
+                video_convert: VideoConvertElement::V4l2,
+                h264_encoder: H264EncoderElement::V4l2,
+                jpeg_encoder: JpegEncoderElement::V4l2,
+            },
+            GstElementTarget::Software => GstElementSettings {
+                camera_src: CameraSrcElement::Libcamera,
+                video_convert: VideoConvertElement::VideoConvert,
+                h264_encoder: H264EncoderElement::X264,
+                jpeg_encoder: JpegEncoderElement::Jpeg,
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for GstElementTarget {
+    type Err = PrintNannySettingsError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().replace('_', "-").as_str() {
+            "host" => Ok(GstElementTarget::Host),
+            "raspberry-pi" | "rpi" => Ok(GstElementTarget::RaspberryPi),
+            "software" => Ok(GstElementTarget::Software),
+            other => Err(PrintNannySettingsError::InvalidValue {
+                value: other.to_string(),
+            }),
+        }
+    }
+}
+
+// Event which captures a timelapse frame
+#[derive(
+    Copy, Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub enum TimelapseTrigger {
+    // capture a frame every TimelapseSettings.interval_secs
+    #[default]
+    #[serde(rename = "interval")]
+    Interval,
+    // capture a frame on each layer change reported by OctoPrint gcode events
+    #[serde(rename = "layer")]
+    Layer,
+}
+
+impl TimelapseTrigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimelapseTrigger::Interval => "interval",
+            TimelapseTrigger::Layer => "layer",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct TimelapseSettings {
+    // directory containing one sub-directory of frames (and assembled mp4) per print job
+    pub path: String,
+    pub enabled: bool,
+    #[serde(default)]
+    pub trigger: TimelapseTrigger,
+    pub interval_secs: i32,
+    // framerate of the assembled mp4
+    pub framerate: i32,
+}
+
+impl Default for TimelapseSettings {
+    fn default() -> Self {
+        Self {
+            path: "/home/printnanny/.local/share/printnanny/timelapse".into(),
+            enabled: false,
+            trigger: TimelapseTrigger::Interval,
+            interval_secs: 10,
+            framerate: 30,
+        }
+    }
+}
+
+// Container written by the recording pipeline's splitmuxsink
+#[derive(
+    Copy, Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub enum RecordingContainer {
+    #[serde(rename = "mp4")]
+    Mp4,
+    // mp4 with fragments written every second, playable if the recording is interrupted
+    #[serde(rename = "fmp4")]
+    FragmentedMp4,
+    #[serde(rename = "mkv")]
+    Mkv,
+    #[default]
+    #[serde(rename = "ts")]
+    Ts,
+}
+
+impl RecordingContainer {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordingContainer::Mp4 | RecordingContainer::FragmentedMp4 => "mp4",
+            RecordingContainer::Mkv => "mkv",
+            RecordingContainer::Ts => "ts",
+        }
+    }
+
+    // splitmuxsink muxer properties
+    pub fn muxer_description(&self) -> &'static str {
+        match self {
+            RecordingContainer::Mp4 => "muxer=mp4mux",
+            RecordingContainer::FragmentedMp4 => {
+                "muxer-factory=mp4mux muxer-properties=properties,fragment-duration=1000"
+            }
+            RecordingContainer::Mkv => "muxer=matroskamux",
+            RecordingContainer::Ts => "muxer=mpegtsmux",
+        }
+    }
+}
+
+// Container and segment policy of video recordings
+// printnanny_os_models::RecordingSettings is shared with PrintNanny Cloud, so these are configured in a separate table
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct RecordingFormatSettings {
+    pub container: RecordingContainer,
+    // a new segment is started when either limit is reached, 0 disables the limit
+    pub segment_bytes: i64,
+    pub segment_secs: i32,
+    // number of segments kept on disk, 0 keeps all segments
+    pub max_files: i32,
+    // segment filename without extension, must contain a single integer conversion, e.g. "%d" or "segment%05d"
+    // the index is parsed back from the last run of digits, so digits may not follow or directly precede the conversion
+    pub filename_template: String,
+}
+
+impl Default for RecordingFormatSettings {
+    fn default() -> Self {
+        Self {
+            container: RecordingContainer::Ts,
+            segment_bytes: 10_000_000, // 10MB
+            segment_secs: 0,
+            max_files: 50,
+            filename_template: "%d".into(),
+        }
+    }
+}
+
+impl RecordingFormatSettings {
+    pub fn validate(&self) -> Result<(), PrintNannySettingsError> {
+        let template = &self.filename_template;
+        let conversion = Regex::new(r"%0?\d*d").unwrap();
+        let valid = conversion.find_iter(template).count() == 1
+            && template.matches('%').count() == 1
+            && !template.contains(|c: char| c == '/' || c == '.' || c.is_whitespace())
+            && conversion.find(template).map_or(false, |m| {
+                !template[..m.start()].ends_with(|c: char| c.is_ascii_digit())
+                    && !template[m.end()..].contains(|c: char| c.is_ascii_digit())
+            });
+        if !valid {
+            return Err(PrintNannySettingsError::InvalidValue {
+                value: template.clone(),
+            });
+        }
+        if self.segment_bytes < 0 || self.segment_secs < 0 || self.max_files < 0 {
+            return Err(PrintNannySettingsError::InvalidValue {
+                value: format!("{self:?}"),
+            });
+        }
+        Ok(())
+    }
+
+    // splitmuxsink location in dir, e.g. {dir}/%d.ts
+    pub fn location(&self, dir: &str) -> String {
+        format!(
+            "{}/{}.{}",
+            dir.trim_end_matches('/'),
+            self.filename_template,
+            self.container.extension()
+        )
+    }
+
+    // splitmuxsink muxer, segment and retention properties
+    pub fn splitmuxsink_properties(&self) -> String {
+        let mut properties = vec![self.container.muxer_description().to_string()];
+        if self.max_files > 0 {
+            properties.push(format!("max-files={}", self.max_files));
+        }
+        if self.segment_bytes > 0 {
+            properties.push(format!("max-size-bytes={}", self.segment_bytes));
+        }
+        if self.segment_secs > 0 {
+            properties.push(format!(
+                "max-size-time={}",
+                self.segment_secs as u64 * 1_000_000_000
+            ));
+        }
+        properties.join(" ")
+    }
+}
+
+// Continuously written ring buffer of H.264 fragments, which can be committed into a VideoRecording
+// including the seconds before (pre-roll) and after (post-roll) the commit request
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct PrerollSettings {
+    // ring buffer directory, should be backed by tmpfs
+    pub path: String,
+    // opt-in, the ring buffer continuously writes to path while the camera is running
+    pub enabled: bool,
+    // target duration of each fragment; fragments are split on keyframes, so committed recordings are aligned to fragment boundaries
+    pub fragment_secs: i32,
+    pub preroll_secs: i32,
+    pub postroll_secs: i32,
+    // commit a recording when the windowed mean score of trigger_label exceeds trigger_threshold (0-100)
+    pub trigger_enabled: bool,
+    pub trigger_label: String,
+    pub trigger_threshold: i32,
+    // minimum time between detection-triggered commits
+    pub trigger_cooldown_secs: i32,
+}
+
+impl Default for PrerollSettings {
+    fn default() -> Self {
+        Self {
+            path: "/var/run/printnanny-preroll".into(),
+            enabled: false,
+            fragment_secs: 4,
+            preroll_secs: 10,
+            postroll_secs: 10,
+            trigger_enabled: false,
+            trigger_label: "spaghetti".into(),
+            trigger_threshold: 50,
+            trigger_cooldown_secs: 300,
+        }
+    }
+}
+
+impl PrerollSettings {
+    // splitmuxsink max-files: enough fragments to cover pre-roll and post-roll, plus the fragment being written
+    pub fn max_files(&self) -> i32 {
+        let fragment_secs = self.fragment_secs.max(1);
+        let window_secs = self.preroll_secs.max(0) + self.postroll_secs.max(0);
+        (window_secs + fragment_secs - 1) / fragment_secs + 2
+    }
+}
+
+// Score print failures from dataframe_agg windows, raising a debounced print quality alert
+// when either rule is confirmed for enough consecutive windows, see printnanny_nats_apps::failure
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct FailureAlertSettings {
+    pub enabled: bool,
+    // failure rule: windowed mean score of failure_label exceeds failure_threshold (0-100)
+    pub failure_label: String,
+    pub failure_threshold: i32,
+    pub failure_windows: i32,
+    // missing rule: missing_label (e.g. the nozzle) is not detected; an empty label disables the rule
+    pub missing_label: String,
+    pub missing_windows: i32,
+    // minimum time between alerts
+    pub cooldown_secs: i32,
+}
+
+impl Default for FailureAlertSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            failure_label: "spaghetti".into(),
+            failure_threshold: 50,
+            failure_windows: 3,
+            missing_label: "nozzle".into(),
+            missing_windows: 10,
+            cooldown_secs: 600,
+        }
+    }
+}
+
+// Start and stop video recordings from OctoPrint job events
+// recordings are started when a job starts if RecordingSettings.auto_start is enabled
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct RecordingPolicySettings {
+    // stop and finalize the recording when a job is done, cancelled or failed
+    pub auto_stop: bool,
+    // delay before stopping, so back-to-back jobs share one recording
+    pub stop_grace_secs: i32,
+    // stop the recording if OctoPrint shuts down mid-print and no new job starts within this window
+    pub shutdown_grace_secs: i32,
+}
+
+impl Default for RecordingPolicySettings {
+    fn default() -> Self {
+        Self {
+            auto_stop: true,
+            stop_grace_secs: 30,
+            shutdown_grace_secs: 300,
+        }
+    }
+}
+
+// How the bounding_boxes pipeline draws detections
+#[derive(
+    Copy, Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+pub enum DetectionOverlayMode {
+    // encode bounding boxes as a second H.264 stream on RtpSettings.overlay_udp_port, composited by clients
+    #[default]
+    #[serde(rename = "stream")]
+    Stream,
+    // draw bounding boxes onto camera frames before h264_encode, so rtp, hls, preroll and recordings are annotated
+    #[serde(rename = "composite")]
+    Composite,
+}
+
+// Label text and per-class colors of the bounding box overlay
+// keys of labels and colors are class labels from DetectionSettings.label_file
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct DetectionOverlaySettings {
+    pub mode: DetectionOverlayMode,
+    pub show_labels: bool,
+    pub show_scores: bool,
+    // display text drawn instead of the class label, e.g. {"nozzle": "Nozzle"}
+    pub labels: BTreeMap<String, String>,
+    // box and label colors in #RRGGBB format, e.g. {"spaghetti": "#FF0000"}
+    pub colors: BTreeMap<String, String>,
+    // color of classes missing from colors
+    pub default_color: String,
+    pub line_width: i32,
+}
+
+impl Default for DetectionOverlaySettings {
+    fn default() -> Self {
+        Self {
+            mode: DetectionOverlayMode::default(),
+            show_labels: true,
+            show_scores: true,
+            labels: BTreeMap::new(),
+            colors: BTreeMap::from([
+                ("adhesion".into(), "#FFA500".into()),
+                ("nozzle".into(), "#00BFFF".into()),
+                ("print".into(), "#00FF00".into()),
+                ("raft".into(), "#FFFF00".into()),
+                ("spaghetti".into(), "#FF0000".into()),
+            ]),
+            default_color: "#FFFFFF".into(),
+            line_width: 2,
+        }
+    }
+}
+
+impl DetectionOverlaySettings {
+    // color of label, falling back to default_color
+    pub fn color(&self, label: &str) -> &str {
+        self.colors
+            .get(label)
+            .map(|v| v.as_str())
+            .unwrap_or(&self.default_color)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct VideoStreamSettings {
+    // identifies the camera in pipeline names and NATS subjects, e.g. pi.{pi_id}.settings.camera.{camera_id}.apply
+    #[serde(rename = "camera_id", default = "default_camera_id")]
+    pub camera_id: String,
     #[serde(rename = "camera")]
     pub camera: Box<printnanny_os_models::CameraSettings>,
     #[serde(rename = "detection")]
@@ -394,6 +1102,30 @@ pub struct VideoStreamSettings {
     pub rtp: Box<printnanny_os_models::RtpSettings>,
     #[serde(rename = "snapshot")]
     pub snapshot: Box<printnanny_os_models::SnapshotSettings>,
+    // gstd pipeline graph, see PrintNannyPipelineFactory
+    #[serde(rename = "pipelines", default = "default_pipelines")]
+    pub pipelines: Vec<PipelineNodeSettings>,
+    #[serde(rename = "elements", default)]
+    pub elements: GstElementSettings,
+    #[serde(rename = "timelapse", default)]
+    pub timelapse: TimelapseSettings,
+    #[serde(rename = "preroll", default)]
+    pub preroll: PrerollSettings,
+    #[serde(rename = "failure_alert", default)]
+    pub failure_alert: FailureAlertSettings,
+    #[serde(rename = "recording_format", default)]
+    pub recording_format: RecordingFormatSettings,
+    #[serde(rename = "recording_policy", default)]
+    pub recording_policy: RecordingPolicySettings,
+    // bounding box label text, colors and compositing; DetectionSettings is generated from the PrintNanny OS API schema
+    #[serde(rename = "detection_overlay", default)]
+    pub detection_overlay: DetectionOverlaySettings,
+    // active ModelRegistry model, parameterising inference, bounding box and dataframe pipelines
+    #[serde(rename = "model", default)]
+    pub model: DetectionModelSettings,
+    // file or network stream used instead of the libcamera device
+    #[serde(rename = "media", default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<MediaVideoSource>,
 }
 
 impl From<VideoStreamSettings> for printnanny_os_models::VideoStreamSettings {
@@ -409,15 +1141,30 @@ impl From<VideoStreamSettings> for printnanny_os_models::VideoStreamSettings {
     }
 }
 
+fn default_camera_id() -> String {
+    DEFAULT_CAMERA_ID.into()
+}
+
 impl From<printnanny_os_models::VideoStreamSettings> for VideoStreamSettings {
     fn from(obj: printnanny_os_models::VideoStreamSettings) -> VideoStreamSettings {
         VideoStreamSettings {
+            camera_id: default_camera_id(),
             camera: obj.camera,
             detection: obj.detection,
             hls: obj.hls,
             recording: obj.recording,
             snapshot: obj.snapshot,
             rtp: obj.rtp,
+            pipelines: default_pipelines(),
+            elements: GstElementSettings::default(),
+            timelapse: TimelapseSettings::default(),
+            preroll: PrerollSettings::default(),
+            failure_alert: FailureAlertSettings::default(),
+            recording_format: RecordingFormatSettings::default(),
+            recording_policy: RecordingPolicySettings::default(),
+            detection_overlay: DetectionOverlaySettings::default(),
+            model: DetectionModelSettings::default(),
+            media: None,
         }
     }
 }
@@ -472,17 +1219,139 @@ impl Default for VideoStreamSettings {
         });
 
         Self {
+            camera_id: default_camera_id(),
             camera,
             detection,
             hls,
             recording,
             rtp,
             snapshot,
+            pipelines: default_pipelines(),
+            elements: GstElementSettings::default(),
+            timelapse: TimelapseSettings::default(),
+            preroll: PrerollSettings::default(),
+            failure_alert: FailureAlertSettings::default(),
+            recording_format: RecordingFormatSettings::default(),
+            recording_policy: RecordingPolicySettings::default(),
+            detection_overlay: DetectionOverlaySettings::default(),
+            model: DetectionModelSettings::default(),
+            media: None,
         }
     }
 }
 
 impl VideoStreamSettings {
+    // settings for an additional camera, with RTP ports offset by index and HLS/snapshot locations namespaced by camera_id
+    pub fn for_camera(camera_id: &str, index: i32) -> Self {
+        let defaults = Self::default();
+        let port_offset = index * 10;
+        Self {
+            camera_id: camera_id.into(),
+            hls: Box::new(printnanny_os_models::HlsSettings {
+                segments: format!("/var/run/printnanny-hls/{camera_id}/segment%05d.ts"),
+                playlist: format!("/var/run/printnanny-hls/{camera_id}/playlist.m3u8"),
+                playlist_root: format!("/printnanny-hls/{camera_id}/"),
+                ..*defaults.hls
+            }),
+            rtp: Box::new(printnanny_os_models::RtpSettings {
+                video_udp_port: defaults.rtp.video_udp_port + port_offset,
+                overlay_udp_port: defaults.rtp.overlay_udp_port + port_offset,
+            }),
+            snapshot: Box::new(printnanny_os_models::SnapshotSettings {
+                path: format!("/var/run/printnanny-snapshot/{camera_id}/snapshot-%d.jpg"),
+                ..*defaults.snapshot
+            }),
+            timelapse: TimelapseSettings {
+                path: format!("{}/{camera_id}", defaults.timelapse.path),
+                ..defaults.timelapse.clone()
+            },
+            preroll: PrerollSettings {
+                path: format!("{}/{camera_id}", defaults.preroll.path),
+                ..defaults.preroll.clone()
+            },
+            ..defaults
+        }
+    }
+
+    // None for the default camera, otherwise pipeline names are prefixed with "{camera_id}_"
+    pub fn pipeline_namespace(&self) -> Option<&str> {
+        match self.camera_id.as_str() {
+            DEFAULT_CAMERA_ID => None,
+            camera_id => Some(camera_id),
+        }
+    }
+
+    // settings which would render pipelines gstd can't parse or run
+    pub fn validate(&self) -> Result<(), PrintNannySettingsError> {
+        if let Some(media) = &self.media {
+            media.validate()?;
+        }
+        self.recording_format.validate()?;
+        // rendered as the timelapse pipeline's framerate=1/interval_secs caps
+        if self.timelapse.interval_secs <= 0 {
+            return Err(PrintNannySettingsError::InvalidValue {
+                value: format!("timelapse.interval_secs={}", self.timelapse.interval_secs),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn namespaced_pipeline_name(&self, name: &str) -> String {
+        match self.pipeline_namespace() {
+            Some(namespace) => format!("{namespace}_{name}"),
+            None => name.to_string(),
+        }
+    }
+
+    // apply os_models::VideoStreamSettings (e.g. from a NATS request), keeping settings which aren't part of the os_models type
+    pub fn merge_os_models(&self, obj: printnanny_os_models::VideoStreamSettings) -> Self {
+        Self {
+            camera_id: self.camera_id.clone(),
+            pipelines: self.pipelines.clone(),
+            elements: self.elements.clone(),
+            timelapse: self.timelapse.clone(),
+            preroll: self.preroll.clone(),
+            failure_alert: self.failure_alert.clone(),
+            recording_format: self.recording_format.clone(),
+            recording_policy: self.recording_policy.clone(),
+            detection_overlay: self.detection_overlay.clone(),
+            model: self.model.clone(),
+            media: self.media.clone(),
+            ..obj.into()
+        }
+    }
+
+    // manifest of the active detection model
+    pub fn detection_model(&self) -> Result<ModelManifest, PrintNannySettingsError> {
+        self.model.manifest(&self.detection)
+    }
+
+    // VideoSource rendered by the camera pipeline
+    pub fn video_source(&self) -> VideoSource {
+        match &self.media {
+            Some(media) if media.is_file() => VideoSource::File(media.clone()),
+            Some(media) => VideoSource::Uri(media.clone()),
+            None => {
+                let camera = CameraVideoSource {
+                    index: 0,
+                    device_name: self.camera.device_name.clone(),
+                    label: self.camera.label.clone(),
+                    caps: printnanny_os_models::GstreamerCaps {
+                        colorimetry: self.camera.colorimetry.clone(),
+                        media_type: "video/x-raw".into(),
+                        format: self.camera.format.clone(),
+                        width: self.camera.width,
+                        height: self.camera.height,
+                    },
+                };
+                match camera.camera_source_type() {
+                    printnanny_os_models::CameraSourceType::Usb => VideoSource::USB(camera),
+                    printnanny_os_models::CameraSourceType::Csi => VideoSource::CSI(camera),
+                }
+            }
+        }
+    }
+
     pub fn gst_tensor_decoder_caps(&self) -> String {
         // Raspberry Pi Camera module v2 sensor - imx219
         // Raspberry Pi Camera module v3 sensor - imx708
@@ -538,6 +1407,10 @@ impl VideoStreamSettings {
     }
 
     pub async fn hotplug(mut self) -> Result<Self, PrintNannySettingsError> {
+        // file and network sources aren't hotplugged
+        if self.media.is_some() {
+            return Ok(self);
+        }
         // list available devices
         let camera_sources = CameraVideoSource::from_libcamera_list().await?;
         let selected_camera = *(self.camera.clone());
@@ -586,7 +1459,7 @@ mod tests {
         let result = CameraVideoSource::parse_list_cameras_command_output(MULTIPLE_CAMERAS);
 
         assert_eq!(
-            *result.get(0).unwrap(),
+            *result.first().unwrap(),
             CameraVideoSource {
                 index: 1,
                 label: "imx219".into(),
@@ -609,7 +1482,7 @@ mod tests {
         let result = CameraVideoSource::parse_list_cameras_command_output(ONE_CSI_CAMERA);
 
         assert_eq!(
-            *result.get(0).unwrap(),
+            *result.first().unwrap(),
             CameraVideoSource {
                 index: 1,
                 label: "imx219".into(),
@@ -622,7 +1495,7 @@ mod tests {
     fn test_parse_one_usb_libcamera_list_command_output() {
         let result = CameraVideoSource::parse_list_cameras_command_output(ONE_USB_CAMERA);
         assert_eq!(
-            *result.get(0).unwrap(),
+            *result.first().unwrap(),
             CameraVideoSource {
                 index: 1,
                 label: "Logitech BRIO".into(),
@@ -637,4 +1510,112 @@ mod tests {
         let result = CameraVideoSource::parse_list_cameras_command_output("");
         assert_eq!(result.len(), 0)
     }
+
+    #[test_log::test]
+    fn test_media_video_source_camera_conversion() {
+        let file = VideoSource::File(MediaVideoSource::new("/home/printnanny/failed-print.ts"));
+        let camera: printnanny_os_models::Camera = file.into();
+        assert_eq!(camera.device_name, "file:///home/printnanny/failed-print.ts");
+        let result: VideoSource = camera.into();
+        assert_eq!(
+            result,
+            VideoSource::File(MediaVideoSource::new(
+                "file:///home/printnanny/failed-print.ts"
+            ))
+        );
+
+        let uri = VideoSource::Uri(MediaVideoSource::new("rtsp://192.168.1.2:554/stream"));
+        let camera: printnanny_os_models::Camera = uri.clone().into();
+        let result: VideoSource = camera.into();
+        assert_eq!(result, uri);
+    }
+
+    #[test_log::test]
+    fn test_additional_camera_namespace() {
+        let default = VideoStreamSettings::default();
+        assert_eq!(default.pipeline_namespace(), None);
+        assert_eq!(default.namespaced_pipeline_name("camera"), "camera");
+
+        let nozzle = VideoStreamSettings::for_camera("nozzle", 1);
+        assert_eq!(nozzle.namespaced_pipeline_name("camera"), "nozzle_camera");
+        assert_eq!(nozzle.rtp.video_udp_port, default.rtp.video_udp_port + 10);
+        assert_eq!(nozzle.rtp.overlay_udp_port, default.rtp.overlay_udp_port + 10);
+        assert_eq!(
+            nozzle.snapshot.path,
+            "/var/run/printnanny-snapshot/nozzle/snapshot-%d.jpg"
+        );
+        assert_eq!(nozzle.preroll.path, "/var/run/printnanny-preroll/nozzle");
+        // 20 seconds of pre-roll and post-roll in 4 second fragments, plus the fragment being written and one spare
+        assert_eq!(nozzle.preroll.max_files(), 7);
+
+        let merged = nozzle.merge_os_models(default.clone().into());
+        assert_eq!(merged.camera_id, "nozzle");
+        assert_eq!(merged.rtp, default.rtp);
+    }
+
+    #[test_log::test]
+    fn test_detection_overlay_settings() {
+        let mut settings = VideoStreamSettings::default();
+        assert_eq!(settings.detection_overlay.mode, DetectionOverlayMode::Stream);
+        assert_eq!(settings.detection_overlay.color("spaghetti"), "#FF0000");
+        assert_eq!(settings.detection_overlay.color("unknown"), "#FFFFFF");
+
+        // overlay settings are kept when os_models settings are applied
+        settings.detection_overlay.mode = DetectionOverlayMode::Composite;
+        let merged = settings.merge_os_models(settings.clone().into());
+        assert_eq!(merged.detection_overlay.mode, DetectionOverlayMode::Composite);
+    }
+
+    #[test_log::test]
+    fn test_detection_model_settings() {
+        let mut settings = VideoStreamSettings::default();
+        assert_eq!(settings.model.active, None);
+        assert_eq!(
+            settings.detection_model().unwrap().model_file,
+            std::path::PathBuf::from(settings.detection.model_file.as_str())
+        );
+
+        // active model is kept when os_models settings are applied
+        settings.model.active = Some("printnanny-v2".into());
+        let merged = settings.merge_os_models(settings.clone().into());
+        assert_eq!(merged.model.active.as_deref(), Some("printnanny-v2"));
+        // registry doesn't contain printnanny-v2
+        assert!(merged.detection_model().is_err());
+    }
+
+    #[test_log::test]
+    fn test_video_source_from_media_settings() {
+        let mut settings = VideoStreamSettings::default();
+        assert!(matches!(settings.video_source(), VideoSource::CSI(_)));
+        settings.media = Some(MediaVideoSource::new("https://example.com/stream.m3u8"));
+        assert!(matches!(settings.video_source(), VideoSource::Uri(_)));
+        settings.media = Some(MediaVideoSource::new("/home/printnanny/failed-print.ts"));
+        assert!(matches!(settings.video_source(), VideoSource::File(_)));
+    }
+
+    #[test_log::test]
+    fn test_media_video_source_loop_playback() {
+        let looped = |uri: &str| MediaVideoSource {
+            loop_playback: true,
+            ..MediaVideoSource::new(uri)
+        };
+        assert!(looped("/home/printnanny/failed-print.ts")
+            .validate()
+            .is_ok());
+        assert!(looped("file:///home/printnanny/failed-print.M2TS")
+            .validate()
+            .is_ok());
+        for uri in [
+            "/home/printnanny/failed-print.mp4",
+            "/home/printnanny/failed-print.mkv",
+            "/home/printnanny/failed-print",
+            "rtsp://192.168.1.2:554/stream.ts",
+        ] {
+            assert!(looped(uri).validate().is_err(), "{}", uri);
+        }
+        // files which aren't looped are decoded by uridecodebin
+        assert!(MediaVideoSource::new("/home/printnanny/failed-print.mp4")
+            .validate()
+            .is_ok());
+    }
 }
@@ -40,6 +40,15 @@ pub enum PrintNannySettingsError {
     #[error("Failed to handle invalid config value {value:?}")]
     InvalidValue { value: String },
 
+    #[error("Camera with camera_id={camera_id} is not configured")]
+    CameraNotFound { camera_id: String },
+
+    #[error("Model with name={name} was not found in registry {registry:?}")]
+    ModelNotFound { name: String, registry: PathBuf },
+
+    #[error("Invalid manifest for model name={name}: {detail}")]
+    InvalidModelManifest { name: String, detail: String },
+
     #[error(transparent)]
     FromUtf8Error(#[from] std::string::FromUtf8Error),
 
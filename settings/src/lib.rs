@@ -1,7 +1,11 @@
+// the settings error types wrap large errors like figment::Error, boxing them would change the public error types
+#![allow(clippy::result_large_err)]
+
 pub mod cam;
 pub mod error;
 pub mod klipper;
 pub mod mainsail;
+pub mod model;
 pub mod moonraker;
 pub mod octoprint;
 pub mod paths;
@@ -0,0 +1,469 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error::PrintNannySettingsError;
+use crate::paths::DEFAULT_PRINTNANNY_DATA_DIR;
+
+pub const MODEL_MANIFEST_FILENAME: &str = "manifest.toml";
+// name of the model described by DetectionSettings, used when no registry model is active
+pub const BUNDLED_MODEL_NAME: &str = "bundled";
+// class labels of the bundled model, in class id order
+pub const BUNDLED_MODEL_LABELS: [&str; 5] = ["nozzle", "adhesion", "spaghetti", "print", "raft"];
+
+// Input tensor expected by the model, rendered as tensor_converter and tensor_transform caps/options
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ModelInputSettings {
+    pub width: i32,
+    pub height: i32,
+    // must match format, e.g. 3 for RGB
+    pub channels: i32,
+    // video/x-raw format of pixel data, e.g. RGB
+    #[serde(default = "default_input_format")]
+    pub format: String,
+    // tensor_transform typecast, e.g. uint8 or float32
+    #[serde(default = "default_input_dtype")]
+    pub dtype: String,
+    // pixel values are normalized to (value + add) / div
+    #[serde(default)]
+    pub add: f32,
+    #[serde(default = "default_input_div")]
+    pub div: f32,
+}
+
+fn default_input_format() -> String {
+    "RGB".into()
+}
+
+fn default_input_dtype() -> String {
+    "uint8".into()
+}
+
+fn default_input_div() -> f32 {
+    1.0
+}
+
+// channels of a video/x-raw format accepted by tensor_converter, None if format isn't known
+fn format_channels(format: &str) -> Option<i32> {
+    match format {
+        "GRAY8" => Some(1),
+        "RGB" | "BGR" => Some(3),
+        "RGBA" | "BGRA" | "ARGB" | "ABGR" | "RGBx" | "BGRx" | "xRGB" | "xBGR" => Some(4),
+        _ => None,
+    }
+}
+
+impl ModelInputSettings {
+    // other/tensors dimensions produced by tensor_converter, innermost first: channels:width:height:batch
+    pub fn tensor_dimensions(&self) -> String {
+        format!("{}:{}:{}:1", self.channels, self.width, self.height)
+    }
+
+    // tensor_transform mode=arithmetic option
+    pub fn transform_option(&self) -> String {
+        format!("typecast:{},add:{},div:{}", self.dtype, self.add, self.div)
+    }
+}
+
+// Layout of the model's output tensors, selects the tensor_decoder bounding_boxes option1 sub-mode
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ModelOutputLayout {
+    // locations, classes, scores and number of detections, post-processed by the TFLite_Detection_PostProcess op
+    #[default]
+    #[serde(rename = "mobilenet-ssd-postprocess")]
+    MobilenetSsdPostprocess,
+}
+
+impl std::fmt::Display for ModelOutputLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelOutputLayout::MobilenetSsdPostprocess => write!(f, "mobilenet-ssd-postprocess"),
+        }
+    }
+}
+
+// the number of detections is read from the num_detections tensor, manifests written with max_detections still parse
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ModelOutputSettings {
+    #[serde(default)]
+    pub layout: ModelOutputLayout,
+    // output tensor indexes of locations:classes:scores:num_detections
+    #[serde(default = "default_output_tensors")]
+    pub tensors: String,
+}
+
+fn default_output_tensors() -> String {
+    "0:1:2:3".into()
+}
+
+impl Default for ModelOutputSettings {
+    fn default() -> Self {
+        Self {
+            layout: ModelOutputLayout::default(),
+            tensors: default_output_tensors(),
+        }
+    }
+}
+
+// Metadata of a detection model, read from {registry}/{name}/manifest.toml
+// model_file and label_file are relative to the manifest's directory
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ModelManifest {
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    // tensor_filter framework
+    #[serde(default = "default_framework")]
+    pub framework: String,
+    pub model_file: PathBuf,
+    pub label_file: PathBuf,
+    // class labels in class id order, must match label_file
+    pub labels: Vec<String>,
+    pub input: ModelInputSettings,
+    #[serde(default)]
+    pub output: ModelOutputSettings,
+    // multiplier converting raw output scores to 0-1, e.g. 0.00390625 for uint8 scores
+    #[serde(default = "default_score_scale")]
+    pub score_scale: f32,
+}
+
+fn default_framework() -> String {
+    "tensorflow2-lite".into()
+}
+
+fn default_score_scale() -> f32 {
+    1.0
+}
+
+impl From<&printnanny_os_models::DetectionSettings> for ModelManifest {
+    fn from(detection: &printnanny_os_models::DetectionSettings) -> Self {
+        Self {
+            name: BUNDLED_MODEL_NAME.into(),
+            version: "0".into(),
+            description: "Model installed with PrintNanny OS".into(),
+            framework: default_framework(),
+            model_file: detection.model_file.clone().into(),
+            label_file: detection.label_file.clone().into(),
+            labels: BUNDLED_MODEL_LABELS.iter().map(|l| l.to_string()).collect(),
+            input: ModelInputSettings {
+                width: detection.tensor_width,
+                height: detection.tensor_height,
+                channels: 3,
+                format: default_input_format(),
+                dtype: default_input_dtype(),
+                add: 0.0,
+                div: default_input_div(),
+            },
+            output: ModelOutputSettings::default(),
+            score_scale: default_score_scale(),
+        }
+    }
+}
+
+impl ModelManifest {
+    // raw score compared against model output, for a threshold in percent (e.g. DetectionSettings.nms_threshold)
+    pub fn score_threshold(&self, percent: i32) -> f32 {
+        percent as f32 / 100_f32 / self.score_scale
+    }
+
+    // comma-separated labels, e.g. the dataframe_agg labels property
+    pub fn label_list(&self) -> String {
+        self.labels.join(",")
+    }
+
+    fn invalid(&self, detail: &str) -> PrintNannySettingsError {
+        PrintNannySettingsError::InvalidModelManifest {
+            name: self.name.clone(),
+            detail: detail.to_string(),
+        }
+    }
+
+    // labels are rendered into pipeline descriptions and dataframe column names
+    pub fn validate(&self) -> Result<(), PrintNannySettingsError> {
+        if self.labels.is_empty() {
+            return Err(self.invalid("labels must not be empty"));
+        }
+        if let Some(label) = self
+            .labels
+            .iter()
+            .find(|l| l.is_empty() || l.contains(|c: char| c.is_whitespace() || c == ','))
+        {
+            return Err(self.invalid(&format!(
+                "label={label:?} must not be empty or contain whitespace or commas"
+            )));
+        }
+        if self.input.width <= 0 || self.input.height <= 0 || self.input.channels <= 0 {
+            return Err(self.invalid("input width, height and channels must be positive"));
+        }
+        if self.input.div == 0.0 {
+            return Err(self.invalid("input div must not be 0"));
+        }
+        if let Some(channels) = format_channels(&self.input.format) {
+            if channels != self.input.channels {
+                return Err(self.invalid(&format!(
+                    "input format={} has {} channels, but channels={}",
+                    self.input.format, channels, self.input.channels
+                )));
+            }
+        }
+        if self.score_scale <= 0.0 {
+            return Err(self.invalid("score_scale must be positive"));
+        }
+        Ok(())
+    }
+
+    // parse manifest.toml in dir, resolving model_file and label_file against dir
+    pub fn load(dir: &Path) -> Result<Self, PrintNannySettingsError> {
+        let path = dir.join(MODEL_MANIFEST_FILENAME);
+        let content = fs::read_to_string(&path)
+            .map_err(|error| PrintNannySettingsError::ReadIOError { path, error })?;
+        let mut manifest: ModelManifest = toml::from_str(&content)?;
+        manifest.model_file = dir.join(&manifest.model_file);
+        manifest.label_file = dir.join(&manifest.label_file);
+        manifest.validate()?;
+
+        for file in [&manifest.model_file, &manifest.label_file] {
+            if !file.exists() {
+                return Err(manifest.invalid(&format!("{} does not exist", file.display())));
+            }
+        }
+        let label_file = fs::read_to_string(&manifest.label_file).map_err(|error| {
+            PrintNannySettingsError::ReadIOError {
+                path: manifest.label_file.clone(),
+                error,
+            }
+        })?;
+        let labels: Vec<&str> = label_file
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect();
+        if labels != manifest.labels {
+            return Err(manifest.invalid(&format!(
+                "labels={:?} do not match label_file={}",
+                manifest.labels,
+                manifest.label_file.display()
+            )));
+        }
+        Ok(manifest)
+    }
+}
+
+// Directory of detection models, one sub-directory per model containing manifest.toml
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ModelRegistry {
+    pub path: PathBuf,
+}
+
+impl ModelRegistry {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn load(&self, name: &str) -> Result<ModelManifest, PrintNannySettingsError> {
+        // name is a single directory, e.g. from a NATS request
+        if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+            return Err(PrintNannySettingsError::InvalidValue {
+                value: name.to_string(),
+            });
+        }
+        let dir = self.path.join(name);
+        if !dir.join(MODEL_MANIFEST_FILENAME).exists() {
+            return Err(PrintNannySettingsError::ModelNotFound {
+                name: name.to_string(),
+                registry: self.path.clone(),
+            });
+        }
+        let manifest = ModelManifest::load(&dir)?;
+        if manifest.name != name {
+            return Err(manifest.invalid(&format!(
+                "manifest name does not match directory name={name}"
+            )));
+        }
+        Ok(manifest)
+    }
+
+    // valid manifests ordered by name, invalid manifests are logged and skipped
+    pub fn list(&self) -> Result<Vec<ModelManifest>, PrintNannySettingsError> {
+        if !self.path.exists() {
+            info!("Model registry path={} does not exist", self.path.display());
+            return Ok(vec![]);
+        }
+        let entries =
+            fs::read_dir(&self.path).map_err(|error| PrintNannySettingsError::ReadIOError {
+                path: self.path.clone(),
+                error,
+            })?;
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join(MODEL_MANIFEST_FILENAME).exists())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        names.sort();
+
+        let mut result = vec![];
+        for name in names.iter() {
+            match self.load(name) {
+                Ok(manifest) => result.push(manifest),
+                Err(e) => warn!("Skipping invalid model name={} error={}", name, e),
+            }
+        }
+        Ok(result)
+    }
+}
+
+// Detection model used by the tflite_inference, bounding_boxes and df pipelines
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct DetectionModelSettings {
+    // ModelRegistry directory
+    pub registry: String,
+    // registry model name, or None to use the model described by DetectionSettings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub active: Option<String>,
+}
+
+impl Default for DetectionModelSettings {
+    fn default() -> Self {
+        Self {
+            registry: format!("{DEFAULT_PRINTNANNY_DATA_DIR}/models"),
+            active: None,
+        }
+    }
+}
+
+impl DetectionModelSettings {
+    pub fn registry(&self) -> ModelRegistry {
+        ModelRegistry::new(&self.registry)
+    }
+
+    // manifest of the active model, falling back to the model described by detection
+    pub fn manifest(
+        &self,
+        detection: &printnanny_os_models::DetectionSettings,
+    ) -> Result<ModelManifest, PrintNannySettingsError> {
+        match &self.active {
+            Some(name) => self.registry().load(name),
+            None => Ok(ModelManifest::from(detection)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+name = "printnanny-v2"
+version = "2.0.0"
+model_file = "model.tflite"
+label_file = "labels.txt"
+labels = ["nozzle", "adhesion", "spaghetti", "print", "raft", "blob"]
+score_scale = 0.00390625
+
+[input]
+width = 416
+height = 416
+channels = 3
+dtype = "float32"
+add = -127.5
+div = 127.5
+"#;
+
+    fn write_model(jail: &mut figment::Jail, name: &str, manifest: &str, labels: &str) {
+        let dir = jail.directory().join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(MODEL_MANIFEST_FILENAME), manifest).unwrap();
+        std::fs::write(dir.join("model.tflite"), b"").unwrap();
+        std::fs::write(dir.join("labels.txt"), labels).unwrap();
+    }
+
+    #[test_log::test]
+    fn test_model_registry() {
+        figment::Jail::expect_with(|jail| {
+            let labels = "nozzle\nadhesion\nspaghetti\nprint\nraft\nblob\n";
+            write_model(jail, "printnanny-v2", MANIFEST, labels);
+            // label_file doesn't match labels
+            write_model(
+                jail,
+                "mismatch",
+                &MANIFEST.replace("printnanny-v2", "mismatch"),
+                "nozzle\n",
+            );
+            // channels doesn't match input format
+            write_model(
+                jail,
+                "gray",
+                &MANIFEST
+                    .replace("printnanny-v2", "gray")
+                    .replace("channels = 3", "channels = 1"),
+                labels,
+            );
+
+            let registry = ModelRegistry::new(jail.directory());
+            let manifest = registry.load("printnanny-v2").unwrap();
+            assert_eq!(
+                manifest.model_file,
+                jail.directory().join("printnanny-v2/model.tflite")
+            );
+            assert_eq!(
+                manifest.input.transform_option(),
+                "typecast:float32,add:-127.5,div:127.5"
+            );
+            assert_eq!(
+                manifest.output.layout,
+                ModelOutputLayout::MobilenetSsdPostprocess
+            );
+            assert_eq!(manifest.output.tensors, "0:1:2:3");
+            assert_eq!(manifest.input.tensor_dimensions(), "3:416:416:1");
+            assert_eq!(manifest.score_threshold(50), 128.0);
+            assert_eq!(
+                manifest.label_list(),
+                "nozzle,adhesion,spaghetti,print,raft,blob"
+            );
+
+            assert!(matches!(
+                registry.load("mismatch"),
+                Err(PrintNannySettingsError::InvalidModelManifest { .. })
+            ));
+            assert!(matches!(
+                registry.load("gray"),
+                Err(PrintNannySettingsError::InvalidModelManifest { .. })
+            ));
+            assert!(matches!(
+                registry.load("missing"),
+                Err(PrintNannySettingsError::ModelNotFound { .. })
+            ));
+            assert!(registry.load("../printnanny-v2").is_err());
+
+            let names: Vec<String> = registry
+                .list()
+                .unwrap()
+                .into_iter()
+                .map(|m| m.name)
+                .collect();
+            assert_eq!(names, vec!["printnanny-v2"]);
+            Ok(())
+        });
+    }
+
+    #[test_log::test]
+    fn test_bundled_model_manifest() {
+        let settings = DetectionModelSettings::default();
+        let detection = crate::cam::VideoStreamSettings::default().detection;
+        let manifest = settings.manifest(&detection).unwrap();
+        assert_eq!(manifest.name, BUNDLED_MODEL_NAME);
+        assert_eq!(
+            manifest.label_list(),
+            "nozzle,adhesion,spaghetti,print,raft"
+        );
+        assert_eq!(
+            manifest.input.transform_option(),
+            "typecast:uint8,add:0,div:1"
+        );
+        assert_eq!(manifest.score_threshold(66), 0.66);
+        manifest.validate().unwrap();
+    }
+}
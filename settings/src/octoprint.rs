@@ -292,7 +292,7 @@ mod tests {
 
     #[test]
     fn test_pip_packages() {
-        let actual = parse_pip_list_json(EXAMPLE.into()).unwrap();
+        let actual = parse_pip_list_json(EXAMPLE).unwrap();
         let expected = vec![
             PipPackage {
                 name: "apturl".into(),
@@ -119,6 +119,11 @@ impl PrintNannyPaths {
         self.state_dir.join("video")
     }
 
+    // VideoRecording parts concatenated into a single playable file
+    pub fn video_exports(&self) -> PathBuf {
+        self.video().join("exports")
+    }
+
     pub fn license_zip(&self) -> PathBuf {
         self.creds().join("license.zip")
     }
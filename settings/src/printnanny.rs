@@ -93,12 +93,137 @@ impl Default for GitSettings {
     }
 }
 
+// Disk budget for recordings, snapshots and HLS segments, enforced by printnanny-services retention manager
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RetentionSettings {
+    // disabled by default, enabling it deletes local recordings which exceed max_bytes or max_age_secs
+    // recording parts which are not uploaded yet are kept until UploadQueueSettings.max_attempts is reached
+    pub enabled: bool,
+    // total bytes allowed across recording, snapshot and HLS segment directories
+    pub max_bytes: i64,
+    // files older than max_age_secs are deleted, 0 disables age policy
+    pub max_age_secs: i64,
+    // refuse to start recording when datafs usage exceeds this percent
+    pub datafs_max_used_percent: i32,
+    pub interval_secs: i64,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_bytes: 4 * 1024 * 1024 * 1024,
+            max_age_secs: 30 * 24 * 60 * 60,
+            datafs_max_used_percent: 90,
+            interval_secs: 300,
+        }
+    }
+}
+
+// VideoRecordingPart upload queue, see printnanny-services video_recording_sync
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct UploadQueueSettings {
+    // maximum number of parts uploaded at the same time
+    pub concurrency: i32,
+    // parts are abandoned after max_attempts failed uploads, 0 retries forever
+    // abandoned parts are reported by pi.{pi_id}.command.upload_queue.status
+    pub max_attempts: i64,
+    // delay after the first failed upload, doubled after each failure up to backoff_max_secs
+    pub backoff_secs: i64,
+    pub backoff_max_secs: i64,
+    pub interval_secs: i64,
+}
+
+impl Default for UploadQueueSettings {
+    fn default() -> Self {
+        Self {
+            concurrency: 2,
+            max_attempts: 0,
+            backoff_secs: 10,
+            backoff_max_secs: 3600,
+            interval_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FailureAction {
+    #[serde(rename = "pause")]
+    Pause,
+    #[serde(rename = "cancel")]
+    Cancel,
+}
+
+impl std::fmt::Display for FailureAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureAction::Pause => write!(f, "pause"),
+            FailureAction::Cancel => write!(f, "cancel"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PrinterApi {
+    // OctoPrint REST API, authenticated with the api key of the edge db OctoPrintServer
+    #[serde(rename = "octoprint")]
+    OctoPrint,
+    // Moonraker JSON-RPC API at FailureActionSettings.moonraker_url
+    #[serde(rename = "moonraker")]
+    Moonraker,
+}
+
+impl std::fmt::Display for PrinterApi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrinterApi::OctoPrint => write!(f, "octoprint"),
+            PrinterApi::Moonraker => write!(f, "moonraker"),
+        }
+    }
+}
+
+// Pause or cancel the current print job when a print quality alert is confirmed, see printnanny-services printer_control
+// every action is recorded as a PrintJobAction in the edge db
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FailureActionSettings {
+    pub enabled: bool,
+    pub action: FailureAction,
+    pub printer_api: PrinterApi,
+    pub moonraker_url: String,
+    // record the action without sending it to the printer
+    pub dry_run: bool,
+    // minimum time between actions, including dry runs
+    pub cooldown_secs: i64,
+}
+
+impl Default for FailureActionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            action: FailureAction::Pause,
+            printer_api: PrinterApi::OctoPrint,
+            moonraker_url: "http://127.0.0.1:7125".into(),
+            dry_run: false,
+            cooldown_secs: 1800,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct PrintNannySettings {
     pub video_stream: VideoStreamSettings,
     pub cloud: PrintNannyApiConfig,
     pub git: GitSettings,
     pub paths: PrintNannyPaths,
+    #[serde(default)]
+    pub retention: RetentionSettings,
+    #[serde(default)]
+    pub upload_queue: UploadQueueSettings,
+    #[serde(default)]
+    pub failure_action: FailureActionSettings,
+    // additional cameras, identified by VideoStreamSettings.camera_id
+    #[serde(default)]
+    pub cameras: Vec<VideoStreamSettings>,
 }
 
 impl Default for PrintNannySettings {
@@ -111,6 +236,10 @@ impl Default for PrintNannySettings {
             paths: PrintNannyPaths::default(),
             git,
             video_stream,
+            retention: RetentionSettings::default(),
+            upload_queue: UploadQueueSettings::default(),
+            failure_action: FailureActionSettings::default(),
+            cameras: vec![],
         }
     }
 }
@@ -124,6 +253,44 @@ impl PrintNannySettings {
         Ok(result)
     }
 
+    // settings of the default camera, followed by additional cameras
+    pub fn video_streams(&self) -> Vec<VideoStreamSettings> {
+        let mut result = vec![self.video_stream.clone()];
+        result.extend(self.cameras.iter().cloned());
+        result
+    }
+
+    pub fn get_video_stream(&self, camera_id: &str) -> Option<&VideoStreamSettings> {
+        if self.video_stream.camera_id == camera_id {
+            return Some(&self.video_stream);
+        }
+        self.cameras.iter().find(|c| c.camera_id == camera_id)
+    }
+
+    // replace settings of the camera with matching camera_id
+    pub fn set_video_stream(
+        &mut self,
+        video_stream: VideoStreamSettings,
+    ) -> Result<(), PrintNannySettingsError> {
+        if self.video_stream.camera_id == video_stream.camera_id {
+            self.video_stream = video_stream;
+            return Ok(());
+        }
+        match self
+            .cameras
+            .iter_mut()
+            .find(|c| c.camera_id == video_stream.camera_id)
+        {
+            Some(camera) => {
+                *camera = video_stream;
+                Ok(())
+            }
+            None => Err(PrintNannySettingsError::CameraNotFound {
+                camera_id: video_stream.camera_id,
+            }),
+        }
+    }
+
     pub fn to_octoprint_settings(&self) -> OctoPrintSettings {
         let git_settings = self.git.clone();
         let settings_file = self.git.path.join(DEFAULT_OCTOPRINT_SETTINGS_FILE);
@@ -385,7 +552,7 @@ mod tests {
             )?;
             jail.set_env("PRINTNANNY_SETTINGS", PRINTNANNY_SETTINGS_FILENAME);
             let expected = PathBuf::from("testing");
-            jail.set_env("PRINTNANNY_SETTINGS_PATHS__LOG_DIR", &expected.display());
+            jail.set_env("PRINTNANNY_SETTINGS_PATHS__LOG_DIR", expected.display());
             let figment = Runtime::new()
                 .unwrap()
                 .block_on(PrintNannySettings::figment())
@@ -471,6 +638,35 @@ mod tests {
         });
     }
 
+    #[test_log::test]
+    fn test_failure_action_settings() {
+        figment::Jail::expect_with(|jail| {
+            jail.create_file(
+                "Local.toml",
+                r#"
+                [failure_action]
+                enabled = true
+                printer_api = "moonraker"
+                dry_run = true
+                "#,
+            )?;
+            jail.set_env("PRINTNANNY_SETTINGS", "Local.toml");
+
+            let settings = Runtime::new()
+                .unwrap()
+                .block_on(PrintNannySettings::new())
+                .unwrap();
+
+            assert!(settings.failure_action.enabled);
+            assert!(settings.failure_action.dry_run);
+            assert_eq!(settings.failure_action.printer_api, PrinterApi::Moonraker);
+            assert_eq!(settings.failure_action.action, FailureAction::Pause);
+            assert_eq!(settings.failure_action.cooldown_secs, 1800);
+
+            Ok(())
+        });
+    }
+
     #[test_log::test]
     fn test_save() {
         figment::Jail::expect_with(|jail| {
@@ -604,6 +800,28 @@ mod tests {
                 ))
                 .unwrap();
             assert_eq!(settings.video_stream.detection.tensor_framerate, 1);
+            assert!(settings.video_stream.validate().is_ok());
+
+            for interval_secs in [0, -1] {
+                let filename = format!("timelapse-{interval_secs}.toml");
+                jail.create_file(
+                    &filename,
+                    &format!(
+                        r#"
+                [video_stream.timelapse]
+                interval_secs = {interval_secs}
+                "#
+                    ),
+                )?;
+                let settings = Runtime::new()
+                    .unwrap()
+                    .block_on(PrintNannySettings::from_toml(
+                        PathBuf::from(output).join(&filename),
+                    ))
+                    .unwrap();
+                assert_eq!(settings.video_stream.timelapse.interval_secs, interval_secs);
+                assert!(settings.video_stream.validate().is_err());
+            }
 
             Ok(())
         });
@@ -1,13 +1,126 @@
 #[macro_use]
 extern crate rocket;
+use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 use rocket::fs::NamedFile;
+use rocket::http::Header;
 use rocket::response::status::NotFound;
+use rocket::serde::json::Json;
+use rocket::serde::Serialize;
 use rocket::State;
 
+use printnanny_edge_db::diesel;
+use printnanny_edge_db::video_recording::VideoRecording;
+use printnanny_gst_pipelines::export::RecordingExport;
+use printnanny_gst_pipelines::factory::PrintNannyPipelineFactory;
 use printnanny_settings::printnanny::PrintNannySettings;
 
+#[derive(Responder)]
+struct RecordingDownload {
+    inner: NamedFile,
+    content_disposition: Header<'static>,
+}
+
+#[derive(Responder)]
+enum RecordingResponse {
+    Download(RecordingDownload),
+    // export was started (or is still running), poll the status route until it's ready
+    #[response(status = 202)]
+    Accepted(Json<RecordingExportStatus>),
+}
+
+#[derive(Debug, Responder)]
+enum RecordingError {
+    #[response(status = 404)]
+    NotFound(String),
+    #[response(status = 500)]
+    Internal(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+enum ExportState {
+    // not exported, or parts were added since the last export
+    Pending,
+    Running,
+    Ready,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+struct RecordingExportStatus {
+    video_recording_id: String,
+    state: ExportState,
+    error: Option<String>,
+    // download is served once state is ready
+    download_url: String,
+    status_url: String,
+}
+
+impl RecordingExportStatus {
+    fn new(id: &str, state: ExportState, error: Option<String>) -> Self {
+        Self {
+            video_recording_id: id.to_string(),
+            state,
+            error,
+            download_url: uri!(recording(id)).to_string(),
+            status_url: uri!(recording_status(id)).to_string(),
+        }
+    }
+}
+
+// exports started by the recording route, keyed by VideoRecording id
+// finished exports are removed, RecordingExport::is_current reports whether they're ready
+#[derive(Clone, Debug, Default)]
+struct RecordingExports {
+    running: Arc<Mutex<HashMap<String, Option<String>>>>,
+}
+
+impl RecordingExports {
+    // None if no export was started (or it succeeded), Some(None) while running, Some(Some(error)) if it failed
+    fn get(&self, video_recording_id: &str) -> Option<Option<String>> {
+        self.running
+            .lock()
+            .unwrap()
+            .get(video_recording_id)
+            .cloned()
+    }
+
+    fn status(&self, export: &RecordingExport) -> RecordingExportStatus {
+        let id = &export.video_recording_id;
+        match self.get(id) {
+            Some(None) => RecordingExportStatus::new(id, ExportState::Running, None),
+            Some(Some(error)) => RecordingExportStatus::new(id, ExportState::Failed, Some(error)),
+            None if export.is_current() => RecordingExportStatus::new(id, ExportState::Ready, None),
+            None => RecordingExportStatus::new(id, ExportState::Pending, None),
+        }
+    }
+
+    // run export in the background, unless an export of the same recording is already running
+    fn start(&self, export: RecordingExport, factory: PrintNannyPipelineFactory) {
+        {
+            let mut running = self.running.lock().unwrap();
+            if let Some(None) = running.get(&export.video_recording_id) {
+                return;
+            }
+            running.insert(export.video_recording_id.clone(), None);
+        }
+        let running = self.running.clone();
+        rocket::tokio::spawn(async move {
+            let result = factory.export_video_recording(&export).await;
+            let mut running = running.lock().unwrap();
+            match result {
+                Ok(_) => running.remove(&export.video_recording_id),
+                Err(e) => running.insert(export.video_recording_id.clone(), Some(e.to_string())),
+            };
+        });
+    }
+}
+
 #[get("/jpeg")]
 async fn jpeg(state: &State<PrintNannySettings>) -> Result<NamedFile, NotFound<String>> {
     let settings = state;
@@ -29,11 +142,93 @@ async fn jpeg(state: &State<PrintNannySettings>) -> Result<NamedFile, NotFound<S
     }
 }
 
+// local VideoRecordingPart files of video_recording_id, NotFound if the VideoRecording doesn't exist
+fn recording_export(
+    video_recording_id: &str,
+    settings: &PrintNannySettings,
+) -> Result<RecordingExport, RecordingError> {
+    let sqlite_connection = settings.paths.db().display().to_string();
+    match VideoRecording::get_by_id(&sqlite_connection, video_recording_id) {
+        Ok(_) => (),
+        Err(diesel::result::Error::NotFound) => {
+            return Err(RecordingError::NotFound(format!(
+                "VideoRecording id={video_recording_id} does not exist"
+            )))
+        }
+        Err(e) => return Err(RecordingError::Internal(e.to_string())),
+    }
+    RecordingExport::new(
+        &sqlite_connection,
+        video_recording_id,
+        &settings.paths.video_exports(),
+    )
+    .map_err(|e| RecordingError::Internal(e.to_string()))
+}
+
+async fn recording_download(output: &Path) -> Result<RecordingDownload, RecordingError> {
+    let file_name = output
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let inner = NamedFile::open(output)
+        .await
+        .map_err(|e| RecordingError::Internal(e.to_string()))?;
+    Ok(RecordingDownload {
+        inner,
+        content_disposition: Header::new(
+            "Content-Disposition",
+            format!("attachment; filename=\"{file_name}\""),
+        ),
+    })
+}
+
+// download local VideoRecordingPart files concatenated into one playable file
+// exports can take minutes, so they run in the background and 202 Accepted is returned until the export is ready
+#[get("/recordings/<video_recording_id>")]
+async fn recording(
+    video_recording_id: &str,
+    settings: &State<PrintNannySettings>,
+    factory: &State<PrintNannyPipelineFactory>,
+    exports: &State<RecordingExports>,
+) -> Result<RecordingResponse, RecordingError> {
+    let export = recording_export(video_recording_id, settings)?;
+    let status = exports.status(&export);
+    match status.state {
+        ExportState::Ready => {
+            let download = recording_download(&export.output).await?;
+            Ok(RecordingResponse::Download(download))
+        }
+        ExportState::Running => Ok(RecordingResponse::Accepted(Json(status))),
+        // failed exports are retried
+        ExportState::Pending | ExportState::Failed => {
+            exports.start(export, factory.inner().clone());
+            Ok(RecordingResponse::Accepted(Json(
+                RecordingExportStatus::new(video_recording_id, ExportState::Running, None),
+            )))
+        }
+    }
+}
+
+#[get("/recordings/<video_recording_id>/status")]
+async fn recording_status(
+    video_recording_id: &str,
+    settings: &State<PrintNannySettings>,
+    exports: &State<RecordingExports>,
+) -> Result<Json<RecordingExportStatus>, RecordingError> {
+    let export = recording_export(video_recording_id, settings)?;
+    Ok(Json(exports.status(&export)))
+}
+
 #[launch]
 async fn rocket() -> _ {
     let settings = PrintNannySettings::new()
         .await
         .expect("Failed to initialize PrintNannySettings");
+    let factory = PrintNannyPipelineFactory::default();
 
-    rocket::build().manage(settings).mount("/", routes![jpeg])
+    rocket::build()
+        .manage(settings)
+        .manage(factory)
+        .manage(RecordingExports::default())
+        .mount("/", routes![jpeg, recording, recording_status])
 }